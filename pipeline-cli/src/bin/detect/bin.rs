@@ -0,0 +1,182 @@
+#[macro_use]
+extern crate clap;
+
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Write};
+use std::time::Instant;
+
+use clap::{App, ArgMatches};
+use dialoguer::console::{Emoji, style};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use num_format::{Locale, ToFormattedString};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use pipeline_cli as cli;
+use pipeline_cli::OutputFile;
+use pipeline_core::detect::{detect_heuristic, HeuristicThresholds, HeuristicVerdict};
+use pipeline_core::shared_interface::{Codec, CodecError, LogRecord, PrimStatsReader, RecordReader};
+
+static LOADING: Emoji<'_, '_> = Emoji("⏳ ", "");
+static WORKING: Emoji<'_, '_> = Emoji("🛠️ ", "");
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", "");
+
+struct HeuristicOpts {
+    in_records: cli::InputFile,
+    in_prim: cli::InputFile,
+    out_verdicts: OutputFile,
+    run_metadata: String,
+    thresholds: HeuristicThresholds,
+    codec: Codec,
+    quiet: bool,
+}
+
+/// Output row: a verdict and the primary domain of its query.
+#[derive(Serialize)]
+struct VerdictRow {
+    id: usize,
+    prim_id: u32,
+    payload_length: u8,
+    entropy: f32,
+    n_unique_hour: u32,
+    suspicious: bool,
+    rules: u8,
+}
+
+fn parse_heuristic_opts(m: &ArgMatches) -> HeuristicOpts {
+    let quiet = m.is_present("quiet");
+
+    let thresholds = HeuristicThresholds {
+        max_payload_length: cli::value_or_exit::<u8>(m, "max_length"),
+        max_entropy: cli::value_or_exit::<f32>(m, "max_entropy"),
+        max_unique_per_hour: cli::value_or_exit::<u32>(m, "max_unique_per_hour"),
+    };
+
+    let codec = match m.value_of("codec").unwrap_or("bincode").parse::<Codec>() {
+        Ok(codec) => codec,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--codec"), msg))
+    };
+
+    let parse_input = |name: &str| match cli::parse_input(m.value_of(name).unwrap_or_default()) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    };
+    let in_records = parse_input("in_records");
+    let in_prim = parse_input("in_prim");
+
+    let output = m.value_of("out_verdicts").unwrap_or_default();
+    let out_verdicts = match cli::parse_output_file(output, quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    };
+    let stem = output.strip_suffix(".gz").unwrap_or(output);
+    let run_metadata = format!("{}.meta.json", stem.strip_suffix(".csv").unwrap_or(stem));
+
+    HeuristicOpts { in_records, in_prim, out_verdicts, run_metadata, thresholds, codec, quiet }
+}
+
+fn exit_with_read_error(arg: &str, err: CodecError) -> ! {
+    let err = cli::CliError::Intermediate(String::from(arg), err);
+    cli::exit_with_error(err)
+}
+
+/// Load the records grouped by primary domain, with the length of the primary domain.
+fn load_records(opts: &HeuristicOpts) -> HashMap<u32, (u8, Vec<LogRecord>)> {
+    cli::print_output(format!("\n{}   {}Loading filtered entries...\n", style("[1/2]").bold().dim(), LOADING), opts.quiet);
+
+    let mut queries: HashMap<u32, (u8, Vec<LogRecord>)> = HashMap::new();
+    let mut n_entries: u64 = 0;
+    for stats in PrimStatsReader::new(BufReader::new(&opts.in_prim), opts.codec) {
+        let stats = stats.unwrap_or_else(|err| exit_with_read_error("--in-prim", err));
+        n_entries += stats.count as u64;
+        queries.insert(stats.id, (stats.length, Vec::with_capacity(stats.count as usize)));
+    }
+
+    let pb = cli::make_progress_bar(n_entries, opts.quiet);
+    for entry in RecordReader::new(BufReader::new(&opts.in_records), opts.codec) {
+        let (prim_id, record) = entry.unwrap_or_else(|err| exit_with_read_error("--in-records", err));
+        match queries.get_mut(&prim_id) {
+            Some((_, records)) => records.push(record),
+            None => {
+                let err = cli::CliError::InvalidInput(String::from("--in-records"), format!("record {} has unknown primary domain {}", record.id, prim_id));
+                cli::exit_with_error(err)
+            }
+        }
+        if let Some(pb) = &pb { pb.inc(1); }
+    }
+    if let Some(pb) = &pb { pb.finish(); }
+
+    queries
+}
+
+fn run_heuristic(opts: HeuristicOpts) {
+    let start = Instant::now();
+    let queries = load_records(&opts);
+    let n_domains = queries.len();
+
+    cli::print_output(format!("\n{}   {}Applying heuristics...\n", style("[2/2]").bold().dim(), WORKING), opts.quiet);
+
+    let mut verdicts: Vec<(u32, HeuristicVerdict)> = queries.into_par_iter()
+        .flat_map_iter(|(prim_id, (prim_len, records))| {
+            detect_heuristic(&records, prim_len, &opts.thresholds).into_iter().map(move |verdict| (prim_id, verdict))
+        })
+        .collect();
+    verdicts.sort_unstable_by_key(|(_, verdict)| verdict.id);
+
+    let mut w = csv::Writer::from_writer(GzEncoder::new(BufWriter::new(&opts.out_verdicts), Compression::fast()));
+    let result = verdicts.iter()
+        .map(|(prim_id, v)| VerdictRow {
+            id: v.id,
+            prim_id: *prim_id,
+            payload_length: v.payload_length,
+            entropy: v.entropy,
+            n_unique_hour: v.n_unique_hour,
+            suspicious: v.suspicious,
+            rules: v.rules.0,
+        })
+        .try_for_each(|row| w.serialize(row));
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+
+    let result = w.into_inner()
+        .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))
+        .and_then(|gz| gz.finish())
+        .and_then(|mut buf| buf.flush());
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+    if let Err(e) = opts.out_verdicts.commit() {
+        cli::exit_with_error(e);
+    }
+
+    // Record checksums of all outputs, for verification
+    if let Err(e) = cli::write_run_metadata(&opts.run_metadata, None) {
+        cli::exit_with_error(e);
+    }
+
+    let mut suspicious_domains: Vec<u32> = verdicts.iter().filter(|(_, v)| v.suspicious).map(|(prim_id, _)| *prim_id).collect();
+    suspicious_domains.sort_unstable();
+    suspicious_domains.dedup();
+
+    cli::print_output("\n".to_string(), opts.quiet);
+    eprintln!("           {} of {} queries suspicious, in {} of {} primary domains",
+              verdicts.iter().filter(|(_, v)| v.suspicious).count().to_formatted_string(&Locale::en),
+              verdicts.len().to_formatted_string(&Locale::en),
+              suspicious_domains.len().to_formatted_string(&Locale::en),
+              n_domains.to_formatted_string(&Locale::en));
+    eprintln!("\n        {}Finished in {:.1?}", SPARKLE, start.elapsed());
+}
+
+fn main() {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+
+    match m.subcommand() {
+        ("heuristic", Some(sub)) => run_heuristic(parse_heuristic_opts(sub)),
+        _ => unreachable!("subcommand is required"),
+    }
+}
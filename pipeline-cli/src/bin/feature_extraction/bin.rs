@@ -0,0 +1,1015 @@
+#[macro_use]
+extern crate clap;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use clap::App;
+use dialoguer::console::{Emoji, style};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use num_format::{Locale, ToFormattedString};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeTuple;
+
+use pipeline_cli::bundle::{BundleDomain, BundleMetadata, DOMAINS_FILE, FEATURES_FILE, METADATA_FILE, pack_bundle, RECORD_DOMAINS_FILE, SCHEMA_FILE, write_bundle_files};
+use pipeline_cli as cli;
+use pipeline_cli::{FloatFormat, OutputFile};
+use pipeline_core::enrichment::{CsvEnrichment, DomainEnrichment};
+use pipeline_core::feature_extraction::{BigramModel, DnsLengthModel, extract_features_per_domain, extract_features_per_domain_split, CostTracker, ExtractOpts, FeatureCost, FeatureDeltas, FeatureVector, GlobalWindowFeatureVector, LabelIdf, LabelIdfFeatures, NameFormat, NanPolicy, OutOfOrderPolicy, query_time_series, RateUnit, RowFilter, StreamingExtractor, TimeSeriesPoint};
+use pipeline_core::shared_interface::{Codec, CodecError, DomainTags, GeoInfo, LogRecord, PrimaryDomainStats, PrimStatsReader, RecordReader, SerializedLogEntry};
+
+use crate::output::{ColumnFilter, CsvOutput, FeatureOutput, FeatureWriter, output_stem, Partitioning, PartWriter, Rotation};
+use crate::spill::{Spill, Spilled, SpilledDomain};
+
+mod output;
+mod spill;
+
+// Key for both maps is primary domain ID
+type QueryMap = HashMap<u32, Vec<LogRecord>>;
+type PrimStats = HashMap<u32, PrimaryDomainStats>;
+
+// Feature vector with columns appended from the primary domain stats
+type OutputRow = (FeatureVector, RowTags, EnrichmentColumns, TagColumns);
+
+#[derive(Serialize)]
+struct RowTags {
+    /// Change since the previous row of the primary domain, only written with `--deltas` (empty for its first row).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta_entropy: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta_unique_rate: Option<Option<f32>>,
+    /// Label IDF features of the (last) query, only written with `--label-idf`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avg_label_idf: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_label_idf: Option<f32>,
+    domain_tags: DomainTags,
+    /// Label of the query from the input log, only written if the log has a label field (empty for unlabeled queries).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// GeoIP columns of the query, only written if the input has GeoIP information (empty if unknown).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_country: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_asn: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolver_country: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolver_asn: Option<Option<u32>>,
+}
+
+/// Columns from `--enrich` (none without enrichment) of a primary domain, empty for domains that are not in the enrichment source.
+/// Only the values are serialized, the column names are written in the header (see `output::header`).
+#[derive(Clone)]
+struct EnrichmentColumns {
+    columns: Arc<[String]>,
+    values: Option<Arc<Vec<String>>>,
+}
+
+impl Serialize for EnrichmentColumns {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_tuple(self.columns.len())?;
+        for i in 0..self.columns.len() {
+            let value = self.values.as_ref().and_then(|values| values.get(i)).map_or("", |value| value.as_str());
+            state.serialize_element(value)?;
+        }
+        state.end()
+    }
+}
+
+/// Constant `key=value` columns from `--tag` (e.g. dataset name or capture site), the same for every row. Only the values
+/// are serialized, the keys are written in the header (see `output::header`).
+#[derive(Debug, Clone, Default)]
+struct TagColumns(Arc<[(String, String)]>);
+
+impl Serialize for TagColumns {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_tuple(self.0.len())?;
+        for (_, value) in self.0.iter() {
+            state.serialize_element(value)?;
+        }
+        state.end()
+    }
+}
+
+/// Enrichment source with its column names.
+struct Enrichment<'a> {
+    source: &'a dyn DomainEnrichment,
+    columns: Arc<[String]>,
+}
+
+impl<'a> Enrichment<'a> {
+    fn new(source: &'a dyn DomainEnrichment) -> Self {
+        Enrichment { source, columns: source.columns().into() }
+    }
+
+    fn lookup(enrichment: Option<&Self>, primary_domain: &str) -> EnrichmentColumns {
+        match enrichment {
+            Some(enrichment) => EnrichmentColumns {
+                columns: enrichment.columns.clone(),
+                values: enrichment.source.lookup(primary_domain).map(Arc::new),
+            },
+            None => EnrichmentColumns { columns: Arc::new([]), values: None },
+        }
+    }
+}
+
+/// Sources of the columns appended to the feature vectors.
+struct ExtraColumns<'a> {
+    has_labels: bool,
+    has_client_geo: bool,
+    has_resolver_geo: bool,
+    idf: Option<&'a LabelIdf>,
+    enrichment: Option<Enrichment<'a>>,
+}
+
+/// Per-query columns, looked up by the record ID of a feature vector. Columns that are not written are None.
+#[derive(Default)]
+struct QueryColumns {
+    /// Empty for unlabeled queries if the input has labels.
+    label: Option<String>,
+    idf: Option<LabelIdfFeatures>,
+    /// Default (empty) for queries without GeoIP information if the input has it.
+    client_geo: Option<GeoInfo>,
+    resolver_geo: Option<GeoInfo>,
+}
+
+/// Take the per-query columns out of the records, keyed by record ID. Returns None if there are no such columns (no
+/// labels or GeoIP information in the input and no label IDF).
+fn take_query_columns<'a, I>(records: I, extra: &ExtraColumns) -> Option<HashMap<usize, QueryColumns>>
+    where I: Iterator<Item=&'a mut LogRecord> {
+    if !extra.has_labels && !extra.has_client_geo && !extra.has_resolver_geo && extra.idf.is_none() { return None; }
+    Some(records
+        .map(|record| {
+            let label = if extra.has_labels { Some(record.label.take().unwrap_or_default()) } else { None };
+            let client_geo = if extra.has_client_geo { Some(record.client_geo.take().unwrap_or_default()) } else { None };
+            let resolver_geo = if extra.has_resolver_geo { Some(record.resolver_geo.take().unwrap_or_default()) } else { None };
+            let idf = extra.idf.map(|idf| idf.features(&record.payload));
+            (record.id, QueryColumns { label, idf, client_geo, resolver_geo })
+        })
+        .collect())
+}
+
+/// Columns appended to a feature vector (of the last query, for windows).
+fn row_tags(domain_tags: DomainTags, columns: &Option<HashMap<usize, QueryColumns>>, fv: &FeatureVector) -> RowTags {
+    let query = columns.as_ref().and_then(|columns| columns.get(&fv.id()));
+    let idf = query.and_then(|query| query.idf);
+
+    RowTags {
+        delta_entropy: None,
+        delta_unique_rate: None,
+        avg_label_idf: idf.map(|idf| idf.avg_label_idf),
+        max_label_idf: idf.map(|idf| idf.max_label_idf),
+        domain_tags,
+        label: query.and_then(|query| query.label.clone()),
+        client_country: query.and_then(|query| query.client_geo.as_ref()).map(|geo| geo.country.clone()),
+        client_asn: query.and_then(|query| query.client_geo.as_ref()).map(|geo| geo.asn),
+        resolver_country: query.and_then(|query| query.resolver_geo.as_ref()).map(|geo| geo.country.clone()),
+        resolver_asn: query.and_then(|query| query.resolver_geo.as_ref()).map(|geo| geo.asn),
+    }
+}
+
+/// Names of the columns that a `--tag` key would shadow: the feature columns of every vector type, all row tags and the
+/// enrichment columns.
+fn reserved_columns(enrichment: Option<&dyn DomainEnrichment>) -> Vec<String> {
+    let vectors = vec![
+        FeatureVector::Payload(Default::default()), FeatureVector::Time(Default::default()), FeatureVector::Fixed(Default::default()),
+        FeatureVector::Bytes(Default::default()), FeatureVector::Decay(Default::default()), FeatureVector::Global(Default::default()),
+    ];
+    let enrichment = EnrichmentColumns { columns: enrichment.map_or(Arc::new([]), |source| source.columns().into()), values: None };
+    vectors.into_iter().flat_map(|fv| {
+        let tags = RowTags {
+            delta_entropy: Some(None),
+            delta_unique_rate: Some(None),
+            avg_label_idf: Some(0.),
+            max_label_idf: Some(0.),
+            domain_tags: DomainTags::default(),
+            label: Some(String::new()),
+            client_country: Some(None),
+            client_asn: Some(None),
+            resolver_country: Some(None),
+            resolver_asn: Some(None),
+        };
+        output::header(&(fv, tags, enrichment.clone(), TagColumns::default())).unwrap_or_default()
+    }).collect()
+}
+
+static LOADING: Emoji<'_, '_> = Emoji("⏳ ", "");
+static WORKING: Emoji<'_, '_> = Emoji("🛠️ ", "");
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", "");
+
+/// Output bundle: the directory with the bundle files, which are packed into a `.tar` archive if given.
+#[derive(Debug)]
+struct BundleTarget {
+    dir: PathBuf,
+    tar: Option<OutputFile>,
+}
+
+#[derive(Debug)]
+pub struct Opts {
+    pub extract_opts: ExtractOpts,
+    pub in_records: cli::InputFile,
+    pub in_prim: cli::InputFile,
+    /// Feature vectors, or the manifest of a partitioned output.
+    pub out_features: OutputFile,
+    rotation: Option<Rotation>,
+    partitioning: Option<Partitioning>,
+    out_parts: Vec<OutputFile>,
+    /// Run metadata with checksums of all outputs, next to the output.
+    run_metadata: String,
+    float_format: FloatFormat,
+    label_idf: bool,
+    /// Append the change in entropy and unique rate since the previous row of the same primary domain.
+    deltas: bool,
+    /// Output only the columns that are meaningful for reverse lookups (see `FeatureVector::is_reverse_zone_column`).
+    reverse_zones: bool,
+    enrichment: Option<CsvEnrichment>,
+    /// Constant columns appended to every row.
+    tags: TagColumns,
+    /// Seed for shuffling the output rows, rows are grouped by primary domain otherwise.
+    shuffle: Option<u64>,
+    /// Split primary domains with more records into segments (one per thread) that are extracted in parallel.
+    split_domains: Option<usize>,
+    /// Spill primary domains with more records to temporary files, which are extracted record by record.
+    spill_domains: Option<u32>,
+    bundle: Option<BundleTarget>,
+    /// Query volume per primary domain and interval (`--timeseries-interval` seconds).
+    out_timeseries: Option<OutputFile>,
+    timeseries_interval: f64,
+    /// Feature matrices per family in HDF5 format, in addition to the CSV output.
+    out_hdf5: Option<OutputFile>,
+    codec: Codec,
+    /// Time spent per feature extraction stage, reported at the end of the run.
+    timings: Option<Arc<FeatureCost>>,
+    quiet: bool,
+}
+
+fn parse_opts() -> Opts {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+
+    let quiet = m.is_present("quiet");
+    let timings = if m.is_present("timings") { Some(Arc::new(FeatureCost::default())) } else { None };
+
+    // Parse and validate feature extraction arguments
+    let extract_opts = ExtractOpts {
+        payload: m.is_present("payload"),
+
+        time: if m.is_present("time") {
+            let duration = cli::value_or_exit::<f32>(&m, "time");
+            if duration <= 0. {
+                let err = cli::CliError::InvalidArgument(String::from("-t/--time"), String::from("window duration too short"));
+                cli::exit_with_error(err)
+            }
+            Some(duration)
+        } else { None },
+
+        fixed: if m.is_present("fixed") {
+            let size = cli::value_or_exit::<usize>(&m, "fixed");
+            if size == 0 {
+                let err = cli::CliError::InvalidArgument(String::from("-f/--fixed"), String::from("window size too small"));
+                cli::exit_with_error(err)
+            }
+            Some(size)
+        } else { None },
+
+        bytes: if m.is_present("bytes") {
+            let size = cli::value_or_exit::<usize>(&m, "bytes");
+            if size == 0 {
+                let err = cli::CliError::InvalidArgument(String::from("--bytes"), String::from("window size too small"));
+                cli::exit_with_error(err)
+            }
+            Some(size)
+        } else { None },
+
+        decay: if m.is_present("decay") {
+            let decay = cli::value_or_exit::<f64>(&m, "decay");
+            if !(decay > 0. && decay < 1.) {
+                let err = cli::CliError::InvalidArgument(String::from("--decay"), String::from("decay factor must be between 0 and 1 (exclusive)"));
+                cli::exit_with_error(err)
+            }
+            Some(decay)
+        } else { None },
+
+        // Entries are sorted by timestamp before extraction
+        out_of_order: OutOfOrderPolicy::Clamp,
+
+        global: if m.is_present("global") {
+            let duration = cli::value_or_exit::<f32>(&m, "global");
+            if duration <= 0. {
+                let err = cli::CliError::InvalidArgument(String::from("-g/--global"), String::from("window duration too short"));
+                cli::exit_with_error(err)
+            }
+            Some(duration)
+        } else { None },
+
+        rare_threshold: cli::value_or_exit::<u32>(&m, "rare_threshold"),
+
+        nan_policy: match m.value_of("nan_policy").unwrap_or("keep") {
+            "keep" => NanPolicy::Keep,
+            "zero" => NanPolicy::Zero,
+            "skip" => NanPolicy::Skip,
+            sentinel => match sentinel.parse::<f32>() {
+                Ok(value) if value.is_finite() => NanPolicy::Sentinel(value),
+                _ => {
+                    let err = cli::CliError::InvalidArgument(String::from("--nan"), String::from("expected keep, zero, skip or a finite number"));
+                    cli::exit_with_error(err)
+                }
+            }
+        },
+
+        length_model: {
+            let format = match m.value_of("name_format").unwrap_or("presentation").parse::<NameFormat>() {
+                Ok(format) => format,
+                Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--name-format"), msg))
+            };
+            let max_name_length = if m.is_present("max_name_length") {
+                cli::value_or_exit::<u16>(&m, "max_name_length")
+            } else if format == NameFormat::Wire { 255 } else { 253 };
+            DnsLengthModel { format, max_name_length }
+        },
+
+        bigram_model: match m.value_of("bigram_model") {
+            Some(input) => {
+                let file = cli::parse_input_file(input).unwrap_or_else(|err| cli::exit_with_error(err));
+                match BigramModel::from_hostnames(BufReader::new(file)) {
+                    Ok(model) => Arc::new(model),
+                    Err(err) => cli::exit_with_error(cli::CliError::IO(input.to_string(), err))
+                }
+            }
+            None => BigramModel::embedded(),
+        },
+
+        rate_unit: match m.value_of("rate_unit").unwrap_or("second").parse::<RateUnit>() {
+            Ok(unit) => unit,
+            Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--rate-unit"), msg))
+        },
+
+        row_filter: m.value_of("only_if").map(|filter| match filter.parse::<RowFilter>() {
+            Ok(filter) => filter,
+            Err(err) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--only-if"), err.to_string()))
+        }),
+
+        cost: timings.clone().map(CostTracker::new).unwrap_or_default(),
+    };
+
+    // Check filter columns against the selected feature family
+    if let Some(filter) = &extract_opts.row_filter {
+        let unknown = filter.unknown_columns(&example_vector(&extract_opts));
+        if !unknown.is_empty() {
+            let err = cli::CliError::InvalidArgument(String::from("--only-if"), format!("unknown column(s) {}", unknown.join(", ")));
+            cli::exit_with_error(err)
+        }
+    }
+
+    let float_format = match m.value_of("float_format").unwrap_or("shortest") {
+        "shortest" => FloatFormat::Shortest,
+        decimals => match decimals.parse::<usize>() {
+            Ok(decimals) => FloatFormat::Fixed(decimals),
+            Err(_) => {
+                let err = cli::CliError::InvalidArgument(String::from("--float-format"), String::from("expected shortest or a number of decimals"));
+                cli::exit_with_error(err)
+            }
+        }
+    };
+
+    let codec = match m.value_of("codec").unwrap_or("bincode").parse::<Codec>() {
+        Ok(codec) => codec,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--codec"), msg))
+    };
+
+    // Parse and validate input/output file arguments
+    let in_records = match m.value_of("in_records") {
+        Some(input) => match cli::parse_input(input) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("--in-records"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let in_prim = match m.value_of("in_prim") {
+        Some(input) => match cli::parse_input(input) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("--in-prim"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let rotation = if m.is_present("rotate_size") {
+        let size_mib = cli::value_or_exit::<u64>(&m, "rotate_size");
+        if size_mib == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--rotate-size"), String::from("size must be at least 1 MiB"));
+            cli::exit_with_error(err)
+        }
+        Some(Rotation::new(m.value_of("out_features").unwrap_or_default(), size_mib * 1024 * 1024))
+    } else { None };
+
+    let partitioning = if m.is_present("parts") {
+        let n_parts = cli::value_or_exit::<usize>(&m, "parts");
+        if n_parts == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--parts"), String::from("number of parts must be at least 1"));
+            cli::exit_with_error(err)
+        }
+        Some(Partitioning::new(m.value_of("out_features").unwrap_or_default(), n_parts))
+    } else { None };
+
+    let out_parts = partitioning.iter()
+        .flat_map(|partitioning| (0..partitioning.n_parts).map(move |index| partitioning.part_path(index)))
+        .map(|path| match cli::parse_output_file(&path, quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        })
+        .collect();
+
+    // Bundles are written to a directory, which is packed into a temporary one next to the archive for a .tar bundle
+    let bundle = m.value_of("out_features").filter(|_| m.is_present("bundle")).map(|output| {
+        let tar = if output.ends_with(".tar") {
+            match cli::parse_output_file(output, quiet) {
+                Ok(file) => Some(file),
+                Err(err) => cli::exit_with_error(err)
+            }
+        } else { None };
+        let dir = if tar.is_some() { PathBuf::from(format!("{}.d.tmp", output)) } else { PathBuf::from(output) };
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            cli::exit_with_error(cli::CliError::IO(dir.display().to_string(), e));
+        }
+        BundleTarget { dir, tar }
+    });
+
+    // With rotation, the output path is the template for the numbered files (and for the part files and manifest)
+    let out_features = match m.value_of("out_features") {
+        Some(_) if partitioning.is_some() => match cli::parse_output_file(&partitioning.as_ref().unwrap().manifest_path(), quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        Some(_) if bundle.is_some() => {
+            let bundle = bundle.as_ref().unwrap();
+            match cli::parse_output_file(&bundle.dir.join(FEATURES_FILE).display().to_string(), quiet || bundle.tar.is_some()) {
+                Ok(file) => file,
+                Err(err) => cli::exit_with_error(err)
+            }
+        }
+        Some(input) if m.is_present("append") => match cli::parse_append_file(input) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        Some(input) => match cli::parse_output_file(&rotation.as_ref().map_or(input.to_string(), |r| r.path(1)), quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("<out_features>"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let run_metadata = match m.value_of("out_features").unwrap_or_default() {
+        output if bundle.is_some() => format!("{}.meta.json", output.trim_end_matches(['/', '\\'])),
+        output => format!("{}.meta.json", output_stem(output)),
+    };
+
+    let enrichment = m.value_of("enrich").map(|input| {
+        let path = match cli::parse_input_file(input) {
+            Ok(_) => std::path::Path::new(input),
+            Err(err) => cli::exit_with_error(err)
+        };
+        match CsvEnrichment::from_file(path) {
+            Ok(enrichment) => enrichment,
+            Err(err) => cli::exit_with_error(err)
+        }
+    });
+
+    let reserved = reserved_columns(enrichment.as_ref().map(|source| source as &dyn DomainEnrichment));
+    let mut tags: Vec<(String, String)> = Vec::new();
+    for value in m.values_of("tag").into_iter().flatten() {
+        let (key, value) = match value.split_once('=') {
+            Some((key, value)) if !key.is_empty() => (key, value),
+            _ => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--tag"), format!("expected <key>=<value>, got \"{}\"", value)))
+        };
+        if tags.iter().any(|(other, _)| *other == key) {
+            cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--tag"), format!("duplicate key \"{}\"", key)));
+        }
+        if reserved.iter().any(|column| column == key) {
+            cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--tag"), format!("key \"{}\" is the name of another column", key)));
+        }
+        tags.push((key.to_string(), value.to_string()));
+    }
+    let tags = TagColumns(tags.into());
+
+    let shuffle = if m.is_present("seed") {
+        Some(cli::value_or_exit::<u64>(&m, "seed"))
+    } else if m.is_present("shuffle") { Some(0) } else { None };
+
+    let split_domains = if m.is_present("split_domains") {
+        let segment_len = cli::value_or_exit::<usize>(&m, "split_domains");
+        if segment_len == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--split-domains"), String::from("number of records must be at least 1"));
+            cli::exit_with_error(err)
+        }
+        Some(segment_len)
+    } else { None };
+
+    let spill_domains = if m.is_present("spill_domains") { Some(cli::value_or_exit::<u32>(&m, "spill_domains")) } else { None };
+
+    let out_timeseries = m.value_of("out_timeseries").map(|output| match cli::parse_output_file(output, quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    });
+
+    let out_hdf5 = m.value_of("out_hdf5").map(|output| {
+        if !cfg!(feature = "hdf5") {
+            let err = cli::CliError::InvalidArgument(String::from("--out-hdf5"), String::from("built without the hdf5 feature"));
+            cli::exit_with_error(err)
+        }
+        match cli::parse_output_file(output, quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+    });
+
+    let timeseries_interval = cli::value_or_exit::<f64>(&m, "timeseries_interval");
+    if timeseries_interval <= 0. || !timeseries_interval.is_finite() {
+        let err = cli::CliError::InvalidArgument(String::from("--timeseries-interval"), String::from("interval too short"));
+        cli::exit_with_error(err)
+    }
+
+    Opts { extract_opts, in_records, in_prim, out_features, rotation, partitioning, out_parts, run_metadata, float_format, label_idf: m.is_present("label_idf"), deltas: m.is_present("deltas"), reverse_zones: m.is_present("reverse_zones"), enrichment, tags, shuffle, split_domains, spill_domains, bundle, out_timeseries, timeseries_interval, out_hdf5, codec, timings, quiet }
+}
+
+/// Feature vector of the selected feature family, e.g. for its column names.
+fn example_vector(extract_opts: &ExtractOpts) -> FeatureVector {
+    if extract_opts.payload { FeatureVector::Payload(Default::default()) }
+    else if extract_opts.fixed.is_some() { FeatureVector::Fixed(Default::default()) }
+    else if extract_opts.bytes.is_some() { FeatureVector::Bytes(Default::default()) }
+    else if extract_opts.decay.is_some() { FeatureVector::Decay(Default::default()) }
+    else if extract_opts.global.is_some() { FeatureVector::Global(Default::default()) }
+    else { FeatureVector::Time(Default::default()) }
+}
+
+/// Write the bundle files next to the (committed) feature vectors, and pack them if the bundle is a `.tar` archive.
+fn write_bundle(bundle: &BundleTarget, opts: &Opts, domains: Vec<BundleDomain>, record_domains: Vec<(usize, u32)>) {
+    let metadata = BundleMetadata {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        arguments: std::env::args().collect(),
+        n_records: record_domains.len(),
+        n_domains: domains.len(),
+    };
+
+    let family = example_vector(&opts.extract_opts).family();
+    if let Err(e) = write_bundle_files(&bundle.dir, &metadata, family, &domains, &record_domains) {
+        cli::exit_with_error(e);
+    }
+
+    // Files of a directory bundle are produced as well (for a .tar bundle, only the archive)
+    if bundle.tar.is_none() {
+        let result = [METADATA_FILE, SCHEMA_FILE, DOMAINS_FILE, RECORD_DOMAINS_FILE].iter()
+            .try_for_each(|name| cli::register_file(&bundle.dir.join(name)));
+        if let Err(e) = result {
+            cli::exit_with_error(e);
+        }
+    }
+
+    if let Some(tar) = &bundle.tar {
+        if let Err(e) = pack_bundle(&bundle.dir, BufWriter::new(tar)) {
+            cli::exit_with_error(e);
+        }
+        if let Err(e) = tar.commit() {
+            cli::exit_with_error(e);
+        }
+        if let Err(e) = std::fs::remove_dir_all(&bundle.dir) {
+            cli::exit_with_error(cli::CliError::IO(bundle.dir.display().to_string(), e));
+        }
+    }
+}
+
+/// Row of the `--out-timeseries` output.
+#[derive(Serialize)]
+struct TimeSeriesRow<'a> {
+    prim_id: u32,
+    primary_domain: &'a str,
+    interval_start: f64,
+    n_queries: usize,
+    n_unique_labels: usize,
+    n_unique_queries: usize,
+}
+
+/// Write the query volume time series of all primary domains (in ID order), from the loaded and spilled records.
+fn write_time_series(file: &OutputFile, opts: &Opts, queries: &QueryMap, spilled: &[SpilledDomain], prim_stats: &PrimStats) {
+    let domains: Vec<u32> = queries.keys().chain(spilled.iter().map(|domain| &domain.prim_id)).copied().collect();
+    let mut series: Vec<(u32, Vec<TimeSeriesPoint>)> = domains.into_par_iter()
+        .map(|prim_id| match queries.get(&prim_id) {
+            Some(records) => (prim_id, query_time_series(records, opts.timeseries_interval)),
+            None => {
+                let domain = spilled.iter().find(|domain| domain.prim_id == prim_id).unwrap();
+                let records = domain.records().unwrap_or_else(|err| cli::exit_with_error(err));
+                (prim_id, query_time_series(records, opts.timeseries_interval))
+            }
+        })
+        .collect();
+    series.sort_unstable_by_key(|(prim_id, _)| *prim_id);
+
+    let gz_writer = GzEncoder::new(BufWriter::new(file), Compression::fast());
+    let mut w = csv::WriterBuilder::new().quote_style(csv::QuoteStyle::Necessary).from_writer(gz_writer);
+    for (prim_id, points) in &series {
+        let primary_domain = &prim_stats[prim_id].name;
+        for point in points {
+            let row = TimeSeriesRow { prim_id: *prim_id, primary_domain, interval_start: point.interval_start, n_queries: point.n_queries, n_unique_labels: point.n_unique_labels, n_unique_queries: point.n_unique_queries };
+            if let Err(e) = w.serialize(row) {
+                cli::exit_with_error(e);
+            }
+        }
+    }
+
+    let result = w.into_inner()
+        .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))
+        .and_then(|gz| gz.finish())
+        .and_then(|mut buf| buf.flush());
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+    if let Err(e) = file.commit() {
+        cli::exit_with_error(e);
+    }
+}
+
+/// Exit on an intermediate file that cannot be read (e.g. truncated, or written with another codec or version).
+fn exit_with_read_error(arg: &str, err: CodecError) -> ! {
+    let err = cli::CliError::Intermediate(String::from(arg), err);
+    cli::exit_with_error(err)
+}
+
+fn consume_input(opts: &Opts) -> (QueryMap, PrimStats, Option<Spilled>) {
+    cli::print_output(format!("\n{}   {}Loading filtered entries...\n", style("[1/2]").bold().dim(), LOADING), opts.quiet);
+
+    // Load primary domain stats
+    let mut prim_stats: HashMap<u32, PrimaryDomainStats> = HashMap::new();
+    let mut n_entries: u64 = 0;
+
+    for stats in PrimStatsReader::new(BufReader::new(&opts.in_prim), opts.codec) {
+        let stats = stats.unwrap_or_else(|err| exit_with_read_error("--in-prim", err));
+        n_entries += stats.count as u64;
+        prim_stats.insert(stats.id, stats);
+    }
+
+    let pb = cli::make_progress_bar(n_entries, opts.quiet);
+
+    // Map for loaded queries. prim_id <--> (prim_len, [DnsEntry..])
+    let mut queries: QueryMap = HashMap::with_capacity(prim_stats.len());
+    let mut spill = opts.spill_domains.map(|threshold| Spill::new(threshold).unwrap_or_else(|err| cli::exit_with_error(err)));
+
+    // Load records
+    for entry in RecordReader::new(BufReader::new(&opts.in_records), opts.codec) {
+        let (prim_id, log_record) = entry.unwrap_or_else(|err| exit_with_read_error("--in-records", err));
+
+        // Records of giant domains go to disk
+        if let Some(spill) = spill.as_mut().filter(|spill| spill.spills(prim_stats[&prim_id].count)) {
+            if let Err(e) = spill.push(prim_id, &log_record) {
+                cli::exit_with_error(e);
+            }
+            if Option::is_some(&pb) { pb.as_ref().unwrap().inc(1); }
+            continue;
+        }
+
+        // Get or create bucket for primary domain, using known capacity for efficiency
+        let bucket = queries.entry(prim_id).or_insert_with(|| {
+            let prim_capacity = prim_stats[&prim_id].count as usize;
+            Vec::with_capacity(prim_capacity)
+        });
+
+        // Insert query in map
+        bucket.push(log_record);
+
+        // Update progress bar
+        if Option::is_some(&pb) { pb.as_ref().unwrap().inc(1); }
+    }
+    if Option::is_some(&pb) { pb.as_ref().unwrap().finish(); }
+
+    // TODO: warn and exit if n_entries is not the same as lines read
+
+    let spilled = spill.map(|spill| spill.finish().unwrap_or_else(|err| cli::exit_with_error(err)));
+    (queries, prim_stats, spilled)
+}
+
+fn extract_global_features(mut w: FeatureOutput<'_>, opts: &Opts, queries: QueryMap, prim_stats: &HashMap<u32, PrimaryDomainStats>, duration: f32, extra: &ExtraColumns) {
+    cli::print_output(format!("\n{}   {}Extracting global features...\n", style("[2/2]").bold().dim(), WORKING), opts.quiet);
+
+    // Merge all primary domains into a single timeline
+    let mut records: Vec<SerializedLogEntry> = queries.into_iter()
+        .flat_map(|(prim_id, entries)| entries.into_iter().map(move |record| (prim_id, record)))
+        .collect();
+    let columns = take_query_columns(records.iter_mut().map(|(_, record)| record), extra);
+
+    let features = GlobalWindowFeatureVector::extract_for_capture(&opts.extract_opts, duration, records, prim_stats);
+
+    let mut enriched = HashMap::new();
+    let mut rows: Vec<(u32, OutputRow)> = features.into_iter()
+        .map(|(prim_id, fv)| {
+            let prim = &prim_stats[&prim_id];
+            let enriched = enriched.entry(prim_id).or_insert_with(|| Enrichment::lookup(extra.enrichment.as_ref(), &prim.name)).clone();
+            let tags = row_tags(prim.tags, &columns, &fv);
+            (prim_id, (fv, tags, enriched, opts.tags.clone()))
+        })
+        .collect();
+
+    if let Some(seed) = opts.shuffle { shuffle_rows(&mut rows, seed); }
+    rows.iter().for_each(|(prim_id, row)| if let Err(e) = w.serialize(*prim_id, row) {
+        cli::exit_with_error(e);
+    });
+
+    if let Err(e) = w.finish() {
+        cli::exit_with_error(e);
+    }
+}
+
+/// Shuffle rows reproducibly: rows are first put in record ID order, as domains are processed in arbitrary order.
+fn shuffle_rows(rows: &mut [(u32, OutputRow)], seed: u64) {
+    rows.sort_by_key(|(_, (fv, ..))| fv.id());
+    rows.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+}
+
+/// Relative amount of work for a primary domain (sorting dominates for large domains), used for the progress bar.
+fn domain_weight(count: u32) -> u64 {
+    let n = count as f64;
+    (n * n.log2().max(1.)).ceil() as u64
+}
+
+/// Extract the features of a spilled primary domain record by record, so only its window is in memory. Rows are
+/// written in chunks, unless all rows are shuffled at the end (then they are returned).
+fn extract_spilled_domain(domain: &SpilledDomain, opts: &Opts, prim: &PrimaryDomainStats, extra: &ExtraColumns, csv_writer: &Mutex<FeatureOutput<'_>>) -> Vec<(u32, OutputRow)> {
+    const CHUNK_SIZE: usize = 10_000;
+
+    let enriched = Enrichment::lookup(extra.enrichment.as_ref(), &prim.name);
+    let mut extractor = StreamingExtractor::new(&opts.extract_opts, prim.length);
+    let mut previous: Option<FeatureVector> = None;
+    let mut rows: Vec<(u32, OutputRow)> = Vec::new();
+
+    let mut emit = |fv: FeatureVector, columns: &Option<HashMap<usize, QueryColumns>>| {
+        let fv = match opts.extract_opts.output_filter(fv) {
+            Some(fv) => fv,
+            None => return,
+        };
+
+        let mut tags = row_tags(prim.tags, columns, &fv);
+        if opts.deltas {
+            let deltas = previous.as_ref().map(|prev| fv.deltas(prev));
+            tags.delta_entropy = Some(deltas.map(|deltas| deltas.delta_entropy));
+            tags.delta_unique_rate = Some(deltas.map(|deltas| deltas.delta_unique_rate));
+            previous = Some(fv.clone());
+        }
+        rows.push((prim.id, (fv, tags, enriched.clone(), opts.tags.clone())));
+
+        if rows.len() >= CHUNK_SIZE && opts.shuffle.is_none() {
+            let mut w = csv_writer.lock().unwrap();
+            rows.drain(..).for_each(|(prim_id, row)| if let Err(e) = w.serialize(prim_id, &row) {
+                cli::exit_with_error(e);
+            });
+        }
+    };
+
+    let records = domain.records().unwrap_or_else(|err| cli::exit_with_error(err));
+    for mut record in records {
+        let columns = take_query_columns(std::iter::once(&mut record), extra);
+        extractor.process(record).into_iter().for_each(|fv| emit(fv, &columns));
+    }
+    extractor.flush().into_iter().for_each(|fv| emit(fv, &None));
+
+    rows
+}
+
+fn extract_features(w: FeatureOutput<'_>, opts: &Opts, queries: QueryMap, spilled: &[SpilledDomain], prim_stats: &HashMap<u32, PrimaryDomainStats>, extra: &ExtraColumns) {
+    cli::print_output(format!("\n{}   {}Extracting features...\n", style("[2/2]").bold().dim(), WORKING), opts.quiet);
+
+    // Progress is weighted by domain size, so a few giant domains do not stall the bar at 99%
+    let total_weight = queries.keys().chain(spilled.iter().map(|domain| &domain.prim_id))
+        .map(|prim_id| domain_weight(prim_stats[prim_id].count))
+        .sum();
+    let pb = Arc::new(Mutex::new(cli::make_progress_bar(total_weight, opts.quiet)));
+
+    // Sizes of domains currently being processed (size -> number of domains), to show the largest one
+    let active: Mutex<BTreeMap<u32, usize>> = Mutex::new(BTreeMap::new());
+    let update_active = |count: u32, started: bool| {
+        let mut active = match active.lock() {
+            Ok(active) => active,
+            Err(_) => return, // soft fail
+        };
+
+        if started {
+            *active.entry(count).or_insert(0) += 1;
+        } else if let Some(n) = active.get_mut(&count) {
+            *n -= 1;
+            if *n == 0 { active.remove(&count); }
+        }
+
+        if let Ok(pb_lock) = pb.lock() {
+            if let (Some(pb), Some((largest, _))) = (pb_lock.as_ref(), active.iter().next_back()) {
+                pb.set_message(format!("largest active domain: {} entries", largest.to_formatted_string(&Locale::en)));
+            }
+        }
+    };
+
+    // Share CSV writer between threads
+    let csv_writer = Mutex::new(w);
+
+    // Process the largest domains first, so the extraction does not end waiting on a large domain started last
+    let mut queries: Vec<(u32, Vec<LogRecord>)> = queries.into_iter().collect();
+    queries.sort_unstable_by_key(|(prim_id, _)| std::cmp::Reverse(prim_stats[prim_id].count));
+
+    // Process queries
+    let mut features = queries.into_par_iter()
+        .with_max_len(1)
+        .map(|(prim_id, mut entries)| {
+            // Check for empty entry vec, so unwrap when ordering below is safe
+            if entries.is_empty() { return Vec::new(); }
+
+            // Sort entries by timestamp
+            entries.sort_by(|a, b| a.ts.total_cmp(&b.ts));
+
+            // Extract features
+            let prim = &prim_stats[&prim_id];
+            update_active(prim.count, true);
+            let columns = take_query_columns(entries.iter_mut(), extra);
+            let enriched = Enrichment::lookup(extra.enrichment.as_ref(), &prim.name);
+            let features = match opts.split_domains {
+                Some(threshold) if entries.len() > threshold => {
+                    // One segment per thread, so the warm-up (window before each segment) is done as few times as possible
+                    let segment_len = threshold.max(entries.len().div_ceil(rayon::current_num_threads()));
+                    extract_features_per_domain_split(&opts.extract_opts, entries, prim.length, segment_len)
+                }
+                _ => extract_features_per_domain(&opts.extract_opts, entries, prim.length),
+            };
+            let deltas: Vec<Option<FeatureDeltas>> = match opts.deltas {
+                true => (0..features.len()).map(|i| i.checked_sub(1).map(|prev| features[i].deltas(&features[prev]))).collect(),
+                false => Vec::new(),
+            };
+            let features: Vec<OutputRow> = features
+                .into_iter()
+                .enumerate()
+                .map(|(i, fv)| {
+                    let mut tags = row_tags(prim.tags, &columns, &fv);
+                    if opts.deltas {
+                        tags.delta_entropy = Some(deltas[i].map(|deltas| deltas.delta_entropy));
+                        tags.delta_unique_rate = Some(deltas[i].map(|deltas| deltas.delta_unique_rate));
+                    }
+                    (fv, tags, enriched.clone(), opts.tags.clone())
+                })
+                .collect();
+
+            // Write to output file from thread for 1000 vectors or more (performance improvement, empirically determined),
+            // unless all rows are shuffled at the end
+            let ret_val = if prim.count >= 1000 && opts.shuffle.is_none() {
+                let mut w = csv_writer.lock().unwrap();
+                features.iter().for_each(|fv| if let Err(e) = w.serialize(prim_id, fv) {
+                    cli::exit_with_error(e);
+                });
+                Vec::new()
+            } else { features.into_iter().map(|fv| (prim_id, fv)).collect() };
+
+            // Update progress bar (soft fail on error)
+            update_active(prim.count, false);
+            if let Ok(pb_lock) = pb.lock() {
+                if Option::is_some(&pb_lock) { pb_lock.as_ref().unwrap().inc(domain_weight(prim.count)); }
+            }
+
+            ret_val
+        })
+        .flatten().collect::<Vec<_>>();
+
+    // Stream the spilled domains (largest first)
+    let spilled_rows = spilled.par_iter()
+        .with_max_len(1)
+        .map(|domain| {
+            let prim = &prim_stats[&domain.prim_id];
+            update_active(prim.count, true);
+            let rows = extract_spilled_domain(domain, opts, prim, extra, &csv_writer);
+
+            // Update progress bar (soft fail on error)
+            update_active(prim.count, false);
+            if let Ok(pb_lock) = pb.lock() {
+                if Option::is_some(&pb_lock) { pb_lock.as_ref().unwrap().inc(domain_weight(prim.count)); }
+            }
+
+            rows
+        })
+        .flatten().collect::<Vec<_>>();
+    features.extend(spilled_rows);
+
+    if let Some(seed) = opts.shuffle { shuffle_rows(&mut features, seed); }
+
+    // Finalize progress bar (soft fail on error)
+    if let Ok(pb_lock) = pb.lock() {
+        if Option::is_some(&pb_lock) { pb_lock.as_ref().unwrap().finish_with_message(""); }
+    }
+
+    // Write remaining feature vectors to file
+    let mut w = csv_writer.into_inner().unwrap();
+    features.iter().for_each(|(prim_id, fv)| if let Err(e) = w.serialize(*prim_id, fv) {
+        cli::exit_with_error(e);
+    });
+
+    if let Err(e) = w.finish() {
+        cli::exit_with_error(e);
+    }
+}
+
+fn main() {
+    let opts = parse_opts();
+
+    // Load input data
+    let start = Instant::now();
+    let (queries, prim_stats, spilled) = consume_input(&opts);
+
+    // Label and GeoIP columns are only written if the input log had a label field or GeoIP lookups
+    let has_labels = queries.values().flatten().any(|record| record.label.is_some()) || spilled.as_ref().is_some_and(|spilled| spilled.has_labels);
+    let has_client_geo = queries.values().flatten().any(|record| record.client_geo.is_some()) || spilled.as_ref().is_some_and(|spilled| spilled.has_client_geo);
+    let has_resolver_geo = queries.values().flatten().any(|record| record.resolver_geo.is_some()) || spilled.as_ref().is_some_and(|spilled| spilled.has_resolver_geo);
+    let spilled_domains = spilled.as_ref().map_or(&[][..], |spilled| &spilled.domains[..]);
+
+    // First pass over all domains for corpus-level label statistics
+    let idf = if opts.label_idf {
+        Some(LabelIdf::from_domains(queries.values().map(|records| records.as_slice())))
+    } else { None };
+
+    let extra = ExtraColumns {
+        has_labels,
+        has_client_geo,
+        has_resolver_geo,
+        idf: idf.as_ref(),
+        enrichment: opts.enrichment.as_ref().map(|source| Enrichment::new(source)),
+    };
+
+    // Primary domains of all records for a bundle, taken before the records are consumed
+    let bundle_domains = opts.bundle.as_ref().map(|_| {
+        let mut domains: Vec<BundleDomain> = prim_stats.values()
+            .map(|stats| BundleDomain { prim_id: stats.id, name: stats.name.clone(), count: stats.count, tags: stats.tags.0 })
+            .collect();
+        domains.sort_by_key(|domain| domain.prim_id);
+        let mut record_domains: Vec<(usize, u32)> = queries.iter()
+            .flat_map(|(prim_id, records)| records.iter().map(move |record| (record.id, *prim_id)))
+            .collect();
+        for domain in spilled_domains {
+            let record_ids = domain.record_ids().unwrap_or_else(|err| cli::exit_with_error(err));
+            record_domains.extend(record_ids.map(|record_id| (record_id, domain.prim_id)));
+        }
+        record_domains.sort_unstable();
+        (domains, record_domains)
+    });
+
+    // Time series of the records, before they are consumed by the extraction
+    if let Some(file) = &opts.out_timeseries {
+        write_time_series(file, &opts, &queries, spilled_domains, &prim_stats);
+    }
+
+    // Extract features
+    let column_filter: Option<ColumnFilter> = if opts.reverse_zones { Some(FeatureVector::is_reverse_zone_column) } else { None };
+    let csv_output = match &opts.partitioning {
+        Some(partitioning) => {
+            let domain_parts = partitioning.assign(prim_stats.values().map(|stats| (stats.id, stats.count)));
+            CsvOutput::Parts(PartWriter::new(partitioning, &opts.out_parts, domain_parts, opts.float_format, column_filter), &opts.out_features)
+        }
+        None => CsvOutput::Single(Box::new(FeatureWriter::new(&opts.out_features, opts.rotation.clone(), opts.float_format, column_filter))),
+    };
+    let writer = FeatureOutput::new(csv_output, opts.out_hdf5.as_ref(), column_filter);
+    if let Some(duration) = opts.extract_opts.global {
+        extract_global_features(writer, &opts, queries, &prim_stats, duration, &extra);
+    } else {
+        extract_features(writer, &opts, queries, spilled_domains, &prim_stats, &extra);
+    }
+
+    if let Err(e) = opts.out_features.commit() {
+        cli::exit_with_error(e);
+    }
+
+    if let (Some(bundle), Some((domains, record_domains))) = (&opts.bundle, bundle_domains) {
+        write_bundle(bundle, &opts, domains, record_domains);
+    }
+
+    // Record checksums of all outputs, for verification
+    if let Err(e) = cli::write_run_metadata(&opts.run_metadata, Some(FeatureVector::SCHEMA_VERSION)) {
+        cli::exit_with_error(e);
+    }
+
+    if let Some(cost) = &opts.timings {
+        eprintln!("\n        Time per stage (summed over threads):");
+        cost.to_string().lines().for_each(|line| eprintln!("        {}", line));
+    }
+
+    // Print total duration
+    eprintln!("\n        {}Finished in {:.1?}", SPARKLE, start.elapsed());
+}
@@ -0,0 +1,367 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use csv::QuoteStyle;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+
+use pipeline_cli::{FloatFormat, Formatted, OutputFile};
+use pipeline_core::feature_extraction::{FeatureMatrix, FeatureVector};
+
+use crate::OutputRow;
+
+type CsvWriter<'a> = csv::Writer<CountingWriter<GzEncoder<BufWriter<Box<dyn Write + Send + 'a>>>>>;
+
+/// Selection of the output columns by name (e.g. `FeatureVector::is_reverse_zone_column` with `--reverse-zones`).
+pub type ColumnFilter = fn(&str) -> bool;
+
+/// Numbered output files (`{stem}.0001.csv.gz`, ...), a new file is started after `max_bytes` of uncompressed CSV.
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    pub stem: String,
+    pub max_bytes: u64,
+}
+
+/// Stem of an output path, e.g. `out_features.csv.gz` -> `out_features`.
+pub fn output_stem(path: &str) -> &str {
+    path.strip_suffix(".csv.gz").or_else(|| path.strip_suffix(".gz")).unwrap_or(path)
+}
+
+impl Rotation {
+    pub fn new(path: &str, max_bytes: u64) -> Self {
+        Rotation { stem: output_stem(path).to_string(), max_bytes }
+    }
+
+    pub fn path(&self, index: usize) -> String {
+        format!("{}.{:04}.csv.gz", self.stem, index)
+    }
+}
+
+/// Output in part files (`{stem}.part-0000.csv.gz`, ...) for parallel processing, each primary domain in one part, with
+/// a manifest (`{stem}.manifest.json`) to verify completeness.
+#[derive(Debug, Clone)]
+pub struct Partitioning {
+    pub stem: String,
+    pub n_parts: usize,
+}
+
+impl Partitioning {
+    pub fn new(path: &str, n_parts: usize) -> Self {
+        Partitioning { stem: output_stem(path).to_string(), n_parts }
+    }
+
+    pub fn part_path(&self, index: usize) -> String {
+        format!("{}.part-{:04}.csv.gz", self.stem, index)
+    }
+
+    pub fn manifest_path(&self) -> String {
+        format!("{}.manifest.json", self.stem)
+    }
+
+    /// Assign primary domains (ID, number of records) to parts with about the same number of records: the largest
+    /// domains first, each to the part with the fewest records so far.
+    pub fn assign(&self, domains: impl Iterator<Item=(u32, u32)>) -> HashMap<u32, usize> {
+        let mut domains: Vec<(u32, u32)> = domains.collect();
+        domains.sort_unstable_by_key(|&(prim_id, count)| (std::cmp::Reverse(count), prim_id));
+
+        let mut part_sizes = vec![0u64; self.n_parts];
+        domains.into_iter()
+            .map(|(prim_id, count)| {
+                let part = (0..self.n_parts).min_by_key(|&part| (part_sizes[part], part)).unwrap_or(0);
+                part_sizes[part] += count as u64;
+                (prim_id, part)
+            })
+            .collect()
+    }
+}
+
+/// Manifest of a partitioned output, written after all parts are complete.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    /// `FeatureVector::SCHEMA_VERSION` of the feature vectors.
+    pub schema_version: u32,
+    pub n_rows: u64,
+    pub n_domains: usize,
+    pub parts: Vec<ManifestPart>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestPart {
+    /// File name, relative to the manifest.
+    pub file: String,
+    pub n_rows: u64,
+    /// Size of the (compressed) file and its CRC-32 checksum (hexadecimal).
+    pub n_bytes: u64,
+    pub crc32: String,
+    /// Primary domain IDs of the rows in this part.
+    pub domains: Vec<u32>,
+}
+
+/// Feature vectors of one part and its counts.
+struct Part<'a> {
+    writer: FeatureWriter<'a>,
+    file: &'a OutputFile,
+    name: String,
+    n_rows: u64,
+    domains: BTreeSet<u32>,
+}
+
+/// Writer of the part files of a partitioned output.
+pub struct PartWriter<'a> {
+    parts: Vec<Part<'a>>,
+    domain_parts: HashMap<u32, usize>,
+}
+
+impl<'a> PartWriter<'a> {
+    /// Create a writer for the part `files` (in order of `partitioning`), with domains assigned by `domain_parts`.
+    pub fn new(partitioning: &Partitioning, files: &'a [OutputFile], domain_parts: HashMap<u32, usize>, float_format: FloatFormat, column_filter: Option<ColumnFilter>) -> Self {
+        let parts = files.iter().enumerate()
+            .map(|(index, file)| {
+                let bytes_written = Arc::new(AtomicU64::new(0));
+                let writer = make_csv_writer(Box::new(file), bytes_written.clone());
+                let path = partitioning.part_path(index);
+                let name = path.rsplit(['/', '\\']).next().unwrap_or(&path).to_string();
+                Part { writer: FeatureWriter { writer, bytes_written, write_header: true, rotation: None, rotated_file: None, float_format, column_filter, kept: None }, file, name, n_rows: 0, domains: BTreeSet::new() }
+            })
+            .collect();
+        PartWriter { parts, domain_parts }
+    }
+
+    pub fn serialize(&mut self, prim_id: u32, row: &OutputRow) -> csv::Result<()> {
+        let index = self.domain_parts.get(&prim_id).copied().unwrap_or(0);
+        let part = &mut self.parts[index];
+        part.n_rows += 1;
+        part.domains.insert(prim_id);
+        part.writer.serialize(row)
+    }
+
+    /// Finish and commit all parts, then write the manifest to `manifest_file` (committed by the caller).
+    pub fn finish(self, manifest_file: &OutputFile) -> csv::Result<()> {
+        let mut manifest = Manifest { schema_version: FeatureVector::SCHEMA_VERSION, n_rows: 0, n_domains: 0, parts: Vec::with_capacity(self.parts.len()) };
+        for part in self.parts {
+            part.writer.finish()?;
+            part.file.commit().map_err(to_io_error)?;
+
+            let (n_bytes, crc32) = part.file.checksum();
+            manifest.n_rows += part.n_rows;
+            manifest.n_domains += part.domains.len();
+            manifest.parts.push(ManifestPart { file: part.name, n_rows: part.n_rows, n_bytes, crc32, domains: part.domains.into_iter().collect() });
+        }
+
+        let mut writer = BufWriter::new(manifest_file);
+        serde_json::to_writer_pretty(&mut writer, &manifest).map_err(std::io::Error::other)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Feature vector output: a single (optionally rotating) file or part files, and optionally an HDF5 file.
+pub struct FeatureOutput<'a> {
+    csv: CsvOutput<'a>,
+    hdf5: Option<Hdf5Output<'a>>,
+}
+
+/// CSV output of the feature vectors.
+pub enum CsvOutput<'a> {
+    Single(Box<FeatureWriter<'a>>),
+    /// Part files and the manifest file.
+    Parts(PartWriter<'a>, &'a OutputFile),
+}
+
+impl<'a> FeatureOutput<'a> {
+    pub fn new(csv: CsvOutput<'a>, hdf5_file: Option<&'a OutputFile>, column_filter: Option<ColumnFilter>) -> Self {
+        FeatureOutput { csv, hdf5: hdf5_file.map(|file| Hdf5Output { file, matrices: Vec::new(), column_filter }) }
+    }
+
+    pub fn serialize(&mut self, prim_id: u32, row: &OutputRow) -> csv::Result<()> {
+        if let Some(hdf5) = &mut self.hdf5 {
+            hdf5.push(&row.0)?;
+        }
+        match &mut self.csv {
+            CsvOutput::Single(writer) => writer.serialize(row),
+            CsvOutput::Parts(writer, _) => writer.serialize(prim_id, row),
+        }
+    }
+
+    pub fn finish(self) -> csv::Result<()> {
+        match self.csv {
+            CsvOutput::Single(writer) => writer.finish()?,
+            CsvOutput::Parts(writer, manifest_file) => writer.finish(manifest_file)?,
+        }
+        match self.hdf5 {
+            Some(hdf5) => hdf5.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Feature matrices per family, kept in memory and written to an HDF5 file (`--out-hdf5`) when the output is finished.
+/// Only the feature columns are written, tags and enrichment columns are not numeric.
+struct Hdf5Output<'a> {
+    file: &'a OutputFile,
+    matrices: Vec<(&'static str, FeatureMatrix)>,
+    column_filter: Option<ColumnFilter>,
+}
+
+impl Hdf5Output<'_> {
+    fn push(&mut self, fv: &FeatureVector) -> csv::Result<()> {
+        let index = match self.matrices.iter().position(|(family, _)| *family == fv.family()) {
+            Some(index) => index,
+            None => {
+                self.matrices.push((fv.family(), FeatureMatrix { columns: Vec::new(), ids: Vec::new(), values: Vec::new() }));
+                self.matrices.len() - 1
+            }
+        };
+        Ok(self.matrices[index].1.push(fv).map_err(std::io::Error::other)?)
+    }
+
+    /// Write the HDF5 file (the library needs a path, so it is written to a local staging file first) and commit it.
+    fn finish(mut self) -> csv::Result<()> {
+        if let Some(filter) = self.column_filter {
+            self.matrices.iter_mut().for_each(|(_, matrix)| matrix.retain_columns(filter));
+        }
+        let staging = std::env::temp_dir().join(format!("tdp-hdf5-{}.h5", std::process::id()));
+        FeatureMatrix::write_hdf5(&staging, &self.matrices).map_err(std::io::Error::other)?;
+
+        let mut writer = self.file;
+        let copied = File::open(&staging).and_then(|mut staged| std::io::copy(&mut staged, &mut writer));
+        std::fs::remove_file(&staging)?;
+        copied?;
+        Ok(self.file.commit().map_err(to_io_error)?)
+    }
+}
+
+/// Gzipped CSV writer for feature vectors, optionally rotating over multiple files (each with a header).
+pub struct FeatureWriter<'a> {
+    writer: CsvWriter<'a>,
+    bytes_written: Arc<AtomicU64>,
+    /// Whether the header is still to be written to the current file (before its first row).
+    write_header: bool,
+    rotation: Option<(Rotation, usize)>,
+    /// Current numbered file after the first rotation, committed when it is complete (the first file is the caller's).
+    rotated_file: Option<OutputFile>,
+    float_format: FloatFormat,
+    column_filter: Option<ColumnFilter>,
+    /// Which columns are kept by `column_filter`, determined from the first row.
+    kept: Option<Vec<bool>>,
+}
+
+impl<'a> FeatureWriter<'a> {
+    /// Create a writer for `file`, which is the first numbered file if `rotation` is given. No header is written when
+    /// appending to existing data.
+    pub fn new(file: &'a OutputFile, rotation: Option<Rotation>, float_format: FloatFormat, column_filter: Option<ColumnFilter>) -> Self {
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        let writer = make_csv_writer(Box::new(file), bytes_written.clone());
+        FeatureWriter { writer, bytes_written, write_header: !file.has_existing_data(), rotation: rotation.map(|rotation| (rotation, 1)), rotated_file: None, float_format, column_filter, kept: None }
+    }
+
+    pub fn serialize(&mut self, row: &OutputRow) -> csv::Result<()> {
+        if let Some((rotation, index)) = &mut self.rotation {
+            if self.bytes_written.load(Ordering::Relaxed) >= rotation.max_bytes {
+                *index += 1;
+                self.bytes_written = Arc::new(AtomicU64::new(0));
+                let next_file = OutputFile::create(rotation.path(*index)).map_err(to_io_error)?;
+                let next = make_csv_writer(Box::new(next_file.try_clone_writer()?), self.bytes_written.clone());
+                finish(std::mem::replace(&mut self.writer, next))?;
+                self.write_header = true;
+                if let Some(file) = self.rotated_file.replace(next_file) {
+                    file.commit().map_err(to_io_error)?;
+                }
+            }
+        }
+
+        if let (Some(filter), None) = (self.column_filter, &self.kept) {
+            self.kept = Some(header(row)?.iter().map(|column| filter(column)).collect());
+        }
+        if self.write_header {
+            let header = header(row)?;
+            self.writer.write_record(select(header.iter().map(String::as_bytes), self.kept.as_deref()))?;
+            self.write_header = false;
+        }
+        match &self.kept {
+            // The row is serialized on its own first to drop fields, which is slower, but only used for reverse zones
+            Some(kept) => self.writer.write_record(select(&to_record(&Formatted(row, self.float_format), false)?, Some(kept))),
+            None => self.writer.serialize(Formatted(row, self.float_format)),
+        }
+    }
+
+    /// Flush and finish the gzip stream of the current file.
+    pub fn finish(self) -> csv::Result<()> {
+        finish(self.writer)?;
+        match self.rotated_file {
+            Some(file) => Ok(file.commit().map_err(to_io_error)?),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Column names of an output row: the feature and tag columns (their field names, as derived by `csv`), followed by the
+/// enrichment and `--tag` columns, whose names are only known at run time.
+pub fn header(row: &OutputRow) -> csv::Result<Vec<String>> {
+    let mut columns = to_record(&(&row.0, &row.1), true)?
+        .iter().map(|column| String::from_utf8_lossy(column).into_owned()).collect::<Vec<String>>();
+    columns.extend(row.2.columns.iter().cloned());
+    columns.extend(row.3.0.iter().map(|(key, _)| key.clone()));
+    Ok(columns)
+}
+
+/// Fields of a value as written by `csv`, or its header (the first record) if `header` is set.
+fn to_record<T: Serialize>(value: &T, header: bool) -> csv::Result<csv::ByteRecord> {
+    let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(Vec::new());
+    writer.serialize(value)?;
+    let data = writer.into_inner().map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))?;
+
+    let mut record = csv::ByteRecord::new();
+    csv::ReaderBuilder::new().has_headers(false).from_reader(&data[..]).read_byte_record(&mut record)?;
+    Ok(record)
+}
+
+/// Fields of a record that are kept (all without selection).
+fn select<'r, I: IntoIterator<Item=&'r [u8]>>(fields: I, kept: Option<&'r [bool]>) -> impl Iterator<Item=&'r [u8]> {
+    fields.into_iter().enumerate()
+        .filter(move |(i, _)| kept.is_none_or(|kept| kept.get(*i).copied().unwrap_or(true)))
+        .map(|(_, field)| field)
+}
+
+/// CSV writer without automatic header, as the header (see `header`) is written with the first row of every file.
+fn make_csv_writer<'a>(file: Box<dyn Write + Send + 'a>, count: Arc<AtomicU64>) -> CsvWriter<'a> {
+    let gz_writer = GzEncoder::new(BufWriter::new(file), Compression::fast());
+    csv::WriterBuilder::new()
+        .flexible(true) // faster, no checking
+        .has_headers(false)
+        .quote_style(QuoteStyle::Necessary) // only free-text columns such as labels can need quotes
+        .from_writer(CountingWriter { inner: gz_writer, count })
+}
+
+fn to_io_error(err: pipeline_cli::CliError) -> std::io::Error {
+    std::io::Error::other(err)
+}
+
+fn finish(writer: CsvWriter<'_>) -> csv::Result<()> {
+    let counting = writer.into_inner()
+        .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))?;
+    counting.inner.finish()?.flush()?;
+    Ok(())
+}
+
+/// Writer that counts the number of bytes written to it (in a counter shared with the `FeatureWriter`).
+struct CountingWriter<W> {
+    inner: W,
+    count: Arc<AtomicU64>,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use pipeline_cli::{CliError, RecordSorter};
+use pipeline_core::shared_interface::{Codec, CodecError, CodecReader, LogRecord};
+
+/// Records of the primary domains with more than `threshold` records, which are written to one temporary file per
+/// domain while loading instead of kept in memory.
+pub struct Spill {
+    dir: SpillDir,
+    threshold: u32,
+    writers: HashMap<u32, SpillWriter>,
+    // Whether any spilled record has a label or GeoIP information (as these columns depend on all records)
+    has_labels: bool,
+    has_client_geo: bool,
+    has_resolver_geo: bool,
+}
+
+struct SpillWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    n_records: usize,
+    last_ts: f64,
+    sorted: bool,
+}
+
+/// Records of a spilled primary domain.
+pub struct SpilledDomain {
+    pub prim_id: u32,
+    pub n_records: usize,
+    path: PathBuf,
+    /// Whether the records were written in timestamp order, so they can be streamed.
+    sorted: bool,
+    /// Number of records per sorted run if they were not.
+    run_capacity: usize,
+}
+
+/// Spilled primary domains (largest first), whose files are removed on drop.
+pub struct Spilled {
+    pub domains: Vec<SpilledDomain>,
+    pub has_labels: bool,
+    pub has_client_geo: bool,
+    pub has_resolver_geo: bool,
+    _dir: SpillDir,
+}
+
+/// Temporary directory of the spilled records, removed on drop.
+struct SpillDir(PathBuf);
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        // (soft fail, only leaves temporary files behind)
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Spill files are only read back by this process
+const SPILL_CODEC: Codec = Codec::Bincode;
+
+/// Maximum number of sorted runs of an unsorted spill file, which are all open while merging.
+const MAX_RUNS: usize = 256;
+
+impl Spill {
+    /// Spill domains with more than `threshold` records to a new directory in the system temporary directory (`TMPDIR`).
+    pub fn new(threshold: u32) -> Result<Self, CliError> {
+        let path = std::env::temp_dir().join(format!("tdp-spill-{}", std::process::id()));
+        std::fs::create_dir_all(&path).map_err(|err| CliError::IO(path.display().to_string(), err))?;
+        Ok(Spill { dir: SpillDir(path), threshold, writers: HashMap::new(), has_labels: false, has_client_geo: false, has_resolver_geo: false })
+    }
+
+    /// Whether the records of a primary domain with `count` records are spilled.
+    pub fn spills(&self, count: u32) -> bool {
+        count > self.threshold
+    }
+
+    pub fn push(&mut self, prim_id: u32, record: &LogRecord) -> Result<(), CliError> {
+        self.has_labels |= record.label.is_some();
+        self.has_client_geo |= record.client_geo.is_some();
+        self.has_resolver_geo |= record.resolver_geo.is_some();
+
+        let writer = match self.writers.get_mut(&prim_id) {
+            Some(writer) => writer,
+            None => {
+                let path = self.dir.0.join(format!("{}.bin", prim_id));
+                let file = File::create(&path).map_err(|err| CliError::IO(path.display().to_string(), err))?;
+                self.writers.entry(prim_id).or_insert(SpillWriter { writer: BufWriter::new(file), path, n_records: 0, last_ts: f64::NEG_INFINITY, sorted: true })
+            }
+        };
+
+        writer.sorted &= record.ts >= writer.last_ts;
+        writer.last_ts = record.ts;
+        writer.n_records += 1;
+        SPILL_CODEC.serialize_into(&mut writer.writer, record).map_err(CliError::from)
+    }
+
+    /// Flush and close the spill files.
+    pub fn finish(self) -> Result<Spilled, CliError> {
+        let mut domains = Vec::with_capacity(self.writers.len());
+        for (prim_id, mut writer) in self.writers {
+            writer.writer.flush().map_err(|err| CliError::IO(writer.path.display().to_string(), err))?;
+            let run_capacity = (self.threshold as usize).max(writer.n_records.div_ceil(MAX_RUNS));
+            domains.push(SpilledDomain { prim_id, n_records: writer.n_records, path: writer.path, sorted: writer.sorted, run_capacity });
+        }
+        domains.sort_unstable_by_key(|domain| std::cmp::Reverse(domain.n_records));
+        Ok(Spilled { domains, has_labels: self.has_labels, has_client_geo: self.has_client_geo, has_resolver_geo: self.has_resolver_geo, _dir: self.dir })
+    }
+}
+
+impl SpilledDomain {
+    /// Records in the order they were written (record ID order).
+    fn read(&self) -> Result<impl Iterator<Item=LogRecord> + '_, CliError> {
+        let file = File::open(&self.path).map_err(|err| CliError::IO(self.path.display().to_string(), err))?;
+        Ok(CodecReader::values(BufReader::new(file), SPILL_CODEC).map(move |record| record.unwrap_or_else(|err| exit_with_spill_error(self, err))))
+    }
+
+    /// IDs of the records (e.g. for a bundle), read from the spill file.
+    pub fn record_ids(&self) -> Result<impl Iterator<Item=usize> + '_, CliError> {
+        Ok(self.read()?.map(|record| record.id))
+    }
+
+    /// Records in timestamp order: streamed from the spill file if they were written in order, otherwise (e.g. for
+    /// merged logs of multiple sensors) sorted externally, in runs of at least the spill threshold, and merged.
+    pub fn records(&self) -> Result<Box<dyn Iterator<Item=LogRecord> + '_>, CliError> {
+        let records = self.read()?;
+        if self.sorted { return Ok(Box::new(records)); }
+
+        // Equal timestamps keep their record ID order, as the records are pushed in that order
+        let mut sorter = RecordSorter::new(self.run_capacity);
+        for record in records { sorter.push((self.prim_id, record))?; }
+        Ok(Box::new(sorter.into_sorted()?.map(|entry| entry.map(|(_, record)| record).unwrap_or_else(|err| pipeline_cli::exit_with_error(err)))))
+    }
+}
+
+fn exit_with_spill_error(domain: &SpilledDomain, err: CodecError) -> ! {
+    pipeline_cli::exit_with_error(CliError::Intermediate(domain.path.display().to_string(), err))
+}
@@ -0,0 +1,58 @@
+#[macro_use]
+extern crate clap;
+
+use std::path::{Path, PathBuf};
+
+use clap::App;
+use dialoguer::console::style;
+
+use pipeline_cli as cli;
+use pipeline_cli::fixtures::{self, INPUT_LOG};
+
+fn write(path: &Path, contents: &str) {
+    if let Err(e) = std::fs::write(path, contents) {
+        cli::exit_with_error(cli::CliError::IO(path.display().to_string(), e));
+    }
+}
+
+fn main() {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+    let quiet = m.is_present("quiet");
+    let out_dir = PathBuf::from(m.value_of("out_dir").unwrap_or_default());
+
+    let bin_dir = match m.value_of("bin_dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::current_exe() {
+            Ok(exe) => exe.parent().map(Path::to_path_buf).unwrap_or_default(),
+            Err(e) => cli::exit_with_error(e)
+        }
+    };
+
+    // Pipeline runs in a temporary directory, only the input log and outputs are written to the fixture directory
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-fixtures-{}", std::process::id()));
+
+    let fixtures = fixtures::fixtures();
+    for fixture in fixtures.iter() {
+        let dir = out_dir.join(fixture.name);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            cli::exit_with_error(cli::CliError::IO(dir.display().to_string(), e));
+        }
+        write(&dir.join(INPUT_LOG), &fixture.log);
+
+        if !m.is_present("logs_only") {
+            let outputs = fixtures::run_pipeline(&bin_dir, fixture, &tmp_dir.join(fixture.name)).unwrap_or_else(|err| cli::exit_with_error(err));
+            outputs.iter().for_each(|(name, contents)| write(&dir.join(name), contents));
+        }
+        cli::print_output(format!("{}  {}\n", style("WRITTEN ").green(), dir.display()), quiet);
+    }
+
+    if tmp_dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(&tmp_dir) {
+            cli::exit_with_error(cli::CliError::IO(tmp_dir.display().to_string(), e));
+        }
+    }
+    cli::print_output(format!("\n{} fixtures written to {}\n", fixtures.len(), out_dir.display()), quiet);
+}
@@ -0,0 +1,880 @@
+#[macro_use]
+extern crate clap;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono_tz::Tz;
+use clap::App;
+use dialoguer::console::{Emoji, style};
+use linereader::LineReader;
+use num_format::{Locale, ToFormattedString};
+
+use pipeline_cli as cli;
+use pipeline_cli::{OutputFile, RecordSorter, Verbosity};
+use pipeline_core::enrichment::GeoIpLookup;
+use pipeline_core::feature_extraction::{HyperLogLog, query_key};
+use pipeline_core::metrics::{Metrics, serve_metrics};
+use pipeline_core::object_store::is_object_uri;
+use pipeline_core::parse_dns::{ALLOWLIST_DOMAINS, download_suffix_list, DYNDNS_PROVIDERS, is_reverse_lookup, load_domain_list, parse_dns_with_config, ParseDnsConfig, ParsedDns, ReservedSuffixMode, SuffixList, ValidationProfile, WildcardConfig, WildcardDetector};
+use pipeline_core::parse_log::{ClockMonitor, detect_separator, escape_bytes, EscapeSyntax, LineParser, LogSchema, NormalizeLineEndings, parse_log_line_with_schema, ParsedLineRef, SequenceGaps};
+use pipeline_core::shared_interface::{Codec, DomainTags, LogRecord, PrimaryDomainStats, RecordIndex, SerializedLogEntry};
+
+use crate::client_cap::ClientCap;
+use crate::parse_stats::ParseStats;
+
+mod client_cap;
+mod parse_stats;
+
+static PAPER: Emoji<'_, '_> = Emoji("📃 ", "");
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", "");
+static BAR_CHART: Emoji<'_, '_> = Emoji("📊 ", "");
+
+struct Opts {
+    schema: LogSchema,
+    /// Whether the field separator was detected from the input (tab is assumed otherwise), None if it was given.
+    detected_separator: Option<bool>,
+    max_per_client_hour: Option<u32>,
+    dns_config: ParseDnsConfig,
+    geoip: Option<GeoIpLookup>,
+    wildcard: Option<WildcardConfig>,
+    /// Keep one in this many records of wildcard-like primary domains after detection.
+    wildcard_sample: Option<u32>,
+    /// Input files with the clock offset (seconds) added to their timestamps, in order of their first (corrected)
+    /// timestamp.
+    in_files: Vec<(PathBuf, File, f64)>,
+    /// Input of unknown length instead of files: a FIFO, Unix domain socket, followed file or objects.
+    in_stream: Option<Box<dyn Read>>,
+    /// Whether the input is continuous (not objects), records are then written as they are processed.
+    live: bool,
+    /// Minimum backward jump (seconds) in timestamps to warn about.
+    clock_jump: f64,
+    /// Value at which the sequence numbers of the input wrap around.
+    seq_modulus: Option<u64>,
+    /// Abort on the first malformed input line instead of skipping it.
+    strict: bool,
+    out_records: OutputFile,
+    out_prim: OutputFile,
+    /// Separate record and primary domain outputs for reverse lookups (`in-addr.arpa` and `ip6.arpa`).
+    out_reverse: Option<(OutputFile, OutputFile)>,
+    out_parse_stats: Option<OutputFile>,
+    /// Plaintext `prim_id<TAB>primary_domain` mapping of all primary domains.
+    out_domains: Option<OutputFile>,
+    /// Byte ranges of the records of every primary domain in `out_records`.
+    out_index: Option<OutputFile>,
+    /// Sort the records by primary domain ID and timestamp, buffering at most this many in memory.
+    sort_records: Option<usize>,
+    /// Counters served in the Prometheus format while processing.
+    metrics: Option<Arc<Metrics>>,
+    /// Run metadata with checksums of all outputs, next to the records.
+    run_metadata: String,
+    codec: Codec,
+    verbosity: Verbosity,
+    /// Print one in this many dropped lines at `Verbosity::Debug`.
+    sample_dropped: u64,
+}
+
+fn parse_opts() -> Opts {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+
+    let verbosity = Verbosity::from_matches(&m);
+    let quiet = verbosity.is_quiet();
+
+    // Input log layout, the field separator is detected from the first lines of the input unless given
+    let separator = match m.value_of("separator").unwrap_or("auto") {
+        "auto" => None,
+        separator => Some(separator.replace("\\t", "\t").into_bytes()),
+    };
+    if separator.as_ref().is_some_and(|separator| separator.is_empty()) {
+        let err = cli::CliError::InvalidArgument(String::from("-s/--separator"), String::from("separator is empty"));
+        cli::exit_with_error(err)
+    }
+
+    let quote = match m.value_of("quote") {
+        Some(quote) if quote.len() == 1 => Some(quote.as_bytes()[0]),
+        Some(_) => {
+            let err = cli::CliError::InvalidArgument(String::from("--quote"), String::from("quote must be a single ASCII character"));
+            cli::exit_with_error(err)
+        }
+        None => None
+    };
+
+    let assume_tz = match m.value_of("assume_tz").unwrap_or("UTC").parse::<Tz>() {
+        Ok(tz) => tz,
+        Err(msg) => {
+            let err = cli::CliError::InvalidArgument(String::from("--assume-tz"), msg.to_string());
+            cli::exit_with_error(err)
+        }
+    };
+
+    let escapes = match m.value_of("escapes").unwrap_or("zeek").parse::<EscapeSyntax>() {
+        Ok(escapes) => escapes,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--escapes"), msg))
+    };
+
+    let ts_decimals = if m.is_present("ts_decimals") {
+        let decimals = cli::value_or_exit::<u8>(&m, "ts_decimals");
+        if decimals > 9 {
+            let err = cli::CliError::InvalidArgument(String::from("--ts-decimals"), String::from("at most 9 decimals (nanoseconds)"));
+            cli::exit_with_error(err)
+        }
+        Some(decimals)
+    } else { None };
+
+    let mut schema = LogSchema {
+        separator: separator.clone().unwrap_or_else(|| b"\t".to_vec()),
+        quote,
+        assume_tz,
+        client_field: m.is_present("client_field"),
+        resolver_field: m.is_present("resolver_field"),
+        ecs_field: m.is_present("ecs_field"),
+        label_field: m.is_present("label_field"),
+        ttl_field: m.is_present("ttl_field"),
+        cname_field: m.is_present("cname_field"),
+        seq_field: m.is_present("seq_field"),
+        escapes,
+        ts_decimals,
+    };
+
+    let max_per_client_hour = if m.is_present("max_per_client_hour") {
+        let cap = cli::value_or_exit::<u32>(&m, "max_per_client_hour");
+        if cap == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--max-per-client-hour"), String::from("cap must be at least 1"));
+            cli::exit_with_error(err)
+        }
+        Some(cap)
+    } else { None };
+
+    // DNS filter configuration
+    let reserved_suffix = match m.value_of("reserved").unwrap_or("drop") {
+        "drop" => ReservedSuffixMode::Drop,
+        "keep" => ReservedSuffixMode::Keep,
+        "keep-and-tag" => ReservedSuffixMode::KeepAndTag,
+        _ => {
+            let err = cli::CliError::InvalidArgument(String::from("--reserved"), String::from("expected drop, keep or keep-and-tag"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let validation = match m.value_of("validation").unwrap_or("strict") {
+        "strict" => ValidationProfile::Strict,
+        "lenient" => ValidationProfile::Lenient,
+        _ => {
+            let err = cli::CliError::InvalidArgument(String::from("--validation"), String::from("expected strict or lenient"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let suffix_list = match m.value_of("psl") {
+        Some(path) if m.is_present("update_psl") => match download_suffix_list(Path::new(path)) {
+            Ok(list) => list,
+            Err(err) => cli::exit_with_error(err)
+        }
+        Some(path) => match SuffixList::from_file(Path::new(path)) {
+            Ok(list) => list,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => SuffixList::Builtin
+    };
+
+    let geoip = m.values_of("geoip").map(|inputs| {
+        let paths: Vec<&str> = inputs.collect();
+        for path in &paths {
+            if let Err(err) = cli::parse_input_file(path) { cli::exit_with_error(err) }
+        }
+        match GeoIpLookup::open(&paths) {
+            Ok(geoip) => geoip,
+            Err(err) => cli::exit_with_error(err)
+        }
+    });
+
+    let mut dyndns_providers: Vec<String> = Vec::new();
+    if m.is_present("dyndns") { dyndns_providers.extend(DYNDNS_PROVIDERS.iter().map(|&provider| String::from(provider))); }
+    if let Some(path) = m.value_of("dyndns_list") {
+        match cli::parse_input_file(path).and_then(|_| load_domain_list(Path::new(path)).map_err(|err| cli::CliError::IO(path.to_string(), err))) {
+            Ok(providers) => dyndns_providers.extend(providers),
+            Err(err) => cli::exit_with_error(err)
+        }
+    }
+
+    let mut allowlist: Vec<String> = Vec::new();
+    if m.is_present("tag_allowlisted") { allowlist.extend(ALLOWLIST_DOMAINS.iter().map(|&domain| String::from(domain))); }
+    if let Some(path) = m.value_of("allowlist") {
+        match cli::parse_input_file(path).and_then(|_| load_domain_list(Path::new(path)).map_err(|err| cli::CliError::IO(path.to_string(), err))) {
+            Ok(domains) => allowlist.extend(domains),
+            Err(err) => cli::exit_with_error(err)
+        }
+    }
+
+    let dns_config = ParseDnsConfig {
+        validation,
+        reserved_suffix,
+        unknown_suffix_fallback: m.is_present("keep_unknown_suffix"),
+        suffix_list,
+        generated_name_entropy: if m.is_present("tag_generated") {
+            let threshold = cli::value_or_exit::<f32>(&m, "tag_generated");
+            if threshold.is_nan() || threshold <= 0. {
+                let err = cli::CliError::InvalidArgument(String::from("--tag-generated"), String::from("entropy threshold must be positive"));
+                cli::exit_with_error(err)
+            }
+            Some(threshold)
+        } else { None },
+        dyndns_providers,
+        allowlist,
+        strip_labels: m.values_of("strip_labels")
+            .map(|labels| labels.map(|label| label.trim_end_matches('.').to_ascii_lowercase()).filter(|label| !label.is_empty()).collect())
+            .unwrap_or_default(),
+    };
+
+    let wildcard = if m.is_present("tag_wildcard") {
+        let min_unique_labels = cli::value_or_exit::<usize>(&m, "tag_wildcard");
+        if min_unique_labels == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--tag-wildcard"), String::from("number of unique labels must be at least 1"));
+            cli::exit_with_error(err)
+        }
+        Some(WildcardConfig { min_unique_labels, ..WildcardConfig::default() })
+    } else { None };
+
+    let wildcard_sample = if m.is_present("wildcard_sample") {
+        let sample = cli::value_or_exit::<u32>(&m, "wildcard_sample");
+        if sample == 0 {
+            let err = cli::CliError::InvalidArgument(String::from("--wildcard-sample"), String::from("sample rate must be at least 1"));
+            cli::exit_with_error(err)
+        }
+        Some(sample)
+    } else { None };
+
+    let codec = match m.value_of("codec").unwrap_or("bincode").parse::<Codec>() {
+        Ok(codec) => codec,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--codec"), msg))
+    };
+
+    let in_stream = open_live_input(&m);
+    let live = in_stream.is_some();
+
+    // Objects are streamed in the given order (prefixes in key order), not mixed with local files
+    let inputs: Vec<&str> = m.values_of("input_file").map(|inputs| inputs.collect()).unwrap_or_default();
+    let in_stream = if !live && inputs.iter().any(|input| is_object_uri(input)) {
+        if !inputs.iter().all(|input| is_object_uri(input)) {
+            cli::exit_with_error(cli::CliError::InvalidArgument(String::from("<input_file>"), String::from("cannot mix objects and local files")));
+        }
+        match cli::open_objects(&inputs) {
+            Ok(reader) => Some(Box::new(reader) as Box<dyn Read>),
+            Err(err) => cli::exit_with_error(err)
+        }
+    } else { in_stream };
+
+    // Whether the field separator was detected (None if given), from the first line of a stream
+    let mut detected_separator: Option<bool> = None;
+    let in_stream = match in_stream {
+        Some(stream) if separator.is_none() => {
+            let (stream, first_line) = peek_first_line(stream);
+            detected_separator = Some(detect_field_separator(&mut schema, &[first_line]));
+            Some(stream)
+        }
+        in_stream => in_stream,
+    };
+
+    // Records of continuous inputs are written in place, so they can be read while processing
+    let parse_records_file = if live { cli::parse_in_place_output_file } else { cli::parse_output_file };
+
+    // Clock skew correction per input file (or directory)
+    let clock_offsets: Vec<(PathBuf, f64)> = m.values_of("clock_offset").map(|values| values.map(|value| {
+        match value.rsplit_once('=').and_then(|(path, offset)| Some((PathBuf::from(path), offset.parse::<f64>().ok().filter(|offset| offset.is_finite())?))) {
+            Some(clock_offset) => clock_offset,
+            None => {
+                let err = cli::CliError::InvalidArgument(String::from("--clock-offset"), format!("expected <path>=<seconds>, got \"{}\"", value));
+                cli::exit_with_error(err)
+            }
+        }
+    }).collect()).unwrap_or_default();
+    if in_stream.is_some() && !clock_offsets.is_empty() {
+        cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--clock-offset"), String::from("requires local input files")));
+    }
+    let clock_jump = cli::value_or_exit::<f64>(&m, "clock_jump");
+    let seq_modulus = if m.is_present("seq_modulus") {
+        let modulus = cli::value_or_exit::<u64>(&m, "seq_modulus");
+        if modulus < 2 {
+            let err = cli::CliError::InvalidArgument(String::from("--seq-modulus"), String::from("modulus must be at least 2"));
+            cli::exit_with_error(err)
+        }
+        Some(modulus)
+    } else { None };
+    let sample_dropped = cli::value_or_exit::<u64>(&m, "sample_dropped");
+    if sample_dropped == 0 {
+        cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--sample-dropped"), String::from("must be at least 1")));
+    }
+
+    let in_files = if in_stream.is_some() { Vec::new() } else { match m.values_of("input_file") {
+        Some(inputs) => {
+            let mut paths: Vec<PathBuf> = Vec::new();
+            for input in inputs {
+                match cli::parse_input_paths(input) {
+                    Ok(input_paths) => for path in input_paths {
+                        if !paths.contains(&path) { paths.push(path); }
+                    }
+                    Err(err) => cli::exit_with_error(err)
+                }
+            }
+            if separator.is_none() {
+                let lines = paths.first().map(|path| read_first_lines(path, SEPARATOR_SAMPLE_LINES)).unwrap_or_default();
+                detected_separator = Some(detect_field_separator(&mut schema, &lines));
+            }
+            order_by_first_timestamp(paths, &schema, &clock_offsets)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("<input_file>"));
+            cli::exit_with_error(err)
+        }
+    } };
+
+    let out_records = match m.value_of("out_records") {
+        Some(input) => match parse_records_file(input, quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("--out-records"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let out_prim = match m.value_of("out_prim_stats") {
+        Some(input) => match cli::parse_output_file(input, quiet) {
+            Ok(file) => file,
+            Err(err) => cli::exit_with_error(err)
+        }
+        None => {
+            let err = cli::CliError::MissingInputArg(String::from("--out-prim"));
+            cli::exit_with_error(err)
+        }
+    };
+
+    let out_reverse = m.value_of("out_arpa_records").zip(m.value_of("out_arpa_prim")).map(|(records, prim)| {
+        match (parse_records_file(records, quiet), cli::parse_output_file(prim, quiet)) {
+            (Ok(records), Ok(prim)) => (records, prim),
+            (Err(err), _) | (_, Err(err)) => cli::exit_with_error(err)
+        }
+    });
+
+    let out_parse_stats = m.value_of("out_parse_stats").map(|input| match cli::parse_output_file(input, quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    });
+
+    let out_index = m.value_of("out_index").map(|input| match cli::parse_output_file(input, quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    });
+    let sort_records = if m.is_present("sort_records") { Some(cli::value_or_exit::<usize>(&m, "sort_buffer")) } else { None };
+    let out_domains = m.value_of("out_domains").map(|input| match cli::parse_output_file(input, quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    });
+
+    let run_metadata = format!("{}.meta.json", m.value_of("out_records").unwrap_or_default());
+
+    let metrics = m.value_of("metrics_addr").map(|addr| {
+        let metrics = Arc::new(Metrics::default());
+        if let Err(err) = serve_metrics(addr, metrics.clone()) { cli::exit_with_error(err) }
+        metrics
+    });
+
+    Opts { schema, detected_separator, max_per_client_hour, dns_config, geoip, wildcard, wildcard_sample, in_files, in_stream, live, clock_jump, seq_modulus, strict: m.is_present("strict"), out_records, out_prim, out_reverse, out_parse_stats, out_domains, out_index, sort_records, metrics, run_metadata, codec, verbosity, sample_dropped }
+}
+
+/// Lines from the start of the input to detect the field separator from.
+const SEPARATOR_SAMPLE_LINES: usize = 100;
+
+/// Detect the field separator of the schema from sample lines of the input. Returns whether it was detected, the
+/// separator of the schema (tab) is kept otherwise.
+fn detect_field_separator(schema: &mut LogSchema, lines: &[Vec<u8>]) -> bool {
+    match detect_separator(lines.iter().map(Vec::as_slice), schema) {
+        Some(separator) => {
+            schema.separator = separator;
+            true
+        }
+        None => false,
+    }
+}
+
+/// First lines of an input file, with normalized line endings.
+fn read_first_lines(path: &Path, n_lines: usize) -> Vec<Vec<u8>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => cli::exit_with_error(cli::CliError::IO(path.display().to_string(), e))
+    };
+
+    let mut reader = BufReader::new(NormalizeLineEndings::new(file));
+    let mut lines = Vec::new();
+    while lines.len() < n_lines {
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => lines.push(line),
+            Err(e) => cli::exit_with_error(cli::CliError::IO(path.display().to_string(), e))
+        }
+    }
+    lines
+}
+
+/// Read the first line of a stream (only one, as more may take long to arrive) and put it back in front of the stream.
+fn peek_first_line(stream: Box<dyn Read>) -> (Box<dyn Read>, Vec<u8>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    if let Err(e) = reader.read_until(b'\n', &mut line) {
+        cli::exit_with_error(cli::CliError::IO(String::from("<input_file>"), e));
+    }
+    (Box::new(Cursor::new(line.clone()).chain(reader)), line)
+}
+
+/// Name of a field separator for the summary.
+fn separator_name(separator: &[u8]) -> String {
+    match separator {
+        b"\t" => String::from("tab"),
+        b"," => String::from("comma"),
+        b"|" => String::from("pipe"),
+        b" " => String::from("space"),
+        separator => format!("\"{}\"", String::from_utf8_lossy(separator)),
+    }
+}
+
+/// Continuous input of `--stream` or `--follow`, if any.
+#[cfg(unix)]
+fn open_live_input(m: &clap::ArgMatches) -> Option<Box<dyn Read>> {
+    let idle_timeout = Duration::from_secs(cli::value_or_exit::<u64>(m, "stream_idle"));
+    if let Some(input) = m.value_of("stream") {
+        match cli::open_stream(input, idle_timeout) {
+            Ok(stream) => Some(Box::new(stream)),
+            Err(err) => cli::exit_with_error(err)
+        }
+    } else if m.is_present("follow") {
+        let inputs: Vec<&str> = m.values_of("input_file").map(|inputs| inputs.collect()).unwrap_or_default();
+        if inputs.len() != 1 {
+            cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--follow"), String::from("requires exactly one input file")));
+        }
+        match cli::follow_file(inputs[0], idle_timeout) {
+            Ok(reader) => Some(Box::new(reader)),
+            Err(err) => cli::exit_with_error(err)
+        }
+    } else { None }
+}
+
+/// FIFOs, Unix domain sockets and following files by inode are not available on other platforms.
+#[cfg(not(unix))]
+fn open_live_input(m: &clap::ArgMatches) -> Option<Box<dyn Read>> {
+    match ["stream", "follow"].iter().find(|arg| m.is_present(arg)) {
+        Some(arg) => cli::exit_with_error(cli::CliError::InvalidArgument(format!("--{}", arg), String::from("not supported on this platform"))),
+        None => None,
+    }
+}
+
+/// Open the input files and order them by the timestamp on their first line (e.g. daily rotated logs), files without a
+/// valid first line go last. Ties keep the (sorted) path order.
+/// Open the input files in order of their first timestamp, with the clock offset of the most specific matching path
+/// (a file or a directory of a sensor) added.
+fn order_by_first_timestamp(paths: Vec<PathBuf>, schema: &LogSchema, clock_offsets: &[(PathBuf, f64)]) -> Vec<(PathBuf, File, f64)> {
+    let mut files: Vec<(f64, PathBuf, File, f64)> = paths.into_iter()
+        .map(|path| {
+            let offset = clock_offsets.iter()
+                .filter(|(prefix, _)| path.starts_with(prefix))
+                .max_by_key(|(prefix, _)| prefix.components().count())
+                .map_or(0., |(_, offset)| *offset);
+
+            let mut file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => cli::exit_with_error(cli::CliError::IO(path.display().to_string(), e))
+            };
+
+            let mut first_line = Vec::new();
+            let result = BufReader::new(NormalizeLineEndings::new(&file)).read_until(b'\n', &mut first_line)
+                .and_then(|_| file.seek(SeekFrom::Start(0)));
+            if let Err(e) = result {
+                cli::exit_with_error(cli::CliError::IO(path.display().to_string(), e));
+            }
+
+            let ts = parse_log_line_with_schema(&first_line, schema).map_or(f64::INFINITY, |(ts, _)| ts + offset);
+            (ts, path, file, offset)
+        })
+        .collect();
+
+    files.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+    files.into_iter().map(|(_, path, file, offset)| (path, file, offset)).collect()
+}
+
+/// Primary domain statistics and record writer of an output.
+struct RecordOutput<'a> {
+    /// Primary domain <--> (id, length, count)
+    prim_map: HashMap<String, PrimaryDomainStats>,
+    record_writer: CountingWriter<BufWriter<&'a OutputFile>>,
+    out_prim: &'a OutputFile,
+    /// Index of the written records, if requested.
+    index: Option<(RecordIndex, &'a OutputFile)>,
+    /// Records are sorted before they are written, if set.
+    sorter: Option<RecordSorter>,
+    n_records: usize,
+    /// Primary domain ID <--> distinct query payloads (exact for small domains, estimated for large ones)
+    unique_queries: HashMap<u32, HyperLogLog>,
+}
+
+impl<'a> RecordOutput<'a> {
+    fn new(codec: Codec, out_records: &'a OutputFile, out_prim: &'a OutputFile, out_index: Option<&'a OutputFile>, sort_records: Option<usize>) -> Self {
+        // The header is included in the byte offsets of the index
+        let mut record_writer = CountingWriter { inner: BufWriter::new(out_records), n_bytes: 0 };
+        if let Err(e) = codec.write_header(&mut record_writer) {
+            cli::exit_with_error(e);
+        }
+        let index = out_index.map(|file| (RecordIndex::default(), file));
+        let sorter = sort_records.map(RecordSorter::new);
+        RecordOutput { prim_map: HashMap::new(), record_writer, out_prim, index, sorter, n_records: 0, unique_queries: HashMap::new() }
+    }
+
+    /// Write a record, or hand it to the sorter.
+    fn write(&mut self, codec: Codec, entry: SerializedLogEntry) -> Result<(), cli::CliError> {
+        match self.sorter.as_mut() {
+            Some(sorter) => sorter.push(entry),
+            None => write_entry(&mut self.record_writer, self.index.as_mut().map(|(index, _)| index), codec, &entry),
+        }
+    }
+
+    /// Flush the (sorted) records and write the index and primary domain statistics, tagging wildcard-like domains.
+    fn finish(&mut self, codec: Codec, wildcard_detector: Option<&WildcardDetector>) {
+        if let Some(sorter) = self.sorter.take() {
+            let (record_writer, mut index) = (&mut self.record_writer, self.index.as_mut().map(|(index, _)| index));
+            if let Err(e) = sorter.finish(|entry| write_entry(record_writer, index.as_deref_mut(), codec, &entry)) {
+                cli::exit_with_error(e);
+            }
+        }
+        if let Err(e) = self.record_writer.flush() {
+            cli::exit_with_error(e);
+        }
+
+        if let Some((index, file)) = &self.index {
+            let mut index_writer = BufWriter::new(*file);
+            if let Err(e) = codec.write_header(&mut index_writer).and_then(|_| codec.serialize_into(&mut index_writer, index)) {
+                cli::exit_with_error(e);
+            }
+            if let Err(e) = index_writer.flush() {
+                cli::exit_with_error(e);
+            }
+        }
+
+        let mut prim_stats_writer = BufWriter::new(self.out_prim);
+        if let Err(e) = codec.write_header(&mut prim_stats_writer) {
+            cli::exit_with_error(e);
+        }
+        for stats_entry in self.prim_map.values_mut() {
+            if let Some(unique_queries) = self.unique_queries.get(&stats_entry.id) {
+                stats_entry.n_unique = unique_queries.count().min(u32::MAX as u64) as u32;
+            }
+            if wildcard_detector.is_some_and(|detector| detector.is_wildcard(stats_entry.id)) {
+                stats_entry.tags.insert(DomainTags::WILDCARD);
+            }
+            if let Err(e) = codec.serialize_into(&mut prim_stats_writer, stats_entry) {
+                cli::exit_with_error(e);
+            }
+        }
+        if let Err(e) = prim_stats_writer.flush() {
+            cli::exit_with_error(e);
+        }
+    }
+}
+
+/// Write a record, adding its byte range to the index.
+fn write_entry<W: Write>(writer: &mut CountingWriter<W>, index: Option<&mut RecordIndex>, codec: Codec, entry: &SerializedLogEntry) -> Result<(), cli::CliError> {
+    let start = writer.n_bytes;
+    codec.serialize_into(writer, entry)?;
+    if let Some(index) = index { index.add(entry.0, start, writer.n_bytes); }
+    Ok(())
+}
+
+/// Writer that counts the bytes written through it, for the offsets in the record index.
+struct CountingWriter<W> {
+    inner: W,
+    n_bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.n_bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write the `prim_id<TAB>primary_domain` mapping of the primary domains of all outputs, ordered by ID.
+fn write_domain_dictionary(file: &OutputFile, outputs: &[&RecordOutput]) -> std::io::Result<()> {
+    let mut domains: Vec<&PrimaryDomainStats> = outputs.iter().flat_map(|output| output.prim_map.values()).collect();
+    domains.sort_unstable_by_key(|stats| stats.id);
+
+    let mut writer = BufWriter::new(file);
+    for stats in domains {
+        writeln!(writer, "{}\t{}", stats.id, stats.name)?;
+    }
+    writer.flush()
+}
+
+/// TODO: filter fast retransmissions
+fn main() {
+    let mut opts = parse_opts();
+    let in_stream = opts.in_stream.take();
+    let start_time = Instant::now();
+
+    // Count lines in files for progress bar (and seek to start for reprocessing)
+    let time_count = Instant::now();
+    let mut lc: usize = 0;
+    for (_, in_file, _) in &opts.in_files {
+        let mut in_file = in_file;
+        match linecount::count_lines(NormalizeLineEndings::new(in_file)) {
+            Ok(count) => lc += count,
+            Err(e) => cli::exit_with_error(e)
+        };
+        if let Err(e) = in_file.seek(SeekFrom::Start(0)) {
+            cli::exit_with_error(e);
+        }
+    }
+
+    if in_stream.is_none() {
+        cli::print_output(style(format!("\n           (Counted lines in {:.1?})\n\n", time_count.elapsed())).dim().to_string(), opts.verbosity.is_quiet());
+    } else {
+        cli::print_output(String::from("\n"), opts.verbosity.is_quiet());
+    }
+    cli::print_output(format!("{}   {}Processing log entries...\n", style("[1/2]").bold().dim(), PAPER), opts.verbosity.is_quiet());
+
+    // Make progress bar (not for streams, of which the length is unknown)
+    let pb = cli::make_progress_bar(lc as u64, opts.verbosity.is_quiet() || opts.verbosity >= Verbosity::Debug || in_stream.is_some());
+    let streaming = in_stream.is_some();
+
+    // Initialize readers, per input file for its name (in errors) and clock offset
+    let inputs: Vec<(String, Box<dyn Read + '_>, f64)> = match in_stream {
+        Some(stream) => vec![(String::from("the input stream"), Box::new(stream), 0.)],
+        None => opts.in_files.iter().map(|(path, file, offset)| (path.display().to_string(), Box::new(file) as Box<dyn Read>, *offset)).collect(),
+    };
+    let n_inputs = inputs.len();
+    let mut n_lines: usize = 0;
+    let mut clock = ClockMonitor::new(opts.clock_jump);
+    let mut seq_gaps = SequenceGaps::new(opts.seq_modulus);
+    let mut parser = LineParser::new(opts.schema.clone());
+
+    // Initialize outputs, reverse lookups are parsed with their (reserved) suffix kept
+    let mut output = RecordOutput::new(opts.codec, &opts.out_records, &opts.out_prim, opts.out_index.as_ref(), opts.sort_records);
+    let mut reverse_output = opts.out_reverse.as_ref().map(|(records, prim)| RecordOutput::new(opts.codec, records, prim, None, opts.sort_records));
+    let reverse_dns_config = ParseDnsConfig { reserved_suffix: ReservedSuffixMode::Keep, ..opts.dns_config.clone() };
+
+    // Initialize counters
+    let mut id: usize = 0;
+    let mut prim_id_counter: u32 = 0;
+    let mut parse_stats = ParseStats::with_metrics(opts.metrics.clone());
+    if opts.verbosity >= Verbosity::Debug {
+        parse_stats = parse_stats.sample_rejected(opts.sample_dropped);
+    }
+
+    // Records per (client, primary domain ID, hour), for the per-client cap
+    let mut client_cap = opts.max_per_client_hour.map(ClientCap::new);
+
+    // Wildcard-like primary domains (and records dropped by down-sampling them)
+    let mut wildcard_detector = opts.wildcard.map(WildcardDetector::new);
+    let mut n_sampled_out: usize = 0;
+
+    // Read input line-by-line, per input
+    for (i, (name, input, clock_offset)) in inputs.into_iter().enumerate() {
+        let mut reader = LineReader::new(BufReader::new(NormalizeLineEndings::new(input)));
+        let mut line_no: usize = 0;
+        while let Some(Ok(line)) = reader.next_line() {
+            n_lines += 1;
+            line_no += 1;
+
+            // A newline is inserted if an input file does not end with one (except the last, as for `MultiFileReader`)
+            let terminated;
+            let line = if !line.ends_with(b"\n") && i + 1 < n_inputs {
+                terminated = [line, b"\n"].concat();
+                &terminated[..]
+            } else { line };
+
+            // Parse log line
+            match parser.parse(line) {
+                Ok(ParsedLineRef { ts, client, resolver, client_prefix, label, ttl, cname_depth, seq, query }) => {
+                    let ts = ts + clock_offset;
+                    clock.observe(ts);
+
+                    // Gaps in sequence numbers per resolver, counted before filtering (filtered records are not lost)
+                    let seq_gap = seq.and_then(|seq| seq_gaps.observe(resolver.unwrap_or_default(), seq));
+
+                    // FILTER: negative timestamp
+                    if ts < 0. {
+                        parse_stats.reject_negative_timestamp(line);
+                    } else {
+
+                        // Parse DNS payload
+                        let reverse = reverse_output.is_some() && is_reverse_lookup(query);
+                        let dns_config = if reverse { &reverse_dns_config } else { &opts.dns_config };
+                        match parse_dns_with_config(query, dns_config) {
+                            Ok(ParsedDns { primary_domain, primary_domain_len: prim_len, payload, tags }) => {
+                                let output = if reverse { reverse_output.as_mut().unwrap() } else { &mut output };
+
+                                // Get or insert primary domain stats entry
+                                let prim_entry = output.prim_map.entry(primary_domain).or_insert_with_key(|name| {
+                                    let current_prim_id = prim_id_counter;
+                                    prim_id_counter += 1;
+
+                                    PrimaryDomainStats { id: current_prim_id, name: name.clone(), length: prim_len, count: 0, tags, first_seen: ts, last_seen: ts, n_unique: 0 }
+                                });
+                                prim_entry.tags.insert(tags.0);
+                                if let Some(metrics) = &opts.metrics { metrics.set_domains(prim_id_counter as u64); }
+
+                                // GeoIP lookup of client and resolver addresses
+                                let geo = |address: Option<&[u8]>| opts.geoip.as_ref().zip(address).and_then(|(geoip, address)| geoip.lookup(address));
+                                let (client_geo, resolver_geo) = (geo(client), geo(resolver));
+
+                                // FILTER: per-client cap
+                                if let (Some(client_cap), Some(client)) = (client_cap.as_mut(), client) {
+                                    if !client_cap.admit(client, prim_entry.id, ts) {
+                                        parse_stats.reject_client_cap(line);
+                                        if Option::is_some(&pb) { pb.as_ref().unwrap().inc(1); }
+                                        continue;
+                                    }
+                                }
+
+                                // FILTER: down-sampling of wildcard-like primary domains
+                                if let Some(detector) = wildcard_detector.as_mut() {
+                                    let index = detector.observe(prim_entry.id, &payload);
+                                    if let (Some(sample), Some(index)) = (opts.wildcard_sample, index) {
+                                        if index % sample != 0 {
+                                            n_sampled_out += 1;
+                                            parse_stats.reject_wildcard_sample(line);
+                                            if Option::is_some(&pb) { pb.as_ref().unwrap().inc(1); }
+                                            continue;
+                                        }
+                                    }
+                                }
+
+                                // Unique queries and first/last seen
+                                output.unique_queries.entry(prim_entry.id).or_default().insert(query_key(&payload.labels));
+                                prim_entry.first_seen = prim_entry.first_seen.min(ts);
+                                prim_entry.last_seen = prim_entry.last_seen.max(ts);
+                                prim_entry.count += 1;
+
+                                // Create and output log record. Unlabeled queries get an empty label if the schema has a label field,
+                                // so the label column of the feature output follows the schema instead of the data.
+                                let label = if opts.schema.label_field { Some(label.unwrap_or_default().to_string()) } else { None };
+                                let row_data: SerializedLogEntry = (prim_entry.id, LogRecord { id, ts, payload, client_prefix, label, client_geo, resolver_geo, ttl, cname_depth, seq_gap });
+                                if let Err(e) = output.write(opts.codec, row_data) {
+                                    cli::exit_with_error(e);
+                                }
+
+                                // Emit records of continuous inputs right away (input may be idle for a long time)
+                                if opts.live {
+                                    if let Err(e) = output.record_writer.flush() {
+                                        cli::exit_with_error(e);
+                                    }
+                                }
+
+                                // Increase counts for queries
+                                output.n_records += 1;
+                                id += 1;
+                                parse_stats.accept();
+                            }
+                            Err(err) => parse_stats.reject_dns(&err, query, line)
+                        }
+                    }
+                }
+                // Blank lines are not fatal in strict mode, only rejected
+                Err(err) if opts.strict && !line.trim_ascii().is_empty() => {
+                    let reason = format!("{} (\"{}\")", err, escape_bytes(line.trim_ascii_end()));
+                    cli::exit_with_error(cli::CliError::InvalidInput(format!("{}, line {}", name, line_no), reason))
+                }
+                Err(err) => parse_stats.reject_line(&err, line)
+            }
+
+            if Option::is_some(&pb) { pb.as_ref().unwrap().inc(1); }
+        }
+    }
+    if Option::is_some(&pb) { pb.as_ref().unwrap().finish(); }
+
+    // Lines of streams are only known after reading them
+    if streaming { lc = n_lines; }
+
+    // Write primary domain stats to output as well
+    cli::print_output(format!("\n{}   {}Exporting primary domain statistics... ", style("[2/2]").bold().dim(), BAR_CHART), opts.verbosity.is_quiet());
+    output.finish(opts.codec, wildcard_detector.as_ref());
+    if let Some(reverse_output) = reverse_output.as_mut() {
+        reverse_output.finish(opts.codec, wildcard_detector.as_ref());
+    }
+    cli::print_output("Done!\n\n".to_string(), opts.verbosity.is_quiet());
+
+    // Write parse statistics
+    if let Some(file) = &opts.out_parse_stats {
+        if let Err(e) = parse_stats.write_json(file, lc as u64) {
+            cli::exit_with_error(e);
+        }
+    }
+
+    // Write primary domain dictionary
+    if let Some(file) = &opts.out_domains {
+        let mut outputs = vec![&output];
+        outputs.extend(reverse_output.as_ref());
+        if let Err(e) = write_domain_dictionary(file, &outputs) {
+            cli::exit_with_error(e);
+        }
+    }
+
+    // Move complete output files into place
+    let mut files = vec![&opts.out_records, &opts.out_prim];
+    files.extend(opts.out_parse_stats.as_ref());
+    files.extend(opts.out_domains.as_ref());
+    files.extend(opts.out_index.as_ref());
+    if let Some((records, prim)) = &opts.out_reverse { files.extend(&[records, prim]); }
+    let result = files.iter().try_for_each(|file| file.commit());
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+
+    // Record checksums of all outputs, for verification
+    if let Err(e) = cli::write_run_metadata(&opts.run_metadata, None) {
+        cli::exit_with_error(e);
+    }
+
+    eprintln!("           Input lines:     {}", lc.to_formatted_string(&Locale::en));
+    eprintln!("           Output entries:  {}", output.n_records.to_formatted_string(&Locale::en));
+    eprintln!("           Primary domains: {}", output.prim_map.len().to_formatted_string(&Locale::en));
+    if let Some(reverse_output) = &reverse_output {
+        eprintln!("           Reverse lookups: {} (in {} reverse zones)", reverse_output.n_records.to_formatted_string(&Locale::en), reverse_output.prim_map.len().to_formatted_string(&Locale::en));
+    }
+    if opts.verbosity >= Verbosity::Verbose {
+        for (reason, count) in parse_stats.rejected() {
+            eprintln!("           Rejected lines:  {} {}", count.to_formatted_string(&Locale::en), reason);
+        }
+    }
+    if let Some(client_cap) = &client_cap {
+        eprintln!("           Capped entries:  {} (in {} client/domain/hour buckets)", client_cap.n_capped.to_formatted_string(&Locale::en), client_cap.n_capped_buckets.to_formatted_string(&Locale::en));
+    }
+    if let Some(detector) = &wildcard_detector {
+        let n_wildcard = output.prim_map.values().chain(reverse_output.iter().flat_map(|output| output.prim_map.values())).filter(|stats| detector.is_wildcard(stats.id)).count();
+        eprintln!("           Wildcard-like:   {} primary domains ({} entries sampled out)", n_wildcard.to_formatted_string(&Locale::en), n_sampled_out.to_formatted_string(&Locale::en));
+    }
+    match opts.detected_separator {
+        Some(true) => eprintln!("           Separator:       {} (detected)", separator_name(&opts.schema.separator)),
+        Some(false) => eprintln!("           Separator:       {}", style("not detected from the first lines, assumed tab (set with -s/--separator)").yellow()),
+        None => {}
+    }
+    if seq_gaps.n_observed() > 0 {
+        eprintln!("           Sequence gaps:   {} missing (estimated drop rate {:.2}%)", seq_gaps.n_missing().to_formatted_string(&Locale::en), seq_gaps.drop_rate() * 100.);
+    }
+    if clock.n_jumps() > 0 {
+        let warning = format!("{} backward jumps of more than {}s (largest {:.1}s), correct clock skew with --clock-offset", clock.n_jumps().to_formatted_string(&Locale::en), opts.clock_jump, clock.largest_jump());
+        eprintln!("           Clock jumps:     {}", style(warning).yellow());
+    }
+    eprintln!("           Suffix list:     {}\n", opts.dns_config.suffix_list.version());
+    eprintln!("        {}Finished in {:.1?}", SPARKLE, start_time.elapsed());
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+/// Per-client cap on the number of records per (client, primary domain, hour). Buckets of hours before the previous
+/// one are dropped as timestamps advance, so memory is bounded by the clients and domains of the last two hours (the
+/// previous hour is kept for slightly out-of-order lines).
+pub struct ClientCap {
+    cap: u32,
+    counts: HashMap<(Vec<u8>, u32, i64), u32>,
+    current_hour: i64,
+    /// Records dropped by the cap.
+    pub n_capped: usize,
+    /// Buckets that reached the cap.
+    pub n_capped_buckets: usize,
+}
+
+impl ClientCap {
+    pub fn new(cap: u32) -> Self {
+        ClientCap { cap, counts: HashMap::new(), current_hour: i64::MIN, n_capped: 0, n_capped_buckets: 0 }
+    }
+
+    /// Count a record of `client` to primary domain `prim_id` at `ts`, returns false if its bucket is at the cap.
+    pub fn admit(&mut self, client: &[u8], prim_id: u32, ts: f64) -> bool {
+        let hour = (ts / 3600.).floor() as i64;
+        if hour > self.current_hour {
+            self.current_hour = hour;
+            self.counts.retain(|&(_, _, bucket_hour), _| bucket_hour >= hour - 1);
+        }
+
+        let count = self.counts.entry((client.to_vec(), prim_id, hour)).or_insert(0);
+        if *count >= self.cap {
+            self.n_capped += 1;
+            return false;
+        }
+        *count += 1;
+        if *count == self.cap { self.n_capped_buckets += 1; }
+        true
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use pipeline_cli::OutputFile;
+use pipeline_core::metrics::Metrics;
+use pipeline_core::parse_dns::ParseDnsError;
+use pipeline_core::parse_log::{escape_bytes, ParseLineError};
+
+/// Number of example lines kept per rejection reason.
+const MAX_EXAMPLES: usize = 5;
+
+/// Number of suffixes listed in `top_rejected_suffixes`.
+const MAX_TOP_SUFFIXES: usize = 20;
+
+#[derive(Serialize)]
+struct SuffixCount {
+    suffix: String,
+    count: u64,
+}
+
+/// Counts of rejected log lines per reason, written as JSON with `--out-parse-stats`.
+#[derive(Default)]
+pub struct ParseStats {
+    accepted: u64,
+    rejected: BTreeMap<String, u64>,
+    rejected_suffixes: HashMap<String, u64>,
+    examples: BTreeMap<String, Vec<String>>,
+    /// Print the first rejected line of every reason and then one in this many to stderr (`-vv`).
+    sample_every: Option<u64>,
+    /// Live counters (`--metrics-addr`), updated along with the statistics.
+    metrics: Option<Arc<Metrics>>,
+}
+
+#[derive(Serialize)]
+struct ParseStatsReport<'a> {
+    input_lines: u64,
+    accepted: u64,
+    rejected: &'a BTreeMap<String, u64>,
+    top_rejected_suffixes: Vec<SuffixCount>,
+    examples: &'a BTreeMap<String, Vec<String>>,
+}
+
+impl ParseStats {
+    pub fn with_metrics(metrics: Option<Arc<Metrics>>) -> Self {
+        ParseStats { metrics, ..ParseStats::default() }
+    }
+
+    /// Print a sample of the rejected lines with their reason: the first of every reason, then one in `every`.
+    pub fn sample_rejected(self, every: u64) -> Self {
+        ParseStats { sample_every: Some(every), ..self }
+    }
+
+    /// Number of rejected lines per reason.
+    pub fn rejected(&self) -> &BTreeMap<String, u64> {
+        &self.rejected
+    }
+
+    pub fn accept(&mut self) {
+        self.accepted += 1;
+        if let Some(metrics) = &self.metrics { metrics.inc_records(); }
+    }
+
+    pub fn reject_line(&mut self, err: &ParseLineError, line: &[u8]) {
+        self.reject(format!("{:?}", err), line);
+    }
+
+    pub fn reject_negative_timestamp(&mut self, line: &[u8]) {
+        self.reject(String::from("NegativeTimestamp"), line);
+    }
+
+    pub fn reject_client_cap(&mut self, line: &[u8]) {
+        self.reject(String::from("ClientCap"), line);
+    }
+
+    pub fn reject_wildcard_sample(&mut self, line: &[u8]) {
+        self.reject(String::from("WildcardSample"), line);
+    }
+
+    pub fn reject_dns(&mut self, err: &ParseDnsError, query: &[u8], line: &[u8]) {
+        if let ParseDnsError::UnknownSuffix | ParseDnsError::ReservedSuffix = err {
+            // Last label of the query, without trailing dot
+            let query = query.strip_suffix(b".").unwrap_or(query);
+            let suffix = query.rsplit(|&c| c == b'.').next().unwrap_or_default();
+            *self.rejected_suffixes.entry(escape_bytes(&suffix.to_ascii_lowercase())).or_insert(0) += 1;
+        }
+
+        self.reject(format!("{:?}", err), line);
+    }
+
+    fn reject(&mut self, reason: String, line: &[u8]) {
+        if let Some(metrics) = &self.metrics { metrics.inc_rejected(&reason); }
+
+        let examples = self.examples.entry(reason.clone()).or_default();
+        if examples.len() < MAX_EXAMPLES {
+            examples.push(escape_bytes(line.trim_ascii_end()));
+        }
+
+        let count = self.rejected.entry(reason.clone()).or_insert(0);
+        if self.sample_every.is_some_and(|every| count.is_multiple_of(every)) {
+            eprintln!("           Dropped line:    {} ({} #{})", escape_bytes(line.trim_ascii_end()), reason, *count + 1);
+        }
+        *count += 1;
+    }
+
+    pub fn write_json(&self, file: &OutputFile, input_lines: u64) -> std::io::Result<()> {
+        let mut top_rejected_suffixes: Vec<SuffixCount> = self.rejected_suffixes.iter()
+            .map(|(suffix, &count)| SuffixCount { suffix: suffix.clone(), count })
+            .collect();
+        top_rejected_suffixes.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.suffix.cmp(&b.suffix)));
+        top_rejected_suffixes.truncate(MAX_TOP_SUFFIXES);
+
+        let report = ParseStatsReport {
+            input_lines,
+            accepted: self.accepted,
+            rejected: &self.rejected,
+            top_rejected_suffixes,
+            examples: &self.examples,
+        };
+
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &report)?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,107 @@
+#[macro_use]
+extern crate clap;
+
+use std::path::{Path, PathBuf};
+
+use clap::App;
+use dialoguer::console::style;
+
+use pipeline_cli as cli;
+use pipeline_cli::fixtures;
+
+/// Embedded sample log, processed end-to-end by the installed binaries.
+const SAMPLE_LOG: &str = include_str!("sample.log");
+
+/// Feature extraction runs on the sample, with their expected (golden) output.
+struct Check {
+    name: &'static str,
+    args: &'static [&'static str],
+    golden: &'static str,
+}
+
+const CHECKS: [Check; 3] = [
+    Check { name: "payload", args: &["--payload"], golden: include_str!("golden/payload.csv") },
+    Check { name: "time_60", args: &["--time=60"], golden: include_str!("golden/time_60.csv") },
+    Check { name: "fixed_10", args: &["--fixed=10"], golden: include_str!("golden/fixed_10.csv") },
+];
+
+/// Run a binary of the pipeline, exiting with its output if it fails.
+fn run(bin_dir: &Path, name: &str, args: &[&str]) {
+    if let Err(e) = fixtures::run_binary(bin_dir, name, args) {
+        cli::exit_with_error(e);
+    }
+}
+
+/// Line number (1-based) of the first difference, if any.
+fn first_difference(output: &str, golden: &str) -> Option<usize> {
+    if output == golden { return None; }
+    let n_equal = output.lines().zip(golden.lines()).take_while(|(a, b)| a == b).count();
+    Some(n_equal + 1)
+}
+
+fn main() {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+    let quiet = m.is_present("quiet");
+
+    let bin_dir = match m.value_of("bin_dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::current_exe() {
+            Ok(exe) => exe.parent().map(Path::to_path_buf).unwrap_or_default(),
+            Err(e) => cli::exit_with_error(e)
+        }
+    };
+
+    // Sample inputs and all outputs in a temporary directory
+    let dir = std::env::temp_dir().join(format!("tdp-selfcheck-{}", std::process::id()));
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(dir.join("sample.log"), SAMPLE_LOG)) {
+        cli::exit_with_error(cli::CliError::IO(dir.display().to_string(), e));
+    }
+
+    run(&bin_dir, "preprocess", &["-q", "-r", &path("records.bin"), "-p", &path("prim.bin"), &path("sample.log")]);
+
+    let mut n_failed: usize = 0;
+    for check in CHECKS.iter() {
+        let out = path(&format!("{}.csv.gz", check.name));
+        let in_records = format!("--in-records={}", path("records.bin"));
+        let in_prim = format!("--in-prim={}", path("prim.bin"));
+        let mut args = vec!["-q", &in_records, &in_prim, &out];
+        args.extend(check.args);
+        run(&bin_dir, "extract", &args);
+        let output = fixtures::read_gz(Path::new(&out)).unwrap_or_else(|err| cli::exit_with_error(err));
+
+        if let Some(golden_dir) = m.value_of("write_golden") {
+            let golden = Path::new(golden_dir).join(format!("{}.csv", check.name));
+            if let Err(e) = std::fs::write(&golden, output) {
+                cli::exit_with_error(cli::CliError::IO(golden.display().to_string(), e));
+            }
+            cli::print_output(format!("{}  {}\n", style("WRITTEN ").green(), golden.display()), quiet);
+            continue;
+        }
+
+        match first_difference(&output, check.golden) {
+            None => cli::print_output(format!("{}  {} ({})\n", style("OK      ").green(), check.name, check.args.join(" ")), quiet),
+            Some(line) => {
+                n_failed += 1;
+                eprintln!("{}  {} ({}, first difference on line {})", style("MISMATCH").red(), check.name, check.args.join(" "), line);
+            }
+        }
+    }
+
+    if m.is_present("keep") {
+        cli::print_output(format!("\nSample inputs and outputs kept in {}\n", dir.display()), quiet);
+    } else if let Err(e) = std::fs::remove_dir_all(&dir) {
+        cli::exit_with_error(cli::CliError::IO(dir.display().to_string(), e));
+    }
+
+    if m.is_present("write_golden") { return; }
+
+    if n_failed > 0 {
+        let err = cli::CliError::InvalidInput(String::from("selfcheck"), format!("{} of {} outputs differ from the golden files", n_failed, CHECKS.len()));
+        cli::exit_with_error(err);
+    }
+    cli::print_output(format!("\n{} outputs identical to the golden files\n", CHECKS.len()), quiet);
+}
@@ -0,0 +1,216 @@
+#[macro_use]
+extern crate clap;
+
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Write};
+use std::time::Instant;
+
+use clap::App;
+use csv::{ByteRecord, QuoteStyle};
+use dialoguer::console::{Emoji, style};
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use num_format::{Locale, ToFormattedString};
+
+use pipeline_cli as cli;
+use pipeline_cli::OutputFile;
+use pipeline_core::shared_interface::{Codec, RecordReader};
+use pipeline_core::split::{assign_split, Split, SplitRatios};
+
+type CsvWriter<'a> = csv::Writer<GzEncoder<BufWriter<&'a OutputFile>>>;
+
+/// Marker for record IDs without a primary domain (not present in the records file).
+const NO_PRIM: u32 = u32::MAX;
+
+static LOADING: Emoji<'_, '_> = Emoji("⏳ ", "");
+static WORKING: Emoji<'_, '_> = Emoji("🛠️ ", "");
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", "");
+
+struct Opts {
+    in_features: cli::InputFile,
+    in_records: cli::InputFile,
+    out_splits: Vec<(Split, OutputFile)>,
+    out_domains: OutputFile,
+    out_prefix: String,
+    ratios: SplitRatios,
+    seed: u64,
+    codec: Codec,
+    quiet: bool,
+}
+
+fn parse_opts() -> Opts {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+
+    let quiet = m.is_present("quiet");
+
+    let ratios = match m.value_of("ratios").unwrap_or("0.7,0.15,0.15").parse::<SplitRatios>() {
+        Ok(ratios) => ratios,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--ratios"), msg))
+    };
+    let seed = cli::value_or_exit::<u64>(&m, "seed");
+
+    let codec = match m.value_of("codec").unwrap_or("bincode").parse::<Codec>() {
+        Ok(codec) => codec,
+        Err(msg) => cli::exit_with_error(cli::CliError::InvalidArgument(String::from("--codec"), msg))
+    };
+
+    let in_features = match cli::parse_input(m.value_of("in_features").unwrap_or_default()) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    };
+
+    let in_records = match cli::parse_input(m.value_of("in_records").unwrap_or_default()) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    };
+
+    let prefix = m.value_of("out_prefix").unwrap_or_default();
+    let out_splits = Split::ALL.iter()
+        .map(|split| match cli::parse_output_file(&format!("{}.{}.csv.gz", prefix, split.name()), quiet) {
+            Ok(file) => (*split, file),
+            Err(err) => cli::exit_with_error(err)
+        })
+        .collect();
+
+    let out_domains = match cli::parse_output_file(&format!("{}.domains.csv", prefix), quiet) {
+        Ok(file) => file,
+        Err(err) => cli::exit_with_error(err)
+    };
+
+    Opts { in_features, in_records, out_splits, out_domains, out_prefix: prefix.to_string(), ratios, seed, codec, quiet }
+}
+
+/// Map record IDs to primary domain IDs.
+fn load_record_domains(opts: &Opts) -> Vec<u32> {
+    cli::print_output(format!("\n{}   {}Loading record domains...\n", style("[1/2]").bold().dim(), LOADING), opts.quiet);
+
+    let mut record_prims: Vec<u32> = Vec::new();
+    for entry in RecordReader::new(BufReader::new(&opts.in_records), opts.codec) {
+        let (prim_id, record) = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let err = cli::CliError::Intermediate(String::from("--in-records"), err);
+                cli::exit_with_error(err)
+            }
+        };
+        if record.id >= record_prims.len() { record_prims.resize(record.id + 1, NO_PRIM); }
+        record_prims[record.id] = prim_id;
+    }
+
+    record_prims
+}
+
+fn make_csv_writer(file: &OutputFile) -> CsvWriter<'_> {
+    let gz_writer = GzEncoder::new(BufWriter::new(file), Compression::fast());
+    csv::WriterBuilder::new()
+        .flexible(true)
+        .quote_style(QuoteStyle::Necessary)
+        .from_writer(gz_writer)
+}
+
+fn main() {
+    let opts = parse_opts();
+    let start = Instant::now();
+
+    let record_prims = load_record_domains(&opts);
+
+    cli::print_output(format!("\n{}   {}Splitting feature vectors ({} train/validation/test)...\n", style("[2/2]").bold().dim(), WORKING, opts.ratios), opts.quiet);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(MultiGzDecoder::new(BufReader::new(&opts.in_features)));
+
+    let header = match reader.byte_headers() {
+        Ok(header) => header.clone(),
+        Err(e) => cli::exit_with_error(e)
+    };
+
+    // Writers per split, all with the input header
+    let mut writers: Vec<(Split, CsvWriter<'_>)> = opts.out_splits.iter()
+        .map(|(split, file)| (*split, make_csv_writer(file)))
+        .collect();
+    for (_, w) in writers.iter_mut() {
+        if let Err(e) = w.write_byte_record(&header) {
+            cli::exit_with_error(e);
+        }
+    }
+
+    // Assignment per primary domain and number of rows per split
+    let mut domains: BTreeMap<u32, Split> = BTreeMap::new();
+    let mut n_rows: BTreeMap<&str, usize> = BTreeMap::new();
+
+    let mut record = ByteRecord::new();
+    loop {
+        match reader.read_byte_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => cli::exit_with_error(e)
+        }
+
+        // First column is the record ID
+        let prim_id = std::str::from_utf8(record.get(0).unwrap_or_default()).ok()
+            .and_then(|id| id.parse::<usize>().ok())
+            .and_then(|id| record_prims.get(id).copied())
+            .filter(|prim_id| *prim_id != NO_PRIM);
+
+        let prim_id = match prim_id {
+            Some(prim_id) => prim_id,
+            None => {
+                let err = cli::CliError::InvalidInput(String::from("<in_features>"), format!("row with unknown record ID at line {}", reader.position().line()));
+                cli::exit_with_error(err)
+            }
+        };
+
+        let split = *domains.entry(prim_id).or_insert_with(|| assign_split(prim_id, opts.seed, &opts.ratios));
+        *n_rows.entry(split.name()).or_insert(0) += 1;
+
+        // (unwrap safe here because there is a writer for every split)
+        let (_, w) = writers.iter_mut().find(|(s, _)| *s == split).unwrap();
+        if let Err(e) = w.write_byte_record(&record) {
+            cli::exit_with_error(e);
+        }
+    }
+
+    for (_, w) in writers {
+        let result = w.into_inner()
+            .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))
+            .and_then(|gz| gz.finish())
+            .and_then(|mut buf| buf.flush());
+        if let Err(e) = result {
+            cli::exit_with_error(e);
+        }
+    }
+
+    // Write domain assignment
+    let mut domain_writer = BufWriter::new(&opts.out_domains);
+    let result = writeln!(domain_writer, "prim_id,split")
+        .and_then(|_| domains.iter().try_for_each(|(prim_id, split)| writeln!(domain_writer, "{},{}", prim_id, split.name())))
+        .and_then(|_| domain_writer.flush());
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+
+    let result = opts.out_splits.iter().map(|(_, file)| file)
+        .chain(std::iter::once(&opts.out_domains))
+        .try_for_each(|file| file.commit());
+    if let Err(e) = result {
+        cli::exit_with_error(e);
+    }
+
+    // Record checksums of all outputs, for verification
+    if let Err(e) = cli::write_run_metadata(&format!("{}.meta.json", opts.out_prefix), None) {
+        cli::exit_with_error(e);
+    }
+
+    cli::print_output("\n".to_string(), opts.quiet);
+    for split in Split::ALL.iter() {
+        let n_domains = domains.values().filter(|s| *s == split).count();
+        eprintln!("           {:<11} {} rows, {} domains", format!("{}:", split.name()),
+                  n_rows.get(split.name()).unwrap_or(&0).to_formatted_string(&Locale::en), n_domains.to_formatted_string(&Locale::en));
+    }
+    eprintln!("\n        {}Finished in {:.1?}", SPARKLE, start.elapsed());
+}
@@ -0,0 +1,46 @@
+#[macro_use]
+extern crate clap;
+
+use clap::App;
+use dialoguer::console::style;
+
+use pipeline_cli as cli;
+use pipeline_cli::VerifyStatus;
+
+fn main() {
+    cli::init_terminal();
+
+    let yml = load_yaml!("cli_args.yaml");
+    let m = cli::get_matches(App::from_yaml(yml));
+    let quiet = m.is_present("quiet");
+
+    let mut n_files: usize = 0;
+    let mut n_failed: usize = 0;
+    for input in m.values_of("run_metadata").into_iter().flatten() {
+        let results = match cli::verify_run(input) {
+            Ok(results) => results,
+            Err(err) => cli::exit_with_error(err)
+        };
+
+        for (expected, status) in results {
+            n_files += 1;
+            let line = match status {
+                VerifyStatus::Ok => {
+                    cli::print_output(format!("{}  {}\n", style("OK      ").green(), expected.path), quiet);
+                    continue;
+                }
+                VerifyStatus::Missing => format!("{}  {} (missing or unreadable)", style("MISSING ").red(), expected.path),
+                VerifyStatus::Mismatch { n_bytes, crc32 } => format!("{}  {} ({} bytes, CRC-32 {}; expected {} bytes, CRC-32 {})",
+                                                                     style("MISMATCH").red(), expected.path, n_bytes, crc32, expected.n_bytes, expected.crc32),
+            };
+            n_failed += 1;
+            eprintln!("{}", line);
+        }
+    }
+
+    if n_failed > 0 {
+        let err = cli::CliError::InvalidInput(String::from("<run_metadata>"), format!("{} of {} files failed verification", n_failed, n_files));
+        cli::exit_with_error(err);
+    }
+    cli::print_output(format!("\n{} files verified\n", n_files), quiet);
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use pipeline_core::feature_extraction::FeatureVector;
+use serde::{Deserialize, Serialize};
+
+/// Feature vectors (gzipped CSV with header).
+pub const FEATURES_FILE: &str = "features.csv.gz";
+/// Feature family and column names (`BundleSchema`).
+pub const SCHEMA_FILE: &str = "schema.json";
+/// Primary domains by ID (`BundleDomain`).
+pub const DOMAINS_FILE: &str = "domains.csv";
+/// Primary domain ID of every record, to map feature vectors (by record ID) to their primary domain.
+pub const RECORD_DOMAINS_FILE: &str = "record_domains.csv";
+/// How the bundle was created (`BundleMetadata`).
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Run metadata of a bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleMetadata {
+    /// Version of the pipeline that created the bundle.
+    pub version: String,
+    /// Creation time in seconds since the Unix epoch.
+    pub created: u64,
+    /// Command line of the run.
+    pub arguments: Vec<String>,
+    pub n_records: usize,
+    pub n_domains: usize,
+}
+
+/// Layout of the feature file of a bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleSchema {
+    /// Feature family, which is also the column prefix (e.g. pl, win_time or win_fixed).
+    pub family: String,
+    /// Column with the record ID of a feature vector (the first column).
+    pub id_column: String,
+    pub columns: Vec<String>,
+    /// `FeatureVector::SCHEMA_VERSION` of the extraction (0 for bundles written before versioning).
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// Primary domain in a bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleDomain {
+    pub prim_id: u32,
+    pub name: String,
+    pub count: u32,
+    /// Bitmask of `DomainTags`.
+    pub tags: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordDomain {
+    record_id: usize,
+    prim_id: u32,
+}
+
+/// Output bundle (a directory or `.tar` archive) with the feature vectors, their schema, the primary domains and run
+/// metadata, loaded with `load_bundle`.
+#[derive(Debug)]
+pub struct Bundle {
+    pub metadata: BundleMetadata,
+    pub schema: BundleSchema,
+    pub domains: Vec<BundleDomain>,
+    /// Record ID <--> primary domain ID
+    pub record_domains: HashMap<usize, u32>,
+    path: PathBuf,
+    is_tar: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("could not read bundle")]
+    IO(#[source] std::io::Error),
+    #[error("invalid bundle metadata")]
+    Json(#[source] serde_json::Error),
+    #[error("invalid bundle file")]
+    Csv(#[source] csv::Error),
+    #[error("invalid bundle: {0} is missing")]
+    MissingFile(&'static str),
+    #[error("incompatible bundle: feature schema version {0}, expected {}", FeatureVector::SCHEMA_VERSION)]
+    SchemaVersion(u32),
+}
+
+impl Bundle {
+    /// Reader for the (decompressed) feature vectors. Features of a `.tar` bundle are read into memory.
+    pub fn features(&self) -> Result<csv::Reader<Box<dyn Read>>, BundleError> {
+        let file: Box<dyn Read> = if self.is_tar {
+            Box::new(Cursor::new(read_tar_entry(&self.path, FEATURES_FILE)?))
+        } else {
+            Box::new(BufReader::new(File::open(self.path.join(FEATURES_FILE)).map_err(BundleError::IO)?))
+        };
+        Ok(csv::Reader::from_reader(Box::new(MultiGzDecoder::new(file))))
+    }
+
+    /// Primary domain of a feature vector, by its record ID.
+    pub fn domain_of(&self, record_id: usize) -> Option<&BundleDomain> {
+        let prim_id = self.record_domains.get(&record_id)?;
+        self.domains.iter().find(|domain| domain.prim_id == *prim_id)
+    }
+}
+
+/// Load a bundle directory or `.tar` archive (everything but the feature vectors, see `Bundle::features`). Fails if the
+/// bundle was extracted with other feature definitions (see `FeatureVector::SCHEMA_VERSION`).
+pub fn load_bundle<P: AsRef<Path>>(path: P) -> Result<Bundle, BundleError> {
+    let path = path.as_ref().to_path_buf();
+    let is_tar = path.is_file();
+    let read = |name: &'static str| -> Result<Vec<u8>, BundleError> {
+        if is_tar { read_tar_entry(&path, name) } else {
+            std::fs::read(path.join(name)).map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => BundleError::MissingFile(name),
+                _ => BundleError::IO(err),
+            })
+        }
+    };
+
+    let metadata = serde_json::from_slice(&read(METADATA_FILE)?).map_err(BundleError::Json)?;
+    let schema: BundleSchema = serde_json::from_slice(&read(SCHEMA_FILE)?).map_err(BundleError::Json)?;
+    if schema.schema_version != FeatureVector::SCHEMA_VERSION {
+        return Err(BundleError::SchemaVersion(schema.schema_version));
+    }
+    let domains = csv::Reader::from_reader(read(DOMAINS_FILE)?.as_slice()).deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(BundleError::Csv)?;
+    let record_domains = csv::Reader::from_reader(read(RECORD_DOMAINS_FILE)?.as_slice()).deserialize()
+        .map(|row| row.map(|row: RecordDomain| (row.record_id, row.prim_id)))
+        .collect::<Result<_, _>>()
+        .map_err(BundleError::Csv)?;
+
+    Ok(Bundle { metadata, schema, domains, record_domains, path, is_tar })
+}
+
+/// Write the bundle files next to the feature vectors in `dir` (which must have been written already, as the schema
+/// is taken from their header).
+pub fn write_bundle_files(dir: &Path, metadata: &BundleMetadata, family: &str, domains: &[BundleDomain], record_domains: &[(usize, u32)]) -> Result<(), BundleError> {
+    let features = File::open(dir.join(FEATURES_FILE)).map_err(BundleError::IO)?;
+    let columns: Vec<String> = csv::Reader::from_reader(MultiGzDecoder::new(BufReader::new(features))).headers()
+        .map_err(BundleError::Csv)?
+        .iter()
+        .map(String::from)
+        .collect();
+    let schema = BundleSchema { family: family.to_string(), id_column: columns.first().cloned().unwrap_or_default(), columns, schema_version: FeatureVector::SCHEMA_VERSION };
+
+    write_json(&dir.join(SCHEMA_FILE), &schema)?;
+    write_json(&dir.join(METADATA_FILE), metadata)?;
+
+    let mut w = csv::Writer::from_path(dir.join(DOMAINS_FILE)).map_err(BundleError::Csv)?;
+    domains.iter().try_for_each(|domain| w.serialize(domain)).map_err(BundleError::Csv)?;
+    w.flush().map_err(BundleError::IO)?;
+
+    let mut w = csv::Writer::from_path(dir.join(RECORD_DOMAINS_FILE)).map_err(BundleError::Csv)?;
+    record_domains.iter().try_for_each(|&(record_id, prim_id)| w.serialize(RecordDomain { record_id, prim_id })).map_err(BundleError::Csv)?;
+    w.flush().map_err(BundleError::IO)
+}
+
+/// Pack the bundle files in `dir` into a `.tar` archive.
+pub fn pack_bundle<W: std::io::Write>(dir: &Path, writer: W) -> Result<(), BundleError> {
+    let mut archive = tar::Builder::new(writer);
+    for name in &[METADATA_FILE, SCHEMA_FILE, DOMAINS_FILE, RECORD_DOMAINS_FILE, FEATURES_FILE] {
+        archive.append_path_with_name(dir.join(name), name).map_err(BundleError::IO)?;
+    }
+    archive.into_inner().and_then(|mut writer| writer.flush()).map_err(BundleError::IO)
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), BundleError> {
+    let file = File::create(path).map_err(BundleError::IO)?;
+    serde_json::to_writer_pretty(file, value).map_err(BundleError::Json)
+}
+
+fn read_tar_entry(path: &Path, name: &'static str) -> Result<Vec<u8>, BundleError> {
+    let mut archive = tar::Archive::new(BufReader::new(File::open(path).map_err(BundleError::IO)?));
+    for entry in archive.entries().map_err(BundleError::IO)? {
+        let mut entry = entry.map_err(BundleError::IO)?;
+        if entry.path().map_err(BundleError::IO)?.as_os_str() == name {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(BundleError::IO)?;
+            return Ok(data);
+        }
+    }
+    Err(BundleError::MissingFile(name))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use crate::bundle::{BundleDomain, BundleError, BundleMetadata, FEATURES_FILE, load_bundle, pack_bundle, SCHEMA_FILE, write_bundle_files};
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dir = std::env::temp_dir().join(format!("tdp-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut features = GzEncoder::new(File::create(dir.join(FEATURES_FILE)).unwrap(), Compression::default());
+        features.write_all(b"pl_id,pl_n_unique\n4,3\n").unwrap();
+        features.finish().unwrap();
+
+        let metadata = BundleMetadata { version: String::from("0.1.0"), created: 0, arguments: vec![String::from("extract")], n_records: 1, n_domains: 1 };
+        let domains = vec![BundleDomain { prim_id: 7, name: String::from("example.com"), count: 1, tags: 0 }];
+        write_bundle_files(&dir, &metadata, "payload", &domains, &[(4, 7)]).unwrap();
+
+        let tar_path = dir.with_extension("tar");
+        pack_bundle(&dir, File::create(&tar_path).unwrap()).unwrap();
+
+        for path in &[&dir, &tar_path] {
+            let bundle = load_bundle(path).unwrap();
+            assert_eq!(metadata, bundle.metadata);
+            assert_eq!(("payload", vec!["pl_id", "pl_n_unique"]), (bundle.schema.family.as_str(), bundle.schema.columns.iter().map(String::as_str).collect()));
+            assert_eq!("example.com", bundle.domain_of(4).unwrap().name);
+
+            let rows: Vec<(usize, u32)> = bundle.features().unwrap().deserialize().collect::<Result<_, _>>().unwrap();
+            assert_eq!(vec![(4, 3)], rows);
+        }
+
+        assert!(load_bundle(dir.join("missing")).is_err());
+
+        // Extracted with other feature definitions
+        std::fs::write(dir.join(SCHEMA_FILE), r#"{"family":"payload","id_column":"pl_id","columns":["pl_id"]}"#).unwrap();
+        assert!(matches!(load_bundle(&dir), Err(BundleError::SchemaVersion(0))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&tar_path).unwrap();
+    }
+}
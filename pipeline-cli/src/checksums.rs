@@ -0,0 +1,206 @@
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::Crc;
+use pipeline_core::object_store::is_object_uri;
+use serde::{Deserialize, Serialize};
+
+use crate::{CliError, parse_input, parse_output_file};
+
+lazy_static! {
+    /// Checksums of the files produced by this run so far.
+    static ref PRODUCED: Mutex<Vec<FileChecksum>> = Mutex::new(Vec::new());
+}
+
+/// Size and CRC-32 checksum of a produced file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileChecksum {
+    /// Path relative to the run metadata file if it is in the same directory (or below), else absolute (or an object URI).
+    pub path: String,
+    pub n_bytes: u64,
+    /// CRC-32 (hexadecimal).
+    pub crc32: String,
+}
+
+/// Metadata of a run, with the checksums of all files it produced (see `write_run_metadata`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunMetadata {
+    /// Version of the pipeline that produced the files.
+    pub version: String,
+    /// Creation time in seconds since the Unix epoch.
+    pub created: u64,
+    /// Command line of the run.
+    pub arguments: Vec<String>,
+    /// `FeatureVector::SCHEMA_VERSION` of runs that extract features.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_schema_version: Option<u32>,
+    pub files: Vec<FileChecksum>,
+}
+
+/// Result of verifying a produced file against its recorded checksum.
+#[derive(Debug, PartialEq)]
+pub enum VerifyStatus {
+    Ok,
+    Missing,
+    Mismatch { n_bytes: u64, crc32: String },
+}
+
+/// Running CRC-32 and size of written data.
+#[derive(Debug, Default)]
+pub(crate) struct Checksum {
+    crc: Crc,
+    n_bytes: u64,
+}
+
+impl Checksum {
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc.update(data);
+        self.n_bytes += data.len() as u64;
+    }
+
+    pub fn of_reader<R: Read>(reader: R) -> std::io::Result<Self> {
+        let mut checksum = Checksum::default();
+        let mut reader = BufReader::with_capacity(1 << 16, reader);
+        let mut buf = vec![0u8; 1 << 16];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(checksum),
+                n => checksum.update(&buf[..n]),
+            }
+        }
+    }
+
+    /// Size and CRC-32 (hexadecimal).
+    pub fn summary(&self) -> (u64, String) {
+        (self.n_bytes, format!("{:08x}", self.crc.sum()))
+    }
+
+    fn to_file_checksum(&self, path: String) -> FileChecksum {
+        let (n_bytes, crc32) = self.summary();
+        FileChecksum { path, n_bytes, crc32 }
+    }
+}
+
+/// Record the checksum of a committed output file (an absolute path or object URI).
+pub(crate) fn register(path: String, checksum: &Checksum) {
+    // (soft fail, only the run metadata would be incomplete)
+    if let Ok(mut produced) = PRODUCED.lock() {
+        produced.push(checksum.to_file_checksum(path));
+    }
+}
+
+/// Record the checksum of a file that was not written as `OutputFile` (e.g. bundle files), by reading it.
+pub fn register_file(path: &Path) -> std::io::Result<()> {
+    let checksum = Checksum::of_reader(std::fs::File::open(path)?)?;
+    register(std::fs::canonicalize(path)?.display().to_string(), &checksum);
+    Ok(())
+}
+
+/// Write the run metadata with the checksums of all files produced so far (replacing an existing file), which
+/// `verify_run` checks later.
+pub fn write_run_metadata(input: &str, feature_schema_version: Option<u32>) -> Result<(), CliError> {
+    let file = parse_output_file(input, true)?;
+    let dir = Path::new(input).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+
+    let files = PRODUCED.lock().map(|produced| produced.clone()).unwrap_or_default().into_iter()
+        .map(|checksum| match Path::new(&checksum.path).strip_prefix(&dir) {
+            Ok(relative) => FileChecksum { path: relative.display().to_string(), ..checksum },
+            Err(_) => checksum,
+        })
+        .collect();
+
+    let metadata = RunMetadata {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        arguments: std::env::args().collect(),
+        feature_schema_version,
+        files,
+    };
+    serde_json::to_writer_pretty(&file, &metadata).map_err(|err| CliError::IO(input.to_string(), err.into()))?;
+    file.commit()
+}
+
+/// Read run metadata and verify every file it lists (relative paths are relative to the metadata file).
+pub fn verify_run(input: &str) -> Result<Vec<(FileChecksum, VerifyStatus)>, CliError> {
+    let metadata: RunMetadata = serde_json::from_reader(BufReader::new(&parse_input(input)?))
+        .map_err(|err| CliError::InvalidInput(input.to_string(), err.to_string()))?;
+    let dir = Path::new(input).parent().unwrap_or(Path::new(""));
+
+    Ok(metadata.files.into_iter()
+        .map(|expected| {
+            let path = if is_object_uri(&expected.path) || Path::new(&expected.path).is_absolute() {
+                expected.path.clone()
+            } else {
+                dir.join(&expected.path).display().to_string()
+            };
+            let status = verify_file(&path, &expected);
+            (FileChecksum { path, ..expected }, status)
+        })
+        .collect())
+}
+
+fn verify_file(path: &str, expected: &FileChecksum) -> VerifyStatus {
+    let actual = match parse_input(path) {
+        Ok(input) => Checksum::of_reader(&input).map(|checksum| checksum.to_file_checksum(path.to_string())),
+        Err(_) => return VerifyStatus::Missing,
+    };
+
+    match actual {
+        Ok(actual) if actual.n_bytes == expected.n_bytes && actual.crc32 == expected.crc32 => VerifyStatus::Ok,
+        Ok(actual) => VerifyStatus::Mismatch { n_bytes: actual.n_bytes, crc32: actual.crc32 },
+        // Unreadable (e.g. an I/O error on a corrupt disk)
+        Err(_) => VerifyStatus::Missing,
+    }
+}
+
+/// Absolute path of a committed local output, for the run metadata.
+pub(crate) fn absolute_path(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)).display().to_string();
+
+    // Canonical paths on Windows are verbatim (`\\?\C:\...`), keep the usual form of local drives
+    match path.strip_prefix(r"\\?\") {
+        Some(local) if cfg!(windows) && !local.starts_with("UNC\\") => local.to_string(),
+        _ => path,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::checksums::{verify_run, VerifyStatus, write_run_metadata};
+    use crate::OutputFile;
+
+    #[test]
+    fn test_verify_run() {
+        let dir = std::env::temp_dir().join(format!("tdp-checksums-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut out = OutputFile::create(dir.join("records.bin")).unwrap();
+        out.write_all(b"some records").unwrap();
+        out.commit().unwrap();
+
+        let metadata = dir.join("records.bin.meta.json").display().to_string();
+        write_run_metadata(&metadata, None).unwrap();
+
+        let result = verify_run(&metadata).unwrap();
+        let (checksum, status) = result.iter().find(|(checksum, _)| checksum.path.ends_with("records.bin")).unwrap();
+        assert_eq!(VerifyStatus::Ok, *status);
+        assert_eq!(12, checksum.n_bytes);
+
+        // Flipped byte
+        std::fs::write(dir.join("records.bin"), b"some recordz").unwrap();
+        let result = verify_run(&metadata).unwrap();
+        assert!(result.iter().any(|(checksum, status)| checksum.path.ends_with("records.bin") && matches!(status, VerifyStatus::Mismatch { n_bytes: 12, .. })));
+
+        std::fs::remove_file(dir.join("records.bin")).unwrap();
+        let result = verify_run(&metadata).unwrap();
+        assert!(result.iter().any(|(checksum, status)| checksum.path.ends_with("records.bin") && *status == VerifyStatus::Missing));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+//! Synthetic input logs covering parser edge cases, and an end-to-end run of `preprocess` and `extract` on them. The
+//! `fixtures` binary writes the logs with their outputs as golden files, which the integration tests compare against.
+
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use flate2::read::MultiGzDecoder;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+use crate::CliError;
+
+/// Start of the synthetic logs (2021-03-04 12:00:00 UTC).
+const START_TS: f64 = 1614859200.;
+
+/// Name of the input log in a fixture directory.
+pub const INPUT_LOG: &str = "input.log";
+
+/// Feature extraction runs on every fixture, as (output name, arguments).
+pub const EXTRACT_RUNS: [(&str, &[&str]); 3] = [
+    ("payload", &["--payload"]),
+    ("time_60", &["--time=60"]),
+    ("fixed_10", &["--fixed=10"]),
+];
+
+/// Synthetic input log with the preprocessing arguments it needs.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub preprocess_args: &'static [&'static str],
+    pub log: String,
+}
+
+/// All fixtures. Generation is deterministic, so the same logs (and outputs) are produced on every run.
+pub fn fixtures() -> Vec<Fixture> {
+    vec![
+        Fixture { name: "escapes", preprocess_args: &["--escapes=decimal,percent,backslash"], log: escapes_log() },
+        Fixture { name: "long_labels", preprocess_args: &[], log: long_labels_log() },
+        Fixture { name: "reserved_tlds", preprocess_args: &["--reserved=keep-and-tag"], log: reserved_tlds_log() },
+        Fixture { name: "unordered", preprocess_args: &[], log: unordered_log() },
+        Fixture { name: "malformed_lines", preprocess_args: &[], log: malformed_lines_log() },
+        Fixture { name: "comma_separated", preprocess_args: &[], log: comma_separated_log() },
+        Fixture { name: "sequence_gaps", preprocess_args: &["--resolver-field", "--seq-field", "--seq-modulus=65536"], log: sequence_gaps_log() },
+    ]
+}
+
+/// Random lowercase hex label, like the encoded data of a tunnel.
+fn hex_label(rng: &mut ChaCha8Rng, len: usize) -> String {
+    (0..len).map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap()).collect()
+}
+
+/// Tunnel-like queries to `primary_domain`, with benign queries in between, `step` seconds apart.
+fn background(rng: &mut ChaCha8Rng, log: &mut String, n: usize, step: f64, primary_domain: &str) {
+    for i in 0..n {
+        let ts = START_TS + i as f64 * step;
+        match i % 3 {
+            0 => writeln!(log, "{:.6}\twww.example.com", ts),
+            _ => writeln!(log, "{:.6}\t{}.{}.{}", ts, hex_label(rng, 30), hex_label(rng, 12), primary_domain),
+        }.unwrap();
+    }
+}
+
+/// Zeek `\xHH` escapes (also binary and invalid ones), and the decimal, percent and doubled backslash syntaxes.
+fn escapes_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut log = String::new();
+    background(&mut rng, &mut log, 30, 2., "t.tunnel.example.org");
+
+    let queries = [
+        r"a\x2eb.escaped.example.com",
+        r"\x00\x01\xff\xfe.binary.example.com",
+        r"\x41\x42c.case.example.com",
+        r"\xZZ.invalid-escape.example.com",
+        r"trailing\.escape.example.com",
+        r"a\046b.decimal.example.com",
+        r"\032space.decimal.example.com",
+        r"\256.out-of-range.example.com",
+        r"a%2Eb.percent.example.com",
+        r"%41%42%zz.percent.example.com",
+        r"back\\slash.example.com",
+        r"mixed\x2d%2d\045\\.example.com",
+        r"dotted\x2eprimary\x2eexample\x2ecom",
+    ];
+    for (i, query) in queries.iter().enumerate() {
+        writeln!(log, "{:.6}\t{}", START_TS + 60. + i as f64, query).unwrap();
+    }
+
+    // Binary payloads (all byte values), as a tunnel with a raw encoding would send
+    for i in 0..16 {
+        let label: String = (0..16).map(|j| format!("\\x{:02x}", i * 16 + j)).collect();
+        writeln!(log, "{:.6}\t{}.raw.tunnel.example.org", START_TS + 80. + i as f64 / 4., label).unwrap();
+    }
+    log
+}
+
+/// Labels and names around the length limits of DNS (63 bytes per label, 253 characters per name).
+fn long_labels_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    let mut log = String::new();
+    background(&mut rng, &mut log, 30, 2., "t.tunnel.example.org");
+
+    let mut queries: Vec<String> = vec![
+        format!("{}.long.example.com", hex_label(&mut rng, 63)),
+        format!("{}.long.example.com", hex_label(&mut rng, 64)),
+        format!("{}.long.example.com", "a".repeat(255)),
+        format!("{}.many.example.com", vec!["x"; 100].join(".")),
+        String::from("a..empty-label.example.com"),
+        String::from(".leading-dot.example.com"),
+        String::from("trailing-dot.example.com."),
+        String::from("single"),
+        String::from("example.com"),
+    ];
+
+    // Names of exactly 253 and 254 characters (the maximum in presentation format, and one more), in labels of 63
+    for len in [253, 254] {
+        let suffix = ".max.example.com";
+        let labels: String = hex_label(&mut rng, len - suffix.len()).char_indices()
+            .map(|(i, ch)| if i % 64 == 63 { '.' } else { ch })
+            .collect();
+        queries.push(format!("{}{}", labels, suffix));
+    }
+
+    for (i, query) in queries.iter().enumerate() {
+        writeln!(log, "{:.6}\t{}", START_TS + 60. + i as f64, query).unwrap();
+    }
+    log
+}
+
+/// Special use suffixes (RFC 6761, reverse DNS and private TLDs in common use).
+fn reserved_tlds_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let mut log = String::new();
+    background(&mut rng, &mut log, 30, 2., "t.tunnel.example.org");
+
+    let suffixes = ["local", "corp", "internal", "home.arpa", "in-addr.arpa", "ip6.arpa", "test", "invalid",
+        "localhost", "onion", "example", "lan", "home"];
+    for (i, suffix) in suffixes.iter().enumerate() {
+        writeln!(log, "{:.6}\thost.name.{}", START_TS + 60. + i as f64, suffix).unwrap();
+        writeln!(log, "{:.6}\t{}.tunnel.{}", START_TS + 60.5 + i as f64, hex_label(&mut rng, 24), suffix).unwrap();
+    }
+    writeln!(log, "{:.6}\t1.0.0.127.in-addr.arpa", START_TS + 80.).unwrap();
+    log
+}
+
+/// Timestamps out of order: shuffled within a few seconds, duplicates, a backward clock jump and textual timestamps.
+fn unordered_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+    let mut lines: Vec<(f64, String)> = (0..120)
+        .map(|i| {
+            let ts = START_TS + i as f64 * 1.5;
+            let query = match i % 4 {
+                0 => String::from("mail.example.net"),
+                _ => format!("{}.t.tunnel.example.org", hex_label(&mut rng, 40)),
+            };
+            (ts, query)
+        })
+        .collect();
+
+    // Local reordering, as by multiple resolver threads writing to the same log
+    for chunk in lines.chunks_mut(5) {
+        chunk.shuffle(&mut rng);
+    }
+
+    let mut log = String::new();
+    for (i, (ts, query)) in lines.iter().enumerate() {
+        match i % 30 {
+            // Textual timestamps, with and without offset
+            7 => writeln!(log, "2021-03-04T12:{:02}:{:09.6}Z\t{}", (ts - START_TS) as u64 / 60, (ts - START_TS) % 60., query),
+            8 => writeln!(log, "2021-03-04 12:{:02}:{:09.6}\t{}", (ts - START_TS) as u64 / 60, (ts - START_TS) % 60., query),
+            // Duplicate timestamp of the previous line
+            9 => writeln!(log, "{:.6}\t{}", lines[i - 1].0, query),
+            _ => writeln!(log, "{:.6}\t{}", ts, query),
+        }.unwrap();
+    }
+
+    // Backward clock jump of more than a minute, then forward again
+    for i in 0..10 {
+        writeln!(log, "{:.6}\t{}.t.tunnel.example.org", START_TS + 30. + i as f64 * 0.1, hex_label(&mut rng, 40)).unwrap();
+    }
+    writeln!(log, "{:.6}\tmail.example.net", START_TS + 200.).unwrap();
+    log
+}
+
+/// Lines that are (partly) invalid: line endings, empty and truncated lines, 0x20 case randomization, www-only queries
+/// and unknown suffixes.
+fn malformed_lines_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    let mut log = String::new();
+    background(&mut rng, &mut log, 30, 2., "t.tunnel.example.org");
+
+    let lines = [
+        String::from("1614859260.000000\tcrlf.example.com\r"),
+        String::from(""),
+        String::from("   "),
+        String::from("1614859261.000000"),
+        String::from("1614859262.000000\t"),
+        String::from("not-a-timestamp\tbad-ts.example.com"),
+        String::from("1614859263.000000\tMiXeD.CaSe.ExAmPlE.CoM"),
+        String::from("1614859264.000000\tmixed.case.example.com"),
+        String::from("1614859265.000000\twww.example.com"),
+        String::from("1614859266.000000\tWWW.example.com"),
+        String::from("1614859267.000000\thost.unknown-suffix.zzzz"),
+        String::from("1614859268.000000\thost.under_score.example.com"),
+        String::from("1614859269.000000\t_dmarc.example.com"),
+        String::from("1614859270.000000\thost.-hyphen.com"),
+        String::from("1614859271.000000\textra\tfields.example.com"),
+        format!("1614859272.000000\t{}.T.TUNNEL.EXAMPLE.ORG", hex_label(&mut rng, 30).to_uppercase()),
+    ];
+    for line in lines.iter() {
+        writeln!(log, "{}", line).unwrap();
+    }
+
+    // No newline at the end of the file
+    write!(log, "1614859280.000000\tlast-line.example.com").unwrap();
+    log
+}
+
+/// Comma-separated log with a header line, for detection of the field separator.
+fn comma_separated_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(6);
+    let mut log = String::from("ts,query\n");
+    background(&mut rng, &mut log, 30, 2., "t.tunnel.example.org");
+    log.replace('\t', ",")
+}
+
+/// Two resolvers with 16-bit transaction IDs that wrap around, one of them losing records (also across the wrap),
+/// and lines without a transaction ID.
+fn sequence_gaps_log() -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let mut log = String::new();
+    for i in 0..40u64 {
+        let ts = START_TS + i as f64;
+        let (resolver, seq) = if i % 2 == 0 { ("192.0.2.53", (65520 + i) % 65536) } else { ("198.51.100.53", 100 + i + i / 7 * 3) };
+        let seq = if i % 13 == 12 { String::from("-") } else { seq.to_string() };
+        writeln!(log, "{:.6}\t{}\t{}\t{}.{}.t.tunnel.example.org", ts, resolver, seq, hex_label(&mut rng, 30), hex_label(&mut rng, 12)).unwrap();
+    }
+    log
+}
+
+/// Run a binary of the pipeline, with its exit status and error output as error if it fails.
+pub fn run_binary(bin_dir: &Path, name: &str, args: &[&str]) -> Result<(), CliError> {
+    let bin = bin_dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    let output = Command::new(&bin).args(args).output().map_err(|e| CliError::IO(bin.display().to_string(), e))?;
+
+    if !output.status.success() {
+        let msg = format!("{} ({})", output.status, String::from_utf8_lossy(&output.stderr).trim());
+        return Err(CliError::IO(bin.display().to_string(), std::io::Error::other(msg)));
+    }
+    Ok(())
+}
+
+/// Contents of a gzipped text file.
+pub fn read_gz(path: &Path) -> Result<String, CliError> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|file| MultiGzDecoder::new(file).read_to_string(&mut contents))
+        .map_err(|e| CliError::IO(path.display().to_string(), e))?;
+    Ok(contents)
+}
+
+/// CSV with its rows (after the header) sorted, as rows of primary domains are written in arbitrary order.
+fn sort_rows(csv: &str) -> String {
+    let mut lines: Vec<&str> = csv.lines().collect();
+    if let Some(rows) = lines.get_mut(1..) {
+        rows.sort_unstable();
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Write the log of a fixture to `dir` and run `preprocess` and `extract` (in `bin_dir`) on it. Returns the outputs
+/// (parse statistics, primary domains and the features of every `EXTRACT_RUNS`, with sorted rows) as (file name,
+/// contents).
+pub fn run_pipeline(bin_dir: &Path, fixture: &Fixture, dir: &Path) -> Result<Vec<(String, String)>, CliError> {
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).map_err(|e| CliError::IO(path(name), e));
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(dir.join(INPUT_LOG), &fixture.log))
+        .map_err(|e| CliError::IO(dir.display().to_string(), e))?;
+
+    let (records, prim) = (path("records.bin"), path("prim.bin"));
+    let (parse_stats, domains) = (format!("--out-parse-stats={}", path("parse_stats.json")), format!("--out-domains={}", path("domains.txt")));
+    let mut args = vec!["-q", "-r", &records, "-p", &prim, &parse_stats, &domains];
+    args.extend(fixture.preprocess_args);
+    let input = path(INPUT_LOG);
+    args.push(&input);
+    run_binary(bin_dir, "preprocess", &args)?;
+
+    let mut outputs = vec![
+        (String::from("parse_stats.json"), read("parse_stats.json")?),
+        (String::from("domains.txt"), read("domains.txt")?),
+    ];
+
+    let (in_records, in_prim) = (format!("--in-records={}", records), format!("--in-prim={}", prim));
+    for (name, extract_args) in EXTRACT_RUNS.iter() {
+        let out = path(&format!("{}.csv.gz", name));
+        let mut args = vec!["-q", &in_records, &in_prim, &out];
+        args.extend(*extract_args);
+        run_binary(bin_dir, "extract", &args)?;
+        outputs.push((format!("{}.csv", name), sort_rows(&read_gz(Path::new(&out))?)));
+    }
+    Ok(outputs)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::fixtures;
+
+    #[test]
+    fn test_fixtures_deterministic() {
+        let (a, b) = (fixtures(), fixtures());
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.log, b.log, "fixture {}", a.name);
+        }
+
+        // Names of exactly 253 and 254 characters
+        let long_labels = &fixtures()[1].log;
+        let lengths: Vec<usize> = long_labels.lines()
+            .filter_map(|line| line.split('\t').nth(1))
+            .filter(|query| query.ends_with(".max.example.com"))
+            .map(str::len)
+            .collect();
+        assert_eq!(vec![253, 254], lengths);
+    }
+}
@@ -0,0 +1,266 @@
+use serde::ser::{self, Serialize, Serializer};
+
+/// Formatting of floating point values in the output files.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FloatFormat {
+    /// Shortest representation that round-trips (default serde output).
+    #[default]
+    Shortest,
+    /// Fixed number of decimals, negative zero is written as zero.
+    Fixed(usize),
+}
+
+impl FloatFormat {
+    fn format(&self, value: f64) -> Option<String> {
+        match self {
+            FloatFormat::Shortest => None,
+            FloatFormat::Fixed(decimals) => {
+                let formatted = format!("{:.*}", decimals, value);
+
+                // Values that round to zero are written without sign
+                match formatted.strip_prefix('-') {
+                    Some(unsigned) if unsigned.bytes().all(|c| c == b'0' || c == b'.') => Some(unsigned.to_string()),
+                    _ => Some(formatted),
+                }
+            }
+        }
+    }
+}
+
+/// Wrapper that serializes the inner value with all floats formatted according to the given `FloatFormat`.
+pub struct Formatted<'a, T: ?Sized>(pub &'a T, pub FloatFormat);
+
+impl<T: ?Sized + Serialize> Serialize for Formatted<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(FloatSerializer { inner: serializer, format: self.1 })
+    }
+}
+
+/// Serializer that forwards everything to the inner serializer, except for floats when a format is set.
+struct FloatSerializer<S> {
+    inner: S,
+    format: FloatFormat,
+}
+
+/// Compound serializer (struct, sequence, ...) that wraps all its elements in `Formatted`.
+struct FloatCompound<C> {
+    inner: C,
+    format: FloatFormat,
+}
+
+macro_rules! forward {
+    ($($method:ident($ty:ty)),*) => {
+        $(fn $method(self, v: $ty) -> Result<Self::Ok, Self::Error> { self.inner.$method(v) })*
+    };
+}
+
+impl<S: Serializer> Serializer for FloatSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = FloatCompound<S::SerializeSeq>;
+    type SerializeTuple = FloatCompound<S::SerializeTuple>;
+    type SerializeTupleStruct = FloatCompound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = FloatCompound<S::SerializeTupleVariant>;
+    type SerializeMap = FloatCompound<S::SerializeMap>;
+    type SerializeStruct = FloatCompound<S::SerializeStruct>;
+    type SerializeStructVariant = FloatCompound<S::SerializeStructVariant>;
+
+    forward!(serialize_bool(bool), serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64), serialize_char(char),
+        serialize_str(&str), serialize_bytes(&[u8]), serialize_unit_struct(&'static str));
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        match self.format.format(v as f64) {
+            Some(formatted) => self.inner.serialize_str(&formatted),
+            None => self.inner.serialize_f32(v),
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        match self.format.format(v) {
+            Some(formatted) => self.inner.serialize_str(&formatted),
+            None => self.inner.serialize_f64(v),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_some(&Formatted(value, self.format))
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, index: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit_variant(name, index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_newtype_struct(name, &Formatted(value, self.format))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, index: u32, variant: &'static str, value: &T) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_newtype_variant(name, index, variant, &Formatted(value, self.format))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_seq(len)?, format: self.format })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_tuple(len)?, format: self.format })
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_tuple_struct(name, len)?, format: self.format })
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, index: u32, variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_tuple_variant(name, index, variant, len)?, format: self.format })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_map(len)?, format: self.format })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_struct(name, len)?, format: self.format })
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, index: u32, variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(FloatCompound { inner: self.inner.serialize_struct_variant(name, index, variant, len)?, format: self.format })
+    }
+}
+
+impl<C: ser::SerializeSeq> ser::SerializeSeq for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_element(&Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTuple> ser::SerializeTuple for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_element(&Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTupleStruct> ser::SerializeTupleStruct for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_field(&Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeTupleVariant> ser::SerializeTupleVariant for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_field(&Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeMap> ser::SerializeMap for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_key(&Formatted(key, self.format))
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_value(&Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeStruct> ser::SerializeStruct for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_field(key, &Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: ser::SerializeStructVariant> ser::SerializeStructVariant for FloatCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        self.inner.serialize_field(key, &Formatted(value, self.format))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use crate::float_format::{FloatFormat, Formatted};
+
+    #[derive(Serialize)]
+    struct Row {
+        id: usize,
+        ratio: f32,
+        zero: f32,
+        nested: (f64, bool),
+    }
+
+    fn to_csv(format: FloatFormat) -> String {
+        let row = Row { id: 7, ratio: 0.1, zero: -0.0000001, nested: (2. / 3., true) };
+        let mut w = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
+        w.serialize(Formatted(&row, format)).unwrap();
+        String::from_utf8(w.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_shortest() {
+        assert_eq!("7,0.1,-1e-7,0.6666666666666666,true\n", to_csv(FloatFormat::Shortest));
+    }
+
+    #[test]
+    fn test_fixed() {
+        assert_eq!("7,0.100000,0.000000,0.666667,true\n", to_csv(FloatFormat::Fixed(6)));
+        assert_eq!("7,0,0,1,true\n", to_csv(FloatFormat::Fixed(0)));
+    }
+}
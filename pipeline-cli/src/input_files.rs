@@ -0,0 +1,160 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use glob::MatchOptions;
+use pipeline_core::object_store::{is_object_uri, MultiObjectReader, ObjectStore, ObjectUri};
+
+use crate::{CliError, parse_input_file};
+
+/// Expand an input argument to a sorted list of files: a directory expands to all files below it (recursively), a
+/// glob pattern (e.g. `logs/**/dns.*.log`) to all matching files. Hidden files are skipped in both cases.
+pub fn parse_input_paths(input: &str) -> Result<Vec<PathBuf>, CliError> {
+    let path = Path::new(input);
+
+    let pattern = if path.is_dir() {
+        path.join("**").join("*").to_string_lossy().into_owned()
+    } else if input.contains(['*', '?', '[']) && !path.exists() {
+        input.to_string()
+    } else {
+        parse_input_file(input)?;
+        return Ok(vec![path.to_path_buf()]);
+    };
+
+    let options = MatchOptions { require_literal_leading_dot: true, ..MatchOptions::new() };
+    let entries = glob::glob_with(&pattern, options)
+        .map_err(|err| CliError::InvalidArgument(input.to_string(), err.to_string()))?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(path) if path.is_file() => paths.push(path),
+            Ok(_) => {}
+            Err(err) => return Err(CliError::IO(err.path().display().to_string(), err.into())),
+        }
+    }
+
+    if paths.is_empty() { return Err(CliError::FileNotFound(input.to_string())); }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Sequentially read input: a local file, or object(s) streamed from an object store.
+pub enum InputFile {
+    Local(File),
+    Object(String, Box<Mutex<MultiObjectReader>>),
+}
+
+impl fmt::Debug for InputFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputFile::Local(file) => f.debug_tuple("Local").field(file).finish(),
+            InputFile::Object(uri, _) => f.debug_tuple("Object").field(uri).finish(),
+        }
+    }
+}
+
+impl Read for &InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            InputFile::Local(file) => (&*file).read(buf),
+            InputFile::Object(_, reader) => reader.lock().map_err(|_| std::io::Error::other("poisoned object reader"))?.read(buf),
+        }
+    }
+}
+
+/// Open an input file, or an object (all objects below a prefix ending with `/`, concatenated) for `s3://` URIs.
+pub fn parse_input(input: &str) -> Result<InputFile, CliError> {
+    if is_object_uri(input) {
+        Ok(InputFile::Object(input.to_string(), Box::new(Mutex::new(open_objects(&[input])?))))
+    } else {
+        parse_input_file(input).map(InputFile::Local)
+    }
+}
+
+/// Reader over the objects of the given `s3://` URIs, prefixes (ending with `/`) expand to all objects below them in key
+/// order.
+pub fn open_objects(inputs: &[&str]) -> Result<MultiObjectReader, CliError> {
+    let store = ObjectStore::from_env().map_err(CliError::from)?;
+
+    let mut uris = Vec::new();
+    for input in inputs {
+        let uri = ObjectUri::parse(input).map_err(CliError::from)?;
+        uris.extend(store.expand(&uri).map_err(CliError::from)?);
+    }
+    Ok(MultiObjectReader::new(store, uris))
+}
+
+/// Reader over the concatenation of multiple files, a newline is inserted between files if a file does not end with
+/// one (so the last line of a file is never joined with the first line of the next).
+pub struct MultiFileReader<'a> {
+    files: std::slice::Iter<'a, File>,
+    current: Option<&'a File>,
+    last_byte: Option<u8>,
+}
+
+impl<'a> MultiFileReader<'a> {
+    pub fn new(files: &'a [File]) -> Self {
+        let mut files = files.iter();
+        let current = files.next();
+        MultiFileReader { files, current, last_byte: None }
+    }
+}
+
+impl Read for MultiFileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while let Some(mut file) = self.current {
+            if buf.is_empty() { return Ok(0); }
+
+            let n = file.read(buf)?;
+            if n > 0 {
+                self.last_byte = Some(buf[n - 1]);
+                return Ok(n);
+            }
+
+            // End of file, continue with next file
+            self.current = self.files.next();
+            if self.current.is_some() && self.last_byte.is_some_and(|byte| byte != b'\n') {
+                self.last_byte = Some(b'\n');
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+        }
+        Ok(0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use crate::input_files::{MultiFileReader, parse_input_paths};
+
+    #[test]
+    fn test_directory_and_glob() {
+        let dir = std::env::temp_dir().join(format!("tdp-input-files-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("2021-03-02")).unwrap();
+        std::fs::write(dir.join("2021-03-02").join("dns.log"), "2\tb.com\n").unwrap();
+        std::fs::write(dir.join("dns.1.log"), "1\ta.com").unwrap();
+        std::fs::write(dir.join(".hidden.log"), "").unwrap();
+
+        let paths = parse_input_paths(dir.to_str().unwrap()).unwrap();
+        assert_eq!(vec![dir.join("2021-03-02").join("dns.log"), dir.join("dns.1.log")], paths);
+
+        let paths = parse_input_paths(dir.join("*.log").to_str().unwrap()).unwrap();
+        assert_eq!(vec![dir.join("dns.1.log")], paths);
+        assert!(parse_input_paths(dir.join("*.csv").to_str().unwrap()).is_err());
+
+        // Missing trailing newline between files
+        let files: Vec<File> = [dir.join("dns.1.log"), dir.join("2021-03-02").join("dns.log")].iter().map(|p| File::open(p).unwrap()).collect();
+        let mut content = String::new();
+        MultiFileReader::new(&files).read_to_string(&mut content).unwrap();
+        assert_eq!("1\ta.com\n2\tb.com\n", content);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+// No unsafe here!
+#![forbid(unsafe_code)]
+
+#[macro_use]
+extern crate lazy_static;
+
+use std::fs::File;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use dialoguer::Confirm;
+use dialoguer::console;
+use dialoguer::theme::ColorfulTheme;
+use pipeline_core::enrichment::EnrichmentError;
+use pipeline_core::metrics::MetricsError;
+use pipeline_core::object_store::{is_object_uri, ObjectStore, ObjectStoreError, ObjectUri};
+use pipeline_core::parse_dns::SuffixListError;
+use pipeline_core::shared_interface::CodecError;
+
+use crate::bundle::BundleError;
+
+// Self-describing output bundles
+pub mod bundle;
+
+// Synthetic logs with parser edge cases for end-to-end tests
+pub mod fixtures;
+
+pub use self::checksums::{FileChecksum, register_file, RunMetadata, verify_run, VerifyStatus, write_run_metadata};
+pub use self::float_format::{FloatFormat, Formatted};
+pub use self::input_files::{InputFile, MultiFileReader, open_objects, parse_input, parse_input_paths};
+pub use self::output_file::{OutputFile, OutputWriter};
+pub use self::progress::Progress;
+pub use self::record_sort::RecordSorter;
+#[cfg(unix)]
+pub use self::stream_input::{follow_file, FollowReader, open_stream, StreamReader};
+pub use self::verbosity::Verbosity;
+
+mod checksums;
+mod float_format;
+mod input_files;
+mod output_file;
+mod progress;
+mod record_sort;
+#[cfg(unix)]
+mod stream_input;
+mod verbosity;
+
+/// Error of the command line tools: invalid arguments or files, or an error of the library (`pipeline_core::Error`)
+/// or of an output bundle. The underlying cause is available with `source()`.
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("Missing input argument: {0}")]
+    MissingInputArg(String),
+    #[error("Invalid input for argument \"{0}\": {1}")]
+    InvalidArgument(String, String),
+    #[error("Invalid input data in {0}: {1}")]
+    InvalidInput(String, String),
+    #[error("Invalid input data in {0}: could not read intermediate file")]
+    Intermediate(String, #[source] CodecError),
+    #[error("Could not find file \"{0}\"")]
+    FileNotFound(String),
+    #[error("Provided file \"{0}\" is a directory")]
+    FileIsDirectory(String),
+    #[error("File \"{0}\" already exists")]
+    FileExists(String),
+    #[error("File \"{0}\" already exists and cannot confirm overwrite without a terminal (use --quiet to overwrite)")]
+    NotInteractive(String),
+    #[error("I/O error for {0}")]
+    IO(String, #[source] std::io::Error),
+    #[error("I/O error")]
+    Output(#[from] std::io::Error),
+    #[error("Invalid CSV data")]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+    #[error(transparent)]
+    Core(#[from] pipeline_core::Error),
+}
+
+/// Errors of the library convert via `pipeline_core::Error`.
+macro_rules! from_core_error {
+    ($($err:ty),*) => {
+        $(impl From<$err> for CliError {
+            fn from(err: $err) -> Self {
+                CliError::Core(err.into())
+            }
+        })*
+    };
+}
+
+from_core_error!(CodecError, EnrichmentError, MetricsError, ObjectStoreError, SuffixListError);
+
+/// Open an output file for an atomic write (see `OutputFile`), asking for confirmation if the file already exists.
+pub fn parse_output_file(input: &str, force_overwrite: bool) -> Result<OutputFile, CliError> {
+    open_output_file(input, force_overwrite, OutputFile::create::<PathBuf>)
+}
+
+/// Open an output file that is written in place, e.g. to read it while it is written, asking for confirmation if the
+/// file already exists.
+pub fn parse_in_place_output_file(input: &str, force_overwrite: bool) -> Result<OutputFile, CliError> {
+    open_output_file(input, force_overwrite, OutputFile::create_in_place::<PathBuf>)
+}
+
+fn open_output_file(input: &str, force_overwrite: bool, create: fn(PathBuf) -> Result<OutputFile, CliError>) -> Result<OutputFile, CliError> {
+    if is_object_uri(input) { return open_output_object(input, force_overwrite); }
+    let path = PathBuf::from(input);
+
+    if path.is_dir() {
+        Err(CliError::FileIsDirectory(input.to_string()))
+    } else if path.exists() && !force_overwrite {
+        // Prompting would block forever in batch jobs
+        if !std::io::stdin().is_terminal() { return Err(CliError::NotInteractive(input.to_string())); }
+
+        match Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("File \"{}\" exists, overwrite?", input))
+            .default(false).wait_for_newline(true).interact_opt()
+        {
+            Ok(Some(true)) => create(path),
+            _ => Err(CliError::FileExists(input.to_string()))
+        }
+    } else {
+        create(path)
+    }
+}
+
+/// Open an output object (`s3://bucket/key`), which is uploaded on commit, asking for confirmation if it already exists.
+fn open_output_object(input: &str, force_overwrite: bool) -> Result<OutputFile, CliError> {
+    let uri = ObjectUri::parse(input).map_err(CliError::from)?;
+    if uri.is_prefix() { return Err(CliError::InvalidArgument(input.to_string(), String::from("output object needs a key"))); }
+    let store = ObjectStore::from_env().map_err(CliError::from)?;
+
+    if !force_overwrite && store.exists(&uri).map_err(CliError::from)? {
+        if !std::io::stdin().is_terminal() { return Err(CliError::NotInteractive(input.to_string())); }
+
+        match Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Object \"{}\" exists, overwrite?", input))
+            .default(false).wait_for_newline(true).interact_opt()
+        {
+            Ok(Some(true)) => {}
+            _ => return Err(CliError::FileExists(input.to_string()))
+        }
+    }
+    OutputFile::create_upload(store, uri)
+}
+
+/// Open an output file for appending, creating it if it does not exist.
+pub fn parse_append_file(input: &str) -> Result<OutputFile, CliError> {
+    if is_object_uri(input) { return Err(CliError::InvalidArgument(input.to_string(), String::from("cannot append to an object"))); }
+    let path = PathBuf::from(input);
+
+    if path.is_dir() {
+        Err(CliError::FileIsDirectory(input.to_string()))
+    } else {
+        OutputFile::append(path)
+    }
+}
+
+pub fn parse_input_file(input: &str) -> Result<File, CliError> {
+    let path = PathBuf::from(input);
+
+    if !path.exists() {
+        Err(CliError::FileNotFound(input.to_string()))
+    } else if path.is_dir() {
+        Err(CliError::FileIsDirectory(input.to_string()))
+    } else {
+        match File::open(path) {
+            Ok(file) => Ok(file),
+            Err(io_err) => Err(CliError::IO(input.to_string(), io_err))
+        }
+    }
+}
+
+/// Process exit codes per error class (values from BSD `sysexits.h`), so scripts can retry transient failures but
+/// not configuration mistakes. Argument errors detected by clap (see `get_matches` and `value_or_exit`) exit with
+/// `Argument` as well.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    /// Invalid command line arguments or missing/existing files.
+    Argument = 64,
+    /// Malformed input data.
+    InputFormat = 65,
+    /// Unexpected internal error.
+    Internal = 70,
+    /// Failed to read or write a file (or download).
+    IO = 74,
+}
+
+impl ExitCode {
+    pub fn for_error(e: &CliError) -> Self {
+        match e {
+            CliError::InvalidInput(..) | CliError::Intermediate(..) => ExitCode::InputFormat,
+            CliError::IO(..) | CliError::Output(_) => ExitCode::IO,
+            CliError::Csv(err) => if err.is_io_error() { ExitCode::IO } else { ExitCode::InputFormat },
+            CliError::Bundle(err) => match err {
+                BundleError::IO(_) => ExitCode::IO,
+                BundleError::Csv(err) if err.is_io_error() => ExitCode::IO,
+                _ => ExitCode::InputFormat,
+            },
+            CliError::Core(err) => ExitCode::for_core_error(err),
+            _ => ExitCode::Argument,
+        }
+    }
+
+    fn for_core_error(e: &pipeline_core::Error) -> Self {
+        use pipeline_core::Error;
+
+        match e {
+            Error::SuffixList(SuffixListError::Parse(_)) => ExitCode::InputFormat,
+            Error::SuffixList(_) => ExitCode::IO,
+            Error::Enrichment(EnrichmentError::IO(_)) => ExitCode::IO,
+            Error::Enrichment(_) => ExitCode::InputFormat,
+            Error::Metrics(MetricsError::IO(_)) => ExitCode::IO,
+            Error::Metrics(MetricsError::Disabled) => ExitCode::Argument,
+            Error::ObjectStore(ObjectStoreError::Request(_) | ObjectStoreError::IO(_)) => ExitCode::IO,
+            Error::ObjectStore(_) => ExitCode::Argument,
+            // Only raised when writing intermediate files (read errors are `CliError::Intermediate`)
+            Error::Codec(_) => ExitCode::IO,
+            Error::ParseLine(_) | Error::ParseDns(_) => ExitCode::InputFormat,
+            Error::RowFilter(_) => ExitCode::Argument,
+            Error::Columnar(_) => ExitCode::Internal,
+        }
+    }
+}
+
+/// Message of an error followed by the messages of its sources, e.g. `I/O error for out.csv: disk full`.
+pub fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(err) = source {
+        msg.push_str(": ");
+        msg.push_str(&err.to_string());
+        source = err.source();
+    }
+    msg
+}
+
+pub fn exit_with_error(e: impl Into<CliError>) -> ! {
+    let e = e.into();
+    eprintln!("Error: {}.", error_chain(&e));
+    std::process::exit(ExitCode::for_error(&e) as i32)
+}
+
+/// Parse the command line, with clap's argument errors (unknown or missing arguments, invalid values) exiting with
+/// `ExitCode::Argument` instead of clap's default 1. Help and version output still exit with 0.
+pub fn get_matches<'a>(app: clap::App<'a, '_>) -> clap::ArgMatches<'a> {
+    app.get_matches_safe().unwrap_or_else(|err| {
+        if !err.use_stderr() { err.exit() }
+        eprintln!("{}", err.message);
+        std::process::exit(ExitCode::Argument as i32)
+    })
+}
+
+/// Value of an argument parsed as `T`, exiting with `ExitCode::Argument` if it is invalid (like `get_matches`, instead
+/// of `value_t_or_exit!`, which exits with 1).
+pub fn value_or_exit<T: std::str::FromStr>(m: &clap::ArgMatches, name: &str) -> T {
+    let value = m.value_of(name).ok_or_else(|| clap::Error::argument_not_found_auto(name)).and_then(|value| {
+        value.parse::<T>().map_err(|_| clap::Error::value_validation_auto(format!("The argument '{}' isn't a valid value for '{}'", value, name)))
+    });
+    value.unwrap_or_else(|err| {
+        eprintln!("{}", err.message);
+        std::process::exit(ExitCode::Argument as i32)
+    })
+}
+
+/// Configure terminal output: all (styled) output goes to stderr, so colors follow stderr, which honors `NO_COLOR`
+/// and is disabled when stderr is not a terminal.
+pub fn init_terminal() {
+    console::set_colors_enabled(console::colors_enabled_stderr());
+}
+
+pub fn print_output(what: String, quiet: bool) {
+    if quiet { return; }
+    eprint!("{}", what);
+}
+
+pub fn make_progress_bar(size: u64, quiet: bool) -> Option<Progress> {
+    if quiet { return None; }
+    Some(Progress::new(size))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pipeline_core::feature_extraction::ColumnarError;
+    use pipeline_core::parse_dns::SuffixListError;
+
+    use crate::{CliError, error_chain, ExitCode};
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(ExitCode::Argument, ExitCode::for_error(&CliError::InvalidArgument(String::from("--time"), String::from("negative"))));
+        assert_eq!(ExitCode::Argument, ExitCode::for_error(&CliError::FileNotFound(String::from("in.log"))));
+        assert_eq!(ExitCode::InputFormat, ExitCode::for_error(&CliError::InvalidInput(String::from("in.csv"), String::from("bad row"))));
+        assert_eq!(ExitCode::IO, ExitCode::for_error(&std::io::Error::other("disk full").into()));
+        assert_eq!(ExitCode::InputFormat, ExitCode::for_error(&SuffixListError::Parse(String::from("no rules")).into()));
+        assert_eq!(ExitCode::Internal, ExitCode::for_error(&CliError::Core(ColumnarError::MixedFamilies.into())));
+    }
+
+    #[test]
+    fn test_error_chain() {
+        let err = CliError::IO(String::from("out.csv"), std::io::Error::other("disk full"));
+        assert_eq!("I/O error for out.csv: disk full", error_chain(&err));
+
+        let err = CliError::from(SuffixListError::IO(std::io::Error::other("no such file")));
+        assert_eq!("could not read public suffix list: no such file", error_chain(&err));
+    }
+}
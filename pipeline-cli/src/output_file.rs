@@ -0,0 +1,199 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pipeline_core::object_store::{ObjectStore, ObjectUri};
+
+use crate::checksums::{absolute_path, Checksum, register};
+use crate::CliError;
+
+/// Counter for unique names of local staging files of uploads.
+static N_UPLOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Output file that is written atomically: data goes to `{path}.tmp`, which replaces `path` on `commit`, so a failed
+/// run never clobbers a previous output and partial files are recognizable. In append and in-place mode, data is written
+/// to `path` directly. An object store output is staged in a local temporary file and uploaded on `commit`.
+///
+/// Written data is checksummed, and the checksum is recorded for the run metadata on `commit`.
+#[derive(Debug)]
+pub struct OutputFile {
+    file: File,
+    path: PathBuf,
+    tmp_path: Option<PathBuf>,
+    upload: Option<(ObjectStore, ObjectUri)>,
+    checksum: Arc<Mutex<Checksum>>,
+}
+
+/// Separate writer to an `OutputFile` (see `try_clone_writer`), whose data is included in its checksum.
+pub struct OutputWriter {
+    file: File,
+    checksum: Arc<Mutex<Checksum>>,
+}
+
+impl OutputFile {
+    /// Create the temporary file for an atomic write of `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CliError> {
+        let path = path.as_ref().to_path_buf();
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        match File::create(&tmp_path) {
+            Ok(file) => Ok(OutputFile { file, path, tmp_path: Some(tmp_path), upload: None, checksum: Default::default() }),
+            Err(io_err) => Err(CliError::IO(tmp_path.display().to_string(), io_err))
+        }
+    }
+
+    /// Create `path` and write to it directly (not atomic), so the output can be read while it is written.
+    pub fn create_in_place<P: AsRef<Path>>(path: P) -> Result<Self, CliError> {
+        let path = path.as_ref().to_path_buf();
+        match File::create(&path) {
+            Ok(file) => Ok(OutputFile { file, path, tmp_path: None, upload: None, checksum: Default::default() }),
+            Err(io_err) => Err(CliError::IO(path.display().to_string(), io_err))
+        }
+    }
+
+    /// Create a local staging file for an object, which is uploaded on commit.
+    pub fn create_upload(store: ObjectStore, uri: ObjectUri) -> Result<Self, CliError> {
+        let name = uri.key.rsplit('/').next().unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("tdp-upload-{}-{}-{}", std::process::id(), N_UPLOADS.fetch_add(1, Ordering::Relaxed), name));
+
+        match File::create(&path) {
+            Ok(file) => Ok(OutputFile { file, path, tmp_path: None, upload: Some((store, uri)), checksum: Default::default() }),
+            Err(io_err) => Err(CliError::IO(path.display().to_string(), io_err))
+        }
+    }
+
+    /// Open `path` for appending, creating it if it does not exist.
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<Self, CliError> {
+        let path = path.as_ref().to_path_buf();
+        let io_err = |err| CliError::IO(path.display().to_string(), err);
+        let file = OpenOptions::new().read(true).append(true).create(true).open(&path).map_err(io_err)?;
+
+        // The checksum covers the existing data as well
+        let checksum = Checksum::of_reader(&file).map_err(io_err)?;
+        Ok(OutputFile { file, path, tmp_path: None, upload: None, checksum: Arc::new(Mutex::new(checksum)) })
+    }
+
+    /// Separate writer to the underlying file, e.g. for a writer that needs ownership.
+    pub fn try_clone_writer(&self) -> std::io::Result<OutputWriter> {
+        Ok(OutputWriter { file: self.file.try_clone()?, checksum: self.checksum.clone() })
+    }
+
+    /// Size and CRC-32 checksum (hexadecimal) of the data written so far.
+    pub fn checksum(&self) -> (u64, String) {
+        match self.checksum.lock() {
+            Ok(checksum) => checksum.summary(),
+            Err(_) => (0, String::new()),
+        }
+    }
+
+    /// Whether data is appended to an existing, non-empty file (e.g. to skip writing a header).
+    pub fn has_existing_data(&self) -> bool {
+        self.tmp_path.is_none() && self.file.metadata().map(|meta| meta.len() > 0).unwrap_or(false)
+    }
+
+    /// Flush the file to disk and move it into place. Must be called once all data has been written.
+    pub fn commit(&self) -> Result<(), CliError> {
+        let io_err = |err| CliError::IO(self.path.display().to_string(), err);
+        self.file.sync_all().map_err(io_err)?;
+        let checksum = self.checksum.lock().map_err(|_| io_err(std::io::Error::other("poisoned checksum")))?;
+
+        if let Some((store, uri)) = &self.upload {
+            store.put_file(uri, &self.path).map_err(CliError::from)?;
+            register(uri.to_string(), &checksum);
+            return std::fs::remove_file(&self.path).map_err(io_err);
+        }
+        if let Some(tmp_path) = &self.tmp_path {
+            replace_file(tmp_path, &self.path).map_err(io_err)?;
+        }
+        register(absolute_path(&self.path), &checksum);
+        Ok(())
+    }
+}
+
+/// Move `from` to `to`, replacing it. On Windows, replacing a file can be denied (e.g. a read-only file, or one that is
+/// open without delete sharing), in which case the existing file is removed first.
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(err) if cfg!(windows) && err.kind() == std::io::ErrorKind::PermissionDenied && to.is_file() => {
+            std::fs::remove_file(to)?;
+            std::fs::rename(from, to)
+        }
+        result => result,
+    }
+}
+
+/// Write to `file` and include the written data in `checksum`.
+fn write_checksummed(mut file: &File, checksum: &Mutex<Checksum>, buf: &[u8]) -> std::io::Result<usize> {
+    let n = file.write(buf)?;
+    checksum.lock().map_err(|_| std::io::Error::other("poisoned checksum"))?.update(&buf[..n]);
+    Ok(n)
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write_checksummed(&self.file, &self.checksum, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Write for &OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write_checksummed(&self.file, &self.checksum, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (&self.file).flush()
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write_checksummed(&self.file, &self.checksum, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::output_file::OutputFile;
+
+    #[test]
+    fn test_atomic_write() {
+        let dir = std::env::temp_dir().join(format!("tdp-output-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.csv");
+        std::fs::write(&path, "previous").unwrap();
+
+        // Previous output is untouched until commit
+        let mut out = OutputFile::create(&path).unwrap();
+        out.write_all(b"new").unwrap();
+        assert_eq!("previous", std::fs::read_to_string(&path).unwrap());
+        assert!(dir.join("out.csv.tmp").exists());
+
+        out.commit().unwrap();
+        assert_eq!("new", std::fs::read_to_string(&path).unwrap());
+        assert!(!dir.join("out.csv.tmp").exists());
+
+        // Append to existing data
+        let mut out = OutputFile::append(&path).unwrap();
+        assert!(out.has_existing_data());
+        out.write_all(b",more").unwrap();
+        out.commit().unwrap();
+        assert_eq!("new,more", std::fs::read_to_string(&path).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
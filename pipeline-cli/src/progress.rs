@@ -0,0 +1,79 @@
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Interval between progress lines when stderr is not a terminal.
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress indicator: an interactive bar on a terminal, periodic plain lines otherwise (e.g. in batch job logs).
+pub enum Progress {
+    Bar(ProgressBar),
+    Lines(Mutex<LineProgress>),
+}
+
+pub struct LineProgress {
+    len: u64,
+    pos: u64,
+    start: Instant,
+    last_line: Instant,
+    message: String,
+}
+
+impl Progress {
+    pub fn new(size: u64) -> Self {
+        if std::io::stderr().is_terminal() {
+            let pb = ProgressBar::new(size);
+            pb.set_draw_rate(5);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+                .progress_chars("#>-"));
+            Progress::Bar(pb)
+        } else {
+            let now = Instant::now();
+            Progress::Lines(Mutex::new(LineProgress { len: size, pos: 0, start: now, last_line: now, message: String::new() }))
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        match self {
+            Progress::Bar(pb) => pb.inc(delta),
+            Progress::Lines(lines) => if let Ok(mut lines) = lines.lock() {
+                lines.pos += delta;
+                if lines.last_line.elapsed() >= LINE_INTERVAL { lines.print(); }
+            }
+        }
+    }
+
+    pub fn set_message(&self, msg: String) {
+        match self {
+            Progress::Bar(pb) => pb.set_message(msg),
+            Progress::Lines(lines) => if let Ok(mut lines) = lines.lock() { lines.message = msg; }
+        }
+    }
+
+    pub fn finish(&self) {
+        match self {
+            Progress::Bar(pb) => pb.finish(),
+            Progress::Lines(lines) => if let Ok(mut lines) = lines.lock() {
+                lines.pos = lines.len;
+                lines.print();
+            }
+        }
+    }
+
+    pub fn finish_with_message(&self, msg: &str) {
+        self.set_message(msg.to_string());
+        self.finish();
+    }
+}
+
+impl LineProgress {
+    fn print(&mut self) {
+        let percentage = if self.len == 0 { 100. } else { 100. * self.pos as f64 / self.len as f64 };
+        let elapsed = self.start.elapsed().as_secs();
+        eprintln!("[{:02}:{:02}:{:02}] {:>5.1}% ({}/{}) {}", elapsed / 3600, elapsed / 60 % 60, elapsed % 60, percentage, self.pos, self.len, self.message);
+        self.last_line = Instant::now();
+    }
+}
@@ -0,0 +1,220 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use pipeline_core::shared_interface::{Codec, CodecError, CodecReader, SerializedLogEntry};
+
+use crate::CliError;
+
+/// Counter for unique names of the run directories of sorters.
+static N_SORTERS: AtomicUsize = AtomicUsize::new(0);
+
+// Sorted runs are only read back by this process
+const RUN_CODEC: Codec = Codec::Bincode;
+
+/// External sort of intermediate records by primary domain ID, then timestamp (ties keep the order in which they were
+/// pushed). At most `capacity` records are buffered: full buffers are sorted and written to temporary run files (in
+/// `TMPDIR`), which are merged on `finish`.
+pub struct RecordSorter {
+    dir: PathBuf,
+    capacity: usize,
+    buffer: Vec<(usize, SerializedLogEntry)>,
+    runs: Vec<PathBuf>,
+    n_pushed: usize,
+}
+
+impl RecordSorter {
+    pub fn new(capacity: usize) -> Self {
+        let dir = std::env::temp_dir().join(format!("tdp-sort-{}-{}", std::process::id(), N_SORTERS.fetch_add(1, AtomicOrdering::Relaxed)));
+        RecordSorter { dir, capacity: capacity.max(1), buffer: Vec::new(), runs: Vec::new(), n_pushed: 0 }
+    }
+
+    pub fn push(&mut self, entry: SerializedLogEntry) -> Result<(), CliError> {
+        self.buffer.push((self.n_pushed, entry));
+        self.n_pushed += 1;
+        if self.buffer.len() >= self.capacity { self.write_run()?; }
+        Ok(())
+    }
+
+    /// Number of temporary run files written so far.
+    pub fn n_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn write_run(&mut self) -> Result<(), CliError> {
+        if self.runs.is_empty() {
+            std::fs::create_dir_all(&self.dir).map_err(|err| CliError::IO(self.dir.display().to_string(), err))?;
+        }
+        let path = self.dir.join(format!("{}.bin", self.runs.len()));
+        let io_err = |err| CliError::IO(path.display().to_string(), err);
+
+        let mut writer = BufWriter::new(File::create(&path).map_err(io_err)?);
+        self.buffer.sort_unstable_by(cmp_entries);
+        for entry in self.buffer.drain(..) {
+            RUN_CODEC.serialize_into(&mut writer, &entry)?;
+        }
+        writer.flush().map_err(io_err)?;
+
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Pass all records to `emit` in sorted order, e.g. to write them.
+    pub fn finish<F: FnMut(SerializedLogEntry) -> Result<(), CliError>>(self, mut emit: F) -> Result<(), CliError> {
+        self.into_sorted()?.try_for_each(|entry| emit(entry?))
+    }
+
+    /// All records in sorted order, merged from the runs while iterating.
+    pub fn into_sorted(mut self) -> Result<SortedRecords, CliError> {
+        // Everything fits in memory
+        if self.runs.is_empty() {
+            self.buffer.sort_unstable_by(cmp_entries);
+            let buffered = std::mem::take(&mut self.buffer).into_iter();
+            return Ok(SortedRecords { buffered, readers: Vec::new(), heap: BinaryHeap::new(), sorter: self });
+        }
+
+        if !self.buffer.is_empty() { self.write_run()?; }
+
+        let mut readers = Vec::with_capacity(self.runs.len());
+        for path in &self.runs {
+            let file = File::open(path).map_err(|err| CliError::IO(path.display().to_string(), err))?;
+            readers.push(CodecReader::values(BufReader::new(file), RUN_CODEC));
+        }
+
+        // K-way merge of the runs, with the next record of every run in a min-heap
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(entry) = reader.next() {
+                heap.push(HeapEntry(entry.map_err(|err| self.run_error(run, err))?, run));
+            }
+        }
+        Ok(SortedRecords { buffered: Vec::new().into_iter(), readers, heap, sorter: self })
+    }
+
+    fn run_error(&self, run: usize, err: CodecError) -> CliError {
+        CliError::Intermediate(self.runs[run].display().to_string(), err)
+    }
+}
+
+/// Sorted records of a `RecordSorter`, whose run files are removed when this is dropped.
+pub struct SortedRecords {
+    /// Records if everything fitted in memory.
+    buffered: std::vec::IntoIter<(usize, SerializedLogEntry)>,
+    readers: Vec<CodecReader<BufReader<File>, (usize, SerializedLogEntry)>>,
+    heap: BinaryHeap<HeapEntry>,
+    sorter: RecordSorter,
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<SerializedLogEntry, CliError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((_, entry)) = self.buffered.next() { return Some(Ok(entry)); }
+
+        let HeapEntry((_, entry), run) = self.heap.pop()?;
+        match self.readers[run].next() {
+            Some(Ok(next)) => self.heap.push(HeapEntry(next, run)),
+            Some(Err(err)) => return Some(Err(self.sorter.run_error(run, err))),
+            None => {}
+        }
+        Some(Ok(entry))
+    }
+}
+
+impl Drop for RecordSorter {
+    fn drop(&mut self) {
+        // (soft fail, only leaves temporary files behind)
+        if !self.runs.is_empty() { let _ = std::fs::remove_dir_all(&self.dir); }
+    }
+}
+
+/// Order of (push index, entry): primary domain ID, timestamp, push index.
+fn cmp_entries(a: &(usize, SerializedLogEntry), b: &(usize, SerializedLogEntry)) -> Ordering {
+    let ((a_index, (a_prim, a_record)), (b_index, (b_prim, b_record))) = (a, b);
+    a_prim.cmp(b_prim).then(a_record.ts.total_cmp(&b_record.ts)).then(a_index.cmp(b_index))
+}
+
+/// Next record of a run, ordered for a min-heap.
+struct HeapEntry((usize, SerializedLogEntry), usize);
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_entries(&other.0, &self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pipeline_core::parse_dns::DnsPayload;
+    use pipeline_core::shared_interface::{LogRecord, SerializedLogEntry};
+
+    use crate::record_sort::RecordSorter;
+
+    fn entry(id: usize, prim_id: u32, ts: f64) -> SerializedLogEntry {
+        let payload = DnsPayload { payload_len: 0, labels: Vec::new() };
+        (prim_id, LogRecord { id, ts, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    fn sorted(capacity: usize, entries: &[(u32, f64)]) -> (usize, Vec<(u32, usize)>) {
+        let mut sorter = RecordSorter::new(capacity);
+        for (id, &(prim_id, ts)) in entries.iter().enumerate() {
+            sorter.push(entry(id, prim_id, ts)).unwrap();
+        }
+        let n_runs = sorter.n_runs();
+
+        let mut sorted = Vec::new();
+        sorter.finish(|(prim_id, record)| {
+            sorted.push((prim_id, record.id));
+            Ok(())
+        }).unwrap();
+        (n_runs, sorted)
+    }
+
+    #[test]
+    fn test_record_sorter() {
+        // Grouped by primary domain, ordered by timestamp, ties in push order
+        let entries = [(2, 5.), (1, 3.), (2, 1.), (1, 3.), (0, 9.), (1, 2.), (2, 5.)];
+        let expected = vec![(0, 4), (1, 5), (1, 1), (1, 3), (2, 2), (2, 0), (2, 6)];
+
+        // In memory, and merged from runs (including a partial last run)
+        assert_eq!((0, expected.clone()), sorted(100, &entries));
+        assert_eq!((2, expected.clone()), sorted(3, &entries));
+        assert_eq!((7, expected), sorted(1, &entries));
+        assert_eq!((0, Vec::new()), sorted(3, &[]));
+    }
+
+    #[test]
+    fn test_into_sorted() {
+        let mut sorter = RecordSorter::new(2);
+        for (id, &ts) in [3., 1., 2., 1.].iter().enumerate() {
+            sorter.push(entry(id, 7, ts)).unwrap();
+        }
+        let dir = sorter.dir.clone();
+
+        let records = sorter.into_sorted().unwrap();
+        assert!(dir.exists());
+        let ids: Vec<usize> = records.map(|entry| entry.unwrap().1.id).collect();
+        assert_eq!(vec![1, 3, 2, 0], ids);
+
+        // Run files are removed with the iterator
+        assert!(!dir.exists());
+    }
+}
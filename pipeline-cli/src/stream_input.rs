@@ -0,0 +1,291 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::CliError;
+
+/// Interval between attempts to accept a connection on a socket.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Interval between checks for new data in a followed file.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+enum StreamSource {
+    Fifo,
+    Socket(UnixListener),
+}
+
+/// Continuous input from a FIFO or Unix domain socket, e.g. where a resolver tees its query log. When the writer
+/// closes the stream (restart or log rotation), the next writer is waited for; the input ends when no writer
+/// (re)connects within the idle timeout. As for `MultiFileReader`, a newline is inserted if a stream does not end with
+/// one.
+pub struct StreamReader {
+    path: PathBuf,
+    source: StreamSource,
+    idle_timeout: Duration,
+    current: Option<Box<dyn Read + Send>>,
+    last_byte: Option<u8>,
+    n_connections: usize,
+}
+
+/// Open an existing FIFO, or listen on a Unix domain socket at `input` (replacing a stale socket file).
+pub fn open_stream(input: &str, idle_timeout: Duration) -> Result<StreamReader, CliError> {
+    let path = PathBuf::from(input);
+
+    let source = match std::fs::metadata(&path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => StreamSource::Fifo,
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            std::fs::remove_file(&path).map_err(|err| CliError::IO(input.to_string(), err))?;
+            StreamSource::Socket(bind(input)?)
+        }
+        Ok(_) => return Err(CliError::InvalidArgument(input.to_string(), String::from("not a FIFO or Unix domain socket"))),
+        Err(_) => StreamSource::Socket(bind(input)?),
+    };
+
+    Ok(StreamReader { path, source, idle_timeout, current: None, last_byte: None, n_connections: 0 })
+}
+
+fn bind(input: &str) -> Result<UnixListener, CliError> {
+    let listener = UnixListener::bind(input).map_err(|err| CliError::IO(input.to_string(), err))?;
+    listener.set_nonblocking(true).map_err(|err| CliError::IO(input.to_string(), err))?;
+    Ok(listener)
+}
+
+impl StreamReader {
+    /// Number of writers (connections or FIFO openings) so far.
+    pub fn n_connections(&self) -> usize {
+        self.n_connections
+    }
+
+    /// Wait for the next writer, None if there is none within the idle timeout.
+    fn connect(&mut self) -> std::io::Result<Option<Box<dyn Read + Send>>> {
+        let source: Option<Box<dyn Read + Send>> = match &self.source {
+            StreamSource::Fifo => open_fifo(&self.path, self.idle_timeout)?,
+            StreamSource::Socket(listener) => {
+                let deadline = Instant::now() + self.idle_timeout;
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            stream.set_nonblocking(false)?;
+                            break Some(Box::new(stream));
+                        }
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                            if Instant::now() >= deadline { break None; }
+                            std::thread::sleep(ACCEPT_INTERVAL);
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+        };
+
+        if source.is_some() { self.n_connections += 1; }
+        Ok(source)
+    }
+}
+
+/// Open a FIFO for reading, which blocks until a writer opens it. None if there is no writer within the timeout.
+fn open_fifo(path: &Path, timeout: Duration) -> std::io::Result<Option<Box<dyn Read + Send>>> {
+    let (tx, rx) = mpsc::channel();
+    let thread_path = path.to_path_buf();
+    std::thread::spawn(move || { let _ = tx.send(File::open(thread_path)); });
+
+    if let Ok(file) = rx.recv_timeout(timeout) {
+        return file.map(|file| Some(Box::new(file) as Box<dyn Read + Send>));
+    }
+
+    // Unblock the pending open with a writer of our own (closed right away). If a writer connected at the same time,
+    // its data is still read, otherwise the FIFO is at its end.
+    drop(OpenOptions::new().write(true).open(path)?);
+    let mut file = rx.recv().map_err(std::io::Error::other)??;
+    let mut first = [0u8];
+    match file.read(&mut first)? {
+        0 => Ok(None),
+        _ => Ok(Some(Box::new(Cursor::new(first).chain(file)))),
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if buf.is_empty() { return Ok(0); }
+
+            let current = match self.current.as_mut() {
+                Some(current) => current,
+                None => match self.connect()? {
+                    Some(source) => self.current.insert(source),
+                    None => return Ok(0),
+                },
+            };
+
+            let n = current.read(buf)?;
+            if n > 0 {
+                self.last_byte = Some(buf[n - 1]);
+                return Ok(n);
+            }
+
+            // Writer closed the stream, wait for the next one
+            self.current = None;
+            if self.last_byte.is_some_and(|byte| byte != b'\n') {
+                self.last_byte = Some(b'\n');
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+        }
+    }
+}
+
+impl Drop for StreamReader {
+    fn drop(&mut self) {
+        if let StreamSource::Socket(_) = self.source {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Reader that follows a file while it is written, like `tail -F`: after the end of the file, new data is waited for.
+/// A rotated file (a new file at the path) is read to its end before continuing with the new file from its start, a
+/// truncated file is read again from its start. The input ends when there is no new data within the idle timeout.
+pub struct FollowReader {
+    path: PathBuf,
+    file: File,
+    inode: u64,
+    position: u64,
+    idle_timeout: Duration,
+    last_data: Instant,
+    last_byte: Option<u8>,
+    n_rotations: usize,
+}
+
+/// Follow the file at `input` from its start.
+pub fn follow_file(input: &str, idle_timeout: Duration) -> Result<FollowReader, CliError> {
+    let io_err = |err| CliError::IO(input.to_string(), err);
+    let file = File::open(input).map_err(io_err)?;
+    let metadata = file.metadata().map_err(io_err)?;
+    if !metadata.is_file() { return Err(CliError::InvalidArgument(input.to_string(), String::from("can only follow regular files"))); }
+
+    Ok(FollowReader { path: PathBuf::from(input), file, inode: metadata.ino(), position: 0, idle_timeout, last_data: Instant::now(), last_byte: None, n_rotations: 0 })
+}
+
+impl FollowReader {
+    /// Number of rotations of the followed file so far.
+    pub fn n_rotations(&self) -> usize {
+        self.n_rotations
+    }
+
+    fn read_current(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read(buf)?;
+        if n > 0 {
+            self.position += n as u64;
+            self.last_byte = Some(buf[n - 1]);
+            self.last_data = Instant::now();
+        }
+        Ok(n)
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if buf.is_empty() { return Ok(0); }
+
+            let n = self.read_current(buf)?;
+            if n > 0 { return Ok(n); }
+
+            // At the end of the file: rotated, truncated or no new data yet (a missing path is a rotation in progress)
+            match std::fs::metadata(&self.path) {
+                Ok(metadata) if metadata.ino() != self.inode => {
+                    // Data written to the rotated file before the check
+                    let n = self.read_current(buf)?;
+                    if n > 0 { return Ok(n); }
+
+                    self.file = File::open(&self.path)?;
+                    self.inode = self.file.metadata()?.ino();
+                    self.position = 0;
+                    self.n_rotations += 1;
+
+                    if self.last_byte.is_some_and(|byte| byte != b'\n') {
+                        self.last_byte = Some(b'\n');
+                        buf[0] = b'\n';
+                        return Ok(1);
+                    }
+                    continue;
+                }
+                Ok(metadata) if metadata.len() < self.position => {
+                    self.position = self.file.seek(SeekFrom::Start(0))?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if self.last_data.elapsed() >= self.idle_timeout { return Ok(0); }
+            std::thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    use crate::stream_input::{follow_file, open_stream};
+
+    #[test]
+    fn test_socket_reconnect() {
+        let path = std::env::temp_dir().join(format!("tdp-stream-{}.sock", std::process::id()));
+        let mut reader = open_stream(path.to_str().unwrap(), Duration::from_millis(500)).unwrap();
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            // Second writer after a rotation, the first one without trailing newline
+            UnixStream::connect(&writer_path).unwrap().write_all(b"1\ta.com\n2\tb.com").unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            UnixStream::connect(&writer_path).unwrap().write_all(b"3\tc.com\n").unwrap();
+        });
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!("1\ta.com\n2\tb.com\n3\tc.com\n", content);
+        assert_eq!(2, reader.n_connections());
+
+        drop(reader);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_follow_rotation() {
+        let path = std::env::temp_dir().join(format!("tdp-follow-{}.log", std::process::id()));
+        std::fs::write(&path, "1\ta.com\n").unwrap();
+        let mut reader = follow_file(path.to_str().unwrap(), Duration::from_millis(600)).unwrap();
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::OpenOptions::new().append(true).open(&writer_path).unwrap().write_all(b"2\tb.com").unwrap();
+
+            // Rotation: the last line of the rotated file has no newline
+            std::thread::sleep(Duration::from_millis(300));
+            std::fs::rename(&writer_path, writer_path.with_extension("log.1")).unwrap();
+            std::fs::write(&writer_path, "3\tc.com\n").unwrap();
+        });
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!("1\ta.com\n2\tb.com\n3\tc.com\n", content);
+        assert_eq!(1, reader.n_rotations());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("log.1")).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use clap::ArgMatches;
+
+/// Level of the terminal output, from `-q` (none besides the summary) to `-vv` (debugging output).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+    Debug,
+}
+
+impl Verbosity {
+    /// Verbosity of the `quiet` flag and the (repeatable) `verbose` flag, which binaries without it ignore.
+    pub fn from_matches(m: &ArgMatches) -> Self {
+        if m.is_present("quiet") { return Verbosity::Quiet; }
+        match m.occurrences_of("verbose") {
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+
+    pub fn is_quiet(self) -> bool {
+        self == Verbosity::Quiet
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use clap::{App, Arg};
+
+    use crate::Verbosity;
+
+    #[test]
+    fn test_from_matches() {
+        let app = App::new("test")
+            .arg(Arg::with_name("quiet").short("q").conflicts_with("verbose"))
+            .arg(Arg::with_name("verbose").short("v").multiple(true));
+        let verbosity = |args: &[&str]| Verbosity::from_matches(&app.clone().get_matches_from(args));
+
+        assert_eq!(Verbosity::Normal, verbosity(&["test"]));
+        assert_eq!(Verbosity::Quiet, verbosity(&["test", "-q"]));
+        assert_eq!(Verbosity::Verbose, verbosity(&["test", "-v"]));
+        assert_eq!(Verbosity::Debug, verbosity(&["test", "-vv"]));
+        assert_eq!(Verbosity::Debug, verbosity(&["test", "-v", "-vv"]));
+        assert!(app.get_matches_from_safe(["test", "-q", "-v"]).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Runs `preprocess` and `extract` on every fixture and compares the outputs to the golden files in `tests/golden`.
+//! After an intended change of the outputs, regenerate them with `fixtures pipeline-cli/tests/golden`.
+
+use std::path::{Path, PathBuf};
+
+use pipeline_cli::fixtures::{fixtures, INPUT_LOG, run_binary, run_pipeline};
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn read_golden(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+#[test]
+fn test_fixture_logs() {
+    for fixture in fixtures() {
+        let golden = read_golden(&golden_dir().join(fixture.name).join(INPUT_LOG));
+        assert!(golden == fixture.log, "input log of fixture {} differs from the golden file", fixture.name);
+    }
+}
+
+#[test]
+fn test_end_to_end() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-end-to-end-{}", std::process::id()));
+
+    for fixture in fixtures() {
+        let outputs = run_pipeline(bin_dir, &fixture, &tmp_dir.join(fixture.name))
+            .unwrap_or_else(|e| panic!("fixture {}: {}", fixture.name, pipeline_cli::error_chain(&e)));
+
+        for (name, output) in outputs {
+            let golden = read_golden(&golden_dir().join(fixture.name).join(&name));
+            if output != golden {
+                let line = output.lines().zip(golden.lines()).take_while(|(a, b)| a == b).count() + 1;
+                panic!("{} of fixture {} differs from the golden file (first difference on line {})", name, fixture.name, line);
+            }
+        }
+    }
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
+
+#[test]
+fn test_strict() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-strict-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp_dir).unwrap();
+
+    for fixture in fixtures().into_iter().filter(|fixture| ["escapes", "malformed_lines"].contains(&fixture.name)) {
+        let input = tmp_dir.join(format!("{}.log", fixture.name));
+        std::fs::write(&input, &fixture.log).unwrap();
+
+        let (records, prim) = (tmp_dir.join("records.bin"), tmp_dir.join("prim.bin"));
+        let mut args = vec!["-q", "--strict", "-r", records.to_str().unwrap(), "-p", prim.to_str().unwrap(), input.to_str().unwrap()];
+        args.extend(fixture.preprocess_args);
+        let result = run_binary(bin_dir, "preprocess", &args);
+
+        match fixture.name {
+            "malformed_lines" => {
+                let err = pipeline_cli::error_chain(&result.unwrap_err());
+                assert!(err.contains("malformed_lines.log, line 34: field separator not found"), "{}", err);
+            }
+            _ => result.unwrap(),
+        }
+    }
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
+
+#[test]
+fn test_appended_columns() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-appended-{}", std::process::id()));
+    let fixture = fixtures().into_iter().next().unwrap();
+    run_pipeline(bin_dir, &fixture, &tmp_dir).unwrap();
+
+    // Enrich the first primary domain with a value that needs quoting
+    let domains = std::fs::read_to_string(tmp_dir.join("domains.txt")).unwrap();
+    let domain = domains.lines().next().unwrap().rsplit('\t').next().unwrap().to_string();
+    let enrich = tmp_dir.join("enrich.csv");
+    std::fs::write(&enrich, format!("domain,registrar\n{},\"Example, Inc.\"\n", domain)).unwrap();
+
+    let path = |name: &str| tmp_dir.join(name).to_string_lossy().into_owned();
+    let (in_records, in_prim, out) = (format!("--in-records={}", path("records.bin")), format!("--in-prim={}", path("prim.bin")), path("out.csv.gz"));
+    let enrich = format!("--enrich={}", enrich.display());
+    run_binary(bin_dir, "extract", &["-q", "--payload", &in_records, &in_prim, &out, &enrich, "--tag", "site=a,b"]).unwrap();
+
+    let output = pipeline_cli::fixtures::read_gz(Path::new(&out)).unwrap();
+    let mut lines = output.lines();
+    assert!(lines.next().unwrap().ends_with(",registrar,site"));
+    assert!(lines.all(|line| line.ends_with(",\"a,b\"")));
+    assert!(output.contains(",\"Example, Inc.\",\"a,b\""), "{}", output);
+
+    // Tag keys that shadow a feature, tag or enrichment column are argument errors
+    let bin = bin_dir.join(format!("extract{}", std::env::consts::EXE_SUFFIX));
+    for tag in ["win_fixed_entropy=x", "domain_tags=x", "label=x", "registrar=x"] {
+        let output = std::process::Command::new(&bin).args(["-q", "--payload", &in_records, &in_prim, &out, &enrich, "--tag", tag]).output().unwrap();
+        assert_eq!(output.status.code(), Some(64), "{}", tag);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("is the name of another column"), "{}", tag);
+    }
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
+
+#[test]
+fn test_argument_exit_code() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    for name in ["preprocess", "extract", "detect", "split", "verify"] {
+        let bin = bin_dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+        let status = std::process::Command::new(&bin).arg("--no-such-flag").output().unwrap().status;
+        assert_eq!(status.code(), Some(64), "{}", name);
+
+        let status = std::process::Command::new(&bin).arg("--help").output().unwrap().status;
+        assert_eq!(status.code(), Some(0), "{}", name);
+    }
+
+    // Values of the wrong type
+    let bin = bin_dir.join(format!("extract{}", std::env::consts::EXE_SUFFIX));
+    let output = std::process::Command::new(&bin).args(["--in-records=x", "--in-prim=y", "out.csv.gz", "--fixed=abc"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'abc' isn't a valid value for 'fixed'"));
+}
+
+#[test]
+fn test_reverse_zones() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-reverse-{}", std::process::id()));
+    let fixture = fixtures().into_iter().next().unwrap();
+    let outputs = run_pipeline(bin_dir, &fixture, &tmp_dir).unwrap();
+
+    let path = |name: &str| tmp_dir.join(name).to_string_lossy().into_owned();
+    let (in_records, in_prim, out) = (format!("--in-records={}", path("records.bin")), format!("--in-prim={}", path("prim.bin")), path("out.csv.gz"));
+    run_binary(bin_dir, "extract", &["-q", "--fixed=10", "--reverse-zones", &in_records, &in_prim, &out]).unwrap();
+
+    // The character features are left out, the other columns and rows are the same
+    let output = pipeline_cli::fixtures::read_gz(Path::new(&out)).unwrap();
+    let header: Vec<&str> = output.lines().next().unwrap().split(',').collect();
+    assert!(header.contains(&"win_fixed_n_unique_labels") && header.contains(&"win_fixed_max_label_length"));
+    assert!(!header.iter().any(|column| column.contains("entropy") || column.contains("bigram")));
+
+    let full = &outputs.iter().find(|(name, _)| name == "fixed_10.csv").unwrap().1;
+    assert_eq!(full.lines().count(), output.lines().count());
+    assert_eq!(full.lines().nth(1).unwrap().split(',').count(), header.len() + 7);
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
+
+#[test]
+fn test_unique_queries() {
+    use pipeline_core::shared_interface::{Codec, PrimStatsReader};
+
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-unique-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp_dir).unwrap();
+
+    // Small domain (counted exactly, case-insensitive) and large domain (estimated), every query twice
+    let mut log = String::new();
+    for (i, query) in ["a.example.net", "A.example.net", "b.example.net"].iter().enumerate() {
+        log.push_str(&format!("{}\t{}\n", 1614859200 + i, query));
+    }
+    for i in 0..5000 {
+        log.push_str(&format!("{}\tq{}.example.org\n1614859300\tQ{}.example.org\n", 1614859300, i, i));
+    }
+    let input = tmp_dir.join("input.log");
+    std::fs::write(&input, log).unwrap();
+
+    let (records, prim) = (tmp_dir.join("records.bin"), tmp_dir.join("prim.bin"));
+    run_binary(bin_dir, "preprocess", &["-q", "-r", records.to_str().unwrap(), "-p", prim.to_str().unwrap(), input.to_str().unwrap()]).unwrap();
+
+    let mut stats: Vec<(String, u32, u32)> = PrimStatsReader::open(&prim, Codec::default()).unwrap()
+        .map(|stats| stats.unwrap())
+        .map(|stats| (stats.name, stats.count, stats.n_unique))
+        .collect();
+    stats.sort();
+    assert_eq!(("example.net".to_string(), 3, 2), stats[0]);
+    assert_eq!(("example.org", 10000), (stats[1].0.as_str(), stats[1].1));
+    assert!((4800..5200).contains(&stats[1].2), "{}", stats[1].2);
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
+
+#[test]
+fn test_spill_unsorted() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_preprocess")).parent().unwrap();
+    let tmp_dir = std::env::temp_dir().join(format!("tdp-spill-unsorted-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp_dir).unwrap();
+
+    // Merged log of two sensors, so the spilled domain is not in timestamp order
+    let mut log = String::new();
+    for i in 0..3000 {
+        log.push_str(&format!("{}\tq{}.example.org\n", 1614859200 + (i % 2) * 1000 + i / 2, i % 97));
+    }
+    let input = tmp_dir.join("input.log");
+    std::fs::write(&input, log).unwrap();
+
+    let path = |name: &str| tmp_dir.join(name).to_string_lossy().into_owned();
+    run_binary(bin_dir, "preprocess", &["-q", "-r", &path("records.bin"), "-p", &path("prim.bin"), &path("input.log")]).unwrap();
+
+    // Sorted in many runs (the threshold is below the run size limit of 3000 / 256 records)
+    let (in_records, in_prim) = (format!("--in-records={}", path("records.bin")), format!("--in-prim={}", path("prim.bin")));
+    run_binary(bin_dir, "extract", &["-q", "--fixed=10", &in_records, &in_prim, &path("memory.csv.gz")]).unwrap();
+    run_binary(bin_dir, "extract", &["-q", "--fixed=10", "--spill-domains=5", &in_records, &in_prim, &path("spill.csv.gz")]).unwrap();
+
+    let read = |name: &str| pipeline_cli::fixtures::read_gz(Path::new(&path(name))).unwrap();
+    let (memory, spill) = (read("memory.csv.gz"), read("spill.csv.gz"));
+    assert_eq!(3001, memory.lines().count());
+    assert!(memory == spill, "spilled output differs");
+
+    std::fs::remove_dir_all(&tmp_dir).unwrap();
+}
@@ -0,0 +1,53 @@
+//! Throughput of log line parsing for plain ASCII queries and queries with many byte escapes (binary payload
+//! tunnels), run with `cargo bench --bench parse_log`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use pipeline_core::parse_log::{LineParser, LogSchema, parse_log_line_with_schema};
+
+const N_LINES: usize = 200_000;
+
+fn bench(name: &str, lines: &[Vec<u8>]) {
+    let schema = LogSchema::default();
+
+    // Warm up
+    lines.iter().take(1000).for_each(|line| { black_box(parse_log_line_with_schema(line, &schema).is_ok()); });
+
+    let start = Instant::now();
+    for line in lines {
+        black_box(parse_log_line_with_schema(black_box(line), &schema).is_ok());
+    }
+    report(name, lines, start);
+
+    // Reused buffers
+    let mut parser = LineParser::new(schema);
+    let start = Instant::now();
+    for line in lines {
+        black_box(parser.parse(black_box(line)).is_ok());
+    }
+    report(&format!("{} (reused)", name), lines, start);
+}
+
+fn report(name: &str, lines: &[Vec<u8>], start: Instant) {
+    let elapsed = start.elapsed();
+    println!("{:<16} {:>8.1} ns/line  {:>7.1} MB/s", name,
+             elapsed.as_nanos() as f64 / lines.len() as f64,
+             lines.iter().map(Vec::len).sum::<usize>() as f64 / elapsed.as_secs_f64() / 1e6);
+}
+
+fn main() {
+    let ascii: Vec<Vec<u8>> = (0..N_LINES)
+        .map(|i| format!("1600000000.{}\tdGhpcyBpcyBhIHRlc3Q{}.aGVsbG8gd29ybGQ.t.example.com\n", i, i).into_bytes())
+        .collect();
+
+    let escaped: Vec<Vec<u8>> = (0..N_LINES)
+        .map(|i| {
+            let payload: String = (0..40).map(|j| format!("\\x{:02x}", (i + j * 7) % 256)).collect();
+            format!("1600000000.{}\t{}.t.example.com\n", i, payload).into_bytes()
+        })
+        .collect();
+
+    bench("ascii", &ascii);
+    bench("escaped", &escaped);
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::feature_extraction::PayloadExtractor;
+use crate::shared_interface::LogRecord;
+
+/// Thresholds of the baseline heuristic detector, as used in prior work on DNS tunnel detection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicThresholds {
+    /// Maximum payload length (bytes of the labels in front of the primary domain, excluding dots).
+    pub max_payload_length: u8,
+    /// Maximum Shannon entropy of the payload characters (bits per character).
+    pub max_entropy: f32,
+    /// Maximum number of unique (case-insensitive) payloads per primary domain per clock hour.
+    pub max_unique_per_hour: u32,
+}
+
+impl Default for HeuristicThresholds {
+    fn default() -> Self {
+        HeuristicThresholds { max_payload_length: 52, max_entropy: 4., max_unique_per_hour: 100 }
+    }
+}
+
+/// Set of heuristic rules a query triggered, written as a bitmask in the output.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct HeuristicRules(pub u8);
+
+impl HeuristicRules {
+    /// Payload is longer than `max_payload_length`.
+    pub const LONG_PAYLOAD: u8 = 1;
+    /// Payload entropy exceeds `max_entropy`.
+    pub const HIGH_ENTROPY: u8 = 1 << 1;
+    /// Primary domain has more than `max_unique_per_hour` unique payloads in the hour of the query.
+    pub const UNIQUE_RATE: u8 = 1 << 2;
+
+    pub fn insert(&mut self, rule: u8) {
+        self.0 |= rule;
+    }
+
+    pub fn contains(&self, rule: u8) -> bool {
+        self.0 & rule == rule
+    }
+}
+
+/// Verdict of the heuristic detector for a single query, with the values the thresholds were applied to.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HeuristicVerdict {
+    pub id: usize,
+    pub payload_length: u8,
+    pub entropy: f32,
+    pub n_unique_hour: u32,
+    /// Whether any rule was triggered.
+    pub suspicious: bool,
+    pub rules: HeuristicRules,
+}
+
+/// Apply the heuristic thresholds to the queries of a single primary domain (in any order).
+pub fn detect_heuristic(queries: &[LogRecord], primary_domain_length: u8, thresholds: &HeuristicThresholds) -> Vec<HeuristicVerdict> {
+    // Unique payloads per clock hour
+    let mut unique_per_hour: HashMap<i64, HashSet<Vec<u8>>> = HashMap::new();
+    for query in queries {
+        unique_per_hour.entry(hour_of(query.ts)).or_default().insert(lowercase_payload(query));
+    }
+
+    let mut extractor = PayloadExtractor::new(primary_domain_length);
+    queries.iter()
+        .map(|query| {
+            let payload_length = query.payload.payload_len;
+            let entropy = extractor.process_entry(query.id, &query.payload).entropy;
+            let n_unique_hour = unique_per_hour[&hour_of(query.ts)].len() as u32;
+
+            let mut rules = HeuristicRules::default();
+            if payload_length > thresholds.max_payload_length { rules.insert(HeuristicRules::LONG_PAYLOAD); }
+            if entropy > thresholds.max_entropy { rules.insert(HeuristicRules::HIGH_ENTROPY); }
+            if n_unique_hour > thresholds.max_unique_per_hour { rules.insert(HeuristicRules::UNIQUE_RATE); }
+
+            HeuristicVerdict { id: query.id, payload_length, entropy, n_unique_hour, suspicious: rules.0 != 0, rules }
+        })
+        .collect()
+}
+
+fn hour_of(ts: f64) -> i64 {
+    (ts / 3600.).floor() as i64
+}
+
+fn lowercase_payload(query: &LogRecord) -> Vec<u8> {
+    query.payload.labels.join(&b'.').to_ascii_lowercase()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::detect::{detect_heuristic, HeuristicRules, HeuristicThresholds};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn record(id: usize, ts: f64, payload: &str) -> LogRecord {
+        let labels: Vec<Vec<u8>> = payload.split('.').filter(|l| !l.is_empty()).map(|l| l.as_bytes().to_vec()).collect();
+        let payload = DnsPayload { payload_len: labels.iter().map(Vec::len).sum::<usize>() as u8, labels };
+        LogRecord { id, ts, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+    }
+
+    #[test]
+    fn test_detect_heuristic() {
+        let thresholds = HeuristicThresholds { max_payload_length: 20, max_entropy: 4., max_unique_per_hour: 2 };
+        let queries = vec![
+            record(0, 10., "www"),
+            record(1, 20., "WWW"),
+            record(2, 30., "mail"),
+            record(3, 3700., "a1b2c3d4e5f6g7h8i9j0k.lmnopqrstuvwxyz"),
+        ];
+
+        let verdicts = detect_heuristic(&queries, 11, &thresholds);
+        assert_eq!(vec![2, 2, 2, 1], verdicts.iter().map(|v| v.n_unique_hour).collect::<Vec<_>>());
+        assert!(verdicts[..3].iter().all(|v| !v.suspicious));
+
+        let verdict = &verdicts[3];
+        assert!(verdict.suspicious);
+        assert!(verdict.rules.contains(HeuristicRules::LONG_PAYLOAD | HeuristicRules::HIGH_ENTROPY));
+        assert!(!verdict.rules.contains(HeuristicRules::UNIQUE_RATE));
+
+        // Third unique payload in the first hour
+        let mut queries = queries;
+        queries.push(record(4, 40., "ftp"));
+        let verdicts = detect_heuristic(&queries, 11, &thresholds);
+        assert!(verdicts.iter().filter(|v| v.id != 3).all(|v| v.rules == HeuristicRules(HeuristicRules::UNIQUE_RATE)));
+    }
+}
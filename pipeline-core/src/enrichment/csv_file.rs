@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::enrichment::{DomainEnrichment, EnrichmentError};
+
+/// File-based enrichment from a CSV file with a header, the first column is the primary domain and the other columns
+/// are appended (e.g. `domain,registered,registrar,category`). Domains are matched case-insensitively.
+#[derive(Debug, Default)]
+pub struct CsvEnrichment {
+    columns: Vec<String>,
+    values: HashMap<String, Vec<String>>,
+}
+
+impl CsvEnrichment {
+    pub fn from_file(path: &Path) -> Result<Self, EnrichmentError> {
+        Self::from_reader(File::open(path).map_err(EnrichmentError::IO)?)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, EnrichmentError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+
+        let header = reader.headers().map_err(EnrichmentError::Csv)?;
+        if header.len() < 2 { return Err(EnrichmentError::NoColumns); }
+        let columns = header.iter().skip(1).map(|column| column.to_string()).collect();
+
+        let mut values = HashMap::new();
+        for record in reader.records() {
+            let record = record.map_err(EnrichmentError::Csv)?;
+            let mut fields = record.iter();
+            if let Some(domain) = fields.next() {
+                values.insert(normalize(domain), fields.map(|field| field.to_string()).collect());
+            }
+        }
+
+        Ok(CsvEnrichment { columns, values })
+    }
+}
+
+impl DomainEnrichment for CsvEnrichment {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    fn lookup(&self, primary_domain: &str) -> Option<Vec<String>> {
+        self.values.get(&normalize(primary_domain)).cloned()
+    }
+}
+
+/// Lowercase domain without trailing dot.
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::enrichment::{CsvEnrichment, DomainEnrichment, EnrichmentError};
+
+    #[test]
+    fn test_csv_enrichment() {
+        let csv = "domain,registered,category\nexample.com,1995-08-14,reserved\nEvil.org., 2021-03-01 ,malware\n";
+        let enrichment = CsvEnrichment::from_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(&["registered", "category"], enrichment.columns());
+        assert_eq!(Some(vec![String::from("2021-03-01"), String::from("malware")]), enrichment.lookup("evil.org"));
+        assert_eq!(Some(vec![String::from("1995-08-14"), String::from("reserved")]), enrichment.lookup("EXAMPLE.com."));
+        assert_eq!(None, enrichment.lookup("unknown.net"));
+    }
+
+    #[test]
+    fn test_no_columns() {
+        assert!(matches!(CsvEnrichment::from_reader("domain\nexample.com\n".as_bytes()), Err(EnrichmentError::NoColumns)));
+    }
+}
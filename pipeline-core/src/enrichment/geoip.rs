@@ -0,0 +1,97 @@
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::enrichment::EnrichmentError;
+use crate::shared_interface::GeoInfo;
+
+/// Local lookup of the country and autonomous system of IP addresses in MaxMind-format databases (e.g. GeoLite2-Country
+/// and GeoLite2-ASN), requires the `geoip` feature. Each field is taken from the first database that has it.
+#[derive(Debug)]
+pub struct GeoIpLookup {
+    #[cfg(feature = "geoip")]
+    readers: Vec<maxminddb::Reader<Vec<u8>>>,
+}
+
+/// Fields of interest in both country/city and ASN databases.
+#[cfg(feature = "geoip")]
+#[derive(serde::Deserialize)]
+struct GeoRecord {
+    country: Option<GeoCountry>,
+    autonomous_system_number: Option<u32>,
+}
+
+#[cfg(feature = "geoip")]
+#[derive(serde::Deserialize)]
+struct GeoCountry {
+    iso_code: Option<String>,
+}
+
+impl GeoIpLookup {
+    /// Open (and read into memory) one or more databases.
+    #[cfg(feature = "geoip")]
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<Self, EnrichmentError> {
+        let readers = paths.iter()
+            .map(|path| maxminddb::Reader::open_readfile(path)
+                .map_err(|err| EnrichmentError::GeoIp(format!("{}: {}", path.as_ref().display(), err))))
+            .collect::<Result<_, _>>()?;
+        Ok(GeoIpLookup { readers })
+    }
+
+    /// Open (and read into memory) one or more databases.
+    #[cfg(not(feature = "geoip"))]
+    pub fn open<P: AsRef<Path>>(_paths: &[P]) -> Result<Self, EnrichmentError> {
+        Err(EnrichmentError::GeoIp(String::from("built without the geoip feature")))
+    }
+
+    /// Look up an address in textual form (e.g. a client field). Returns None if it is not an IP address or not in
+    /// any of the databases.
+    pub fn lookup(&self, address: &[u8]) -> Option<GeoInfo> {
+        let ip = parse_ip(address)?;
+        self.lookup_ip(ip)
+    }
+
+    #[cfg(feature = "geoip")]
+    fn lookup_ip(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let mut info = GeoInfo::default();
+        for reader in &self.readers {
+            if let Ok(record) = reader.lookup::<GeoRecord>(ip) {
+                if info.country.is_none() { info.country = record.country.and_then(|country| country.iso_code); }
+                if info.asn.is_none() { info.asn = record.autonomous_system_number; }
+            }
+        }
+        Some(info).filter(|info| info != &GeoInfo::default())
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    fn lookup_ip(&self, _ip: IpAddr) -> Option<GeoInfo> {
+        None
+    }
+}
+
+/// Parse an IPv4 or IPv6 address, ignoring an IPv6 zone index (e.g. `fe80::1%eth0`).
+fn parse_ip(address: &[u8]) -> Option<IpAddr> {
+    let address = std::str::from_utf8(address).ok()?;
+    address.split('%').next()?.parse().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::enrichment::geoip::parse_ip;
+
+    #[test]
+    fn test_parse_ip() {
+        assert_eq!(Some(IpAddr::from([192, 0, 2, 1])), parse_ip(b"192.0.2.1"));
+        assert_eq!(Some("fe80::1".parse().unwrap()), parse_ip(b"fe80::1%eth0"));
+        assert_eq!(None, parse_ip(b"workstation-12"));
+        assert_eq!(None, parse_ip(b"\xff"));
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    #[test]
+    fn test_without_feature() {
+        assert!(crate::enrichment::GeoIpLookup::open(&["GeoLite2-ASN.mmdb"]).is_err());
+    }
+}
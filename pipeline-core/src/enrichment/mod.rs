@@ -0,0 +1,29 @@
+#[cfg(feature = "csv")]
+pub use self::csv_file::CsvEnrichment;
+pub use self::geoip::GeoIpLookup;
+
+#[cfg(feature = "csv")]
+mod csv_file;
+mod geoip;
+
+/// Lookup of additional (e.g. reputation) information per primary domain, appended as columns to the feature vectors.
+pub trait DomainEnrichment: Send + Sync {
+    /// Names of the appended columns.
+    fn columns(&self) -> &[String];
+
+    /// Values for a primary domain, one per column. Returns None for unknown domains.
+    fn lookup(&self, primary_domain: &str) -> Option<Vec<String>>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnrichmentError {
+    #[error("could not read enrichment file")]
+    IO(#[source] std::io::Error),
+    #[cfg(feature = "csv")]
+    #[error("invalid enrichment file")]
+    Csv(#[source] csv::Error),
+    #[error("invalid enrichment file: expected a domain column and at least one other column")]
+    NoColumns,
+    #[error("could not open GeoIP database: {0}")]
+    GeoIp(String),
+}
@@ -0,0 +1,52 @@
+use crate::enrichment::EnrichmentError;
+use crate::feature_extraction::{ColumnarError, RowFilterError};
+use crate::metrics::MetricsError;
+use crate::object_store::ObjectStoreError;
+use crate::parse_dns::{ParseDnsError, SuffixListError};
+use crate::parse_log::ParseLineError;
+use crate::shared_interface::CodecError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Any error of the library, wrapping the error of the module it originates from. Display and `source()` are those of
+/// the wrapped error, so the underlying cause (e.g. an I/O error) remains reachable with `source()`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    ParseLine(#[from] ParseLineError),
+    #[error(transparent)]
+    ParseDns(#[from] ParseDnsError),
+    #[error(transparent)]
+    SuffixList(#[from] SuffixListError),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    RowFilter(#[from] RowFilterError),
+    #[error(transparent)]
+    Columnar(#[from] ColumnarError),
+    #[error(transparent)]
+    Enrichment(#[from] EnrichmentError),
+    #[error(transparent)]
+    Metrics(#[from] MetricsError),
+    #[error(transparent)]
+    ObjectStore(#[from] ObjectStoreError),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use crate::Error;
+    use crate::parse_dns::SuffixListError;
+
+    #[test]
+    fn test_source_chain() {
+        let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let err = Error::from(SuffixListError::IO(io_err));
+
+        assert_eq!("could not read public suffix list", err.to_string());
+        assert_eq!("no such file", err.source().unwrap().to_string());
+        assert!(err.source().unwrap().source().is_none());
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::io::{BufRead, Result};
+use std::sync::Arc;
+
+use crate::parse_dns::DnsPayload;
+
+/// Symbols of the model: letters (case-insensitive), digits, hyphen, underscore, any other byte and the label boundary.
+const N_SYMBOLS: usize = 40;
+const OTHER: usize = 38;
+const BOUNDARY: usize = 39;
+
+/// Fixed-point scale of the log-probabilities, so window sums are exact when queries are removed again.
+const SCALE: f64 = 65536.;
+
+lazy_static! {
+    static ref EMBEDDED: Arc<BigramModel> = Arc::new(BigramModel::from_hostnames(include_str!("bigram_hostnames.txt").as_bytes())
+        .expect("embedded hostnames"));
+}
+
+fn symbol(ch: u8) -> usize {
+    match ch.to_ascii_lowercase() {
+        ch @ b'a'..=b'z' => (ch - b'a') as usize,
+        ch @ b'0'..=b'9' => 26 + (ch - b'0') as usize,
+        b'-' => 36,
+        b'_' => 37,
+        _ => OTHER,
+    }
+}
+
+/// Character bigram (first-order Markov chain) model of benign hostname labels, with add-one smoothing. The
+/// log-likelihood of a label is low for character sequences that are rare in hostnames, such as encoded data.
+#[derive(Clone, PartialEq)]
+pub struct BigramModel {
+    /// Log2-probability of every (symbol, next symbol) transition, in `SCALE` units.
+    log_probs: Vec<i32>,
+}
+
+impl BigramModel {
+    /// Model trained on the embedded list of benign hostnames.
+    pub fn embedded() -> Arc<BigramModel> {
+        EMBEDDED.clone()
+    }
+
+    /// Train on labels, where every label is a sequence of transitions from and to the label boundary.
+    pub fn train<'a, I: IntoIterator<Item=&'a [u8]>>(labels: I) -> Self {
+        let mut counts = vec![1u64; N_SYMBOLS * N_SYMBOLS];
+        for label in labels {
+            transitions(label).for_each(|(from, to)| counts[from * N_SYMBOLS + to] += 1);
+        }
+
+        let log_probs = counts.chunks(N_SYMBOLS)
+            .flat_map(|row| {
+                let total: u64 = row.iter().sum();
+                row.iter().map(move |&count| ((count as f64 / total as f64).log2() * SCALE).round() as i32)
+            })
+            .collect();
+        BigramModel { log_probs }
+    }
+
+    /// Train on the labels of a list of hostnames, one per line. Empty lines and lines starting with `#` are skipped.
+    pub fn from_hostnames<R: BufRead>(reader: R) -> Result<Self> {
+        let mut labels: Vec<Vec<u8>> = Vec::new();
+        for line in reader.split(b'\n') {
+            let line = line?;
+            let hostname = line.trim_ascii();
+            if hostname.is_empty() || hostname.starts_with(b"#") { continue; }
+            labels.extend(hostname.split(|&ch| ch == b'.').filter(|label| !label.is_empty()).map(<[u8]>::to_vec));
+        }
+        Ok(Self::train(labels.iter().map(Vec::as_slice)))
+    }
+
+    /// Sum of the (fixed-point) log-probabilities of the transitions of the labels of a query, and the number of
+    /// transitions.
+    fn score(&self, payload: &DnsPayload) -> (i64, usize) {
+        payload.labels.iter()
+            .flat_map(|label| transitions(label))
+            .fold((0, 0), |(sum, n), (from, to)| (sum + self.log_probs[from * N_SYMBOLS + to] as i64, n + 1))
+    }
+
+    /// Average log2-probability per character transition of the labels of a query, NaN for a query without labels.
+    pub fn avg_log_likelihood(&self, payload: &DnsPayload) -> f32 {
+        let (sum, n) = self.score(payload);
+        average(sum, n)
+    }
+}
+
+impl Default for BigramModel {
+    fn default() -> Self {
+        BigramModel::embedded().as_ref().clone()
+    }
+}
+
+impl fmt::Debug for BigramModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BigramModel").finish_non_exhaustive()
+    }
+}
+
+/// Transitions of a label, including those from and to the label boundary.
+fn transitions(label: &[u8]) -> impl Iterator<Item=(usize, usize)> + '_ {
+    let symbols = || std::iter::once(BOUNDARY).chain(label.iter().map(|&ch| symbol(ch)));
+    symbols().zip(symbols().skip(1).chain(std::iter::once(BOUNDARY)))
+}
+
+fn average(sum: i64, n: usize) -> f32 {
+    (sum as f64 / SCALE / n as f64) as f32
+}
+
+/// Average bigram log-likelihood of the labels of the queries in a window.
+#[derive(Debug)]
+pub struct WindowLikelihood {
+    model: Arc<BigramModel>,
+    sum: i64,
+    n_transitions: usize,
+}
+
+impl WindowLikelihood {
+    pub fn new(model: Arc<BigramModel>) -> Self {
+        WindowLikelihood { model, sum: 0, n_transitions: 0 }
+    }
+
+    pub fn add(&mut self, payload: &DnsPayload) {
+        let (sum, n) = self.model.score(payload);
+        self.sum += sum;
+        self.n_transitions += n;
+    }
+
+    pub fn remove(&mut self, payload: &DnsPayload) {
+        let (sum, n) = self.model.score(payload);
+        self.sum -= sum;
+        self.n_transitions -= n;
+    }
+
+    /// Average log2-probability per character transition, NaN for a window without labels.
+    pub fn get(&self) -> f32 {
+        average(self.sum, self.n_transitions)
+    }
+}
+
+impl Default for WindowLikelihood {
+    fn default() -> Self {
+        WindowLikelihood::new(BigramModel::embedded())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::bigram::{BigramModel, WindowLikelihood};
+    use crate::parse_dns::DnsPayload;
+
+    fn payload(labels: &[&[u8]]) -> DnsPayload {
+        let labels: Vec<Vec<u8>> = labels.iter().map(|label| label.to_vec()).collect();
+        let payload_len = labels.iter().map(|label| label.len()).sum::<usize>() as u8;
+        DnsPayload { labels, payload_len }
+    }
+
+    #[test]
+    fn test_embedded_model() {
+        let model = BigramModel::embedded();
+
+        // Hostname-like labels are more likely than encoded data
+        let benign = model.avg_log_likelihood(&payload(&[b"mail", b"www"]));
+        let encoded = model.avg_log_likelihood(&payload(&[b"q8zx0vjk3nfw7y"]));
+        assert!(benign > encoded, "{} <= {}", benign, encoded);
+        assert!(benign < 0.);
+
+        // Case-insensitive, NaN without labels
+        assert_eq!(benign, model.avg_log_likelihood(&payload(&[b"MAIL", b"Www"])));
+        assert!(model.avg_log_likelihood(&payload(&[])).is_nan());
+    }
+
+    #[test]
+    fn test_model_from_hostnames() {
+        let model = BigramModel::from_hostnames(&b"# local names\nab.ab.example\n\nAB.local\n"[..]).unwrap();
+        assert!(model.avg_log_likelihood(&payload(&[b"ab"])) > model.avg_log_likelihood(&payload(&[b"ba"])));
+        assert_ne!(BigramModel::default(), model);
+    }
+
+    #[test]
+    fn test_window_likelihood() {
+        let model = BigramModel::embedded();
+        let (a, b) = (payload(&[b"mail"]), payload(&[b"x9q", b"zz"]));
+
+        let mut window = WindowLikelihood::new(model.clone());
+        assert!(window.get().is_nan());
+
+        window.add(&a);
+        window.add(&b);
+        window.remove(&a);
+        assert_eq!(model.avg_log_likelihood(&b), window.get());
+
+        window.remove(&b);
+        assert!(window.get().is_nan());
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use crate::feature_extraction::state::LabelKey;
+
+/// Number of bits of a key that select the register of a HyperLogLog sketch.
+const PRECISION: u32 = 12;
+const N_REGISTERS: usize = 1 << PRECISION;
+
+/// Number of keys that are counted exactly, before switching to the sketch (which is then about as large).
+const MAX_EXACT: usize = N_REGISTERS / std::mem::size_of::<LabelKey>();
+
+/// Number of distinct keys (e.g. `query_key` of the queries of a primary domain) in bounded memory: exact for up to
+/// `MAX_EXACT` keys, a HyperLogLog estimate (standard error of about 1.6%) for more.
+#[derive(Debug, Clone)]
+pub enum HyperLogLog {
+    Exact(HashSet<LabelKey>),
+    /// Maximum rank (position of the first one bit) of the keys per register.
+    Sketch(Box<[u8]>),
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::Exact(HashSet::new())
+    }
+}
+
+impl HyperLogLog {
+    /// Add a key (a 64-bit hash, such as `query_key`).
+    pub fn insert(&mut self, key: LabelKey) {
+        match self {
+            HyperLogLog::Exact(keys) => {
+                keys.insert(key);
+                if keys.len() > MAX_EXACT {
+                    let mut registers = vec![0; N_REGISTERS].into_boxed_slice();
+                    keys.iter().for_each(|&key| update_register(&mut registers, key));
+                    *self = HyperLogLog::Sketch(registers);
+                }
+            }
+            HyperLogLog::Sketch(registers) => update_register(registers, key),
+        }
+    }
+
+    /// (Estimated) number of distinct keys.
+    pub fn count(&self) -> u64 {
+        match self {
+            HyperLogLog::Exact(keys) => keys.len() as u64,
+            HyperLogLog::Sketch(registers) => {
+                let m = N_REGISTERS as f64;
+                let alpha = 0.7213 / (1. + 1.079 / m);
+                let sum: f64 = registers.iter().map(|&rank| 2f64.powi(-(rank as i32))).sum();
+                let estimate = alpha * m * m / sum;
+
+                // Linear counting for small cardinalities, where the raw estimate is biased
+                let n_zero = registers.iter().filter(|&&rank| rank == 0).count();
+                if estimate <= 2.5 * m && n_zero > 0 {
+                    (m * (m / n_zero as f64).ln()).round() as u64
+                } else {
+                    estimate.round() as u64
+                }
+            }
+        }
+    }
+}
+
+fn update_register(registers: &mut [u8], key: LabelKey) {
+    let index = (key >> (64 - PRECISION)) as usize;
+    let rank = ((key << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+    registers[index] = registers[index].max(rank);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::cardinality::{HyperLogLog, MAX_EXACT};
+    use crate::feature_extraction::state::query_key;
+
+    #[test]
+    fn test_exact() {
+        let mut counter = HyperLogLog::default();
+        for label in [&b"abc"[..], b"ABC", b"def", b"abc"].iter() {
+            counter.insert(query_key(&[label.to_vec()]));
+        }
+        assert_eq!(2, counter.count());
+        assert!(matches!(counter, HyperLogLog::Exact(_)));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut counter = HyperLogLog::default();
+        for i in 0..100_000u32 {
+            counter.insert(query_key(&[i.to_string().into_bytes()]));
+            if i as usize == MAX_EXACT - 1 { assert_eq!(MAX_EXACT as u64, counter.count()); }
+        }
+        assert!(matches!(counter, HyperLogLog::Sketch(_)));
+
+        // Within a few standard errors, and duplicates are not counted
+        let estimate = counter.count();
+        assert!((95_000..105_000).contains(&estimate), "{}", estimate);
+        counter.insert(query_key(&[b"1".to_vec()]));
+        assert_eq!(estimate, counter.count());
+    }
+}
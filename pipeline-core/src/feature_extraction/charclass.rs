@@ -0,0 +1,139 @@
+/// Number of bytes per class in a byte string (see `classify`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CharClasses {
+    pub digits: usize,
+    pub uppercase: usize,
+    pub lowercase: usize,
+    pub underscore: usize,
+    pub dots: usize,
+    /// Bytes other than letters, digits, hyphens, underscores and dots.
+    pub invalid: usize,
+    /// Bytes outside printable ASCII (a subset of `invalid`).
+    pub nonprintable: usize,
+}
+
+impl CharClasses {
+    /// Whether there are both upper and lower case letters, consistent with 0x20 case randomization.
+    pub fn is_mixed_case(&self) -> bool {
+        self.uppercase > 0 && self.lowercase > 0
+    }
+
+    /// Whether all bytes are letters, digits, hyphens or underscores (so they are all ASCII).
+    pub fn is_hostname(&self) -> bool {
+        self.invalid == 0 && self.dots == 0
+    }
+}
+
+/// Bytes per step of `classify`, processed as independent lanes (u8x16).
+const LANES: usize = 16;
+
+/// Per-lane counters of one class, which are summed after at most 255 steps so they cannot overflow.
+type Lanes = [u8; LANES];
+
+/// Count the character classes of a byte string.
+///
+/// The bytes are classified branch-free in lanes of 16 with per-lane counters, which the compiler turns into SIMD
+/// instructions of the target (e.g. SSE2 on x86-64, or AVX2 with `-C target-cpu=native`). The crates do not allow
+/// unsafe code, which rules out selecting instructions at runtime with `std::arch`.
+pub fn classify(bytes: &[u8]) -> CharClasses {
+    let mut classes = CharClasses::default();
+    let n_full = bytes.len() - bytes.len() % LANES;
+
+    for block in bytes[..n_full].chunks(LANES * u8::MAX as usize) {
+        let (mut digits, mut uppercase, mut lowercase, mut underscore): (Lanes, Lanes, Lanes, Lanes) = Default::default();
+        let (mut dots, mut valid, mut printable): (Lanes, Lanes, Lanes) = Default::default();
+
+        for chunk in block.chunks_exact(LANES) {
+            for i in 0..LANES {
+                let class = classify_byte(chunk[i]);
+                digits[i] += class[0];
+                uppercase[i] += class[1];
+                lowercase[i] += class[2];
+                underscore[i] += class[3];
+                dots[i] += class[4];
+                valid[i] += class[5];
+                printable[i] += class[6];
+            }
+        }
+
+        let sum = |lanes: Lanes| lanes.iter().map(|&n| n as usize).sum::<usize>();
+        classes.digits += sum(digits);
+        classes.uppercase += sum(uppercase);
+        classes.lowercase += sum(lowercase);
+        classes.underscore += sum(underscore);
+        classes.dots += sum(dots);
+        classes.invalid += block.len() - sum(valid);
+        classes.nonprintable += block.len() - sum(printable);
+    }
+
+    for &ch in &bytes[n_full..] {
+        let class = classify_byte(ch);
+        classes.digits += class[0] as usize;
+        classes.uppercase += class[1] as usize;
+        classes.lowercase += class[2] as usize;
+        classes.underscore += class[3] as usize;
+        classes.dots += class[4] as usize;
+        classes.invalid += 1 - class[5] as usize;
+        classes.nonprintable += 1 - class[6] as usize;
+    }
+
+    classes
+}
+
+/// Digit, uppercase, lowercase, underscore, dot, valid and printable flags (0 or 1) of a byte, without branches.
+#[inline(always)]
+fn classify_byte(ch: u8) -> [u8; 7] {
+    let digit = ch.wrapping_sub(b'0') < 10;
+    let upper = ch.wrapping_sub(b'A') < 26;
+    let lower = ch.wrapping_sub(b'a') < 26;
+    let underscore = ch == b'_';
+    let dot = ch == b'.';
+    let valid = digit | upper | lower | underscore | dot | (ch == b'-');
+    let printable = ch.wrapping_sub(b' ') < 95;
+    [digit as u8, upper as u8, lower as u8, underscore as u8, dot as u8, valid as u8, printable as u8]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::charclass::{CharClasses, classify};
+
+    fn classify_scalar(bytes: &[u8]) -> CharClasses {
+        let mut classes = CharClasses::default();
+        for &ch in bytes {
+            match ch {
+                b'0'..=b'9' => classes.digits += 1,
+                b'A'..=b'Z' => classes.uppercase += 1,
+                b'_' => classes.underscore += 1,
+                b'.' => classes.dots += 1,
+                b'a'..=b'z' => classes.lowercase += 1,
+                b'-' => {}
+                b' '..=b'~' => classes.invalid += 1,
+                _ => { classes.invalid += 1; classes.nonprintable += 1 }
+            }
+        }
+        classes
+    }
+
+    #[test]
+    fn test_classify() {
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(classify_scalar(&all), classify(&all));
+        assert_eq!(CharClasses { digits: 10, uppercase: 26, lowercase: 26, underscore: 1, dots: 1, invalid: 256 - 65, nonprintable: 256 - 95 }, classify(&all));
+
+        // Every length around the lane width, and enough steps to overflow per-lane counters
+        let mut state: u32 = 7;
+        let bytes: Vec<u8> = (0..10_000).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+        for len in (0..40).chain([4080, 4081, 10_000]) {
+            assert_eq!(classify_scalar(&bytes[..len]), classify(&bytes[..len]), "length {}", len);
+        }
+        assert_eq!(CharClasses { digits: 4096, ..Default::default() }, classify(&[b'7'; 4096]));
+
+        assert!(classify(b"a-b_C9").is_hostname());
+        assert!(!classify(b"a.b").is_hostname());
+        assert!(!classify(b"a b").is_hostname());
+    }
+}
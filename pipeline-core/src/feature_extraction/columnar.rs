@@ -0,0 +1,323 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::ser::{self, Impossible, Serialize, Serializer};
+
+use crate::feature_extraction::feature_vector::FeatureVector;
+
+/// Feature vectors of a single family in columnar form: record IDs plus a row-major `f32` matrix.
+/// Integer and boolean features are converted to `f32`, column names are the CSV column names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureMatrix {
+    pub columns: Vec<&'static str>,
+    pub ids: Vec<usize>,
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ColumnarError {
+    /// Feature vectors of different families (e.g. payload and time window) in one matrix.
+    #[error("feature vectors of different families cannot be combined in one matrix")]
+    MixedFamilies,
+    #[error("unsupported feature value: {0}")]
+    Unsupported(String),
+    #[error("HDF5 output: {0}")]
+    Hdf5(String),
+}
+
+impl ser::Error for ColumnarError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ColumnarError::Unsupported(msg.to_string())
+    }
+}
+
+impl FeatureMatrix {
+    /// Build a matrix from feature vectors of the same family.
+    pub fn from_vectors<'a, I: IntoIterator<Item=&'a FeatureVector>>(vectors: I) -> Result<Self, ColumnarError> {
+        let mut matrix = FeatureMatrix { columns: Vec::new(), ids: Vec::new(), values: Vec::new() };
+        for fv in vectors {
+            matrix.push(fv)?;
+        }
+        Ok(matrix)
+    }
+
+    /// Append a feature vector of the same family as the rows so far.
+    pub fn push(&mut self, fv: &FeatureVector) -> Result<(), ColumnarError> {
+        let mut row = RowCollector::default();
+        fv.serialize(&mut row)?;
+
+        if self.ids.is_empty() {
+            self.columns = row.columns;
+        } else if self.columns != row.columns {
+            return Err(ColumnarError::MixedFamilies);
+        }
+        self.ids.push(row.id);
+        self.values.extend(row.values);
+        Ok(())
+    }
+
+    /// Keep only the columns for which `keep` returns true.
+    pub fn retain_columns<F: Fn(&str) -> bool>(&mut self, keep: F) {
+        let kept: Vec<bool> = self.columns.iter().map(|column| keep(column)).collect();
+        if kept.iter().all(|&kept| kept) { return; }
+
+        let n_columns = self.columns.len();
+        self.values = self.values.chunks(n_columns.max(1))
+            .flat_map(|row| row.iter().zip(&kept).filter(|(_, &kept)| kept).map(|(&value, _)| value))
+            .collect();
+        self.columns = self.columns.iter().zip(&kept).filter(|(_, &kept)| kept).map(|(&column, _)| column).collect();
+    }
+
+    /// Split feature vectors by family and build a matrix per family, in order of first appearance.
+    pub fn from_mixed<'a, I: IntoIterator<Item=&'a FeatureVector>>(vectors: I) -> Result<Vec<(&'static str, Self)>, ColumnarError> {
+        let mut families: Vec<(&'static str, Vec<&FeatureVector>)> = Vec::new();
+        for fv in vectors {
+            match families.iter_mut().find(|(family, _)| *family == fv.family()) {
+                Some((_, family_vectors)) => family_vectors.push(fv),
+                None => families.push((fv.family(), vec![fv])),
+            }
+        }
+
+        families.into_iter()
+            .map(|(family, family_vectors)| Ok((family, FeatureMatrix::from_vectors(family_vectors)?)))
+            .collect()
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn n_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn row(&self, index: usize) -> &[f32] {
+        &self.values[index * self.n_columns()..(index + 1) * self.n_columns()]
+    }
+
+    /// Values of a single column (by full column name, e.g. `win_time_entropy`).
+    pub fn column(&self, name: &str) -> Option<Vec<f32>> {
+        let idx = self.columns.iter().position(|column| *column == name)?;
+        Some(self.values.iter().skip(idx).step_by(self.n_columns()).copied().collect())
+    }
+
+    /// Write matrices per family (e.g. of `from_mixed`) to an HDF5 file, requires the `hdf5` feature. Every family is a
+    /// group with the record IDs in an `id` dataset and a `features` dataset of shape (rows, columns), whose `columns`
+    /// attribute holds the column names.
+    #[cfg(feature = "hdf5")]
+    pub fn write_hdf5<P: AsRef<Path>>(path: P, matrices: &[(&str, FeatureMatrix)]) -> Result<(), ColumnarError> {
+        use hdf5::types::VarLenUnicode;
+
+        let hdf5_err = |err: hdf5::Error| ColumnarError::Hdf5(err.to_string());
+        let file = hdf5::File::create(path).map_err(hdf5_err)?;
+
+        for (family, matrix) in matrices {
+            let group = file.create_group(family).map_err(hdf5_err)?;
+
+            let ids: Vec<u64> = matrix.ids.iter().map(|&id| id as u64).collect();
+            group.new_dataset_builder().with_data(&ids).create("id").map_err(hdf5_err)?;
+
+            let features = group.new_dataset::<f32>().shape((matrix.n_rows(), matrix.n_columns())).create("features").map_err(hdf5_err)?;
+            features.write_raw(&matrix.values).map_err(hdf5_err)?;
+
+            let columns = matrix.columns.iter()
+                .map(|column| column.parse::<VarLenUnicode>().map_err(|err| ColumnarError::Hdf5(err.to_string())))
+                .collect::<Result<Vec<_>, _>>()?;
+            features.new_attr_builder().with_data(&columns).create("columns").map_err(hdf5_err)?;
+        }
+
+        file.close().map_err(hdf5_err)
+    }
+
+    /// Write matrices per family to an HDF5 file, requires the `hdf5` feature.
+    #[cfg(not(feature = "hdf5"))]
+    pub fn write_hdf5<P: AsRef<Path>>(_path: P, _matrices: &[(&str, FeatureMatrix)]) -> Result<(), ColumnarError> {
+        Err(ColumnarError::Hdf5(String::from("built without the hdf5 feature")))
+    }
+
+    /// Convert into an `ndarray` matrix of shape (rows, columns); the IDs are returned separately.
+    #[cfg(feature = "ndarray")]
+    pub fn into_array2(self) -> (Vec<usize>, ndarray::Array2<f32>) {
+        let shape = (self.ids.len(), self.columns.len());
+        // (unwrap safe here because every row has exactly one value per column)
+        (self.ids, ndarray::Array2::from_shape_vec(shape, self.values).unwrap())
+    }
+}
+
+/// Serializer that collects the fields of a single feature vector, the `id` field is kept separately.
+#[derive(Default)]
+struct RowCollector {
+    id: usize,
+    columns: Vec<&'static str>,
+    values: Vec<f32>,
+}
+
+/// Serializer for a single feature value.
+struct ValueSerializer;
+
+macro_rules! unsupported {
+    ($($method:ident($($arg:ty),*)),*) => {
+        $(fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> { Err(ColumnarError::Unsupported(stringify!($method).to_string())) })*
+    };
+}
+
+macro_rules! unsupported_compound {
+    () => {
+        fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<Self::Ok, Self::Error> { Err(ColumnarError::Unsupported("option".to_string())) }
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, _: &T) -> Result<Self::Ok, Self::Error> { Err(ColumnarError::Unsupported("newtype struct".to_string())) }
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _: &'static str, _: u32, _: &'static str, _: &T) -> Result<Self::Ok, Self::Error> { Err(ColumnarError::Unsupported("newtype variant".to_string())) }
+        fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> { Err(ColumnarError::Unsupported("sequence".to_string())) }
+        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> { Err(ColumnarError::Unsupported("tuple".to_string())) }
+        fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, Self::Error> { Err(ColumnarError::Unsupported("tuple struct".to_string())) }
+        fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeTupleVariant, Self::Error> { Err(ColumnarError::Unsupported("tuple variant".to_string())) }
+        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> { Err(ColumnarError::Unsupported("map".to_string())) }
+        fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeStructVariant, Self::Error> { Err(ColumnarError::Unsupported("struct variant".to_string())) }
+    };
+}
+
+impl Serializer for &mut RowCollector {
+    type Ok = ();
+    type Error = ColumnarError;
+    type SerializeSeq = Impossible<(), ColumnarError>;
+    type SerializeTuple = Impossible<(), ColumnarError>;
+    type SerializeTupleStruct = Impossible<(), ColumnarError>;
+    type SerializeTupleVariant = Impossible<(), ColumnarError>;
+    type SerializeMap = Impossible<(), ColumnarError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), ColumnarError>;
+
+    unsupported!(serialize_bool(bool), serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64), serialize_f32(f32), serialize_f64(f64),
+        serialize_char(char), serialize_str(&str), serialize_bytes(&[u8]), serialize_none(), serialize_unit(),
+        serialize_unit_struct(&'static str), serialize_unit_variant(&'static str, u32, &'static str));
+    unsupported_compound!();
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        self.columns.reserve(len);
+        self.values.reserve(len);
+        Ok(self)
+    }
+}
+
+impl ser::SerializeStruct for &mut RowCollector {
+    type Ok = ();
+    type Error = ColumnarError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        let value = value.serialize(ValueSerializer)?;
+
+        // Prefixed ID column, e.g. `win_time_id`
+        if key.ends_with("_id") && self.columns.is_empty() {
+            self.id = value as usize;
+        } else {
+            self.columns.push(key);
+            self.values.push(value as f32);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = f64;
+    type Error = ColumnarError;
+    type SerializeSeq = Impossible<f64, ColumnarError>;
+    type SerializeTuple = Impossible<f64, ColumnarError>;
+    type SerializeTupleStruct = Impossible<f64, ColumnarError>;
+    type SerializeTupleVariant = Impossible<f64, ColumnarError>;
+    type SerializeMap = Impossible<f64, ColumnarError>;
+    type SerializeStruct = Impossible<f64, ColumnarError>;
+    type SerializeStructVariant = Impossible<f64, ColumnarError>;
+
+    fn serialize_bool(self, v: bool) -> Result<f64, ColumnarError> { Ok(v as u8 as f64) }
+    fn serialize_i8(self, v: i8) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_i16(self, v: i16) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_i32(self, v: i32) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_i64(self, v: i64) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_u8(self, v: u8) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_u16(self, v: u16) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_u32(self, v: u32) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_u64(self, v: u64) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_f32(self, v: f32) -> Result<f64, ColumnarError> { Ok(v as f64) }
+    fn serialize_f64(self, v: f64) -> Result<f64, ColumnarError> { Ok(v) }
+
+    unsupported!(serialize_char(char), serialize_str(&str), serialize_bytes(&[u8]), serialize_none(), serialize_unit(),
+        serialize_unit_struct(&'static str), serialize_unit_variant(&'static str, u32, &'static str));
+    unsupported_compound!();
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(ColumnarError::Unsupported("struct".to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::columnar::{ColumnarError, FeatureMatrix};
+    use crate::feature_extraction::feature_vector::{FeatureVector, GlobalWindowFeatureVector, PayloadFeatureVector};
+
+    fn global(id: usize, query_rate: f32, n_domains: usize) -> FeatureVector {
+        FeatureVector::Global(GlobalWindowFeatureVector { id, query_rate, n_domains, ..Default::default() })
+    }
+
+    #[test]
+    fn test_from_vectors() {
+        let matrix = FeatureMatrix::from_vectors(&[global(3, 0.5, 2), global(8, 1.5, 4)]).unwrap();
+
+        assert_eq!(vec!["win_global_query_rate", "win_global_n_domains", "win_global_rare_domain_ratio", "win_global_n_unique_labels", "win_global_entropy"], matrix.columns);
+        assert_eq!(vec![3, 8], matrix.ids);
+        assert_eq!((2, 5), (matrix.n_rows(), matrix.n_columns()));
+        assert_eq!(&[1.5, 4., 0., 0., 0.], matrix.row(1));
+        assert_eq!(Some(vec![2., 4.]), matrix.column("win_global_n_domains"));
+        assert_eq!(None, matrix.column("win_global_id"));
+    }
+
+    #[test]
+    fn test_mixed_families() {
+        let vectors = [global(1, 0., 0), FeatureVector::Payload(PayloadFeatureVector { id: 1, ..Default::default() }), global(2, 0., 0)];
+        assert_eq!(Err(ColumnarError::MixedFamilies), FeatureMatrix::from_vectors(&vectors));
+
+        let matrices = FeatureMatrix::from_mixed(&vectors).unwrap();
+        assert_eq!(vec![("win_global", 2), ("pl", 1)], matrices.iter().map(|(family, m)| (*family, m.n_rows())).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_push() {
+        let mut matrix = FeatureMatrix::from_vectors(&[global(3, 0.5, 2)]).unwrap();
+        matrix.push(&global(4, 1., 1)).unwrap();
+        assert_eq!(vec![3, 4], matrix.ids);
+        assert_eq!(Err(ColumnarError::MixedFamilies), matrix.push(&FeatureVector::Payload(PayloadFeatureVector::default())));
+    }
+
+    #[test]
+    fn test_retain_columns() {
+        let mut matrix = FeatureMatrix::from_vectors(&[global(3, 0.5, 2), global(8, 1.5, 4)]).unwrap();
+        matrix.retain_columns(|column| !column.ends_with("_entropy") && column != "win_global_query_rate");
+
+        assert_eq!(vec!["win_global_n_domains", "win_global_rare_domain_ratio", "win_global_n_unique_labels"], matrix.columns);
+        assert_eq!((2, 3), (matrix.n_rows(), matrix.n_columns()));
+        assert_eq!(&[4., 0., 0.], matrix.row(1));
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_write_hdf5() {
+        let path = std::env::temp_dir().join(format!("tdp-columnar-{}.h5", std::process::id()));
+        let matrices = FeatureMatrix::from_mixed(&[global(3, 0.5, 2), global(8, 1.5, 4)]).unwrap();
+        FeatureMatrix::write_hdf5(&path, &matrices).unwrap();
+
+        let file = hdf5::File::open(&path).unwrap();
+        let features = file.dataset("win_global/features").unwrap();
+        assert_eq!(vec![2, 5], features.shape());
+        assert_eq!(matrices[0].1.values, features.read_raw::<f32>().unwrap());
+        assert_eq!(vec![3u64, 8], file.dataset("win_global/id").unwrap().read_raw::<u64>().unwrap());
+
+        let columns: Vec<String> = features.attr("columns").unwrap().read_raw::<hdf5::types::VarLenUnicode>().unwrap()
+            .iter().map(|column| column.to_string()).collect();
+        assert_eq!(matrices[0].1.columns, columns);
+        std::fs::remove_file(path).unwrap();
+    }
+}
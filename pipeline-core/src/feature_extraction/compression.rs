@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+#[cfg(feature = "compression")]
+use std::collections::HashSet;
+
+#[cfg(feature = "compression")]
+use flate2::{Compress, Compression, FlushCompress, Status};
+
+#[cfg(feature = "compression")]
+use crate::feature_extraction::state::{label_key, LabelKey};
+use crate::shared_interface::LogRecord;
+
+/// Maximum number of label bytes that are compressed per window, which bounds the cost per feature vector.
+#[cfg(feature = "compression")]
+pub const MAX_COMPRESSION_INPUT: usize = 4096;
+
+/// Deflate compression ratio (compressed / uncompressed size) of the concatenated unique labels of a window, newest
+/// first and up to `MAX_COMPRESSION_INPUT` bytes. An approximation of the Kolmogorov complexity: random encodings
+/// barely compress, while repetitive or structured labels do. Small inputs can have a ratio above 1 due to the
+/// overhead of the format. Requires the `compression` feature, the ratio is NaN without.
+///
+/// The compressor and buffers are reused, as setting up a compressor costs more than compressing a window.
+#[cfg(feature = "compression")]
+pub struct LabelCompression {
+    compress: Compress,
+    seen: HashSet<LabelKey>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+#[cfg(not(feature = "compression"))]
+#[derive(Default)]
+pub struct LabelCompression {}
+
+#[cfg(feature = "compression")]
+impl Default for LabelCompression {
+    fn default() -> Self {
+        LabelCompression {
+            compress: Compress::new(Compression::fast(), false),
+            seen: HashSet::new(),
+            input: Vec::with_capacity(MAX_COMPRESSION_INPUT),
+            // (worst case of deflate is a few bytes per 16 KB block on top of the input)
+            output: Vec::with_capacity(MAX_COMPRESSION_INPUT + 64),
+        }
+    }
+}
+
+impl LabelCompression {
+    /// Compression ratio of the unique labels in `content` (the records in the window), NaN for a window without labels.
+    #[cfg(feature = "compression")]
+    pub fn ratio(&mut self, content: &VecDeque<LogRecord>) -> f32 {
+        self.seen.clear();
+        self.input.clear();
+
+        'records: for record in content.iter().rev() {
+            for label in record.payload.labels.iter().rev() {
+                if !self.seen.insert(label_key(label)) { continue; }
+
+                let n = label.len().min(MAX_COMPRESSION_INPUT - self.input.len());
+                self.input.extend_from_slice(&label[..n]);
+                if self.input.len() == MAX_COMPRESSION_INPUT { break 'records; }
+            }
+        }
+        if self.input.is_empty() { return f32::NAN; }
+
+        self.output.clear();
+        self.compress.reset();
+        // (output capacity suffices for the whole input, compressing in memory does not fail otherwise)
+        let status = self.compress.compress_vec(&self.input, &mut self.output, FlushCompress::Finish).unwrap();
+        debug_assert_eq!(Status::StreamEnd, status);
+
+        self.output.len() as f32 / self.input.len() as f32
+    }
+
+    /// Compression ratio of the unique labels in `content`, always NaN without the `compression` feature.
+    #[cfg(not(feature = "compression"))]
+    pub fn ratio(&mut self, _content: &VecDeque<LogRecord>) -> f32 {
+        f32::NAN
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::feature_extraction::compression::LabelCompression;
+    #[cfg(feature = "compression")]
+    use crate::feature_extraction::compression::MAX_COMPRESSION_INPUT;
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn window(labels: impl Iterator<Item=Vec<u8>>) -> VecDeque<LogRecord> {
+        labels.enumerate().map(|(id, label)| {
+            let payload = DnsPayload { payload_len: label.len() as u8, labels: vec![label] };
+            LogRecord { id, ts: id as f64, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+        }).collect()
+    }
+
+    fn label_compression_ratio(content: &VecDeque<LogRecord>) -> f32 {
+        LabelCompression::default().ratio(content)
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_label_compression_ratio() {
+        assert!(label_compression_ratio(&VecDeque::new()).is_nan());
+
+        // Structured counters compress far better than pseudo-random labels
+        let counters = label_compression_ratio(&window((0..500).map(|i| format!("seq{:05}data", i).into_bytes())));
+        let mut state: u64 = 1;
+        let random = label_compression_ratio(&window((0..500).map(|_| (0..12).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            b"abcdefghijklmnopqrstuvwxyz234567"[(state >> 59) as usize]
+        }).collect())));
+        assert!(counters < 0.4, "{}", counters);
+        assert!(random > 0.6, "{}", random);
+
+        // Duplicate labels (case-insensitive) are compressed once
+        let repeated = window((0..100).map(|i| if i % 2 == 0 { b"abcdef".to_vec() } else { b"ABCDEF".to_vec() }));
+        assert_eq!(label_compression_ratio(&window(std::iter::once(b"ABCDEF".to_vec()))), label_compression_ratio(&repeated));
+
+        // The input is bounded
+        let long = label_compression_ratio(&window((0..1000).map(|i| format!("{:063}", i).into_bytes())));
+        let bounded = label_compression_ratio(&window((1000 - MAX_COMPRESSION_INPUT / 63 - 1..1000).map(|i| format!("{:063}", i).into_bytes())));
+        assert_eq!(bounded, long);
+
+        // Reusing the compressor gives the same ratios
+        let mut compression = LabelCompression::default();
+        assert_eq!(long, compression.ratio(&window((0..1000).map(|i| format!("{:063}", i).into_bytes()))));
+        assert_eq!(counters, compression.ratio(&window((0..500).map(|i| format!("seq{:05}data", i).into_bytes()))));
+        assert!(compression.ratio(&VecDeque::new()).is_nan());
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_without_feature() {
+        assert!(label_compression_ratio(&window((0..10).map(|i| format!("seq{:05}data", i).into_bytes()))).is_nan());
+    }
+}
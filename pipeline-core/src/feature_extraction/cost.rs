@@ -0,0 +1,139 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Part of the feature extraction that is timed on its own by `FeatureCost`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostStage {
+    /// Payload features of single queries.
+    Payload,
+    /// Window bookkeeping: adding and expiring queries (label, character, client and TTL counters).
+    Counters,
+    /// Character entropy estimates of windows.
+    Entropy,
+    /// Min-hash label set similarity to an earlier window.
+    Similarity,
+    /// Compression ratio of the unique labels of windows.
+    Compression,
+    /// Other window features (lengths, quantiles, rates and ratios).
+    Vector,
+    /// NaN policy and row filter.
+    Filters,
+}
+
+impl CostStage {
+    pub const ALL: [CostStage; 7] = [CostStage::Payload, CostStage::Counters, CostStage::Entropy, CostStage::Similarity, CostStage::Compression, CostStage::Vector, CostStage::Filters];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CostStage::Payload => "payload features",
+            CostStage::Counters => "window counters",
+            CostStage::Entropy => "window entropy",
+            CostStage::Similarity => "label set similarity",
+            CostStage::Compression => "label compression",
+            CostStage::Vector => "other window features",
+            CostStage::Filters => "output filters",
+        }
+    }
+}
+
+/// Time spent per `CostStage`, summed over all threads (so the total exceeds the wall time of a parallel run).
+///
+/// Window features are timed including their entropy estimates, which are timed separately as well; `get` reports
+/// `CostStage::Vector` without them.
+#[derive(Debug, Default)]
+pub struct FeatureCost {
+    nanos: [AtomicU64; 7],
+}
+
+impl FeatureCost {
+    pub fn add(&self, stage: CostStage, elapsed: Duration) {
+        self.nanos[stage as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, stage: CostStage) -> Duration {
+        let nanos = |stage: CostStage| self.nanos[stage as usize].load(Ordering::Relaxed);
+        match stage {
+            CostStage::Vector => Duration::from_nanos(nanos(CostStage::Vector).saturating_sub(nanos(CostStage::Entropy))),
+            _ => Duration::from_nanos(nanos(stage)),
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        CostStage::ALL.iter().map(|&stage| self.get(stage)).sum()
+    }
+}
+
+/// Breakdown of the stages that took any time, one per line with their share of the total.
+impl fmt::Display for FeatureCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().as_secs_f64();
+        for stage in CostStage::ALL.iter().filter(|&&stage| !self.get(stage).is_zero()) {
+            let elapsed = self.get(*stage);
+            writeln!(f, "{:<22} {:>10.1?} {:>5.1}%", stage.name(), elapsed, 100. * elapsed.as_secs_f64() / total)?;
+        }
+        Ok(())
+    }
+}
+
+/// Handle of the extractors to a shared `FeatureCost`, or none (the default), in which case nothing is timed.
+#[derive(Debug, Clone, Default)]
+pub struct CostTracker(Option<Arc<FeatureCost>>);
+
+impl CostTracker {
+    pub fn new(cost: Arc<FeatureCost>) -> Self {
+        CostTracker(Some(cost))
+    }
+
+    /// Start of a timed stage (`None` if not tracking).
+    pub fn start(&self) -> Option<Instant> {
+        self.0.as_ref().map(|_| Instant::now())
+    }
+
+    /// Add the time since `start` to `stage` and restart, for consecutive stages.
+    pub fn lap(&self, stage: CostStage, start: &mut Option<Instant>) {
+        if let (Some(cost), Some(start)) = (&self.0, start.as_mut()) {
+            let now = Instant::now();
+            cost.add(stage, now - *start);
+            *start = now;
+        }
+    }
+
+    pub fn time<T, F: FnOnce() -> T>(&self, stage: CostStage, f: F) -> T {
+        let mut start = self.start();
+        let result = f();
+        self.lap(stage, &mut start);
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::feature_extraction::cost::{CostStage, CostTracker, FeatureCost};
+
+    #[test]
+    fn test_feature_cost() {
+        let cost = Arc::new(FeatureCost::default());
+        cost.add(CostStage::Vector, Duration::from_millis(30));
+        cost.add(CostStage::Entropy, Duration::from_millis(10));
+        cost.add(CostStage::Counters, Duration::from_millis(20));
+
+        // Window features are timed including entropy
+        assert_eq!(Duration::from_millis(20), cost.get(CostStage::Vector));
+        assert_eq!(Duration::from_millis(50), cost.total());
+        assert_eq!(3, cost.to_string().lines().count());
+
+        // Not tracking
+        assert!(CostTracker::default().start().is_none());
+        assert_eq!(42, CostTracker::default().time(CostStage::Payload, || 42));
+
+        let tracker = CostTracker::new(cost.clone());
+        tracker.time(CostStage::Payload, || std::thread::sleep(Duration::from_millis(1)));
+        assert!(cost.get(CostStage::Payload) >= Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::feature_extraction::cost::{CostStage, CostTracker};
+use crate::feature_extraction::feature_vector::{DecayFeatureVector, RateUnit};
+use crate::feature_extraction::state::has_mixed_case;
+use crate::shared_interface::LogRecord;
+
+/// Exponentially decayed accumulators over the queries to a primary domain: every query contributes with weight
+/// `decay^age` (age in seconds). Unlike a `WindowState`, no queries are stored, so memory is constant per domain, at the
+/// cost of approximate (smoothed) features and no uniqueness counts.
+#[derive(Debug, Clone)]
+pub struct DecayState {
+    /// Decay factor per second (0..1).
+    decay: f64,
+    last_ts: Option<f64>,
+
+    // Decayed accumulators
+    n_queries: f64,
+    n_mixed_case_queries: f64,
+    n_labels: f64,
+    total_label_len: f64,
+    char_weights: [f64; 256],
+}
+
+impl DecayState {
+    pub fn new(decay: f64) -> Self {
+        DecayState {
+            decay,
+            last_ts: None,
+            n_queries: 0.,
+            n_mixed_case_queries: 0.,
+            n_labels: 0.,
+            total_label_len: 0.,
+            char_weights: [0.; 256],
+        }
+    }
+
+    /// Effective window duration in seconds: the decayed count of a constant rate of one query per second.
+    pub fn effective_duration(&self) -> f64 {
+        -1. / self.decay.ln()
+    }
+
+    /// Decay the accumulators to `ts` (records before the last one are treated as simultaneous with it).
+    fn decay_to(&mut self, ts: f64) {
+        let elapsed = self.last_ts.map_or(0., |last_ts| (ts - last_ts).max(0.));
+        self.last_ts = Some(self.last_ts.map_or(ts, |last_ts| last_ts.max(ts)));
+        if elapsed == 0. { return; }
+
+        let factor = self.decay.powf(elapsed);
+        self.n_queries *= factor;
+        self.n_mixed_case_queries *= factor;
+        self.n_labels *= factor;
+        self.total_label_len *= factor;
+        self.char_weights.iter_mut().for_each(|w| *w *= factor);
+    }
+
+    pub fn add(&mut self, ts: f64, labels: &[Vec<u8>]) {
+        self.decay_to(ts);
+
+        self.n_queries += 1.;
+        if has_mixed_case(labels) { self.n_mixed_case_queries += 1.; }
+        self.n_labels += labels.len() as f64;
+        for label in labels {
+            self.total_label_len += label.len() as f64;
+            label.iter().for_each(|ch| self.char_weights[*ch as usize] += 1.);
+        }
+    }
+
+    /// Shannon entropy (bits per character) of the decayed character distribution.
+    pub fn entropy(&self) -> f64 {
+        let total: f64 = self.char_weights.iter().sum();
+        -self.char_weights.iter()
+            .filter(|&&w| w > 0.)
+            .map(|w| (w / total) * (w / total).log2())
+            .sum::<f64>()
+    }
+}
+
+/// Extractor producing smoothed rate and entropy features with a `DecayState`, as an O(1) memory alternative to the
+/// time window for streaming deployments.
+pub struct DecayWindow {
+    state: DecayState,
+    rate_unit: RateUnit,
+    first_ts: Option<f64>,
+    cost: CostTracker,
+}
+
+impl DecayWindow {
+    pub fn new(decay: f64) -> Self {
+        DecayWindow { state: DecayState::new(decay), rate_unit: RateUnit::default(), first_ts: None, cost: CostTracker::default() }
+    }
+
+    /// Report rate features in the given unit of time (instead of per second).
+    pub fn with_rate_unit(mut self, rate_unit: RateUnit) -> Self {
+        self.rate_unit = rate_unit;
+        self
+    }
+
+    /// Track the time spent per stage in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Continue a domain whose first record (at `first_ts`) was processed elsewhere, for the domain age feature.
+    pub fn resume_after(&mut self, first_ts: f64) {
+        self.first_ts = Some(first_ts);
+    }
+
+    pub fn process_record(&mut self, record: &LogRecord) -> DecayFeatureVector {
+        let mut start = self.cost.start();
+        self.state.add(record.ts, &record.payload.labels);
+        self.cost.lap(CostStage::Counters, &mut start);
+        let state = &self.state;
+
+        let entropy = self.cost.time(CostStage::Entropy, || state.entropy());
+        let units = self.rate_unit.units_per_window(state.effective_duration() as f32) as f64;
+        let fv = DecayFeatureVector {
+            id: record.id,
+            query_rate: (state.n_queries / units) as f32,
+            transfer_rate: (state.total_label_len / units) as f32,
+            avg_label_length: (state.total_label_len / state.n_labels) as f32,
+            entropy: entropy as f32,
+            case_randomization_ratio: (state.n_mixed_case_queries / state.n_queries) as f32,
+            domain_age_seconds: (record.ts - *self.first_ts.get_or_insert(record.ts)) as f32,
+        };
+        self.cost.lap(CostStage::Vector, &mut start);
+        fv
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::decay::DecayWindow;
+    use crate::feature_extraction::feature_vector::RateUnit;
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn record(id: usize, ts: f64, label: &[u8]) -> LogRecord {
+        let payload = DnsPayload { labels: vec![label.to_vec()], payload_len: label.len() as u8 };
+        LogRecord { id, ts, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+    }
+
+    #[test]
+    fn test_steady_rate() {
+        // One query per second converges to a rate of one per second (and 60 per minute)
+        let mut window = DecayWindow::new(0.9);
+        let fv = (0..500).map(|id| window.process_record(&record(id, id as f64, b"abcd"))).last().unwrap();
+        assert!((fv.query_rate - 1.).abs() < 0.06);
+        assert!((fv.transfer_rate - 4.).abs() < 0.25);
+        assert_eq!((4., 2.), (fv.avg_label_length, fv.entropy));
+
+        let mut window = DecayWindow::new(0.9).with_rate_unit(RateUnit::Minute);
+        let fv = (0..500).map(|id| window.process_record(&record(id, id as f64, b"abcd"))).last().unwrap();
+        assert!((fv.query_rate - 60.).abs() < 3.5);
+    }
+
+    #[test]
+    fn test_decayed_entropy() {
+        let mut window = DecayWindow::new(0.5);
+        window.process_record(&record(0, 0., b"abcdefgh"));
+
+        // Old characters have (almost) decayed away
+        let fv = window.process_record(&record(1, 100., b"aaaa"));
+        assert!(fv.entropy < 0.01);
+        assert_eq!(100., fv.domain_age_seconds);
+    }
+}
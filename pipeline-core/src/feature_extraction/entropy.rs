@@ -0,0 +1,79 @@
+use std::f32::consts::LN_2;
+
+/// Entropy estimates over the character distribution of a string (or window of strings).
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct EntropyEstimates {
+    /// Number of distinct characters.
+    pub n_symbols: usize,
+    /// Shannon entropy in bits per character.
+    pub shannon: f32,
+    /// Shannon entropy divided by the maximum attainable entropy for the number of characters (0..1).
+    pub normalized: f32,
+    /// Rényi entropy of order 2 (collision entropy) in bits.
+    pub renyi2: f32,
+    /// Min-entropy in bits, only depends on the most frequent character (robust against padding characters).
+    pub min: f32,
+}
+
+/// Compute entropy estimates from character counts (zero counts are ignored) and the total number of characters.
+pub fn estimate_entropy<I: Iterator<Item=usize>>(counts: I, total: usize) -> EntropyEstimates {
+    let n = total as f32;
+
+    let mut n_symbols: usize = 0;
+    let mut shannon_acc: f32 = 0.;
+    let mut sum_squares: f32 = 0.;
+    let mut max_count: usize = 0;
+
+    for c in counts.filter(|&c| c > 0) {
+        n_symbols += 1;
+        shannon_acc += c as f32 * (c as f32 / n).ln();
+        sum_squares += (c as f32 / n) * (c as f32 / n);
+        max_count = max_count.max(c);
+    }
+
+    let shannon = shannon_acc.abs() / (n * LN_2);
+
+    // At most one of each byte value can occur
+    let max_symbols = total.min(256);
+    let normalized = if max_symbols > 1 { shannon / (max_symbols as f32).log2() } else { 0. };
+
+    EntropyEstimates {
+        n_symbols,
+        shannon,
+        normalized,
+        renyi2: -sum_squares.log2(),
+        min: -(max_count as f32 / n).log2(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::entropy::estimate_entropy;
+
+    #[test]
+    fn test_uniform_distribution() {
+        // All estimators agree for a uniform distribution
+        let e = estimate_entropy(vec![2, 2, 2, 2].into_iter(), 8);
+        assert_eq!(4, e.n_symbols);
+        assert_eq!(2., e.shannon);
+        assert_eq!(2., e.renyi2);
+        assert_eq!(2., e.min);
+        assert_eq!(2. / 3., e.normalized);
+    }
+
+    #[test]
+    fn test_padded_distribution() {
+        // Padding character dominates min-entropy, Shannon entropy stays higher
+        let e = estimate_entropy(vec![12, 1, 1, 1, 1, 0].into_iter(), 16);
+        assert_eq!(5, e.n_symbols);
+        assert_eq!(-(12f32 / 16.).log2(), e.min);
+        assert!(e.min < e.renyi2 && e.renyi2 < e.shannon);
+    }
+
+    #[test]
+    fn test_single_character() {
+        let e = estimate_entropy(vec![1].into_iter(), 1);
+        assert_eq!((0., 0., 0.), (e.shannon, e.normalized, e.min));
+    }
+}
@@ -0,0 +1,567 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+#[prefix_all("pl_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct PayloadFeatureVector {
+    pub id: usize,
+    pub n_unique: u16,
+    pub ratio_unique: f32,
+    pub n_digits: u16,
+    /// Fraction of labels that start with a digit, and the average length of the longest run of digits per label, which
+    /// show counters at fixed positions.
+    pub digit_start_ratio: f32,
+    pub avg_longest_digit_run: f32,
+    /// Characters other than letters, digits, hyphens and underscores.
+    pub n_invalid: u16,
+    pub n_uppercase: u16,
+    pub n_underscore: u16,
+    /// Bytes outside printable ASCII (a subset of `n_invalid`).
+    pub n_nonprintable: u16,
+    pub n_labels: u8,
+    pub avg_label_length: f32,
+    pub max_label_length: u8,
+    pub entropy: f32,
+    pub entropy_normalized: f32,
+    pub renyi2_entropy: f32,
+    pub min_entropy: f32,
+    pub fill_ratio: f32,
+    pub case_randomization_ratio: f32,
+    /// Average log2-probability per character transition of the labels under the bigram model (see `BigramModel`).
+    pub bigram_log_likelihood: f32,
+}
+
+#[prefix_all("win_time_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct TimeWindowFeatureVector {
+    pub id: usize,
+    pub n_unique_labels: usize,
+    pub unique_query_rate: f32,
+    pub entropy: f32,
+    pub entropy_normalized: f32,
+    pub renyi2_entropy: f32,
+    pub min_entropy: f32,
+    /// Deflate compression ratio of the unique labels (see `LabelCompression`).
+    pub label_compression_ratio: f32,
+    /// Average log2-probability per character transition of the labels under the bigram model (see `BigramModel`).
+    pub bigram_log_likelihood: f32,
+    pub unique_transfer_rate: f32,
+    pub avg_unique_label_length: f32,
+    pub unique_fill_ratio: f32,
+    pub max_label_length: u8,
+    /// Median and 90th percentile of the unique label lengths.
+    pub label_len_p50: f32,
+    pub label_len_p90: f32,
+    pub unique_query_ratio: f32,
+    pub case_randomization_ratio: f32,
+    pub n_unique_client_prefixes: usize,
+    /// Whether any record in the window has a response TTL, as the TTL columns are NaN (or zero with `--nan zero`)
+    /// without and a TTL of 0 is a tunnel signal itself.
+    pub ttl_present: bool,
+    pub avg_ttl: f32,
+    pub min_ttl: f32,
+    pub ttl_variance: f32,
+    /// Whether any record in the window has CNAME information (see `ttl_present`).
+    pub cname_depth_present: bool,
+    pub avg_cname_depth: f32,
+    /// Estimated fraction of records dropped by the measurement, from the gaps in their sequence numbers.
+    pub seq_drop_rate: f32,
+    /// Fraction of the records with a sequence number that follow a gap.
+    pub seq_gap_ratio: f32,
+    /// Estimated Jaccard similarity of the labels to those of an earlier window (see `WindowSimilarity`).
+    pub label_set_similarity: f32,
+    /// Unique labels in the newer half of the window that are not in the older half, relative to the unique labels in
+    /// the older half (see `UniqueGrowth`).
+    pub unique_label_growth: f32,
+    pub domain_age_seconds: f32,
+    pub is_first_window: bool,
+    /// Closing aggregate of a window whose entries expired without a new record (see `TimeWindow::advance_to`), with
+    /// the ID of the newest record, which also has a regular row. The window may be empty (all entries expired).
+    pub is_closing_window: bool,
+}
+
+#[prefix_all("win_fixed_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct FixedWindowFeatureVector {
+    pub id: usize,
+    pub n_unique_labels: usize,
+    pub entropy: f32,
+    pub entropy_normalized: f32,
+    pub renyi2_entropy: f32,
+    pub min_entropy: f32,
+    /// Deflate compression ratio of the unique labels (see `LabelCompression`).
+    pub label_compression_ratio: f32,
+    /// Average log2-probability per character transition of the labels under the bigram model (see `BigramModel`).
+    pub bigram_log_likelihood: f32,
+    pub avg_unique_label_length: f32,
+    pub unique_fill_ratio: f32,
+    pub max_label_length: u8,
+    /// Median and 90th percentile of the unique label lengths.
+    pub label_len_p50: f32,
+    pub label_len_p90: f32,
+    pub unique_query_ratio: f32,
+    pub case_randomization_ratio: f32,
+    pub n_unique_client_prefixes: usize,
+    /// Whether any record in the window has a response TTL, as the TTL columns are NaN (or zero with `--nan zero`)
+    /// without and a TTL of 0 is a tunnel signal itself.
+    pub ttl_present: bool,
+    pub avg_ttl: f32,
+    pub min_ttl: f32,
+    pub ttl_variance: f32,
+    /// Whether any record in the window has CNAME information (see `ttl_present`).
+    pub cname_depth_present: bool,
+    pub avg_cname_depth: f32,
+    /// Estimated fraction of records dropped by the measurement, from the gaps in their sequence numbers.
+    pub seq_drop_rate: f32,
+    /// Fraction of the records with a sequence number that follow a gap.
+    pub seq_gap_ratio: f32,
+    /// Estimated Jaccard similarity of the labels to those of an earlier window (see `WindowSimilarity`).
+    pub label_set_similarity: f32,
+    /// Unique labels in the newer half of the window that are not in the older half, relative to the unique labels in
+    /// the older half (see `UniqueGrowth`).
+    pub unique_label_growth: f32,
+    pub domain_age_seconds: f32,
+    pub is_first_window: bool,
+}
+
+#[prefix_all("win_bytes_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct ByteWindowFeatureVector {
+    pub id: usize,
+    /// Number of queries in the window, which varies with the size of the payloads.
+    pub n_queries: usize,
+    pub n_unique_labels: usize,
+    pub entropy: f32,
+    pub entropy_normalized: f32,
+    pub renyi2_entropy: f32,
+    pub min_entropy: f32,
+    /// Deflate compression ratio of the unique labels (see `LabelCompression`).
+    pub label_compression_ratio: f32,
+    /// Average log2-probability per character transition of the labels under the bigram model (see `BigramModel`).
+    pub bigram_log_likelihood: f32,
+    pub avg_unique_label_length: f32,
+    pub unique_fill_ratio: f32,
+    pub max_label_length: u8,
+    /// Median and 90th percentile of the unique label lengths.
+    pub label_len_p50: f32,
+    pub label_len_p90: f32,
+    pub unique_query_ratio: f32,
+    pub case_randomization_ratio: f32,
+    pub n_unique_client_prefixes: usize,
+    /// Whether any record in the window has a response TTL, as the TTL columns are NaN (or zero with `--nan zero`)
+    /// without and a TTL of 0 is a tunnel signal itself.
+    pub ttl_present: bool,
+    pub avg_ttl: f32,
+    pub min_ttl: f32,
+    pub ttl_variance: f32,
+    /// Whether any record in the window has CNAME information (see `ttl_present`).
+    pub cname_depth_present: bool,
+    pub avg_cname_depth: f32,
+    /// Estimated fraction of records dropped by the measurement, from the gaps in their sequence numbers.
+    pub seq_drop_rate: f32,
+    /// Fraction of the records with a sequence number that follow a gap.
+    pub seq_gap_ratio: f32,
+    /// Unique labels in the newer half of the window that are not in the older half, relative to the unique labels in
+    /// the older half (see `UniqueGrowth`).
+    pub unique_label_growth: f32,
+    /// Time between the first and last query in the window.
+    pub window_duration_seconds: f32,
+    pub domain_age_seconds: f32,
+    pub is_first_window: bool,
+}
+
+/// Smoothed features from exponentially decayed accumulators (see `DecayState`).
+#[prefix_all("win_decay_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct DecayFeatureVector {
+    pub id: usize,
+    pub query_rate: f32,
+    pub transfer_rate: f32,
+    pub avg_label_length: f32,
+    pub entropy: f32,
+    pub case_randomization_ratio: f32,
+    pub domain_age_seconds: f32,
+}
+
+#[prefix_all("win_global_")]
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct GlobalWindowFeatureVector {
+    pub id: usize,
+    pub query_rate: f32,
+    pub n_domains: usize,
+    pub rare_domain_ratio: f32,
+    pub n_unique_labels: usize,
+    pub entropy: f32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum FeatureVector {
+    Payload(PayloadFeatureVector),
+    Time(TimeWindowFeatureVector),
+    Fixed(FixedWindowFeatureVector),
+    Bytes(ByteWindowFeatureVector),
+    Decay(DecayFeatureVector),
+    Global(GlobalWindowFeatureVector),
+}
+
+/// First differences of window features to the previous feature vector of the same primary domain.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct FeatureDeltas {
+    pub delta_entropy: f32,
+    pub delta_unique_rate: f32,
+}
+
+/// What to do with feature vectors containing NaN or infinite values (e.g. ratios over empty windows).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NanPolicy {
+    /// Write non-finite values as-is.
+    #[default]
+    Keep,
+    /// Replace non-finite values by zero.
+    Zero,
+    /// Replace non-finite values by the given sentinel value.
+    Sentinel(f32),
+    /// Drop feature vectors that contain non-finite values.
+    Skip,
+}
+
+/// Unit of time for the rate features, so rates are comparable between runs with different window durations.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RateUnit {
+    #[default]
+    Second,
+    Minute,
+    /// Count per window, regardless of the window duration.
+    Window,
+}
+
+impl RateUnit {
+    /// Number of units in a window of `window_duration` seconds, which counts are divided by.
+    pub fn units_per_window(&self, window_duration: f32) -> f32 {
+        match self {
+            RateUnit::Second => window_duration,
+            RateUnit::Minute => window_duration / 60.,
+            RateUnit::Window => 1.,
+        }
+    }
+}
+
+impl FromStr for RateUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "second" => Ok(RateUnit::Second),
+            "minute" => Ok(RateUnit::Minute),
+            "window" => Ok(RateUnit::Window),
+            _ => Err(format!("unknown rate unit '{}', expected second, minute or window", s)),
+        }
+    }
+}
+
+impl FeatureVector {
+    /// Version of the feature definitions, stamped in every output. Bump whenever a feature is added, removed or its
+    /// formula changes, so models are not evaluated on incompatible extractions.
+    pub const SCHEMA_VERSION: u32 = 10;
+
+    /// Features of the characters of the labels, which are meaningless for reverse lookups (their labels are the
+    /// nibbles or octets of an IP address) and left out of the output for reverse zones.
+    pub const CHARACTER_FEATURES: [&'static str; 11] = [
+        "entropy", "entropy_normalized", "bigram_log_likelihood", "label_compression_ratio", "case_randomization_ratio",
+        "n_uppercase", "n_digits", "digit_start_ratio", "avg_longest_digit_run", "avg_label_idf", "max_label_idf",
+    ];
+
+    /// Whether an output column (with or without family prefix) is kept for reverse zones, i.e. it is not one of the
+    /// `CHARACTER_FEATURES` (or a variant such as `min_entropy` or `delta_entropy`).
+    pub fn is_reverse_zone_column(column: &str) -> bool {
+        !Self::CHARACTER_FEATURES.iter().any(|feature| {
+            column.strip_suffix(feature).is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('_'))
+        })
+    }
+
+    /// Record ID of the (last) query this feature vector belongs to.
+    pub fn id(&self) -> usize {
+        match self {
+            FeatureVector::Payload(fv) => fv.id,
+            FeatureVector::Time(fv) => fv.id,
+            FeatureVector::Fixed(fv) => fv.id,
+            FeatureVector::Bytes(fv) => fv.id,
+            FeatureVector::Decay(fv) => fv.id,
+            FeatureVector::Global(fv) => fv.id,
+        }
+    }
+
+    /// Name of the feature family, which is also the column prefix (without trailing underscore).
+    pub fn family(&self) -> &'static str {
+        match self {
+            FeatureVector::Payload(_) => "pl",
+            FeatureVector::Time(_) => "win_time",
+            FeatureVector::Fixed(_) => "win_fixed",
+            FeatureVector::Bytes(_) => "win_bytes",
+            FeatureVector::Decay(_) => "win_decay",
+            FeatureVector::Global(_) => "win_global",
+        }
+    }
+
+    /// Character entropy of the payload or window.
+    pub fn entropy(&self) -> f32 {
+        match self {
+            FeatureVector::Payload(fv) => fv.entropy,
+            FeatureVector::Time(fv) => fv.entropy,
+            FeatureVector::Fixed(fv) => fv.entropy,
+            FeatureVector::Bytes(fv) => fv.entropy,
+            FeatureVector::Decay(fv) => fv.entropy,
+            FeatureVector::Global(fv) => fv.entropy,
+        }
+    }
+
+    /// Unique queries per unit of time for time windows, or per query for windows without a fixed duration (NaN
+    /// without uniqueness counts).
+    pub fn unique_rate(&self) -> f32 {
+        match self {
+            FeatureVector::Time(fv) => fv.unique_query_rate,
+            FeatureVector::Fixed(fv) => fv.unique_query_ratio,
+            FeatureVector::Bytes(fv) => fv.unique_query_ratio,
+            FeatureVector::Payload(_) | FeatureVector::Decay(_) | FeatureVector::Global(_) => f32::NAN,
+        }
+    }
+
+    /// Change in entropy and unique rate since `previous` (of the same family and primary domain).
+    pub fn deltas(&self, previous: &FeatureVector) -> FeatureDeltas {
+        FeatureDeltas {
+            delta_entropy: self.entropy() - previous.entropy(),
+            delta_unique_rate: self.unique_rate() - previous.unique_rate(),
+        }
+    }
+
+    /// Apply the NaN policy to this feature vector, returns None if the vector should be skipped.
+    pub fn apply_nan_policy(mut self, policy: NanPolicy) -> Option<Self> {
+        let replacement = match policy {
+            NanPolicy::Keep => return Some(self),
+            NanPolicy::Zero => 0.,
+            NanPolicy::Sentinel(value) => value,
+            NanPolicy::Skip => {
+                let mut finite = true;
+                self.for_each_float(|v| finite &= v.is_finite());
+                return if finite { Some(self) } else { None };
+            }
+        };
+
+        self.for_each_float(|v| if !v.is_finite() { *v = replacement });
+        Some(self)
+    }
+
+    /// Call `f` for every floating point feature in this vector.
+    fn for_each_float<F: FnMut(&mut f32)>(&mut self, mut f: F) {
+        match self {
+            FeatureVector::Payload(fv) => {
+                f(&mut fv.ratio_unique);
+                f(&mut fv.digit_start_ratio);
+                f(&mut fv.avg_longest_digit_run);
+                f(&mut fv.avg_label_length);
+                f(&mut fv.entropy);
+                f(&mut fv.entropy_normalized);
+                f(&mut fv.renyi2_entropy);
+                f(&mut fv.min_entropy);
+                f(&mut fv.fill_ratio);
+                f(&mut fv.case_randomization_ratio);
+                f(&mut fv.bigram_log_likelihood);
+            }
+            FeatureVector::Time(fv) => {
+                f(&mut fv.unique_query_rate);
+                f(&mut fv.entropy);
+                f(&mut fv.entropy_normalized);
+                f(&mut fv.renyi2_entropy);
+                f(&mut fv.min_entropy);
+                f(&mut fv.label_compression_ratio);
+                f(&mut fv.bigram_log_likelihood);
+                f(&mut fv.unique_transfer_rate);
+                f(&mut fv.avg_unique_label_length);
+                f(&mut fv.unique_fill_ratio);
+                f(&mut fv.label_len_p50);
+                f(&mut fv.label_len_p90);
+                f(&mut fv.unique_query_ratio);
+                f(&mut fv.case_randomization_ratio);
+                f(&mut fv.avg_ttl);
+                f(&mut fv.min_ttl);
+                f(&mut fv.ttl_variance);
+                f(&mut fv.avg_cname_depth);
+                f(&mut fv.seq_drop_rate);
+                f(&mut fv.seq_gap_ratio);
+                f(&mut fv.label_set_similarity);
+                f(&mut fv.unique_label_growth);
+                f(&mut fv.domain_age_seconds);
+            }
+            FeatureVector::Fixed(fv) => {
+                f(&mut fv.entropy);
+                f(&mut fv.entropy_normalized);
+                f(&mut fv.renyi2_entropy);
+                f(&mut fv.min_entropy);
+                f(&mut fv.label_compression_ratio);
+                f(&mut fv.bigram_log_likelihood);
+                f(&mut fv.avg_unique_label_length);
+                f(&mut fv.unique_fill_ratio);
+                f(&mut fv.label_len_p50);
+                f(&mut fv.label_len_p90);
+                f(&mut fv.unique_query_ratio);
+                f(&mut fv.case_randomization_ratio);
+                f(&mut fv.avg_ttl);
+                f(&mut fv.min_ttl);
+                f(&mut fv.ttl_variance);
+                f(&mut fv.avg_cname_depth);
+                f(&mut fv.seq_drop_rate);
+                f(&mut fv.seq_gap_ratio);
+                f(&mut fv.label_set_similarity);
+                f(&mut fv.unique_label_growth);
+                f(&mut fv.domain_age_seconds);
+            }
+            FeatureVector::Bytes(fv) => {
+                f(&mut fv.entropy);
+                f(&mut fv.entropy_normalized);
+                f(&mut fv.renyi2_entropy);
+                f(&mut fv.min_entropy);
+                f(&mut fv.label_compression_ratio);
+                f(&mut fv.bigram_log_likelihood);
+                f(&mut fv.avg_unique_label_length);
+                f(&mut fv.unique_fill_ratio);
+                f(&mut fv.label_len_p50);
+                f(&mut fv.label_len_p90);
+                f(&mut fv.unique_query_ratio);
+                f(&mut fv.case_randomization_ratio);
+                f(&mut fv.avg_ttl);
+                f(&mut fv.min_ttl);
+                f(&mut fv.ttl_variance);
+                f(&mut fv.avg_cname_depth);
+                f(&mut fv.seq_drop_rate);
+                f(&mut fv.seq_gap_ratio);
+                f(&mut fv.unique_label_growth);
+                f(&mut fv.window_duration_seconds);
+                f(&mut fv.domain_age_seconds);
+            }
+            FeatureVector::Decay(fv) => {
+                f(&mut fv.query_rate);
+                f(&mut fv.transfer_rate);
+                f(&mut fv.avg_label_length);
+                f(&mut fv.entropy);
+                f(&mut fv.case_randomization_ratio);
+                f(&mut fv.domain_age_seconds);
+            }
+            FeatureVector::Global(fv) => {
+                f(&mut fv.query_rate);
+                f(&mut fv.rare_domain_ratio);
+                f(&mut fv.entropy);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::feature_vector::{FeatureDeltas, FeatureVector, FixedWindowFeatureVector, GlobalWindowFeatureVector, NanPolicy, PayloadFeatureVector, RateUnit, TimeWindowFeatureVector};
+
+    fn header_for(fv: FeatureVector) -> String {
+        let mut w = csv::Writer::from_writer(vec![]);
+        w.serialize(fv).unwrap();
+        let out = String::from_utf8(w.into_inner().unwrap()).unwrap();
+        out.lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_payload_columns() {
+        assert_eq!(
+            "pl_id,pl_n_unique,pl_ratio_unique,pl_n_digits,pl_digit_start_ratio,pl_avg_longest_digit_run,pl_n_invalid,pl_n_uppercase,pl_n_underscore,pl_n_nonprintable,pl_n_labels,pl_avg_label_length,pl_max_label_length,pl_entropy,pl_entropy_normalized,pl_renyi2_entropy,pl_min_entropy,pl_fill_ratio,pl_case_randomization_ratio,pl_bigram_log_likelihood",
+            header_for(FeatureVector::Payload(PayloadFeatureVector::default()))
+        );
+    }
+
+    #[test]
+    fn test_time_window_columns() {
+        assert_eq!(
+            "win_time_id,win_time_n_unique_labels,win_time_unique_query_rate,win_time_entropy,win_time_entropy_normalized,win_time_renyi2_entropy,win_time_min_entropy,win_time_label_compression_ratio,win_time_bigram_log_likelihood,win_time_unique_transfer_rate,win_time_avg_unique_label_length,win_time_unique_fill_ratio,win_time_max_label_length,win_time_label_len_p50,win_time_label_len_p90,win_time_unique_query_ratio,win_time_case_randomization_ratio,win_time_n_unique_client_prefixes,win_time_ttl_present,win_time_avg_ttl,win_time_min_ttl,win_time_ttl_variance,win_time_cname_depth_present,win_time_avg_cname_depth,win_time_seq_drop_rate,win_time_seq_gap_ratio,win_time_label_set_similarity,win_time_unique_label_growth,win_time_domain_age_seconds,win_time_is_first_window,win_time_is_closing_window",
+            header_for(FeatureVector::Time(TimeWindowFeatureVector::default()))
+        );
+    }
+
+    #[test]
+    fn test_reverse_zone_columns() {
+        let columns = header_for(FeatureVector::Fixed(FixedWindowFeatureVector::default()));
+        let kept: Vec<&str> = columns.split(',').filter(|column| FeatureVector::is_reverse_zone_column(column)).collect();
+        assert!(kept.contains(&"win_fixed_n_unique_labels") && kept.contains(&"win_fixed_max_label_length"));
+        assert!(!kept.iter().any(|column| column.contains("entropy") || column.contains("bigram")));
+
+        // Only whole feature names match
+        assert!(!FeatureVector::is_reverse_zone_column("pl_n_digits"));
+        assert!(FeatureVector::is_reverse_zone_column("n_digits_total"));
+        assert!(FeatureVector::is_reverse_zone_column("pl_n_invalid"));
+    }
+
+    #[test]
+    fn test_deltas() {
+        let previous = FeatureVector::Time(TimeWindowFeatureVector { entropy: 2., unique_query_rate: 5., ..Default::default() });
+        let current = FeatureVector::Time(TimeWindowFeatureVector { entropy: 2.5, unique_query_rate: 3., ..Default::default() });
+        assert_eq!(FeatureDeltas { delta_entropy: 0.5, delta_unique_rate: -2. }, current.deltas(&previous));
+
+        let payload = FeatureVector::Payload(PayloadFeatureVector::default());
+        assert!(payload.deltas(&payload).delta_unique_rate.is_nan());
+    }
+
+    #[test]
+    fn test_fixed_window_columns() {
+        assert_eq!(
+            "win_fixed_id,win_fixed_n_unique_labels,win_fixed_entropy,win_fixed_entropy_normalized,win_fixed_renyi2_entropy,win_fixed_min_entropy,win_fixed_label_compression_ratio,win_fixed_bigram_log_likelihood,win_fixed_avg_unique_label_length,win_fixed_unique_fill_ratio,win_fixed_max_label_length,win_fixed_label_len_p50,win_fixed_label_len_p90,win_fixed_unique_query_ratio,win_fixed_case_randomization_ratio,win_fixed_n_unique_client_prefixes,win_fixed_ttl_present,win_fixed_avg_ttl,win_fixed_min_ttl,win_fixed_ttl_variance,win_fixed_cname_depth_present,win_fixed_avg_cname_depth,win_fixed_seq_drop_rate,win_fixed_seq_gap_ratio,win_fixed_label_set_similarity,win_fixed_unique_label_growth,win_fixed_domain_age_seconds,win_fixed_is_first_window",
+            header_for(FeatureVector::Fixed(FixedWindowFeatureVector::default()))
+        );
+    }
+
+    #[test]
+    fn test_global_window_columns() {
+        assert_eq!(
+            "win_global_id,win_global_query_rate,win_global_n_domains,win_global_rare_domain_ratio,win_global_n_unique_labels,win_global_entropy",
+            header_for(FeatureVector::Global(GlobalWindowFeatureVector::default()))
+        );
+    }
+
+    fn with_nan() -> FeatureVector {
+        FeatureVector::Fixed(FixedWindowFeatureVector { entropy: f32::NAN, unique_fill_ratio: f32::INFINITY, unique_query_ratio: 0.5, ..Default::default() })
+    }
+
+    #[test]
+    fn test_nan_policy_keep() {
+        // Same default as `--nan`
+        assert_eq!(NanPolicy::Keep, NanPolicy::default());
+        match with_nan().apply_nan_policy(NanPolicy::Keep) {
+            Some(FeatureVector::Fixed(fv)) => assert!(fv.entropy.is_nan() && fv.unique_fill_ratio.is_infinite()),
+            _ => panic!("expected fixed window vector")
+        }
+    }
+
+    #[test]
+    fn test_nan_policy_replace() {
+        match with_nan().apply_nan_policy(NanPolicy::Zero) {
+            Some(FeatureVector::Fixed(fv)) => assert_eq!((0., 0., 0.5), (fv.entropy, fv.unique_fill_ratio, fv.unique_query_ratio)),
+            _ => panic!("expected fixed window vector")
+        }
+
+        match with_nan().apply_nan_policy(NanPolicy::Sentinel(-1.)) {
+            Some(FeatureVector::Fixed(fv)) => assert_eq!((-1., -1., 0.5), (fv.entropy, fv.unique_fill_ratio, fv.unique_query_ratio)),
+            _ => panic!("expected fixed window vector")
+        }
+    }
+
+    #[test]
+    fn test_rate_unit() {
+        assert_eq!(120., RateUnit::Second.units_per_window(120.));
+        assert_eq!(2., RateUnit::Minute.units_per_window(120.));
+        assert_eq!(1., "window".parse::<RateUnit>().unwrap().units_per_window(120.));
+        assert!("hour".parse::<RateUnit>().is_err());
+    }
+
+    #[test]
+    fn test_nan_policy_skip() {
+        assert!(with_nan().apply_nan_policy(NanPolicy::Skip).is_none());
+        assert!(FeatureVector::Fixed(FixedWindowFeatureVector::default()).apply_nan_policy(NanPolicy::Skip).is_some());
+    }
+}
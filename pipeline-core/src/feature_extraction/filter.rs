@@ -0,0 +1,374 @@
+use std::str::FromStr;
+
+use serde::de::value::Error as SerError;
+use serde::ser::{self, Impossible, Serialize, SerializeStruct, Serializer};
+
+use crate::feature_extraction::feature_vector::{ByteWindowFeatureVector, DecayFeatureVector, FeatureVector, FixedWindowFeatureVector, GlobalWindowFeatureVector, PayloadFeatureVector, TimeWindowFeatureVector};
+
+/// Column prefixes of the feature vector families, conditions may refer to columns with or without prefix.
+const COLUMN_PREFIXES: [&str; 6] = ["pl_", "win_time_", "win_fixed_", "win_bytes_", "win_decay_", "win_global_"];
+
+/// Filter on the values of a feature vector, e.g. `entropy>3.5 && n_unique_labels>100`.
+/// A row is kept if all conditions hold; NaN values and unknown columns never satisfy a condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowFilter {
+    conditions: Vec<Condition>,
+    /// Field index of the column of every condition per feature vector family (see `family`), resolved once so rows
+    /// are not converted to a map to look up their values.
+    fields: Vec<[Option<usize>; 6]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    column: String,
+    op: CmpOp,
+    value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum RowFilterError {
+    #[error("filter is empty")]
+    Empty,
+    #[error("invalid condition \"{0}\", expected <column><op><number>")]
+    InvalidCondition(String),
+    #[error("invalid number in condition \"{0}\"")]
+    InvalidValue(String),
+}
+
+impl FromStr for RowFilter {
+    type Err = RowFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() { return Err(RowFilterError::Empty); }
+
+        let conditions = s.split("&&")
+            .map(|cond| cond.parse::<Condition>())
+            .collect::<Result<Vec<Condition>, RowFilterError>>()?;
+
+        // Resolve the columns against the fields of every feature vector family
+        let families = [
+            FeatureVector::Payload(PayloadFeatureVector::default()),
+            FeatureVector::Time(TimeWindowFeatureVector::default()),
+            FeatureVector::Fixed(FixedWindowFeatureVector::default()),
+            FeatureVector::Bytes(ByteWindowFeatureVector::default()),
+            FeatureVector::Decay(DecayFeatureVector::default()),
+            FeatureVector::Global(GlobalWindowFeatureVector::default()),
+        ];
+        let names: Vec<Vec<&'static str>> = families.iter().map(field_names).collect();
+
+        let fields = conditions.iter()
+            .map(|cond| {
+                let mut fields = [None; 6];
+                for (family, names) in names.iter().enumerate() {
+                    fields[family] = names.iter().position(|name| *name == cond.column
+                        || COLUMN_PREFIXES.iter().any(|prefix| name.strip_prefix(prefix) == Some(cond.column.as_str())));
+                }
+                fields
+            })
+            .collect();
+
+        Ok(RowFilter { conditions, fields })
+    }
+}
+
+impl FromStr for Condition {
+    type Err = RowFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cond = s.trim();
+
+        // Two-character operators first, so ">=" is not parsed as ">"
+        let (idx, op, op_len) = [(">=", CmpOp::Ge), ("<=", CmpOp::Le), ("==", CmpOp::Eq), ("!=", CmpOp::Ne), (">", CmpOp::Gt), ("<", CmpOp::Lt)]
+            .iter()
+            .find_map(|(token, op)| cond.find(token).map(|idx| (idx, *op, token.len())))
+            .ok_or_else(|| RowFilterError::InvalidCondition(cond.to_string()))?;
+
+        let column = cond[..idx].trim();
+        if column.is_empty() { return Err(RowFilterError::InvalidCondition(cond.to_string())); }
+
+        let value = match cond[idx + op_len..].trim() {
+            "true" => 1.,
+            "false" => 0.,
+            value => value.parse::<f64>().map_err(|_| RowFilterError::InvalidValue(cond.to_string()))?,
+        };
+
+        Ok(Condition { column: column.to_string(), op, value })
+    }
+}
+
+impl RowFilter {
+    /// Whether the feature vector satisfies all conditions of this filter.
+    pub fn matches(&self, fv: &FeatureVector) -> bool {
+        let family = family(fv);
+        if self.fields.iter().any(|fields| fields[family].is_none()) { return false; }
+
+        let mut evaluator = Evaluator { filter: self, family, n_satisfied: 0 };
+        fv.serialize(FieldWalker { visitor: &mut evaluator, index: 0 }).is_ok()
+            && evaluator.n_satisfied == self.conditions.len()
+    }
+
+    /// Columns used in conditions that do not exist in the given (example) feature vector.
+    pub fn unknown_columns(&self, fv: &FeatureVector) -> Vec<&str> {
+        let family = family(fv);
+        self.conditions.iter().zip(&self.fields)
+            .filter(|(_, fields)| fields[family].is_none())
+            .map(|(cond, _)| cond.column.as_str())
+            .collect()
+    }
+}
+
+/// Index of the feature vector family, in the order of `COLUMN_PREFIXES`.
+fn family(fv: &FeatureVector) -> usize {
+    match fv {
+        FeatureVector::Payload(_) => 0,
+        FeatureVector::Time(_) => 1,
+        FeatureVector::Fixed(_) => 2,
+        FeatureVector::Bytes(_) => 3,
+        FeatureVector::Decay(_) => 4,
+        FeatureVector::Global(_) => 5,
+    }
+}
+
+/// Column names of a feature vector, in the order of serialization.
+fn field_names(fv: &FeatureVector) -> Vec<&'static str> {
+    let mut names = FieldNames(Vec::new());
+    // Feature vectors are flat structs, so walking the fields does not fail
+    let _ = fv.serialize(FieldWalker { visitor: &mut names, index: 0 });
+    names.0
+}
+
+/// Receives the fields of a feature vector from a `FieldWalker`.
+trait FieldVisitor {
+    /// Whether the value of the field is needed (passed to `value`).
+    fn wants(&mut self, index: usize, name: &'static str) -> bool;
+
+    /// Numeric value of a wanted field (booleans as 0 or 1), None if it is not a number.
+    fn value(&mut self, index: usize, value: Option<f64>);
+}
+
+struct FieldNames(Vec<&'static str>);
+
+impl FieldVisitor for FieldNames {
+    fn wants(&mut self, _index: usize, name: &'static str) -> bool {
+        self.0.push(name);
+        false
+    }
+
+    fn value(&mut self, _index: usize, _value: Option<f64>) {}
+}
+
+/// Counts the conditions of a filter that are satisfied by the fields of a feature vector.
+struct Evaluator<'a> {
+    filter: &'a RowFilter,
+    family: usize,
+    n_satisfied: usize,
+}
+
+impl<'a> FieldVisitor for Evaluator<'a> {
+    fn wants(&mut self, index: usize, _name: &'static str) -> bool {
+        self.filter.fields.iter().any(|fields| fields[self.family] == Some(index))
+    }
+
+    fn value(&mut self, index: usize, value: Option<f64>) {
+        let value = match value {
+            Some(value) if !value.is_nan() => value,
+            _ => return,
+        };
+
+        self.n_satisfied += self.filter.conditions.iter().zip(&self.filter.fields)
+            .filter(|(cond, fields)| fields[self.family] == Some(index) && cond.op.compare(value, cond.value))
+            .count();
+    }
+}
+
+fn not_supported() -> SerError {
+    ser::Error::custom("feature vectors are flat structs of numbers")
+}
+
+/// Serializer that passes the fields of a feature vector struct to a `FieldVisitor`, without building an intermediate
+/// representation of the row.
+struct FieldWalker<'a, V> {
+    visitor: &'a mut V,
+    index: usize,
+}
+
+macro_rules! unsupported {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {
+        $(fn $method(self $(, _: $arg)*) -> Result<Self::Ok, Self::Error> { Err(not_supported()) })*
+    };
+}
+
+impl<'a, V: FieldVisitor> Serializer for FieldWalker<'a, V> {
+    type Ok = ();
+    type Error = SerError;
+    type SerializeSeq = Impossible<(), SerError>;
+    type SerializeTuple = Impossible<(), SerError>;
+    type SerializeTupleStruct = Impossible<(), SerError>;
+    type SerializeTupleVariant = Impossible<(), SerError>;
+    type SerializeMap = Impossible<(), SerError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), SerError>;
+
+    unsupported!(
+        serialize_bool(bool), serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64), serialize_f32(f32), serialize_f64(f64),
+        serialize_char(char), serialize_str(&str), serialize_bytes(&[u8]), serialize_none(), serialize_unit(),
+        serialize_unit_struct(&'static str), serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<(), SerError> { Err(not_supported()) }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, value: &T) -> Result<(), SerError> { value.serialize(self) }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _: &'static str, _: u32, _: &'static str, _: &T) -> Result<(), SerError> { Err(not_supported()) }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, SerError> { Err(not_supported()) }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, SerError> { Err(not_supported()) }
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, SerError> { Err(not_supported()) }
+    fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeTupleVariant, SerError> { Err(not_supported()) }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, SerError> { Err(not_supported()) }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, SerError> { Ok(self) }
+    fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeStructVariant, SerError> { Err(not_supported()) }
+}
+
+impl<'a, V: FieldVisitor> SerializeStruct for FieldWalker<'a, V> {
+    type Ok = ();
+    type Error = SerError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result<(), SerError> {
+        if self.visitor.wants(self.index, name) {
+            let value = value.serialize(NumberSerializer)?;
+            self.visitor.value(self.index, value);
+        }
+        self.index += 1;
+        Ok(())
+    }
+
+    fn skip_field(&mut self, _: &'static str) -> Result<(), SerError> {
+        // Keep the indices of the following fields
+        self.index += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), SerError> {
+        Ok(())
+    }
+}
+
+/// Serializer of a single field value to a number.
+struct NumberSerializer;
+
+macro_rules! numbers {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(fn $method(self, value: $ty) -> Result<Self::Ok, Self::Error> { Ok(Some(value as f64)) })*
+    };
+}
+
+impl Serializer for NumberSerializer {
+    type Ok = Option<f64>;
+    type Error = SerError;
+    type SerializeSeq = Impossible<Option<f64>, SerError>;
+    type SerializeTuple = Impossible<Option<f64>, SerError>;
+    type SerializeTupleStruct = Impossible<Option<f64>, SerError>;
+    type SerializeTupleVariant = Impossible<Option<f64>, SerError>;
+    type SerializeMap = Impossible<Option<f64>, SerError>;
+    type SerializeStruct = Impossible<Option<f64>, SerError>;
+    type SerializeStructVariant = Impossible<Option<f64>, SerError>;
+
+    numbers!(
+        serialize_i8(i8), serialize_i16(i16), serialize_i32(i32), serialize_i64(i64),
+        serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_u64(u64), serialize_f32(f32), serialize_f64(f64),
+    );
+
+    fn serialize_bool(self, value: bool) -> Result<Option<f64>, SerError> { Ok(Some(value as u8 as f64)) }
+    fn serialize_char(self, _: char) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_str(self, _: &str) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_bytes(self, _: &[u8]) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_none(self) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Option<f64>, SerError> { value.serialize(self) }
+    fn serialize_unit(self) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, value: &T) -> Result<Option<f64>, SerError> { value.serialize(self) }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _: &'static str, _: u32, _: &'static str, _: &T) -> Result<Option<f64>, SerError> { Ok(None) }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, SerError> { Err(not_supported()) }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, SerError> { Err(not_supported()) }
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, SerError> { Err(not_supported()) }
+    fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeTupleVariant, SerError> { Err(not_supported()) }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, SerError> { Err(not_supported()) }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, SerError> { Err(not_supported()) }
+    fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeStructVariant, SerError> { Err(not_supported()) }
+}
+
+impl CmpOp {
+    fn compare(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::feature_vector::{FeatureVector, GlobalWindowFeatureVector, PayloadFeatureVector, TimeWindowFeatureVector};
+    use crate::feature_extraction::filter::{RowFilter, RowFilterError};
+
+    fn row(entropy: f32, n_unique_labels: usize) -> FeatureVector {
+        FeatureVector::Time(TimeWindowFeatureVector { entropy, n_unique_labels, is_first_window: true, ..Default::default() })
+    }
+
+    #[test]
+    fn test_matches() {
+        let filter: RowFilter = "entropy>3.5 && n_unique_labels>=100".parse().unwrap();
+        assert!(filter.matches(&row(4., 100)));
+        assert!(!filter.matches(&row(4., 99)));
+        assert!(!filter.matches(&row(3.5, 200)));
+
+        // Prefixed column names and booleans
+        assert!("win_time_entropy < 1 && is_first_window == true".parse::<RowFilter>().unwrap().matches(&row(0.5, 0)));
+    }
+
+    #[test]
+    fn test_nan_never_matches() {
+        let filter: RowFilter = "entropy!=0".parse().unwrap();
+        assert!(!filter.matches(&row(f32::NAN, 0)));
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let filter: RowFilter = "entropy>1 && fill_ratio>0.5".parse().unwrap();
+        assert_eq!(vec!["fill_ratio"], filter.unknown_columns(&row(0., 0)));
+    }
+
+    #[test]
+    fn test_columns_per_family() {
+        // Same column at a different field index in another family, and a column the payload features do not have
+        let filter: RowFilter = "n_unique_labels>=2 && entropy<1".parse().unwrap();
+        let global = FeatureVector::Global(GlobalWindowFeatureVector { n_unique_labels: 2, entropy: 0.5, ..Default::default() });
+        assert!(filter.matches(&global));
+        assert!(filter.matches(&row(0.5, 2)));
+        assert!(!filter.matches(&FeatureVector::Payload(PayloadFeatureVector::default())));
+        assert_eq!(vec!["n_unique_labels"], filter.unknown_columns(&FeatureVector::Payload(PayloadFeatureVector::default())));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err(RowFilterError::Empty), " ".parse::<RowFilter>());
+        assert!(matches!("entropy".parse::<RowFilter>(), Err(RowFilterError::InvalidCondition(_))));
+        assert!(matches!(">3".parse::<RowFilter>(), Err(RowFilterError::InvalidCondition(_))));
+        assert!(matches!("entropy>x".parse::<RowFilter>(), Err(RowFilterError::InvalidValue(_))));
+    }
+}
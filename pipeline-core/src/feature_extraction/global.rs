@@ -0,0 +1,155 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::feature_extraction::cost::{CostStage, CostTracker};
+use crate::feature_extraction::ExtractOpts;
+use crate::feature_extraction::feature_vector::{FeatureVector, GlobalWindowFeatureVector, RateUnit};
+use crate::feature_extraction::state::WindowState;
+use crate::parse_dns::DnsPayload;
+use crate::shared_interface::{ClientPrefix, PrimaryDomainStats, SerializedLogEntry};
+
+impl GlobalWindowFeatureVector {
+    /// Extract global window features over the records of all primary domains, ordered by timestamp, with a window per
+    /// client prefix (records without a client share one window). Every feature vector is returned with the primary
+    /// domain ID of its record.
+    pub fn extract_for_capture(opts: &ExtractOpts, duration: f32, mut records: Vec<SerializedLogEntry>, prim_stats: &HashMap<u32, PrimaryDomainStats>) -> Vec<(u32, FeatureVector)> {
+        records.sort_by(|a, b| a.1.ts.total_cmp(&b.1.ts));
+
+        let mut windows: HashMap<Option<ClientPrefix>, GlobalWindow> = HashMap::new();
+
+        records.into_iter()
+            .map(|(prim_id, record)| {
+                let prim_count = prim_stats.get(&prim_id).map_or(0, |stats| stats.count);
+                let global_window = windows.entry(record.client_prefix)
+                    .or_insert_with(|| GlobalWindow::new(duration, opts.rare_threshold).with_rate_unit(opts.rate_unit).with_cost(opts.cost.clone()));
+                let fv = FeatureVector::Global(global_window.process_entry(record.id, record.ts, prim_id, prim_count, record.payload));
+                (prim_id, fv)
+            })
+            .filter_map(|(prim_id, fv)| opts.output_filter(fv).map(|fv| (prim_id, fv)))
+            .collect()
+    }
+}
+
+/// Time window over the queries of one client to all primary domains (host-level context).
+///
+/// A primary domain is considered rare if it has fewer than `rare_threshold` queries in the entire dataset.
+pub struct GlobalWindow {
+    window_size: f32,
+    rare_threshold: u32,
+    rate_unit: RateUnit,
+    content: VecDeque<(f64, u32, bool, DnsPayload)>,
+    window_state: WindowState,
+    cost: CostTracker,
+
+    // Number of queries in window per primary domain and number of queries to rare domains
+    domain_counts: HashMap<u32, usize>,
+    n_rare: usize,
+}
+
+impl GlobalWindow {
+    pub fn new(duration: f32, rare_threshold: u32) -> Self {
+        Self {
+            window_size: duration,
+            rare_threshold,
+            rate_unit: RateUnit::default(),
+            content: VecDeque::new(),
+            window_state: WindowState::new(),
+            cost: CostTracker::default(),
+            domain_counts: HashMap::new(),
+            n_rare: 0,
+        }
+    }
+
+    /// Report the query rate in the given unit of time (instead of per second).
+    pub fn with_rate_unit(mut self, rate_unit: RateUnit) -> Self {
+        self.rate_unit = rate_unit;
+        self
+    }
+
+    /// Track the time spent per stage (window counters, entropy and other features) in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.window_state.cost = cost.clone();
+        self.cost = cost;
+        self
+    }
+
+    pub fn process_entry(&mut self, id: usize, ts: f64, prim_id: u32, prim_count: u32, new_entry: DnsPayload) -> GlobalWindowFeatureVector {
+        let mut start = self.cost.start();
+
+        // Calculate new minimum timestamp in the queue
+        let min_ts = ts - self.window_size as f64;
+
+        // Remove expired items
+        while let Some(front) = self.content.front() {
+            if front.0 >= min_ts { break; }
+
+            // Pop expired (unwrap safe here because we know we have a value)
+            let (_, expired_prim, is_rare, payload) = self.content.pop_front().unwrap();
+            self.window_state.remove(&payload);
+
+            if let Some(count) = self.domain_counts.get_mut(&expired_prim) {
+                if *count <= 1 {
+                    self.domain_counts.remove(&expired_prim);
+                } else {
+                    *count -= 1;
+                }
+            }
+            if is_rare { self.n_rare -= 1; }
+        }
+
+        // Update window state (accumulators) and subsequently add entry to window buffer
+        let is_rare = prim_count < self.rare_threshold;
+        if is_rare { self.n_rare += 1; }
+        *self.domain_counts.entry(prim_id).or_insert(0) += 1;
+
+        self.window_state.add(&new_entry);
+        self.content.push_back((ts, prim_id, is_rare, new_entry));
+        self.cost.lap(CostStage::Counters, &mut start);
+
+        // Construct features
+        let n_queries = self.window_state.n_queries as f32;
+
+        let fv = GlobalWindowFeatureVector {
+            id,
+            query_rate: n_queries / self.rate_unit.units_per_window(self.window_size),
+            n_domains: self.domain_counts.len(),
+            rare_domain_ratio: self.n_rare as f32 / n_queries,
+            n_unique_labels: self.window_state.unique_labels.len(),
+            entropy: self.window_state.get_entropy(),
+        };
+        self.cost.lap(CostStage::Vector, &mut start);
+        fv
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::feature_extraction::{BigramModel, CostTracker, DnsLengthModel, ExtractOpts, FeatureVector, GlobalWindowFeatureVector, NanPolicy, OutOfOrderPolicy, RateUnit};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{ClientPrefix, LogRecord, SerializedLogEntry};
+
+    fn entry(id: usize, ts: f64, prim_id: u32, client: Option<u8>) -> SerializedLogEntry {
+        let payload = DnsPayload { labels: vec![b"abc".to_vec()], payload_len: 3 };
+        let client_prefix = client.map(|client| ClientPrefix::V4([10, 0, client]));
+        (prim_id, LogRecord { id, ts, payload, client_prefix, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    #[test]
+    fn test_window_per_client() {
+        let opts = ExtractOpts { payload: false, time: None, fixed: None, bytes: None, decay: None, out_of_order: OutOfOrderPolicy::default(), global: Some(10.), rare_threshold: 0, nan_policy: NanPolicy::Keep, length_model: DnsLengthModel::default(), bigram_model: BigramModel::embedded(), rate_unit: RateUnit::default(), row_filter: None, cost: CostTracker::default() };
+        let records = vec![entry(2, 2., 3, Some(1)), entry(0, 0., 1, Some(1)), entry(1, f64::NAN, 2, None), entry(3, 1., 2, Some(2)), entry(4, 3., 4, None)];
+
+        let n_domains: Vec<(usize, usize)> = GlobalWindowFeatureVector::extract_for_capture(&opts, 10., records, &HashMap::new()).into_iter()
+            .map(|(_, fv)| match fv {
+                FeatureVector::Global(fv) => (fv.id, fv.n_domains),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        // Client 10.0.1.0/24 has two domains in its window, the others one; a NaN timestamp sorts last instead of panicking
+        assert_eq!(vec![0, 3, 2, 4, 1], n_domains.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+        assert_eq!(vec![1, 1, 2, 1], n_domains[..4].iter().map(|&(_, n)| n).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::feature_extraction::state::{label_key, LabelKey};
+use crate::parse_dns::DnsPayload;
+use crate::shared_interface::LogRecord;
+
+/// Inverse document frequency of (case-insensitive) subdomain labels across the dataset, where every primary domain
+/// is a document. Random labels (e.g. tunnelled data) are rare across domains, while labels like `www` or `mail` are
+/// common, which can only be seen with corpus-level statistics (first pass over all records).
+#[derive(Debug, Default)]
+pub struct LabelIdf {
+    n_domains: usize,
+    doc_freq: HashMap<LabelKey, u32>,
+}
+
+/// Per-query label IDF features.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct LabelIdfFeatures {
+    pub avg_label_idf: f32,
+    pub max_label_idf: f32,
+}
+
+impl LabelIdf {
+    /// Count document frequencies over the records of each primary domain.
+    pub fn from_domains<'a, I: IntoIterator<Item=&'a [LogRecord]>>(domains: I) -> Self {
+        let mut idf = LabelIdf::default();
+
+        for records in domains {
+            let labels: HashSet<LabelKey> = records.iter()
+                .flat_map(|record| record.payload.labels.iter().map(|label| label_key(label)))
+                .collect();
+
+            idf.n_domains += 1;
+            labels.into_iter().for_each(|key| *idf.doc_freq.entry(key).or_insert(0) += 1);
+        }
+
+        idf
+    }
+
+    /// Smoothed IDF, `ln((1 + n) / (1 + df)) + 1`, so unseen labels have the highest value and labels in every
+    /// domain have an IDF of 1.
+    pub fn idf(&self, label: &[u8]) -> f32 {
+        let df = self.doc_freq.get(&label_key(label)).copied().unwrap_or(0);
+        ((1. + self.n_domains as f32) / (1. + df as f32)).ln() + 1.
+    }
+
+    /// Average and maximum IDF of the labels of a query, zero for queries without subdomain labels.
+    pub fn features(&self, payload: &DnsPayload) -> LabelIdfFeatures {
+        if payload.labels.is_empty() { return LabelIdfFeatures::default(); }
+
+        let idfs: Vec<f32> = payload.labels.iter().map(|label| self.idf(label)).collect();
+        LabelIdfFeatures {
+            avg_label_idf: idfs.iter().sum::<f32>() / idfs.len() as f32,
+            max_label_idf: idfs.iter().copied().fold(0., f32::max),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::idf::LabelIdf;
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn record(labels: &[&str]) -> LogRecord {
+        let labels: Vec<Vec<u8>> = labels.iter().map(|label| label.as_bytes().to_vec()).collect();
+        let payload_len = labels.iter().map(|label| label.len() as u8).sum();
+        LogRecord { id: 0, ts: 0., payload: DnsPayload { labels, payload_len }, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+    }
+
+    #[test]
+    fn test_label_idf() {
+        let domains = [
+            vec![record(&["www"]), record(&["mail"])],
+            vec![record(&["WWW"]), record(&["x7f3a9"])],
+            vec![record(&["www", "api"])],
+        ];
+        let idf = LabelIdf::from_domains(domains.iter().map(|records| records.as_slice()));
+
+        // In every domain (case-insensitive)
+        assert_eq!(1., idf.idf(b"www"));
+        // In a single domain, and unseen
+        assert!((idf.idf(b"x7f3a9") - ((4f32 / 2.).ln() + 1.)).abs() < 1e-6);
+        assert!(idf.idf(b"unseen") > idf.idf(b"x7f3a9"));
+
+        let features = idf.features(&record(&["x7f3a9", "www"]).payload);
+        assert_eq!(idf.idf(b"x7f3a9"), features.max_label_idf);
+        assert!((features.avg_label_idf - (idf.idf(b"x7f3a9") + 1.) / 2.).abs() < 1e-6);
+
+        assert_eq!(0., idf.features(&record(&[]).payload).avg_label_idf);
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+/// How DNS name lengths are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NameFormat {
+    /// Textual names with dots between labels and without trailing dot (at most 253 characters).
+    #[default]
+    Presentation,
+    /// Wire format with a length octet per label and the root label (at most 255 octets).
+    Wire,
+}
+
+/// Model of the space available for a payload in front of the primary domain, used for the fill ratio features of all
+/// extractors. The payload and the primary domain are counted in the same format, so the ratios of both formats agree
+/// for the standard maximum lengths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DnsLengthModel {
+    pub format: NameFormat,
+    /// Maximum name length in this format.
+    pub max_name_length: u16,
+}
+
+impl Default for DnsLengthModel {
+    fn default() -> Self {
+        Self::presentation(253)
+    }
+}
+
+impl DnsLengthModel {
+    pub fn presentation(max_name_length: u16) -> Self {
+        DnsLengthModel { format: NameFormat::Presentation, max_name_length }
+    }
+
+    pub fn wire(max_name_length: u16) -> Self {
+        DnsLengthModel { format: NameFormat::Wire, max_name_length }
+    }
+
+    /// Space left for a payload in front of a primary domain of the given (textual) length, including the dot or
+    /// length octets of the payload labels.
+    pub fn open_space(&self, primary_domain_length: u8) -> f32 {
+        let overhead = match self.format {
+            // Dot between payload and primary domain
+            NameFormat::Presentation => primary_domain_length as u16 + 1,
+            // Extra length octet for the first primary domain label, and the root label
+            NameFormat::Wire => primary_domain_length as u16 + 2,
+        };
+        self.max_name_length.saturating_sub(overhead) as f32
+    }
+
+    /// Space used by payload labels with the given total length.
+    pub fn used_space(&self, total_label_length: usize, n_labels: usize) -> usize {
+        match self.format {
+            // Dots between the payload labels
+            NameFormat::Presentation => (total_label_length + n_labels).saturating_sub(1),
+            // Length octet per label
+            NameFormat::Wire => total_label_length + n_labels,
+        }
+    }
+}
+
+impl FromStr for NameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "presentation" => Ok(NameFormat::Presentation),
+            "wire" => Ok(NameFormat::Wire),
+            _ => Err(format!("unknown name format '{}', expected presentation or wire", s)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::length::DnsLengthModel;
+
+    #[test]
+    fn test_formats_agree() {
+        // ab.cde.example.com: payload of 2 labels (5 characters) in front of a primary domain of 11 characters
+        let presentation = DnsLengthModel::default();
+        let wire = DnsLengthModel::wire(255);
+
+        assert_eq!((6, 241.), (presentation.used_space(5, 2), presentation.open_space(11)));
+        assert_eq!((7, 242.), (wire.used_space(5, 2), wire.open_space(11)));
+
+        // A name of maximal length fills all open space in both formats
+        assert_eq!(presentation.open_space(11) as usize, presentation.used_space(241 - 3, 4));
+        assert_eq!(wire.open_space(11) as usize, wire.used_space(242 - 4, 4));
+    }
+
+    #[test]
+    fn test_larger_capacity() {
+        assert_eq!(288., DnsLengthModel::presentation(300).open_space(11));
+        assert_eq!(0., DnsLengthModel::presentation(10).open_space(11));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::feature_extraction::state::WindowSimilarity;
+use crate::shared_interface::LogRecord;
+
+pub use self::bigram::BigramModel;
+pub use self::cardinality::HyperLogLog;
+pub use self::columnar::{ColumnarError, FeatureMatrix};
+pub use self::cost::{CostStage, CostTracker, FeatureCost};
+pub use self::decay::{DecayState, DecayWindow};
+pub use self::feature_vector::{ByteWindowFeatureVector, DecayFeatureVector, FeatureDeltas, FeatureVector, FixedWindowFeatureVector, GlobalWindowFeatureVector, NanPolicy, PayloadFeatureVector, RateUnit, TimeWindowFeatureVector};
+pub use self::filter::{RowFilter, RowFilterError};
+pub use self::global::GlobalWindow;
+pub use self::idf::{LabelIdf, LabelIdfFeatures};
+pub use self::length::{DnsLengthModel, NameFormat};
+pub use self::payload::{extract_payload_features_from_query, PayloadExtractor};
+pub use self::sliding::{ByteWindow, FixedWindow, OutOfOrderPolicy, TimeWindow};
+pub use self::state::{LabelKey, query_key};
+pub use self::streaming::StreamingExtractor;
+pub use self::timeseries::{query_time_series, TimeSeriesPoint};
+
+mod sliding;
+mod bigram;
+mod cardinality;
+mod charclass;
+mod columnar;
+mod compression;
+mod cost;
+mod decay;
+mod entropy;
+mod feature_vector;
+mod filter;
+mod global;
+mod idf;
+mod length;
+mod payload;
+mod state;
+mod streaming;
+mod timeseries;
+
+
+#[derive(Debug)]
+pub struct ExtractOpts {
+    pub payload: bool,
+    pub time: Option<f32>,
+    pub fixed: Option<usize>,
+    /// Byte window size: the window holds the most recent queries with at most this many unique payload bytes.
+    pub bytes: Option<usize>,
+    /// Decay factor per second of exponentially decayed features (instead of a window).
+    pub decay: Option<f64>,
+    pub out_of_order: OutOfOrderPolicy,
+    pub global: Option<f32>,
+    pub rare_threshold: u32,
+    pub nan_policy: NanPolicy,
+    /// DNS length model for the fill ratio features.
+    pub length_model: DnsLengthModel,
+    /// Character bigram model for the log-likelihood features.
+    pub bigram_model: Arc<BigramModel>,
+    /// Unit of time for the rate features of time, decay and global windows.
+    pub rate_unit: RateUnit,
+    /// Only keep feature vectors that satisfy this filter (evaluated after the NaN policy).
+    pub row_filter: Option<RowFilter>,
+    /// Time spent per feature stage (see `FeatureCost`), if tracking.
+    pub cost: CostTracker,
+}
+
+impl ExtractOpts {
+    /// Apply the NaN policy and row filter to a feature vector (e.g. of a `StreamingExtractor`), None if it is dropped.
+    pub fn output_filter(&self, fv: FeatureVector) -> Option<FeatureVector> {
+        self.cost.time(CostStage::Filters, || fv.apply_nan_policy(self.nan_policy)
+            .filter(|fv| self.row_filter.as_ref().is_none_or(|filter| filter.matches(fv))))
+    }
+}
+
+
+pub fn extract_features_per_domain(opts: &ExtractOpts, queries: Vec<LogRecord>, primary_domain_length: u8) -> Vec<FeatureVector> {
+    let mut extractor = StreamingExtractor::new(opts, primary_domain_length);
+
+    let mut features: Vec<FeatureVector> = queries.into_iter()
+        .flat_map(|record| extractor.process(record))
+        .collect();
+    features.extend(extractor.flush());
+
+    apply_output_filters(opts, features)
+}
+
+/// Same as `extract_features_per_domain` for queries in timestamp order, with the timeline split into segments of
+/// `segment_len` records that are extracted in parallel. Each segment is preceded by (copies of) the records of the
+/// window before it (warm-up, without output), so the feature vectors are the same as without splitting. Decay windows
+/// depend on all preceding records, so they are not split but extracted by `extract_features_per_domain`.
+pub fn extract_features_per_domain_split(opts: &ExtractOpts, mut queries: Vec<LogRecord>, primary_domain_length: u8, segment_len: usize) -> Vec<FeatureVector> {
+    let first_ts = match queries.first() {
+        Some(record) => record.ts,
+        None => return Vec::new(),
+    };
+
+    // Every segment of a decay window would re-process all preceding records, which is slower than not splitting
+    if !opts.payload && opts.fixed.is_none() && opts.bytes.is_none() && opts.decay.is_some() {
+        return extract_features_per_domain(opts, queries, primary_domain_length);
+    }
+
+    // Split off segments from the back, with the records of the window before them
+    let mut segments: Vec<(usize, Vec<LogRecord>, Vec<LogRecord>)> = Vec::new();
+    let starts: Vec<usize> = (0..queries.len()).step_by(segment_len.max(1)).collect();
+    for &start in starts.iter().rev() {
+        let warmup_start = warmup_start(opts, &queries, start);
+        let segment = queries.split_off(start);
+        segments.push((warmup_start, queries[warmup_start..].to_vec(), segment));
+    }
+    segments.reverse();
+
+    let segments: Vec<Vec<FeatureVector>> = segments.into_par_iter()
+        .map(|(n_preceding, warmup, segment)| {
+            let warmup_ids: HashSet<usize> = warmup.iter().map(|record| record.id).collect();
+
+            let mut extractor = StreamingExtractor::new(opts, primary_domain_length);
+            extractor.resume_after(first_ts, n_preceding);
+
+            let mut features: Vec<FeatureVector> = warmup.into_iter().chain(segment)
+                .flat_map(|record| extractor.process(record))
+                .collect();
+            features.extend(extractor.flush());
+            features.retain(|fv| !warmup_ids.contains(&fv.id()));
+            features
+        })
+        .collect();
+
+    apply_output_filters(opts, segments.into_iter().flatten().collect())
+}
+
+/// Index of the first record in the window of the record at `start` (same feature type priority as
+/// `StreamingExtractor::new`).
+fn warmup_start(opts: &ExtractOpts, queries: &[LogRecord], start: usize) -> usize {
+    if opts.payload {
+        start
+    } else if let Some(size) = opts.fixed {
+        // Label set similarity depends on the window at the end of the bucket two before the current one
+        let bucket = WindowSimilarity::bucket(start as f64, size as f64);
+        ((bucket - 2).max(0) as usize * size).min(start.saturating_sub(size.saturating_sub(1)))
+    } else if let Some(size) = opts.bytes {
+        ByteWindow::window_start(&queries[..start], size)
+    } else if opts.decay.is_some() {
+        // Decayed accumulators depend on all preceding records (not split, see `extract_features_per_domain_split`)
+        0
+    } else if let Some(duration) = opts.time {
+        // Window of the last record of the bucket two before the current one, for the label set similarity
+        let bucket = WindowSimilarity::bucket(queries[start].ts, duration as f64);
+        let min_ts = ((bucket - 3) as f64 * duration as f64).min(queries[start].ts - duration as f64);
+        queries[..start].partition_point(|record| record.ts < min_ts)
+    } else {
+        start
+    }
+}
+
+fn apply_output_filters(opts: &ExtractOpts, features: Vec<FeatureVector>) -> Vec<FeatureVector> {
+    features.into_iter().filter_map(|fv| opts.output_filter(fv)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::{BigramModel, CostTracker, DnsLengthModel, extract_features_per_domain, extract_features_per_domain_split, ExtractOpts, NanPolicy, OutOfOrderPolicy, RateUnit};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn opts(time: Option<f32>, fixed: Option<usize>) -> ExtractOpts {
+        ExtractOpts { payload: false, time, fixed, bytes: None, decay: None, out_of_order: OutOfOrderPolicy::default(), global: None, rare_threshold: 0, nan_policy: NanPolicy::Keep, length_model: DnsLengthModel::default(), bigram_model: BigramModel::embedded(), rate_unit: RateUnit::default(), row_filter: None, cost: CostTracker::default() }
+    }
+
+    fn records() -> Vec<LogRecord> {
+        (0..50).map(|id| {
+            let label = format!("l{}", id % 7).into_bytes();
+            let payload = DnsPayload { payload_len: label.len() as u8, labels: vec![label] };
+            LogRecord { id, ts: (id * id) as f64 / 10., payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+        }).collect()
+    }
+
+    #[test]
+    fn test_split_same_as_sequential() {
+        let bytes = ExtractOpts { time: None, bytes: Some(6), ..opts(None, None) };
+        for opts in &[opts(Some(20.), None), opts(None, Some(4)), bytes] {
+            let expected = format!("{:?}", extract_features_per_domain(opts, records(), 11));
+            for &segment_len in &[1, 7, 50, 100] {
+                assert_eq!(expected, format!("{:?}", extract_features_per_domain_split(opts, records(), 11, segment_len)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_decay() {
+        // Extracted without splitting, with the same output
+        let decay = ExtractOpts { decay: Some(10.), ..opts(Some(20.), None) };
+        let expected = format!("{:?}", extract_features_per_domain(&decay, records(), 11));
+        assert!(expected.starts_with("[Decay("));
+        for &segment_len in &[1, 7, 100] {
+            assert_eq!(expected, format!("{:?}", extract_features_per_domain_split(&decay, records(), 11, segment_len)));
+        }
+    }
+
+    #[test]
+    fn test_no_feature_type_selected() {
+        // Payload features, as with `payload: true`
+        let payload = ExtractOpts { payload: true, ..opts(None, None) };
+        let expected = format!("{:?}", extract_features_per_domain(&payload, records(), 11));
+        assert_eq!(expected, format!("{:?}", extract_features_per_domain(&opts(None, None), records(), 11)));
+    }
+}
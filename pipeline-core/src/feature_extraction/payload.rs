@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::feature_extraction::bigram::BigramModel;
+use crate::feature_extraction::charclass::classify;
+use crate::feature_extraction::cost::{CostStage, CostTracker};
+use crate::feature_extraction::entropy::estimate_entropy;
+use crate::feature_extraction::feature_vector::{FeatureVector, PayloadFeatureVector};
+use crate::feature_extraction::length::DnsLengthModel;
+use crate::parse_dns::{DnsPayload, parse_dns, ParseDnsError};
+use crate::shared_interface::LogRecord;
+
+impl PayloadFeatureVector {
+    pub fn extract_for_domain(queries: Vec<LogRecord>, primary_domain_length: u8) -> Vec<FeatureVector> {
+        queries.into_iter()
+            .map(|record| FeatureVector::Payload(payload_features(record.id, &record.payload, primary_domain_length)))
+            .collect()
+    }
+}
+
+/// Payload feature extractor with the same per-record interface as the window extractors.
+pub struct PayloadExtractor {
+    primary_domain_length: u8,
+    length_model: DnsLengthModel,
+    bigram_model: Arc<BigramModel>,
+    cost: CostTracker,
+}
+
+impl PayloadExtractor {
+    pub fn new(primary_domain_length: u8) -> Self {
+        Self { primary_domain_length, length_model: DnsLengthModel::default(), bigram_model: BigramModel::embedded(), cost: CostTracker::default() }
+    }
+
+    /// Use the given DNS length model for the fill ratio (instead of the default presentation format).
+    pub fn with_length_model(mut self, length_model: DnsLengthModel) -> Self {
+        self.length_model = length_model;
+        self
+    }
+
+    /// Use the given character bigram model for the log-likelihood feature (instead of the embedded model).
+    pub fn with_bigram_model(mut self, model: Arc<BigramModel>) -> Self {
+        self.bigram_model = model;
+        self
+    }
+
+    /// Track the time spent on payload features in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn process_entry(&mut self, id: usize, entry: &DnsPayload) -> PayloadFeatureVector {
+        self.cost.time(CostStage::Payload, || payload_features_with_model(id, entry, self.primary_domain_length, &self.length_model, &self.bigram_model))
+    }
+}
+
+/// Parse a single raw DNS query and extract its payload features (with ID 0), e.g. for ad-hoc scoring of a hostname.
+pub fn extract_payload_features_from_query(query: &[u8]) -> Result<PayloadFeatureVector, ParseDnsError> {
+    let (primary_domain, payload) = parse_dns(query)?;
+    Ok(payload_features(0, &payload, primary_domain.len() as u8))
+}
+
+pub fn payload_features(id: usize, entry: &DnsPayload, primary_domain_length: u8) -> PayloadFeatureVector {
+    payload_features_with_model(id, entry, primary_domain_length, &DnsLengthModel::default(), &BigramModel::embedded())
+}
+
+pub fn payload_features_with_model(id: usize, entry: &DnsPayload, primary_domain_length: u8, length_model: &DnsLengthModel, bigram_model: &BigramModel) -> PayloadFeatureVector {
+    let n_labels = entry.labels.len() as u8;
+
+    // Bail if no labels (e.g. only dots in input string)
+    // if n_labels == 0 { return None; }
+
+    // Average label length and maximum label length
+    let label_lengths: Vec<u8> = entry.labels.iter().map(|label| label.len() as u8).collect();
+    let avg_label_length = (label_lengths.iter().map(|&len| len as usize).sum::<usize>() as f32) / label_lengths.len() as f32;
+    let max_label_length = label_lengths.iter().copied().max().unwrap_or(0);
+
+    // Character class counts (u16, as payloads that did not pass through parse_dns may exceed 255 bytes)
+    let mut n_digits: u16 = 0;
+    let mut n_invalid: u16 = 0;
+    let mut n_uppercase: u16 = 0;
+    let mut n_underscore: u16 = 0;
+    let mut n_nonprintable: u16 = 0;
+    let mut n_mixed_case_labels: u8 = 0;
+
+    // Digit positions
+    let mut n_digit_start_labels: u8 = 0;
+    let mut total_longest_digit_run: usize = 0;
+
+    // Entropy
+    let mut char_map: BTreeMap<u8, u16> = BTreeMap::new();
+    let mut ascii_map: [u16; 128] = [0; 128];
+
+    // String length as f32 for entropy division
+    let mut n_total: f32 = 0.;
+
+    for label in entry.labels.iter() {
+        let classes = classify(label);
+        if classes.is_mixed_case() { n_mixed_case_labels += 1; }
+        if label.first().is_some_and(u8::is_ascii_digit) { n_digit_start_labels += 1; }
+        if classes.digits > 0 { total_longest_digit_run += longest_digit_run(label); }
+
+        n_digits += classes.digits as u16;
+        n_uppercase += classes.uppercase as u16;
+        n_underscore += classes.underscore as u16;
+        n_invalid += classes.invalid as u16;
+        n_nonprintable += classes.nonprintable as u16;
+        n_total += (label.len() - classes.dots) as f32;
+
+        if classes.is_hostname() {
+            // Fast path, all characters are in the ASCII map
+            label.iter().for_each(|&ch| ascii_map[ch as usize] += 1);
+            continue;
+        }
+
+        for &ch in label.iter() {
+            if ch == b'.' { continue; }
+
+            if ch.is_ascii() {
+                // Add to ASCII map (fast path for entropy calculation)
+                ascii_map[ch as usize] += 1;
+            } else {
+                // Add to char map (slow path for entropy calculation)
+                *char_map.entry(ch).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let entropy = estimate_entropy(char_map.values().chain(ascii_map.iter()).map(|&c| c as usize), n_total as usize);
+
+    let n_unique = entropy.n_symbols as u16;
+    let ratio_unique: f32 = n_unique as f32 / n_total;
+
+    // Fraction of the total available query space that is used
+    let fill_ratio = length_model.used_space(entry.payload_len as usize, entry.labels.len()) as f32 / length_model.open_space(primary_domain_length);
+
+    // Fraction of labels with mixed case (0x20 case randomization)
+    let case_randomization_ratio = n_mixed_case_labels as f32 / n_labels as f32;
+
+    let digit_start_ratio = n_digit_start_labels as f32 / n_labels as f32;
+    let avg_longest_digit_run = total_longest_digit_run as f32 / n_labels as f32;
+
+    PayloadFeatureVector {
+        id,
+        n_unique,
+        ratio_unique,
+        n_digits,
+        digit_start_ratio,
+        avg_longest_digit_run,
+        n_invalid,
+        n_uppercase,
+        n_underscore,
+        n_nonprintable,
+        n_labels,
+        avg_label_length,
+        max_label_length,
+        entropy: entropy.shannon,
+        entropy_normalized: entropy.normalized,
+        renyi2_entropy: entropy.renyi2,
+        min_entropy: entropy.min,
+        fill_ratio,
+        case_randomization_ratio,
+        bigram_log_likelihood: bigram_model.avg_log_likelihood(entry),
+    }
+}
+
+/// Length of the longest run of consecutive digits in a label.
+fn longest_digit_run(label: &[u8]) -> usize {
+    label.split(|ch| !ch.is_ascii_digit()).map(<[u8]>::len).max().unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::feature_vector::{FeatureVector, NanPolicy};
+    use crate::feature_extraction::payload::{extract_payload_features_from_query, payload_features};
+    use crate::parse_dns::DnsPayload;
+
+    #[test]
+    fn test_features_from_query() {
+        let fv = extract_payload_features_from_query(b"abc1.de.example.com").unwrap();
+
+        assert_eq!(0, fv.id);
+        assert_eq!(2, fv.n_labels);
+        assert_eq!(1, fv.n_digits);
+        assert_eq!(4, fv.max_label_length);
+        // Payload "abc1.de" of 7 characters (including the dot between labels)
+        assert_eq!(7. / (253 - 12) as f32, fv.fill_ratio);
+        assert_eq!(0., fv.case_randomization_ratio);
+
+        let fv = extract_payload_features_from_query(b"aBc1.de.example.com").unwrap();
+        assert_eq!(0.5, fv.case_randomization_ratio);
+    }
+
+    #[test]
+    fn test_character_breakdown() {
+        let payload = DnsPayload { labels: vec![b"Ab_c".to_vec(), b"x+y\x00\xfe".to_vec()], payload_len: 9 };
+        let fv = payload_features(0, &payload, 11);
+
+        assert_eq!((3, 1, 1, 2), (fv.n_invalid, fv.n_uppercase, fv.n_underscore, fv.n_nonprintable));
+    }
+
+    #[test]
+    fn test_digit_positions() {
+        let payload = DnsPayload { labels: vec![b"0042abc7".to_vec(), b"x12y345".to_vec(), b"abc".to_vec(), b"9".to_vec()], payload_len: 22 };
+        let fv = payload_features(0, &payload, 11);
+
+        assert_eq!(0.5, fv.digit_start_ratio);
+        assert_eq!((4. + 3. + 0. + 1.) / 4., fv.avg_longest_digit_run);
+    }
+
+    #[test]
+    fn test_features_from_invalid_query() {
+        assert!(extract_payload_features_from_query(b"example.com").is_err());
+    }
+
+    #[test]
+    fn test_features_without_labels() {
+        let empty = DnsPayload { labels: vec![], payload_len: 0 };
+        let fv = payload_features(0, &empty, 11);
+        assert!(fv.entropy.is_nan());
+
+        match FeatureVector::Payload(fv).apply_nan_policy(NanPolicy::Zero) {
+            Some(FeatureVector::Payload(fv)) => assert_eq!((0., 0., 0.), (fv.entropy, fv.avg_label_length, fv.ratio_unique)),
+            _ => panic!("expected payload vector")
+        }
+    }
+
+    #[test]
+    fn test_maximal_length_query() {
+        // 253 characters in total, of which 238 digits
+        let digits = "1".repeat(60);
+        let query = format!("{}.{}.{}.{}.example.com", digits, digits, digits, &digits[..58]);
+        assert_eq!(253, query.len());
+
+        let fv = extract_payload_features_from_query(query.as_bytes()).unwrap();
+        assert_eq!(238, fv.n_digits);
+        assert_eq!(0, fv.n_invalid);
+        assert_eq!(1, fv.n_unique);
+        assert_eq!(0., fv.entropy);
+    }
+
+    #[test]
+    fn test_counters_do_not_overflow() {
+        // Payloads that did not pass through parse_dns may be longer than 255 bytes
+        let payload = DnsPayload { labels: vec![vec![b'7'; 200], vec![0xFF; 200]], payload_len: 0 };
+        let fv = payload_features(0, &payload, 11);
+
+        assert_eq!(200, fv.n_digits);
+        assert_eq!(200, fv.n_invalid);
+        assert_eq!(2, fv.n_unique);
+        assert_eq!(200., fv.avg_label_length);
+        assert_eq!(1., fv.entropy);
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use crate::feature_extraction::bigram::{BigramModel, WindowLikelihood};
+use crate::feature_extraction::compression::LabelCompression;
+use crate::feature_extraction::cost::{CostStage, CostTracker};
+use crate::feature_extraction::feature_vector::{ByteWindowFeatureVector, FeatureVector, FixedWindowFeatureVector, RateUnit, TimeWindowFeatureVector};
+use crate::feature_extraction::length::DnsLengthModel;
+use crate::feature_extraction::state::{label_key, LabelKey, UniqueGrowth, WindowSimilarity, WindowState};
+use crate::parse_dns::DnsPayload;
+use crate::shared_interface::LogRecord;
+
+impl TimeWindowFeatureVector {
+    pub fn extract_for_domain(duration: f32, queries: Vec<LogRecord>, primary_domain_length: u8) -> Vec<FeatureVector> {
+        let mut time_window = TimeWindow::new(duration, primary_domain_length);
+
+        queries.into_iter()
+            .map(|record| FeatureVector::Time(time_window.process_record(record)))
+            .collect()
+    }
+}
+
+
+impl FixedWindowFeatureVector {
+    pub fn extract_for_domain(size: usize, queries: Vec<LogRecord>, primary_domain_length: u8) -> Vec<FeatureVector> {
+        let mut fixed_window = FixedWindow::new(size, primary_domain_length);
+
+        queries.into_iter()
+            .map(|record| FeatureVector::Fixed(fixed_window.process_record(record)))
+            .collect()
+    }
+}
+
+impl ByteWindowFeatureVector {
+    pub fn extract_for_domain(size: usize, queries: Vec<LogRecord>, primary_domain_length: u8) -> Vec<FeatureVector> {
+        let mut byte_window = ByteWindow::new(size, primary_domain_length);
+
+        queries.into_iter()
+            .map(|record| FeatureVector::Bytes(byte_window.process_record(record)))
+            .collect()
+    }
+}
+
+/// How a `TimeWindow` handles records with a timestamp earlier than the newest record seen so far.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutOfOrderPolicy {
+    /// Skip the record, no feature vector is produced for it.
+    Drop,
+    /// Process the record as if it arrived at the newest timestamp seen so far.
+    #[default]
+    Clamp,
+    /// Hold records back for the given lateness (in seconds) and release them in timestamp order.
+    /// Records arriving after a later record has already been released are dropped.
+    Reorder(f64),
+}
+
+pub struct TimeWindow {
+    window_size: f32,
+    primary_domain_length: u8,
+    length_model: DnsLengthModel,
+    open_space: f32,
+    rate_unit: RateUnit,
+    content: VecDeque<LogRecord>,
+    window_state: WindowState,
+    similarity: WindowSimilarity,
+    growth: UniqueGrowth,
+    compression: LabelCompression,
+    likelihood: WindowLikelihood,
+    cost: CostTracker,
+
+    // Out-of-order handling
+    policy: OutOfOrderPolicy,
+    first_ts: Option<f64>,
+    last_id: usize,
+    last_ts: f64,
+    max_seen_ts: f64,
+    pending: VecDeque<LogRecord>,
+}
+
+impl TimeWindow {
+    pub fn new(duration: f32, primary_domain_length: u8) -> Self {
+        Self::with_policy(duration, OutOfOrderPolicy::default(), primary_domain_length)
+    }
+
+    pub fn with_policy(duration: f32, policy: OutOfOrderPolicy, primary_domain_length: u8) -> Self {
+        let length_model = DnsLengthModel::default();
+        Self {
+            window_size: duration,
+            primary_domain_length,
+            length_model,
+            open_space: length_model.open_space(primary_domain_length),
+            rate_unit: RateUnit::default(),
+            content: VecDeque::new(),
+            window_state: WindowState::new(),
+            similarity: WindowSimilarity::new(duration as f64),
+            growth: UniqueGrowth::default(),
+            compression: LabelCompression::default(),
+            likelihood: WindowLikelihood::default(),
+            cost: CostTracker::default(),
+            policy,
+            first_ts: None,
+            last_id: 0,
+            last_ts: f64::NEG_INFINITY,
+            max_seen_ts: f64::NEG_INFINITY,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Use the given DNS length model for the fill ratio (instead of the default presentation format).
+    pub fn with_length_model(mut self, length_model: DnsLengthModel) -> Self {
+        self.length_model = length_model;
+        self.open_space = length_model.open_space(self.primary_domain_length);
+        self
+    }
+
+    /// Use the given character bigram model for the log-likelihood feature (instead of the embedded model).
+    pub fn with_bigram_model(mut self, model: Arc<BigramModel>) -> Self {
+        self.likelihood = WindowLikelihood::new(model);
+        self
+    }
+
+    /// Track the time spent per stage (window counters, entropy, similarity and other features) in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.window_state.cost = cost.clone();
+        self.cost = cost;
+        self
+    }
+
+    /// Report rate features in the given unit of time (instead of per second).
+    pub fn with_rate_unit(mut self, rate_unit: RateUnit) -> Self {
+        self.rate_unit = rate_unit;
+        self
+    }
+
+    /// Continue a domain whose first record (at `first_ts`) was processed elsewhere, for the domain age features.
+    pub fn resume_after(&mut self, first_ts: f64) {
+        self.first_ts = Some(first_ts);
+    }
+
+    /// Add a record that may be out of order, applying the out-of-order policy of this window.
+    /// Returns the feature vectors of all records that were processed as a result (zero or more).
+    pub fn push(&mut self, id: usize, ts: f64, new_entry: DnsPayload) -> Vec<TimeWindowFeatureVector> {
+        self.push_record(LogRecord { id, ts, payload: new_entry, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    /// Same as `push`, for a complete log record (including client information).
+    pub fn push_record(&mut self, mut record: LogRecord) -> Vec<TimeWindowFeatureVector> {
+        let ts = record.ts;
+        match self.policy {
+            OutOfOrderPolicy::Drop if ts < self.last_ts => Vec::new(),
+            OutOfOrderPolicy::Drop => vec![self.process_record(record)],
+            OutOfOrderPolicy::Clamp => {
+                record.ts = ts.max(self.last_ts);
+                vec![self.process_record(record)]
+            }
+            OutOfOrderPolicy::Reorder(_) if ts < self.last_ts => Vec::new(),
+            OutOfOrderPolicy::Reorder(max_lateness) => {
+                // Insert after all pending records with the same or earlier timestamp (keeps arrival order for ties)
+                let idx = self.pending.partition_point(|pending| pending.ts <= ts);
+                self.pending.insert(idx, record);
+                self.max_seen_ts = self.max_seen_ts.max(ts);
+
+                self.release_until(self.max_seen_ts - max_lateness)
+            }
+        }
+    }
+
+    /// Process all records still held back by the reorder buffer.
+    pub fn flush(&mut self) -> Vec<TimeWindowFeatureVector> {
+        self.release_until(f64::INFINITY)
+    }
+
+    /// Advance the window clock to `ts` without a new record (watermark tick).
+    ///
+    /// Releases held back records up to the watermark and expires entries that fell out of the window.
+    /// If entries expired, a closing aggregate for the window ending at `ts` is appended to the result, carrying the ID
+    /// of the newest record and flagged `is_closing_window`, also if the window is empty now (e.g. an idle tunnel).
+    pub fn advance_to(&mut self, ts: f64) -> Vec<TimeWindowFeatureVector> {
+        let watermark = match self.policy {
+            OutOfOrderPolicy::Reorder(max_lateness) => ts - max_lateness,
+            _ => ts,
+        };
+        let mut result = self.release_until(watermark);
+
+        if watermark <= self.last_ts { return result; }
+        self.last_ts = watermark;
+
+        if self.expire_before(watermark - self.window_size as f64) {
+            let mut closing = self.features(self.last_id, watermark);
+            closing.is_closing_window = true;
+            result.push(closing);
+        }
+
+        result
+    }
+
+    fn release_until(&mut self, watermark: f64) -> Vec<TimeWindowFeatureVector> {
+        let mut released = Vec::new();
+        while let Some(front) = self.pending.front() {
+            if front.ts > watermark { break; }
+
+            // (unwrap safe here because we know we have a value)
+            let record = self.pending.pop_front().unwrap();
+            released.push(self.process_record(record));
+        }
+        released
+    }
+
+    /// Process a record, expecting records to arrive in timestamp order (see `push` otherwise).
+    pub fn process_entry(&mut self, id: usize, ts: f64, new_entry: DnsPayload) -> TimeWindowFeatureVector {
+        self.process_record(LogRecord { id, ts, payload: new_entry, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    /// Same as `process_entry`, for a complete log record (including client information).
+    pub fn process_record(&mut self, record: LogRecord) -> TimeWindowFeatureVector {
+        let (id, ts) = (record.id, record.ts);
+        let mut start = self.cost.start();
+
+        // Calculate new minimum timestamp in the queue and remove expired items
+        self.expire_before(ts - self.window_size as f64);
+
+        // Update window state (accumulators) and subsequently add entry to window buffer
+        self.window_state.add(&record.payload);
+        self.growth.add(&record.payload);
+        self.likelihood.add(&record.payload);
+        self.window_state.add_client_prefix(record.client_prefix);
+        self.window_state.add_ttl(record.ttl);
+        self.window_state.add_cname_depth(record.cname_depth);
+        self.window_state.add_seq_gap(record.seq_gap);
+        self.content.push_back(record);
+        self.first_ts.get_or_insert(ts);
+        self.last_id = id;
+        self.last_ts = ts;
+        self.cost.lap(CostStage::Counters, &mut start);
+
+        // Construct features
+        self.features(id, ts)
+    }
+
+    /// Construct features for the current window state at time `ts`.
+    fn features(&mut self, id: usize, ts: f64) -> TimeWindowFeatureVector {
+        // Time since the first query to this domain, still in the window if that is less than the window duration
+        let domain_age = ts - self.first_ts.unwrap_or(ts);
+        let is_first_window = domain_age <= self.window_size as f64;
+
+        let mut start = self.cost.start();
+        let rate_duration = self.rate_unit.units_per_window(self.window_size);
+        let mut fv = TimeWindowFeatureVector::from_window_state(id, &self.window_state, &self.length_model, &self.open_space, &rate_duration, domain_age as f32, is_first_window);
+        self.cost.lap(CostStage::Vector, &mut start);
+        fv.label_set_similarity = self.similarity.update(ts, &self.window_state);
+        self.cost.lap(CostStage::Similarity, &mut start);
+        self.growth.rebalance(&self.content);
+        fv.unique_label_growth = self.growth.get();
+        fv.bigram_log_likelihood = self.likelihood.get();
+        self.cost.lap(CostStage::Counters, &mut start);
+        fv.label_compression_ratio = self.compression.ratio(&self.content);
+        self.cost.lap(CostStage::Compression, &mut start);
+        fv
+    }
+
+    /// Remove all entries older than `min_ts` from the window, returns whether any entry was removed.
+    fn expire_before(&mut self, min_ts: f64) -> bool {
+        let mut expired = false;
+        while let Some(front) = self.content.front() {
+            if front.ts >= min_ts { break; }
+
+            // Pop expired (unwrap safe here because we know we have a value)
+            let record = self.content.pop_front().unwrap();
+            self.window_state.remove(&record.payload);
+            self.growth.remove(&record.payload);
+            self.likelihood.remove(&record.payload);
+            self.window_state.remove_client_prefix(record.client_prefix);
+            self.window_state.remove_ttl(record.ttl);
+            self.window_state.remove_cname_depth(record.cname_depth);
+            self.window_state.remove_seq_gap(record.seq_gap);
+            expired = true;
+        }
+        expired
+    }
+}
+
+pub struct FixedWindow {
+    window_size: usize,
+    primary_domain_length: u8,
+    length_model: DnsLengthModel,
+    open_space: f32,
+    content: VecDeque<LogRecord>,
+    window_state: WindowState,
+    similarity: WindowSimilarity,
+    growth: UniqueGrowth,
+    compression: LabelCompression,
+    likelihood: WindowLikelihood,
+    cost: CostTracker,
+
+    // Domain age
+    first_ts: Option<f64>,
+    n_processed: usize,
+}
+
+impl FixedWindow {
+    pub fn new(size: usize, primary_domain_length: u8) -> Self {
+        let length_model = DnsLengthModel::default();
+        Self {
+            window_size: size,
+            primary_domain_length,
+            length_model,
+            open_space: length_model.open_space(primary_domain_length),
+            content: VecDeque::new(),
+            window_state: WindowState::new(),
+            similarity: WindowSimilarity::new(size as f64),
+            growth: UniqueGrowth::default(),
+            compression: LabelCompression::default(),
+            likelihood: WindowLikelihood::default(),
+            cost: CostTracker::default(),
+            first_ts: None,
+            n_processed: 0,
+        }
+    }
+
+    /// Use the given DNS length model for the fill ratio (instead of the default presentation format).
+    pub fn with_length_model(mut self, length_model: DnsLengthModel) -> Self {
+        self.length_model = length_model;
+        self.open_space = length_model.open_space(self.primary_domain_length);
+        self
+    }
+
+    /// Use the given character bigram model for the log-likelihood feature (instead of the embedded model).
+    pub fn with_bigram_model(mut self, model: Arc<BigramModel>) -> Self {
+        self.likelihood = WindowLikelihood::new(model);
+        self
+    }
+
+    /// Track the time spent per stage (window counters, entropy, similarity and other features) in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.window_state.cost = cost.clone();
+        self.cost = cost;
+        self
+    }
+
+    /// Continue a domain of which `n_preceding` records, the first at `first_ts`, were processed elsewhere, for the
+    /// domain age features.
+    pub fn resume_after(&mut self, first_ts: f64, n_preceding: usize) {
+        self.first_ts = Some(first_ts);
+        self.n_processed = n_preceding;
+    }
+
+    pub fn process_entry(&mut self, id: usize, ts: f64, new_entry: DnsPayload) -> FixedWindowFeatureVector {
+        self.process_record(LogRecord { id, ts, payload: new_entry, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    /// Same as `process_entry`, for a complete log record (including client information).
+    pub fn process_record(&mut self, record: LogRecord) -> FixedWindowFeatureVector {
+        let (id, ts) = (record.id, record.ts);
+        let mut start = self.cost.start();
+
+        // Pop expired
+        if self.content.len() >= self.window_size {
+            // (unwrap safe here because we know we have a value)
+            let expired = self.content.pop_front().unwrap();
+            self.window_state.remove(&expired.payload);
+            self.growth.remove(&expired.payload);
+            self.likelihood.remove(&expired.payload);
+            self.window_state.remove_client_prefix(expired.client_prefix);
+            self.window_state.remove_ttl(expired.ttl);
+            self.window_state.remove_cname_depth(expired.cname_depth);
+            self.window_state.remove_seq_gap(expired.seq_gap);
+
+            drop(expired);
+        }
+
+        // Update window state (accumulators) and subsequently add entry to window buffer
+        self.window_state.add(&record.payload);
+        self.growth.add(&record.payload);
+        self.likelihood.add(&record.payload);
+        self.window_state.add_client_prefix(record.client_prefix);
+        self.window_state.add_ttl(record.ttl);
+        self.window_state.add_cname_depth(record.cname_depth);
+        self.window_state.add_seq_gap(record.seq_gap);
+        self.content.push_back(record);
+        self.n_processed += 1;
+        self.cost.lap(CostStage::Counters, &mut start);
+
+        // Time since the first query to this domain, still in the window until the window is filled
+        let domain_age = ts - *self.first_ts.get_or_insert(ts);
+        let is_first_window = self.n_processed <= self.window_size;
+
+        // Construct features (positions for the label set similarity are record numbers, starting at zero)
+        let mut fv = FixedWindowFeatureVector::from_window_state(id, &self.window_state, &self.length_model, &self.open_space, domain_age as f32, is_first_window);
+        self.cost.lap(CostStage::Vector, &mut start);
+        fv.label_set_similarity = self.similarity.update((self.n_processed - 1) as f64, &self.window_state);
+        self.cost.lap(CostStage::Similarity, &mut start);
+        self.growth.rebalance(&self.content);
+        fv.unique_label_growth = self.growth.get();
+        fv.bigram_log_likelihood = self.likelihood.get();
+        self.cost.lap(CostStage::Counters, &mut start);
+        fv.label_compression_ratio = self.compression.ratio(&self.content);
+        self.cost.lap(CostStage::Compression, &mut start);
+        fv
+    }
+}
+
+/// Window bounded by the number of unique payload bytes (the total length of the unique labels) instead of a duration
+/// or number of queries, which normalizes across domains with very different query rates. The window holds the most
+/// recent queries with at most `size` unique payload bytes, and at least the newest query.
+pub struct ByteWindow {
+    window_size: usize,
+    primary_domain_length: u8,
+    length_model: DnsLengthModel,
+    open_space: f32,
+    content: VecDeque<LogRecord>,
+    window_state: WindowState,
+    growth: UniqueGrowth,
+    compression: LabelCompression,
+    likelihood: WindowLikelihood,
+    cost: CostTracker,
+
+    // Domain age
+    first_ts: Option<f64>,
+    n_processed: usize,
+}
+
+impl ByteWindow {
+    pub fn new(size: usize, primary_domain_length: u8) -> Self {
+        let length_model = DnsLengthModel::default();
+        Self {
+            window_size: size,
+            primary_domain_length,
+            length_model,
+            open_space: length_model.open_space(primary_domain_length),
+            content: VecDeque::new(),
+            window_state: WindowState::new(),
+            growth: UniqueGrowth::default(),
+            compression: LabelCompression::default(),
+            likelihood: WindowLikelihood::default(),
+            cost: CostTracker::default(),
+            first_ts: None,
+            n_processed: 0,
+        }
+    }
+
+    /// Use the given DNS length model for the fill ratio (instead of the default presentation format).
+    pub fn with_length_model(mut self, length_model: DnsLengthModel) -> Self {
+        self.length_model = length_model;
+        self.open_space = length_model.open_space(self.primary_domain_length);
+        self
+    }
+
+    /// Use the given character bigram model for the log-likelihood feature (instead of the embedded model).
+    pub fn with_bigram_model(mut self, model: Arc<BigramModel>) -> Self {
+        self.likelihood = WindowLikelihood::new(model);
+        self
+    }
+
+    /// Track the time spent per stage (window counters, entropy, similarity and other features) in `cost`.
+    pub fn with_cost(mut self, cost: CostTracker) -> Self {
+        self.window_state.cost = cost.clone();
+        self.cost = cost;
+        self
+    }
+
+    /// Continue a domain of which `n_preceding` records, the first at `first_ts`, were processed elsewhere, for the
+    /// domain age features.
+    pub fn resume_after(&mut self, first_ts: f64, n_preceding: usize) {
+        self.first_ts = Some(first_ts);
+        self.n_processed = n_preceding;
+    }
+
+    /// Index of the first record in the window of the last of `queries` (0 if `queries` is empty).
+    pub fn window_start(queries: &[LogRecord], size: usize) -> usize {
+        let mut unique_labels: HashSet<LabelKey> = HashSet::new();
+        let mut n_unique_bytes: usize = 0;
+
+        for (idx, record) in queries.iter().enumerate().rev() {
+            for label in record.payload.labels.iter() {
+                if unique_labels.insert(label_key(label)) { n_unique_bytes += label.len(); }
+            }
+            if n_unique_bytes > size { return (idx + 1).min(queries.len() - 1); }
+        }
+        0
+    }
+
+    pub fn process_entry(&mut self, id: usize, ts: f64, new_entry: DnsPayload) -> ByteWindowFeatureVector {
+        self.process_record(LogRecord { id, ts, payload: new_entry, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    /// Same as `process_entry`, for a complete log record (including client information).
+    pub fn process_record(&mut self, record: LogRecord) -> ByteWindowFeatureVector {
+        let (id, ts) = (record.id, record.ts);
+        let mut start = self.cost.start();
+
+        // Update window state (accumulators) and subsequently add entry to window buffer
+        self.window_state.add(&record.payload);
+        self.growth.add(&record.payload);
+        self.likelihood.add(&record.payload);
+        self.window_state.add_client_prefix(record.client_prefix);
+        self.window_state.add_ttl(record.ttl);
+        self.window_state.add_cname_depth(record.cname_depth);
+        self.window_state.add_seq_gap(record.seq_gap);
+        self.content.push_back(record);
+        self.n_processed += 1;
+
+        // Pop expired until the unique payload bytes fit, keeping the new entry
+        while self.window_state.total_unique_label_len > self.window_size && self.content.len() > 1 {
+            // (unwrap safe here because we know we have a value)
+            let expired = self.content.pop_front().unwrap();
+            self.window_state.remove(&expired.payload);
+            self.growth.remove(&expired.payload);
+            self.likelihood.remove(&expired.payload);
+            self.window_state.remove_client_prefix(expired.client_prefix);
+            self.window_state.remove_ttl(expired.ttl);
+            self.window_state.remove_cname_depth(expired.cname_depth);
+            self.window_state.remove_seq_gap(expired.seq_gap);
+        }
+        self.cost.lap(CostStage::Counters, &mut start);
+
+        // Time since the first query to this domain, still in the window until something expired
+        let domain_age = ts - *self.first_ts.get_or_insert(ts);
+        let is_first_window = self.n_processed == self.content.len();
+
+        // (unwrap safe here because the window holds at least the new entry)
+        let window_duration = ts - self.content.front().unwrap().ts;
+
+        let mut fv = ByteWindowFeatureVector::from_window_state(id, &self.window_state, &self.length_model, &self.open_space, window_duration as f32, domain_age as f32, is_first_window);
+        self.cost.lap(CostStage::Vector, &mut start);
+        self.growth.rebalance(&self.content);
+        fv.unique_label_growth = self.growth.get();
+        fv.bigram_log_likelihood = self.likelihood.get();
+        self.cost.lap(CostStage::Counters, &mut start);
+        fv.label_compression_ratio = self.compression.ratio(&self.content);
+        self.cost.lap(CostStage::Compression, &mut start);
+        fv
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::feature_vector::RateUnit;
+    use crate::feature_extraction::sliding::{ByteWindow, FixedWindow, OutOfOrderPolicy, TimeWindow};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{ClientPrefix, LogRecord};
+
+    fn payload(label: &[u8]) -> DnsPayload {
+        DnsPayload { labels: vec![label.to_vec()], payload_len: label.len() as u8 }
+    }
+
+    fn ids(window: &mut TimeWindow, records: &[(usize, f64)]) -> Vec<usize> {
+        let mut out: Vec<usize> = records.iter()
+            .flat_map(|(id, ts)| window.push(*id, *ts, payload(b"abc")))
+            .map(|fv| fv.id)
+            .collect();
+        out.extend(window.flush().into_iter().map(|fv| fv.id));
+        out
+    }
+
+    #[test]
+    fn test_out_of_order_drop() {
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Drop, 11);
+        assert_eq!(vec![0, 2], ids(&mut window, &[(0, 5.), (1, 4.), (2, 6.)]));
+    }
+
+    #[test]
+    fn test_out_of_order_clamp() {
+        let mut window = TimeWindow::with_policy(1., OutOfOrderPolicy::Clamp, 11);
+        let first = window.push(0, 5., payload(b"abc"));
+        let late = window.push(1, 0., payload(b"def"));
+
+        // Late record is processed at ts=5, so the first record is not expired
+        assert_eq!(1, first[0].n_unique_labels);
+        assert_eq!(2, late[0].n_unique_labels);
+    }
+
+    #[test]
+    fn test_advance_to_closing_aggregate() {
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Clamp, 11);
+        window.push(0, 0., payload(b"abc"));
+        let regular = window.push(1, 5., payload(b"def"));
+        assert!(!regular[0].is_closing_window);
+
+        // Nothing expired yet
+        assert!(window.advance_to(9.).is_empty());
+
+        // First entry expired, closing aggregate only covers the second
+        let closing = window.advance_to(12.);
+        assert_eq!(1, closing.len());
+        assert_eq!(1, closing[0].id);
+        assert_eq!(1, closing[0].n_unique_labels);
+        assert!(closing[0].is_closing_window);
+
+        // Window is empty now, which is reported once
+        let closing = window.advance_to(20.);
+        assert_eq!(1, closing.len());
+        assert_eq!((1, 0), (closing[0].id, closing[0].n_unique_labels));
+        assert!(closing[0].is_closing_window);
+        assert!(window.advance_to(30.).is_empty());
+
+        // A single tick can expire the whole window
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Clamp, 11);
+        window.push(0, 0., payload(b"abc"));
+        let closing = window.advance_to(100.);
+        assert_eq!(1, closing.len());
+        assert_eq!((0, 0), (closing[0].id, closing[0].n_unique_labels));
+    }
+
+    #[test]
+    fn test_advance_to_releases_pending() {
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Reorder(2.), 11);
+        assert!(window.push(0, 5., payload(b"abc")).is_empty());
+        assert_eq!(vec![0], window.advance_to(7.).into_iter().map(|fv| fv.id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rate_unit() {
+        let mut window = TimeWindow::with_policy(120., OutOfOrderPolicy::Clamp, 11).with_rate_unit(RateUnit::Minute);
+        window.push(0, 0., payload(b"abc"));
+        let fv = window.push(1, 1., payload(b"def"));
+        assert_eq!(1., fv[0].unique_query_rate);
+
+        let mut window = TimeWindow::with_policy(120., OutOfOrderPolicy::Clamp, 11).with_rate_unit(RateUnit::Window);
+        window.push(0, 0., payload(b"abc"));
+        let fv = window.push(1, 1., payload(b"def"));
+        assert_eq!((2., 6.), (fv[0].unique_query_rate, fv[0].unique_transfer_rate));
+    }
+
+    #[test]
+    fn test_domain_age() {
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Clamp, 11);
+        let first = window.push(0, 100., payload(b"abc"));
+        let second = window.push(1, 110., payload(b"abc"));
+        let third = window.push(2, 115., payload(b"abc"));
+
+        assert_eq!((0., true), (first[0].domain_age_seconds, first[0].is_first_window));
+        assert_eq!((10., true), (second[0].domain_age_seconds, second[0].is_first_window));
+        assert_eq!((15., false), (third[0].domain_age_seconds, third[0].is_first_window));
+    }
+
+    #[test]
+    fn test_out_of_order_reorder() {
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Reorder(2.), 11);
+
+        // Record 1 is within the allowed lateness, record 4 arrives after record 3 (ts=7) was released
+        assert_eq!(vec![1, 0, 2, 3, 5], ids(&mut window, &[(0, 5.), (1, 4.), (2, 6.), (3, 7.), (5, 10.), (4, 6.5)]));
+    }
+
+    #[test]
+    fn test_unique_client_prefixes() {
+        let record = |id: usize, ts: f64, prefix: u8| LogRecord { id, ts, payload: payload(b"abc"), client_prefix: Some(ClientPrefix::V4([10, 0, prefix])), label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None };
+
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Clamp, 11);
+        assert_eq!(1, window.push_record(record(0, 0., 1))[0].n_unique_client_prefixes);
+        assert_eq!(2, window.push_record(record(1, 5., 2))[0].n_unique_client_prefixes);
+        assert_eq!(2, window.push_record(record(2, 6., 2))[0].n_unique_client_prefixes);
+
+        // First record expired, records without prefix are not counted
+        assert_eq!(1, window.push(3, 12., payload(b"abc"))[0].n_unique_client_prefixes);
+    }
+
+    #[test]
+    fn test_ttl_present() {
+        let record = |id: usize, ts: f64, ttl: Option<u32>| LogRecord { id, ts, payload: payload(b"abc"), client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl, cname_depth: None, seq_gap: None };
+
+        let mut window = TimeWindow::with_policy(10., OutOfOrderPolicy::Clamp, 11);
+        let fv = &window.push_record(record(0, 0., None))[0];
+        assert!(!fv.ttl_present && !fv.cname_depth_present && fv.avg_ttl.is_nan());
+
+        // A TTL of 0 is told apart from no TTL (which is NaN, or zero with `NanPolicy::Zero`)
+        let fv = &window.push_record(record(1, 5., Some(0)))[0];
+        assert_eq!((true, 0.), (fv.ttl_present, fv.avg_ttl));
+
+        let fv = &window.push_record(record(2, 20., None))[0];
+        assert!(!fv.ttl_present);
+    }
+
+    #[test]
+    fn test_fixed_window_unique_query_ratio() {
+        let mut window = FixedWindow::new(2, 11);
+        assert_eq!(1., window.process_entry(0, 0., payload(b"abc")).unique_query_ratio);
+        assert_eq!(0.5, window.process_entry(1, 1., payload(b"abc")).unique_query_ratio);
+
+        // Only the queries in the window count, the first abc expired
+        assert_eq!(1., window.process_entry(2, 2., payload(b"def")).unique_query_ratio);
+        assert_eq!(1., window.process_entry(3, 3., payload(b"abc")).unique_query_ratio);
+    }
+
+    #[test]
+    fn test_byte_window() {
+        let mut window = ByteWindow::new(8, 11);
+        assert_eq!(1, window.process_entry(0, 0., payload(b"abcd")).n_queries);
+
+        // Duplicate payloads do not count towards the size
+        let fv = window.process_entry(1, 1., payload(b"ABCD"));
+        assert_eq!((2, 1, true), (fv.n_queries, fv.n_unique_labels, fv.is_first_window));
+
+        let fv = window.process_entry(2, 3., payload(b"efgh"));
+        assert_eq!((3, 2, 3.), (fv.n_queries, fv.n_unique_labels, fv.window_duration_seconds));
+
+        // Both abcd queries expire
+        let fv = window.process_entry(3, 4., payload(b"ij"));
+        assert_eq!((2, 2, 1., false), (fv.n_queries, fv.n_unique_labels, fv.window_duration_seconds, fv.is_first_window));
+
+        // A payload larger than the window is kept on its own
+        let fv = window.process_entry(4, 5., payload(b"klmnopqrst"));
+        assert_eq!((1, 10), (fv.n_queries, fv.max_label_length as usize));
+    }
+
+    #[test]
+    fn test_byte_window_start() {
+        let records: Vec<LogRecord> = [&b"abcd"[..], b"abcd", b"efgh", b"ij", b"klmnopqrst"].iter().enumerate()
+            .map(|(id, label)| LogRecord { id, ts: id as f64, payload: payload(label), client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+            .collect();
+
+        assert_eq!(0, ByteWindow::window_start(&records[..0], 8));
+        assert_eq!(0, ByteWindow::window_start(&records[..3], 8));
+        assert_eq!(2, ByteWindow::window_start(&records[..4], 8));
+        assert_eq!(4, ByteWindow::window_start(&records, 8));
+    }
+}
@@ -0,0 +1,931 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use counter::Counter;
+
+use crate::feature_extraction::charclass::classify;
+use crate::feature_extraction::cost::{CostStage, CostTracker};
+use crate::feature_extraction::entropy::{estimate_entropy, EntropyEstimates};
+use crate::feature_extraction::feature_vector::{ByteWindowFeatureVector, FixedWindowFeatureVector, TimeWindowFeatureVector};
+use crate::feature_extraction::length::DnsLengthModel;
+use crate::parse_dns::DnsPayload;
+use crate::shared_interface::{ClientPrefix, LogRecord};
+
+/// Key used for the unique query and label counters (64-bit hash, so no label bytes are cloned).
+pub type LabelKey = u64;
+
+/// Hash a single label into a counter key. Keys are case-insensitive, so 0x20 case randomization by resolvers does
+/// not inflate uniqueness counts.
+pub fn label_key(label: &[u8]) -> LabelKey {
+    let mut hasher = DefaultHasher::new();
+    hash_lowercase(label, &mut hasher);
+    hasher.finish()
+}
+
+/// Hash all labels of a query into a (case-insensitive) counter key.
+pub fn query_key(labels: &[Vec<u8>]) -> LabelKey {
+    let mut hasher = DefaultHasher::new();
+    labels.len().hash(&mut hasher);
+    labels.iter().for_each(|label| hash_lowercase(label, &mut hasher));
+    hasher.finish()
+}
+
+fn hash_lowercase<H: Hasher>(label: &[u8], hasher: &mut H) {
+    label.len().hash(hasher);
+    label.iter().for_each(|ch| hasher.write_u8(ch.to_ascii_lowercase()));
+}
+
+/// Number of hashes in a min-hash (bottom-k) signature of a label set.
+pub const MIN_HASH_SIZE: usize = 64;
+
+/// Estimate the Jaccard similarity of two label sets from their bottom-k signatures (exact for sets smaller than k).
+pub fn min_hash_similarity(a: &[LabelKey], b: &[LabelKey]) -> f32 {
+    // Bottom-k of the union, and how many of those are in both sets
+    let (mut i, mut j) = (0, 0);
+    let (mut n_union, mut n_both) = (0, 0);
+    while n_union < MIN_HASH_SIZE && (i < a.len() || j < b.len()) {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x == y => { n_both += 1; i += 1; j += 1; }
+            (Some(x), Some(y)) if x < y => i += 1,
+            (Some(_), None) => i += 1,
+            _ => j += 1,
+        }
+        n_union += 1;
+    }
+    n_both as f32 / n_union as f32
+}
+
+/// Whether a query mixes upper and lower case letters, consistent with 0x20 case randomization.
+pub fn has_mixed_case(labels: &[Vec<u8>]) -> bool {
+    let mut upper = false;
+    let mut lower = false;
+    for ch in labels.iter().flatten() {
+        upper |= ch.is_ascii_uppercase();
+        lower |= ch.is_ascii_lowercase();
+    }
+    upper && lower
+}
+
+pub struct WindowState {
+    // Accumulators
+    pub n_queries: usize,
+    pub unique_queries: Counter<LabelKey>,
+    pub n_labels: usize,
+    pub unique_labels: Counter<LabelKey>,
+    /// Keys of the unique labels in order, for min-hash signatures (label keys are uniformly distributed hashes).
+    unique_label_order: BTreeSet<LabelKey>,
+    pub total_label_len: usize,
+    pub total_unique_label_len: usize,
+    pub max_label_len: usize,
+    pub n_mixed_case_queries: usize,
+    pub unique_client_prefixes: Counter<ClientPrefix>,
+
+    // Response TTLs (exact integer sums, so removing values does not accumulate rounding errors)
+    ttl_counts: BTreeMap<u32, usize>,
+    n_ttls: usize,
+    ttl_sum: u64,
+    ttl_sq_sum: u128,
+
+    // CNAME chain lengths
+    n_cname_depths: usize,
+    cname_depth_sum: usize,
+
+    // Sequence number gaps
+    n_seqs: usize,
+    n_seq_gaps: usize,
+    seq_missing_sum: u64,
+
+    // Number of unique labels per label length (at most 63 bytes in DNS, but longer labels of lenient or wire-format
+    // input and library callers grow the histogram)
+    unique_label_len_hist: Vec<usize>,
+
+    // Entropy
+    char_map: BTreeMap<u8, usize>,
+    ascii_map: [usize; 128],
+
+    /// Time spent on entropy estimates, if tracking.
+    pub cost: CostTracker,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl WindowState {
+    pub fn new() -> Self {
+        WindowState {
+            n_queries: 0,
+            n_labels: 0,
+            total_label_len: 0,
+            total_unique_label_len: 0,
+            max_label_len: 0,
+            n_mixed_case_queries: 0,
+            unique_label_len_hist: vec![0; 64],
+
+            unique_queries: Counter::new(),
+            unique_labels: Counter::new(),
+            unique_label_order: BTreeSet::new(),
+            unique_client_prefixes: Counter::new(),
+
+            ttl_counts: BTreeMap::new(),
+            n_ttls: 0,
+            ttl_sum: 0,
+            ttl_sq_sum: 0,
+
+            n_cname_depths: 0,
+            cname_depth_sum: 0,
+
+            n_seqs: 0,
+            n_seq_gaps: 0,
+            seq_missing_sum: 0,
+
+            // Entropy
+            char_map: BTreeMap::new(),
+            ascii_map: [0; 128],
+
+            cost: CostTracker::default(),
+        }
+    }
+
+    pub fn add(&mut self, entry: &DnsPayload) {
+        self.n_queries += 1;
+        if has_mixed_case(&entry.labels) { self.n_mixed_case_queries += 1; }
+
+        // Update unique query counter
+        *self.unique_queries.entry(query_key(&entry.labels)).or_insert(0) += 1;
+
+        // Update accumulators
+        self.n_labels += entry.labels.len();
+
+        for label in entry.labels.iter() {
+            self.total_label_len += label.len();
+
+            // Update unique label counter, total unique label length and unique label length histogram
+            let key = label_key(label);
+            let count = self.unique_labels.entry(key).or_insert(0);
+            if *count == 0 {
+                self.unique_label_order.insert(key);
+                self.total_unique_label_len += label.len();
+                if label.len() >= self.unique_label_len_hist.len() {
+                    self.unique_label_len_hist.resize(label.len() + 1, 0);
+                }
+                self.unique_label_len_hist[label.len()] += 1;
+            }
+            *count += 1;
+
+            // Update max label length
+            if label.len() > self.max_label_len {
+                self.max_label_len = label.len();
+            }
+
+            // Update entropy (fast path if all characters are in the ASCII map)
+            if classify(label).is_hostname() {
+                label.iter().for_each(|&ch| self.ascii_map[ch as usize] += 1);
+                continue;
+            }
+            for ch in label.iter() {
+                match ch {
+                    b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'-' | b'_' => self.ascii_map[*ch as usize] += 1,
+                    _ => *self.char_map.entry(*ch).or_insert(0) += 1
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, removed: &DnsPayload) {
+        self.n_queries -= 1;
+        if has_mixed_case(&removed.labels) { self.n_mixed_case_queries -= 1; }
+
+        // Update unique query counter
+        let key = query_key(&removed.labels);
+        if let Some(entry) = self.unique_queries.get_mut(&key) {
+            if *entry <= 1 {
+                self.unique_queries.remove(&key);
+            } else {
+                *entry -= 1;
+            }
+        }
+
+        // Update accumulators
+        self.n_labels -= removed.labels.len();
+
+        for label in removed.labels.iter() {
+            self.total_label_len -= label.len();
+
+            // Below adapted from Counter.subtract (crate)
+            let key = label_key(label);
+            if let Some(entry) = self.unique_labels.get_mut(&key) {
+                if *entry <= 1 {
+                    self.unique_labels.remove(&key);
+                    self.unique_label_order.remove(&key);
+                    self.total_unique_label_len -= label.len();
+                    self.unique_label_len_hist[label.len()] -= 1;
+                } else {
+                    *entry -= 1;
+                }
+            }
+
+            // Update entropy (fast path if all characters are in the ASCII map)
+            if classify(label).is_hostname() {
+                label.iter().for_each(|&ch| self.ascii_map[ch as usize] -= 1);
+                continue;
+            }
+            for ch in label.iter() {
+                match ch {
+                    b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'-' | b'_' => self.ascii_map[*ch as usize] -= 1,
+                    _ => { *self.char_map.entry(*ch).or_insert(0) -= 1; }
+                }
+            }
+        }
+
+        // Find new maximum label length by walking down the histogram (at most 64 steps for DNS labels)
+        while self.max_label_len > 0 && self.unique_label_len_hist[self.max_label_len] == 0 {
+            self.max_label_len -= 1;
+        }
+    }
+
+    pub fn add_client_prefix(&mut self, prefix: Option<ClientPrefix>) {
+        if let Some(prefix) = prefix {
+            *self.unique_client_prefixes.entry(prefix).or_insert(0) += 1;
+        }
+    }
+
+    pub fn remove_client_prefix(&mut self, prefix: Option<ClientPrefix>) {
+        if let Some(prefix) = prefix {
+            if let Some(entry) = self.unique_client_prefixes.get_mut(&prefix) {
+                if *entry <= 1 {
+                    self.unique_client_prefixes.remove(&prefix);
+                } else {
+                    *entry -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn add_ttl(&mut self, ttl: Option<u32>) {
+        if let Some(ttl) = ttl {
+            *self.ttl_counts.entry(ttl).or_insert(0) += 1;
+            self.n_ttls += 1;
+            self.ttl_sum += ttl as u64;
+            self.ttl_sq_sum += ttl as u128 * ttl as u128;
+        }
+    }
+
+    pub fn remove_ttl(&mut self, ttl: Option<u32>) {
+        if let Some(ttl) = ttl {
+            if let Some(entry) = self.ttl_counts.get_mut(&ttl) {
+                if *entry <= 1 {
+                    self.ttl_counts.remove(&ttl);
+                } else {
+                    *entry -= 1;
+                }
+                self.n_ttls -= 1;
+                self.ttl_sum -= ttl as u64;
+                self.ttl_sq_sum -= ttl as u128 * ttl as u128;
+            }
+        }
+    }
+
+    /// Average, minimum and (population) variance of the response TTLs in the window, NaN without TTLs.
+    pub fn get_ttl_stats(&self) -> (f32, f32, f32) {
+        if self.n_ttls == 0 { return (f32::NAN, f32::NAN, f32::NAN); }
+
+        let n = self.n_ttls as f64;
+        let avg = self.ttl_sum as f64 / n;
+        let variance = (self.ttl_sq_sum as f64 / n - avg * avg).max(0.);
+        let min = self.ttl_counts.keys().next().copied().unwrap_or_default();
+        (avg as f32, min as f32, variance as f32)
+    }
+
+    pub fn add_cname_depth(&mut self, depth: Option<u8>) {
+        if let Some(depth) = depth {
+            self.n_cname_depths += 1;
+            self.cname_depth_sum += depth as usize;
+        }
+    }
+
+    pub fn remove_cname_depth(&mut self, depth: Option<u8>) {
+        if let Some(depth) = depth {
+            // Ignore depths that were never added (as `remove_ttl` does), instead of underflowing
+            if self.n_cname_depths == 0 || self.cname_depth_sum < depth as usize { return; }
+            self.n_cname_depths -= 1;
+            self.cname_depth_sum -= depth as usize;
+        }
+    }
+
+    /// Average CNAME chain length of the responses in the window, NaN without CNAME information.
+    pub fn get_avg_cname_depth(&self) -> f32 {
+        self.cname_depth_sum as f32 / self.n_cname_depths as f32
+    }
+
+    pub fn add_seq_gap(&mut self, gap: Option<u32>) {
+        if let Some(gap) = gap {
+            self.n_seqs += 1;
+            self.n_seq_gaps += (gap > 0) as usize;
+            self.seq_missing_sum += gap as u64;
+        }
+    }
+
+    pub fn remove_seq_gap(&mut self, gap: Option<u32>) {
+        if let Some(gap) = gap {
+            self.n_seqs -= 1;
+            self.n_seq_gaps -= (gap > 0) as usize;
+            self.seq_missing_sum -= gap as u64;
+        }
+    }
+
+    /// Estimated fraction of records dropped by the measurement: missing sequence numbers relative to the missing and
+    /// observed ones, NaN without sequence information.
+    pub fn get_seq_drop_rate(&self) -> f32 {
+        (self.seq_missing_sum as f64 / (self.seq_missing_sum + self.n_seqs as u64) as f64) as f32
+    }
+
+    /// Fraction of the records with a sequence number that follow a gap, NaN without sequence information.
+    pub fn get_seq_gap_ratio(&self) -> f32 {
+        self.n_seq_gaps as f32 / self.n_seqs as f32
+    }
+
+    /// Quantile `q` (0..1) of the unique label lengths in the window (nearest rank), NaN for an empty window.
+    pub fn get_unique_label_len_quantile(&self, q: f32) -> f32 {
+        let n_unique: usize = self.unique_label_len_hist.iter().sum();
+        if n_unique == 0 { return f32::NAN; }
+
+        let rank = ((q * n_unique as f32).ceil() as usize).max(1);
+        let mut cumulative = 0;
+        for (len, count) in self.unique_label_len_hist.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank { return len as f32; }
+        }
+        self.max_label_len as f32
+    }
+
+    /// Min-hash (bottom-k) signature of the unique labels in the window, in ascending order.
+    pub fn get_min_hash_signature(&self) -> Vec<LabelKey> {
+        self.unique_label_order.iter().take(MIN_HASH_SIZE).copied().collect()
+    }
+
+    pub fn get_entropy(&self) -> f32 {
+        self.get_entropy_estimates().shannon
+    }
+
+    pub fn get_entropy_estimates(&self) -> EntropyEstimates {
+        self.cost.time(CostStage::Entropy, || estimate_entropy(self.char_map.values().chain(self.ascii_map.iter()).copied(), self.total_label_len))
+    }
+}
+
+/// Similarity of the label set of the current window to that of an earlier, non-overlapping window of the same length.
+///
+/// Positions (timestamps or record numbers) are divided into buckets of one window length. The earlier window is the
+/// window at the last record of the bucket two before the current one, of which only the min-hash signature is kept.
+pub struct WindowSimilarity {
+    window_size: f64,
+    /// Signature at the last record of recent buckets (at most three, the last is the current bucket).
+    snapshots: VecDeque<(i64, Vec<LabelKey>)>,
+}
+
+impl WindowSimilarity {
+    pub fn new(window_size: f64) -> Self {
+        WindowSimilarity { window_size, snapshots: VecDeque::new() }
+    }
+
+    /// Bucket of a position, also used to determine the records the similarity depends on.
+    pub fn bucket(position: f64, window_size: f64) -> i64 {
+        (position / window_size).floor() as i64
+    }
+
+    /// Update with the window state after the record at `position`, returns the estimated Jaccard similarity to the
+    /// earlier window (NaN if the domain had no records in that bucket).
+    pub fn update(&mut self, position: f64, ws: &WindowState) -> f32 {
+        let bucket = Self::bucket(position, self.window_size);
+        let signature = ws.get_min_hash_signature();
+
+        match self.snapshots.back_mut() {
+            Some((last, snapshot)) if *last >= bucket => *snapshot = signature.clone(),
+            _ => self.snapshots.push_back((bucket, signature.clone())),
+        }
+        while self.snapshots.front().is_some_and(|(earlier, _)| *earlier < bucket - 2) {
+            self.snapshots.pop_front();
+        }
+
+        match self.snapshots.front() {
+            Some((earlier, snapshot)) if *earlier == bucket - 2 => min_hash_similarity(&signature, snapshot),
+            _ => f32::NAN,
+        }
+    }
+}
+
+/// Growth of the unique labels over the window: the number of labels in the newer half of the window (by query count)
+/// that are not in the older half, relative to the number of unique labels in the older half.
+///
+/// Steady generation of new labels (e.g. a tunnel) gives about 1, a stable label set about 0 and a ramp-up more than 1.
+/// NaN while the older half has no labels.
+#[derive(Default)]
+pub struct UniqueGrowth {
+    /// Label key <--> number of occurrences in the (older, newer) half.
+    counts: HashMap<LabelKey, (usize, usize)>,
+    /// Number of queries in the older half, which are the first of the window.
+    n_older_queries: usize,
+    n_older_unique: usize,
+    /// Number of unique labels in the newer half that are not in the older half.
+    n_newer_only: usize,
+}
+
+impl UniqueGrowth {
+    /// Add the newest query of the window.
+    pub fn add(&mut self, payload: &DnsPayload) {
+        payload.labels.iter().for_each(|label| self.update(label_key(label), false, true));
+    }
+
+    /// Remove the oldest query of the window.
+    pub fn remove(&mut self, payload: &DnsPayload) {
+        let older = self.n_older_queries > 0;
+        if older { self.n_older_queries -= 1; }
+        payload.labels.iter().for_each(|label| self.update(label_key(label), older, false));
+    }
+
+    /// Move queries from the newer to the older half until the older half holds the oldest half of `content` (the
+    /// records in the window).
+    pub fn rebalance(&mut self, content: &VecDeque<LogRecord>) {
+        while self.n_older_queries < content.len() / 2 {
+            for label in content[self.n_older_queries].payload.labels.iter() {
+                let key = label_key(label);
+                self.update(key, false, false);
+                self.update(key, true, true);
+            }
+            self.n_older_queries += 1;
+        }
+    }
+
+    pub fn get(&self) -> f32 {
+        if self.n_older_unique == 0 { return f32::NAN; }
+        self.n_newer_only as f32 / self.n_older_unique as f32
+    }
+
+    /// Add (or remove) an occurrence of a label in the older (or newer) half.
+    fn update(&mut self, key: LabelKey, older: bool, add: bool) {
+        let (n_older, n_newer) = self.counts.entry(key).or_default();
+        let (count, other) = if older { (n_older, *n_newer) } else { (n_newer, *n_older) };
+
+        let was_present = *count > 0;
+        if add { *count += 1; } else { *count -= 1; }
+        let is_present = *count > 0;
+
+        if was_present != is_present {
+            match (older, is_present) {
+                (true, true) => { self.n_older_unique += 1; if other > 0 { self.n_newer_only -= 1; } }
+                (true, false) => { self.n_older_unique -= 1; if other > 0 { self.n_newer_only += 1; } }
+                (false, true) => if other == 0 { self.n_newer_only += 1; },
+                (false, false) => if other == 0 { self.n_newer_only -= 1; },
+            }
+        }
+        if self.counts[&key] == (0, 0) { self.counts.remove(&key); }
+    }
+}
+
+impl TimeWindowFeatureVector {
+    pub fn from_window_state(id: usize, ws: &WindowState, length_model: &DnsLengthModel, open_space: &f32, rate_duration: &f32, domain_age_seconds: f32, is_first_window: bool) -> Self {
+        let n_unique_queries: f32 = ws.unique_queries.len() as f32;
+        let n_unique_labels: usize = ws.unique_labels.len();
+        let unique_fill_ratio: f32 = length_model.used_space(ws.total_unique_label_len, n_unique_labels) as f32 / (open_space * n_unique_queries);
+
+        let entropy = ws.get_entropy_estimates();
+
+        let unique_query_rate = n_unique_queries / rate_duration;
+        let unique_transfer_rate = ws.total_unique_label_len as f32 / rate_duration;
+
+        let avg_unique_label_length = ws.total_unique_label_len as f32 / n_unique_labels as f32;
+        let max_label_length = ws.max_label_len as u8;
+        let unique_query_ratio = n_unique_queries / ws.n_queries as f32;
+        let case_randomization_ratio = ws.n_mixed_case_queries as f32 / ws.n_queries as f32;
+        let (avg_ttl, min_ttl, ttl_variance) = ws.get_ttl_stats();
+
+        // Return new feature vector
+        TimeWindowFeatureVector {
+            id,
+            n_unique_labels,
+            unique_query_rate,
+            entropy: entropy.shannon,
+            entropy_normalized: entropy.normalized,
+            renyi2_entropy: entropy.renyi2,
+            min_entropy: entropy.min,
+            // (set by the window, see `LabelCompression`)
+            label_compression_ratio: f32::NAN,
+            // (set by the window, see `WindowLikelihood`)
+            bigram_log_likelihood: f32::NAN,
+            unique_transfer_rate,
+            avg_unique_label_length,
+            unique_fill_ratio,
+            max_label_length,
+            label_len_p50: ws.get_unique_label_len_quantile(0.5),
+            label_len_p90: ws.get_unique_label_len_quantile(0.9),
+            unique_query_ratio,
+            case_randomization_ratio,
+            n_unique_client_prefixes: ws.unique_client_prefixes.len(),
+            ttl_present: ws.n_ttls > 0,
+            avg_ttl,
+            min_ttl,
+            ttl_variance,
+            cname_depth_present: ws.n_cname_depths > 0,
+            avg_cname_depth: ws.get_avg_cname_depth(),
+            seq_drop_rate: ws.get_seq_drop_rate(),
+            seq_gap_ratio: ws.get_seq_gap_ratio(),
+            // (set by the window, see `WindowSimilarity`)
+            label_set_similarity: f32::NAN,
+            // (set by the window, see `UniqueGrowth`)
+            unique_label_growth: f32::NAN,
+            domain_age_seconds,
+            is_first_window,
+            is_closing_window: false,
+        }
+    }
+}
+
+impl FixedWindowFeatureVector {
+    pub fn from_window_state(id: usize, ws: &WindowState, length_model: &DnsLengthModel, open_space: &f32, domain_age_seconds: f32, is_first_window: bool) -> Self {
+        let n_unique_queries: f32 = ws.unique_queries.len() as f32;
+        let n_unique_labels: usize = ws.unique_labels.len();
+        let unique_fill_ratio: f32 = length_model.used_space(ws.total_unique_label_len, n_unique_labels) as f32 / (open_space * n_unique_queries);
+
+        let entropy = ws.get_entropy_estimates();
+
+        let avg_unique_label_length = ws.total_unique_label_len as f32 / n_unique_labels as f32;
+        let max_label_length = ws.max_label_len as u8;
+        let unique_query_ratio = n_unique_queries / ws.n_queries as f32;
+        let case_randomization_ratio = ws.n_mixed_case_queries as f32 / ws.n_queries as f32;
+        let (avg_ttl, min_ttl, ttl_variance) = ws.get_ttl_stats();
+
+        // Return new feature vector
+        FixedWindowFeatureVector {
+            id,
+            n_unique_labels,
+            entropy: entropy.shannon,
+            entropy_normalized: entropy.normalized,
+            renyi2_entropy: entropy.renyi2,
+            min_entropy: entropy.min,
+            // (set by the window, see `LabelCompression`)
+            label_compression_ratio: f32::NAN,
+            // (set by the window, see `WindowLikelihood`)
+            bigram_log_likelihood: f32::NAN,
+            avg_unique_label_length,
+            unique_fill_ratio,
+            max_label_length,
+            label_len_p50: ws.get_unique_label_len_quantile(0.5),
+            label_len_p90: ws.get_unique_label_len_quantile(0.9),
+            unique_query_ratio,
+            case_randomization_ratio,
+            n_unique_client_prefixes: ws.unique_client_prefixes.len(),
+            ttl_present: ws.n_ttls > 0,
+            avg_ttl,
+            min_ttl,
+            ttl_variance,
+            cname_depth_present: ws.n_cname_depths > 0,
+            avg_cname_depth: ws.get_avg_cname_depth(),
+            seq_drop_rate: ws.get_seq_drop_rate(),
+            seq_gap_ratio: ws.get_seq_gap_ratio(),
+            // (set by the window, see `WindowSimilarity`)
+            label_set_similarity: f32::NAN,
+            // (set by the window, see `UniqueGrowth`)
+            unique_label_growth: f32::NAN,
+            domain_age_seconds,
+            is_first_window,
+        }
+    }
+}
+
+impl ByteWindowFeatureVector {
+    pub fn from_window_state(id: usize, ws: &WindowState, length_model: &DnsLengthModel, open_space: &f32, window_duration_seconds: f32, domain_age_seconds: f32, is_first_window: bool) -> Self {
+        let n_unique_queries: f32 = ws.unique_queries.len() as f32;
+        let n_unique_labels: usize = ws.unique_labels.len();
+        let unique_fill_ratio: f32 = length_model.used_space(ws.total_unique_label_len, n_unique_labels) as f32 / (open_space * n_unique_queries);
+
+        let entropy = ws.get_entropy_estimates();
+
+        let avg_unique_label_length = ws.total_unique_label_len as f32 / n_unique_labels as f32;
+        let max_label_length = ws.max_label_len as u8;
+        let unique_query_ratio = n_unique_queries / ws.n_queries as f32;
+        let case_randomization_ratio = ws.n_mixed_case_queries as f32 / ws.n_queries as f32;
+        let (avg_ttl, min_ttl, ttl_variance) = ws.get_ttl_stats();
+
+        // Return new feature vector
+        ByteWindowFeatureVector {
+            id,
+            n_queries: ws.n_queries,
+            n_unique_labels,
+            entropy: entropy.shannon,
+            entropy_normalized: entropy.normalized,
+            renyi2_entropy: entropy.renyi2,
+            min_entropy: entropy.min,
+            // (set by the window, see `LabelCompression`)
+            label_compression_ratio: f32::NAN,
+            // (set by the window, see `WindowLikelihood`)
+            bigram_log_likelihood: f32::NAN,
+            avg_unique_label_length,
+            unique_fill_ratio,
+            max_label_length,
+            label_len_p50: ws.get_unique_label_len_quantile(0.5),
+            label_len_p90: ws.get_unique_label_len_quantile(0.9),
+            unique_query_ratio,
+            case_randomization_ratio,
+            n_unique_client_prefixes: ws.unique_client_prefixes.len(),
+            ttl_present: ws.n_ttls > 0,
+            avg_ttl,
+            min_ttl,
+            ttl_variance,
+            cname_depth_present: ws.n_cname_depths > 0,
+            avg_cname_depth: ws.get_avg_cname_depth(),
+            seq_drop_rate: ws.get_seq_drop_rate(),
+            seq_gap_ratio: ws.get_seq_gap_ratio(),
+            // (set by the window, see `UniqueGrowth`)
+            unique_label_growth: f32::NAN,
+            window_duration_seconds,
+            domain_age_seconds,
+            is_first_window,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::feature_extraction::state::{has_mixed_case, label_key, min_hash_similarity, query_key, UniqueGrowth, WindowSimilarity, WindowState};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn payload(labels: &[&[u8]]) -> DnsPayload {
+        let labels: Vec<Vec<u8>> = labels.iter().map(|l| l.to_vec()).collect();
+        let payload_len = labels.iter().map(|l| l.len()).sum::<usize>() as u8;
+        DnsPayload { labels, payload_len }
+    }
+
+    #[test]
+    fn test_max_label_len_after_remove() {
+        let long = payload(&[&[b'a'; 63]]);
+        let medium = payload(&[&[b'b'; 20], b"c"]);
+        let short = payload(&[b"ddd"]);
+
+        let mut ws = WindowState::new();
+        ws.add(&long);
+        ws.add(&medium);
+        ws.add(&short);
+        assert_eq!(63, ws.max_label_len);
+
+        ws.remove(&long);
+        assert_eq!(20, ws.max_label_len);
+
+        ws.remove(&medium);
+        assert_eq!(3, ws.max_label_len);
+
+        ws.remove(&short);
+        assert_eq!(0, ws.max_label_len);
+    }
+
+    #[test]
+    fn test_max_label_len_duplicate_labels() {
+        let long = payload(&[&[b'a'; 40]]);
+
+        let mut ws = WindowState::new();
+        ws.add(&long);
+        ws.add(&long);
+        ws.add(&payload(&[b"ab"]));
+
+        // Label is still present once, so maximum should not change
+        ws.remove(&long);
+        assert_eq!(40, ws.max_label_len);
+
+        ws.remove(&long);
+        assert_eq!(2, ws.max_label_len);
+    }
+
+    #[test]
+    fn test_label_len_quantiles() {
+        let mut ws = WindowState::new();
+        assert!(ws.get_unique_label_len_quantile(0.5).is_nan());
+
+        // Bimodal: short control labels and long data labels
+        for label in &[&b"a"[..], b"b", b"c", b"d", b"e", b"f", b"g", b"h", &[b'x'; 50], &[b'y'; 60]] {
+            ws.add(&payload(&[label]));
+        }
+        assert_eq!((1., 50.), (ws.get_unique_label_len_quantile(0.5), ws.get_unique_label_len_quantile(0.9)));
+
+        // Duplicate labels are counted once
+        ws.add(&payload(&[b"a"]));
+        assert_eq!(1., ws.get_unique_label_len_quantile(0.5));
+        assert_eq!(60., ws.get_unique_label_len_quantile(1.));
+    }
+
+    #[test]
+    fn test_labels_longer_than_dns_limit() {
+        // Lenient or wire-format input and library callers may pass labels of 64 bytes or more
+        let mut ws = WindowState::new();
+        let (long, longer) = (payload(&[&[b'x'; 64]]), payload(&[&[b'y'; 100]]));
+        ws.add(&long);
+        ws.add(&longer);
+        assert_eq!((100, 100.), (ws.max_label_len, ws.get_unique_label_len_quantile(1.)));
+
+        ws.remove(&longer);
+        assert_eq!((64, 64.), (ws.max_label_len, ws.get_unique_label_len_quantile(1.)));
+        ws.remove(&long);
+        assert_eq!(0, ws.max_label_len);
+    }
+
+    #[test]
+    fn test_min_hash_similarity() {
+        let signature = |labels: &[&[u8]]| {
+            let mut ws = WindowState::new();
+            labels.iter().for_each(|label| ws.add(&payload(&[label])));
+            ws.get_min_hash_signature()
+        };
+
+        // Exact for small sets: {a, b, c} and {b, c, d}
+        assert_eq!(0.5, min_hash_similarity(&signature(&[b"a", b"b", b"c"]), &signature(&[b"b", b"c", b"d"])));
+        assert_eq!(1., min_hash_similarity(&signature(&[b"a", b"A"]), &signature(&[b"a"])));
+
+        // Estimate for large sets: 1000 shared labels of 2000
+        let labels: Vec<Vec<u8>> = (0..3000).map(|i| format!("l{}", i).into_bytes()).collect();
+        let refs: Vec<&[u8]> = labels.iter().map(|l| l.as_slice()).collect();
+        let estimate = min_hash_similarity(&signature(&refs[..2000]), &signature(&refs[1000..]));
+        assert!((estimate - 1. / 3.).abs() < 0.15, "{}", estimate);
+    }
+
+    #[test]
+    fn test_window_similarity() {
+        let mut similarity = WindowSimilarity::new(10.);
+        let mut ws = WindowState::new();
+        ws.add(&payload(&[b"abc"]));
+
+        // No earlier window yet
+        assert!(similarity.update(0., &ws).is_nan());
+        assert!(similarity.update(15., &ws).is_nan());
+
+        // Same labels as two buckets ago
+        assert_eq!(1., similarity.update(25., &ws));
+
+        ws.remove(&payload(&[b"abc"]));
+        ws.add(&payload(&[b"def"]));
+        assert_eq!(0., similarity.update(29., &ws));
+
+        // Gap: nothing two buckets before
+        assert!(similarity.update(55., &ws).is_nan());
+    }
+
+    #[test]
+    fn test_entropy_after_remove() {
+        let mixed = payload(&[b"abcd"]);
+        let single = payload(&[b"zzzz"]);
+
+        let mut ws = WindowState::new();
+        ws.add(&mixed);
+        ws.add(&single);
+        ws.remove(&mixed);
+
+        let entropy = ws.get_entropy_estimates();
+        assert_eq!(1, entropy.n_symbols);
+        assert_eq!(0., entropy.shannon);
+    }
+
+    #[test]
+    fn test_entropy_after_remove_binary_labels() {
+        // Labels with bytes outside the hostname alphabet take the slow path, which counts their hostname characters
+        // in the ASCII map as well; removal must decrement them (baseline incremented them again)
+        let (binary, other) = (payload(&[b"ab\xffc"]), payload(&[b"x y"]));
+
+        let mut ws = WindowState::new();
+        ws.add(&binary);
+        ws.add(&other);
+        ws.remove(&binary);
+
+        let mut expected = WindowState::new();
+        expected.add(&other);
+        assert_eq!(expected.get_entropy_estimates(), ws.get_entropy_estimates());
+
+        ws.remove(&other);
+        assert!(ws.ascii_map.iter().all(|&count| count == 0));
+        assert!(ws.char_map.values().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn test_case_insensitive_keys() {
+        assert_eq!(label_key(b"AbC"), label_key(b"abc"));
+        assert_eq!(query_key(&[b"aBc".to_vec(), b"D".to_vec()]), query_key(&[b"abc".to_vec(), b"d".to_vec()]));
+        assert_ne!(query_key(&[b"ab".to_vec(), b"c".to_vec()]), query_key(&[b"a".to_vec(), b"bc".to_vec()]));
+    }
+
+    #[test]
+    fn test_remove_unique_queries() {
+        // Expired queries leave the unique query counter, a query is unique as long as any of its copies is in the window
+        let mut ws = WindowState::new();
+        ws.add(&payload(&[b"a"]));
+        ws.add(&payload(&[b"A"]));
+        ws.add(&payload(&[b"b"]));
+        assert_eq!(2, ws.unique_queries.len());
+
+        ws.remove(&payload(&[b"a"]));
+        assert_eq!(2, ws.unique_queries.len());
+        ws.remove(&payload(&[b"A"]));
+        assert_eq!(1, ws.unique_queries.len());
+    }
+
+    #[test]
+    fn test_mixed_case_queries() {
+        assert!(has_mixed_case(&[b"ABC".to_vec(), b"def".to_vec()]));
+        assert!(!has_mixed_case(&[b"ABC".to_vec(), b"123".to_vec()]));
+
+        let randomized = payload(&[b"aBcD"]);
+        let mut ws = WindowState::new();
+        ws.add(&randomized);
+        ws.add(&payload(&[b"abcd"]));
+        assert_eq!(1, ws.unique_queries.len());
+        assert_eq!(1, ws.n_mixed_case_queries);
+
+        ws.remove(&randomized);
+        assert_eq!(0, ws.n_mixed_case_queries);
+    }
+
+    #[test]
+    fn test_ttl_stats() {
+        let mut ws = WindowState::new();
+        assert!(ws.get_ttl_stats().0.is_nan());
+
+        ws.add_ttl(Some(0));
+        ws.add_ttl(None);
+        ws.add_ttl(Some(300));
+        ws.add_ttl(Some(300));
+        assert_eq!((200., 0., 20000.), ws.get_ttl_stats());
+
+        ws.remove_ttl(Some(0));
+        assert_eq!((300., 300., 0.), ws.get_ttl_stats());
+    }
+
+    #[test]
+    fn test_avg_cname_depth() {
+        let mut ws = WindowState::new();
+        assert!(ws.get_avg_cname_depth().is_nan());
+
+        ws.add_cname_depth(Some(0));
+        ws.add_cname_depth(Some(3));
+        ws.add_cname_depth(None);
+        assert_eq!(1.5, ws.get_avg_cname_depth());
+
+        ws.remove_cname_depth(Some(0));
+        assert_eq!(3., ws.get_avg_cname_depth());
+
+        // Removing depths that were never added does not underflow
+        ws.remove_cname_depth(Some(3));
+        ws.remove_cname_depth(Some(3));
+        assert!(ws.get_avg_cname_depth().is_nan());
+    }
+
+    #[test]
+    fn test_seq_gaps() {
+        let mut ws = WindowState::new();
+        assert!(ws.get_seq_drop_rate().is_nan() && ws.get_seq_gap_ratio().is_nan());
+
+        ws.add_seq_gap(Some(0));
+        ws.add_seq_gap(Some(2));
+        ws.add_seq_gap(Some(0));
+        ws.add_seq_gap(None);
+        assert_eq!((0.4, 1. / 3.), (ws.get_seq_drop_rate(), ws.get_seq_gap_ratio()));
+
+        ws.remove_seq_gap(Some(2));
+        assert_eq!((0., 0.), (ws.get_seq_drop_rate(), ws.get_seq_gap_ratio()));
+    }
+
+    #[test]
+    fn test_unique_growth() {
+        let record = |id, labels: &[&[u8]]| LogRecord { id, ts: id as f64, payload: payload(labels), client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None };
+        let mut content = VecDeque::new();
+        let mut growth = UniqueGrowth::default();
+        let push = |growth: &mut UniqueGrowth, content: &mut VecDeque<LogRecord>, record: LogRecord| {
+            growth.add(&record.payload);
+            content.push_back(record);
+            growth.rebalance(content);
+            growth.get()
+        };
+
+        // No older half yet
+        assert!(push(&mut growth, &mut content, record(0, &[b"a", b"b"])).is_nan());
+
+        // Older half {a, b}, newer half {a}, then {a, b, c} and finally {b, c, C, d} (case-insensitive)
+        assert_eq!(0., push(&mut growth, &mut content, record(1, &[b"a"])));
+        assert_eq!(0.5, push(&mut growth, &mut content, record(2, &[b"b", b"c"])));
+        assert_eq!(1., push(&mut growth, &mut content, record(3, &[b"C", b"d"])));
+
+        // Expire the oldest query and move the next one to the older half: {a, b, c} and {c, d, e}
+        growth.remove(&content.pop_front().unwrap().payload);
+        assert_eq!(2. / 3., push(&mut growth, &mut content, record(4, &[b"e"])));
+
+        // Removing from the newer half when the older half is empty
+        let mut growth = UniqueGrowth::default();
+        growth.add(&payload(&[b"a"]));
+        growth.remove(&payload(&[b"a"]));
+        growth.rebalance(&VecDeque::new());
+        assert!(growth.get().is_nan());
+        assert!(growth.counts.is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::feature_extraction::decay::DecayWindow;
+use crate::feature_extraction::ExtractOpts;
+use crate::feature_extraction::feature_vector::FeatureVector;
+use crate::feature_extraction::payload::PayloadExtractor;
+use crate::feature_extraction::sliding::{ByteWindow, FixedWindow, TimeWindow};
+use crate::shared_interface::LogRecord;
+
+/// Feature extractor for a single primary domain that processes one record at a time.
+///
+/// Records are expected in timestamp order; out-of-order records in time windows are handled according to
+/// `ExtractOpts::out_of_order`. The batch extraction in `extract_features_per_domain`
+/// is built on top of this type, so both produce identical feature vectors (before `ExtractOpts::nan_policy` is applied,
+/// see `FeatureVector::apply_nan_policy`).
+pub enum StreamingExtractor {
+    Payload(PayloadExtractor),
+    Time(TimeWindow),
+    Fixed(FixedWindow),
+    Bytes(ByteWindow),
+    Decay(Box<DecayWindow>),
+}
+
+impl StreamingExtractor {
+    /// Create an extractor for the feature type selected in `opts` (payload > fixed > bytes > decay > time), payload
+    /// features if none is selected.
+    pub fn new(opts: &ExtractOpts, primary_domain_length: u8) -> Self {
+        let payload = || StreamingExtractor::Payload(PayloadExtractor::new(primary_domain_length).with_length_model(opts.length_model).with_bigram_model(opts.bigram_model.clone()).with_cost(opts.cost.clone()));
+        if opts.payload {
+            return payload();
+        }
+
+        if let Some(size) = opts.fixed {
+            return StreamingExtractor::Fixed(FixedWindow::new(size, primary_domain_length).with_length_model(opts.length_model).with_bigram_model(opts.bigram_model.clone()).with_cost(opts.cost.clone()));
+        }
+
+        if let Some(size) = opts.bytes {
+            return StreamingExtractor::Bytes(ByteWindow::new(size, primary_domain_length).with_length_model(opts.length_model).with_bigram_model(opts.bigram_model.clone()).with_cost(opts.cost.clone()));
+        }
+
+        if let Some(decay) = opts.decay {
+            return StreamingExtractor::Decay(Box::new(DecayWindow::new(decay).with_rate_unit(opts.rate_unit).with_cost(opts.cost.clone())));
+        }
+
+        if let Some(duration) = opts.time {
+            return StreamingExtractor::Time(TimeWindow::with_policy(duration, opts.out_of_order, primary_domain_length).with_length_model(opts.length_model).with_bigram_model(opts.bigram_model.clone()).with_rate_unit(opts.rate_unit).with_cost(opts.cost.clone()));
+        }
+
+        payload()
+    }
+
+    /// Continue a domain of which `n_preceding` earlier records, the first at `first_ts`, are extracted elsewhere (e.g.
+    /// in another thread). Only the domain age features of window extractors depend on this.
+    pub fn resume_after(&mut self, first_ts: f64, n_preceding: usize) {
+        match self {
+            StreamingExtractor::Payload(_) => {}
+            StreamingExtractor::Time(ex) => ex.resume_after(first_ts),
+            StreamingExtractor::Fixed(ex) => ex.resume_after(first_ts, n_preceding),
+            StreamingExtractor::Bytes(ex) => ex.resume_after(first_ts, n_preceding),
+            StreamingExtractor::Decay(ex) => ex.resume_after(first_ts),
+        }
+    }
+
+    /// Process the next record of this primary domain and return the resulting feature vectors.
+    /// Time windows may return zero or multiple vectors, depending on their out-of-order policy.
+    pub fn process(&mut self, record: LogRecord) -> Vec<FeatureVector> {
+        match self {
+            StreamingExtractor::Payload(ex) => vec![FeatureVector::Payload(ex.process_entry(record.id, &record.payload))],
+            StreamingExtractor::Time(ex) => ex.push_record(record)
+                .into_iter().map(FeatureVector::Time).collect(),
+            StreamingExtractor::Fixed(ex) => vec![FeatureVector::Fixed(ex.process_record(record))],
+            StreamingExtractor::Bytes(ex) => vec![FeatureVector::Bytes(ex.process_record(record))],
+            StreamingExtractor::Decay(ex) => vec![FeatureVector::Decay(ex.process_record(&record))],
+        }
+    }
+
+    /// Advance the clock of time windows to `ts` without a new record (see `TimeWindow::advance_to`), so idle
+    /// domains still expire entries and emit closing aggregates. Does nothing for other extractors.
+    pub fn advance_to(&mut self, ts: f64) -> Vec<FeatureVector> {
+        match self {
+            StreamingExtractor::Time(ex) => ex.advance_to(ts).into_iter().map(FeatureVector::Time).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Return the feature vectors of records that are still held back (e.g. by a reorder buffer).
+    pub fn flush(&mut self) -> Vec<FeatureVector> {
+        match self {
+            StreamingExtractor::Time(ex) => ex.flush().into_iter().map(FeatureVector::Time).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
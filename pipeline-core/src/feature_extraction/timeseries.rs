@@ -0,0 +1,72 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::feature_extraction::state::{label_key, LabelKey, query_key};
+use crate::shared_interface::LogRecord;
+
+/// Query volume of a primary domain in one interval of its time series.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeSeriesPoint {
+    /// Start of the interval (a multiple of the interval length since the epoch).
+    pub interval_start: f64,
+    pub n_queries: usize,
+    pub n_unique_labels: usize,
+    pub n_unique_queries: usize,
+}
+
+/// Time series of the query volume of a primary domain, with intervals of `interval` seconds aligned to the epoch.
+/// Records may be in any order; intervals without queries are left out.
+pub fn query_time_series<I>(records: I, interval: f64) -> Vec<TimeSeriesPoint>
+    where I: IntoIterator, I::Item: Borrow<LogRecord> {
+    let mut intervals: BTreeMap<i64, (usize, HashSet<LabelKey>, HashSet<LabelKey>)> = BTreeMap::new();
+
+    for record in records {
+        let record = record.borrow();
+        let (n_queries, labels, queries) = intervals.entry((record.ts / interval).floor() as i64).or_default();
+        *n_queries += 1;
+        labels.extend(record.payload.labels.iter().map(|label| label_key(label)));
+        queries.insert(query_key(&record.payload.labels));
+    }
+
+    intervals.into_iter()
+        .map(|(index, (n_queries, labels, queries))| TimeSeriesPoint {
+            interval_start: index as f64 * interval,
+            n_queries,
+            n_unique_labels: labels.len(),
+            n_unique_queries: queries.len(),
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_extraction::timeseries::{query_time_series, TimeSeriesPoint};
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::LogRecord;
+
+    fn record(ts: f64, labels: &[&[u8]]) -> LogRecord {
+        let labels: Vec<Vec<u8>> = labels.iter().map(|label| label.to_vec()).collect();
+        let payload = DnsPayload { payload_len: labels.iter().map(|label| label.len() as u8).sum(), labels };
+        LogRecord { id: 0, ts, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None }
+    }
+
+    #[test]
+    fn test_query_time_series() {
+        let records = vec![
+            record(125., &[b"b"]),
+            record(61., &[b"a", b"x"]),
+            record(119.5, &[b"A", b"X"]),
+            record(60., &[b"x"]),
+            record(300., &[]),
+        ];
+        let point = |interval_start, n_queries, n_unique_labels, n_unique_queries| TimeSeriesPoint { interval_start, n_queries, n_unique_labels, n_unique_queries };
+
+        // Case-insensitive uniqueness, empty intervals are left out
+        assert_eq!(vec![point(60., 3, 2, 2), point(120., 1, 1, 1), point(300., 1, 0, 1)], query_time_series(&records, 60.));
+        assert_eq!(vec![point(0., 5, 3, 4)], query_time_series(records, 3600.));
+        assert!(query_time_series(Vec::<LogRecord>::new(), 60.).is_empty());
+    }
+}
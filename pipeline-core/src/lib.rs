@@ -0,0 +1,40 @@
+// No unsafe here!
+#![forbid(unsafe_code)]
+
+#[macro_use]
+extern crate lazy_static;
+extern crate serde;
+#[macro_use]
+extern crate serde_prefix;
+
+// #[macro_use]
+// extern crate erased_serde;
+
+// Shared (structs) between the pipeline stages
+pub mod shared_interface;
+
+// Errors of all modules
+pub mod error;
+pub use crate::error::{Error, Result};
+
+// Preprocessing
+pub mod parse_log;
+pub mod parse_dns;
+
+// Feature Extraction
+pub mod feature_extraction;
+
+// Dataset splitting
+pub mod split;
+
+// Enrichment with external (file-based) information
+pub mod enrichment;
+
+// Metrics of long-running runs
+pub mod metrics;
+
+// Inputs and outputs in an S3-compatible object store
+pub mod object_store;
+
+// Baseline detectors for comparison
+pub mod detect;
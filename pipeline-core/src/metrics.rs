@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Counters of a long-running run, exposed in the Prometheus text format with `serve_metrics` (e.g. `rate()` of
+/// `tdp_records_total` gives the queries per second).
+#[derive(Debug, Default)]
+pub struct Metrics {
+    records: AtomicU64,
+    domains: AtomicU64,
+    /// Rejection reason <--> count
+    rejected: Mutex<BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetricsError {
+    #[error("could not serve metrics")]
+    IO(#[source] std::io::Error),
+    #[error("could not serve metrics: built without the metrics feature")]
+    Disabled,
+}
+
+impl Metrics {
+    pub fn inc_records(&self) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_rejected(&self, reason: &str) {
+        // (soft fail, metrics are not worth a panic)
+        if let Ok(mut rejected) = self.rejected.lock() {
+            match rejected.get_mut(reason) {
+                Some(count) => *count += 1,
+                None => { rejected.insert(reason.to_string(), 1); }
+            }
+        }
+    }
+
+    /// Number of primary domains tracked so far.
+    pub fn set_domains(&self, n: u64) {
+        self.domains.store(n, Ordering::Relaxed);
+    }
+
+    /// Metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        // (writing to a String does not fail)
+        let _ = self.write_metrics(&mut out);
+        out
+    }
+
+    fn write_metrics(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "# HELP tdp_records_total Queries written as records.")?;
+        writeln!(out, "# TYPE tdp_records_total counter")?;
+        writeln!(out, "tdp_records_total {}", self.records.load(Ordering::Relaxed))?;
+
+        writeln!(out, "# HELP tdp_rejected_total Rejected log lines by reason.")?;
+        writeln!(out, "# TYPE tdp_rejected_total counter")?;
+        if let Ok(rejected) = self.rejected.lock() {
+            for (reason, count) in rejected.iter() {
+                writeln!(out, "tdp_rejected_total{{reason=\"{}\"}} {}", reason.replace('\\', "\\\\").replace('"', "\\\""), count)?;
+            }
+        }
+
+        writeln!(out, "# HELP tdp_primary_domains Primary domains tracked.")?;
+        writeln!(out, "# TYPE tdp_primary_domains gauge")?;
+        writeln!(out, "tdp_primary_domains {}", self.domains.load(Ordering::Relaxed))
+    }
+}
+
+/// Serve the metrics at `http://<addr>/metrics` from a background thread (requires the `metrics` feature).
+#[cfg(feature = "metrics")]
+pub fn serve_metrics(addr: &str, metrics: Arc<Metrics>) -> Result<(), MetricsError> {
+    use std::io::{BufRead, BufReader, Write as IoWrite};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind(addr).map_err(MetricsError::IO)?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Only the request line is of interest, headers are skipped
+            let mut request_line = String::new();
+            if BufReader::new(&stream).read_line(&mut request_line).is_err() { continue; }
+
+            let response = match request_line.split_whitespace().nth(1) {
+                Some("/metrics") => {
+                    let body = metrics.render();
+                    format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+                }
+                _ => String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+            };
+            let _ = (&stream).write_all(response.as_bytes());
+        }
+    });
+    Ok(())
+}
+
+/// Serve the metrics at `http://<addr>/metrics` from a background thread (requires the `metrics` feature).
+#[cfg(not(feature = "metrics"))]
+pub fn serve_metrics(_addr: &str, _metrics: Arc<Metrics>) -> Result<(), MetricsError> {
+    Err(MetricsError::Disabled)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.inc_records();
+        metrics.inc_records();
+        metrics.inc_rejected("UnknownSuffix");
+        metrics.set_domains(1);
+
+        let out = metrics.render();
+        assert!(out.contains("\ntdp_records_total 2\n"));
+        assert!(out.contains("\ntdp_rejected_total{reason=\"UnknownSuffix\"} 1\n"));
+        assert!(out.ends_with("\ntdp_primary_domains 1\n"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Dynamic DNS providers whose tenants (the label directly below the provider domain) are grouped as separate primary
+/// domains. Some of these are in the private section of the public suffix list already, others are not.
+pub const DYNDNS_PROVIDERS: [&str; 14] = [
+    "afraid.org",
+    "chickenkiller.com",
+    "ddns.net",
+    "duckdns.org",
+    "dynu.net",
+    "dyndns.org",
+    "hopto.org",
+    "mooo.com",
+    "no-ip.biz",
+    "no-ip.info",
+    "no-ip.org",
+    "servebeer.com",
+    "sytes.net",
+    "zapto.org",
+];
+
+/// Known services whose queries look tunnel-like: DNS blocklists (DNSBL/URIBL), the NTP pool, anti-virus reputation
+/// lookups and CDNs with encoded object names.
+pub const ALLOWLIST_DOMAINS: [&str; 22] = [
+    // DNSBL and URIBL services
+    "abuseat.org",
+    "barracudacentral.org",
+    "dnswl.org",
+    "mailspike.net",
+    "sorbs.net",
+    "spamcop.net",
+    "spamhaus.org",
+    "surbl.org",
+    "uribl.com",
+    // NTP pool
+    "pool.ntp.org",
+    // Anti-virus telemetry and reputation lookups
+    "avqs.mcafee.com",
+    "e5.sk",
+    "sophosxl.net",
+    "senderbase.org",
+    // CDNs
+    "akadns.net",
+    "akamaiedge.net",
+    "akamaihd.net",
+    "cloudfront.net",
+    "edgekey.net",
+    "edgesuite.net",
+    "fastly.net",
+    "trafficmanager.net",
+];
+
+/// Read a list of domains from a file with one domain per line. Empty lines and comments (`#`) are skipped, domains
+/// are lowercased and a trailing dot is removed.
+pub fn load_domain_list(path: &Path) -> std::io::Result<Vec<String>> {
+    let mut domains = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let domain = line.split('#').next().unwrap_or_default().trim().trim_end_matches('.');
+        if !domain.is_empty() { domains.push(domain.to_ascii_lowercase()); }
+    }
+    Ok(domains)
+}
+
+/// Whether `name` is `domain` or a subdomain of it (case-insensitive, `domain` is expected in lowercase).
+pub fn is_subdomain_of(name: &[u8], domain: &str) -> bool {
+    let domain = domain.as_bytes();
+    match name.len().checked_sub(domain.len()) {
+        Some(0) => name.eq_ignore_ascii_case(domain),
+        Some(offset) => name[offset - 1] == b'.' && name[offset..].eq_ignore_ascii_case(domain),
+        None => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::domain_list::{is_subdomain_of, load_domain_list};
+
+    #[test]
+    fn test_load_domain_list() {
+        let path = std::env::temp_dir().join(format!("tdp-domain-list-{}", std::process::id()));
+        std::fs::write(&path, "# providers\nDuckDNS.org.\n\n  example.net # tenant per label\n").unwrap();
+
+        assert_eq!(vec!["duckdns.org", "example.net"], load_domain_list(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_is_subdomain_of() {
+        assert!(is_subdomain_of(b"a.Tenant.duckdns.org", "duckdns.org"));
+        assert!(is_subdomain_of(b"duckdns.org", "duckdns.org"));
+        assert!(!is_subdomain_of(b"notduckdns.org", "duckdns.org"));
+        assert!(!is_subdomain_of(b"org", "duckdns.org"));
+    }
+}
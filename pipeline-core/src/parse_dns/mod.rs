@@ -0,0 +1,641 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::shared_interface::DomainTags;
+
+pub use self::domain_list::{ALLOWLIST_DOMAINS, DYNDNS_PROVIDERS, is_subdomain_of, load_domain_list};
+pub use self::suffix_list::{download_suffix_list, PSL_URL, SuffixList, SuffixListError};
+pub use self::wildcard::{WildcardConfig, WildcardDetector};
+pub use self::wire::{decode_wire_name, parse_dns_message, parse_dns_wire, parse_dns_wire_message, WireQuestion};
+
+mod domain_list;
+mod suffix_list;
+mod wildcard;
+mod wire;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct DnsPayload {
+    #[serde(with = "labels_as_bytes")]
+    pub labels: Vec<Vec<u8>>,
+    pub payload_len: u8,
+}
+
+/// (De)serialize labels as byte strings (e.g. MessagePack bin and CBOR byte strings instead of arrays of integers), the
+/// bincode layout is unchanged.
+mod labels_as_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::ser::SerializeSeq;
+    use serde_bytes::{ByteBuf, Bytes};
+
+    pub fn serialize<S: Serializer>(labels: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(labels.len()))?;
+        for label in labels {
+            seq.serialize_element(Bytes::new(label))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Ok(Vec::<ByteBuf>::deserialize(deserializer)?.into_iter().map(ByteBuf::into_vec).collect())
+    }
+}
+
+impl DnsPayload {
+    /// Query name of the payload labels in front of the primary domain (only the labels for an empty primary domain).
+    /// Non-printable bytes and backslashes are escaped as `\xHH`, like in Zeek logs, which the log parser decodes.
+    pub fn to_query_string(&self, primary: &str) -> String {
+        let mut query = String::with_capacity(self.payload_len as usize + self.labels.len() + primary.len());
+        // (writing to a String does not fail)
+        let _ = self.write_query(&mut query, primary);
+        query
+    }
+
+    /// Write the query name like `to_query_string`, without intermediate allocations (e.g. for Display).
+    pub fn write_query<W: std::fmt::Write>(&self, w: &mut W, primary: &str) -> std::fmt::Result {
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 { w.write_char(LABEL_SEP as char)?; }
+            write_escaped(w, label)?;
+        }
+        if !primary.is_empty() {
+            if !self.labels.is_empty() { w.write_char(LABEL_SEP as char)?; }
+            w.write_str(primary)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write bytes with non-printable bytes and backslashes escaped as `\xHH`.
+fn write_escaped<W: std::fmt::Write>(w: &mut W, bytes: &[u8]) -> std::fmt::Result {
+    for &byte in bytes {
+        match byte {
+            b'\\' => w.write_str("\\x5c")?,
+            0x20..=0x7e => w.write_char(byte as char)?,
+            _ => write!(w, "\\x{:02x}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+impl std::fmt::Debug for DnsPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "len={} ", self.payload_len)?;
+        self.write_query(f, "")
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ParseDnsError {
+    #[error("query is too short")]
+    QueryLength,
+    #[error("invalid DNS name")]
+    InvalidDnsName,
+    #[error("suffix is not in the public suffix list")]
+    UnknownSuffix,
+    #[error("reserved (special use) suffix")]
+    ReservedSuffix,
+    #[error("query has no labels below the primary domain")]
+    NoLabels,
+    #[error("invalid primary domain")]
+    InvalidPrim,
+    #[error("query has only a www label")]
+    NoStorageChannel,
+    #[error("invalid DNS wire format")]
+    InvalidWireFormat,
+}
+
+/// What to do with queries for special use (reserved) suffixes such as `.corp` or `.internal`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReservedSuffixMode {
+    /// Reject the query (`ParseDnsError::ReservedSuffix`).
+    #[default]
+    Drop,
+    /// Keep the query like any other.
+    Keep,
+    /// Keep the query and tag its primary domain with `DomainTags::RESERVED_SUFFIX`.
+    KeepAndTag,
+}
+
+/// Which primary domains are accepted as valid.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ValidationProfile {
+    /// Hostname rules: letters, digits and hyphens, with an optional leading underscore (`ParseDnsError::InvalidPrim`
+    /// otherwise).
+    #[default]
+    Strict,
+    /// Any bytes in the labels, such as underscores anywhere or binary data, as tunnels do not have to follow hostname
+    /// rules. Non-printable bytes in the primary domain are escaped as `\xHH`, like payload labels.
+    Lenient,
+}
+
+/// Validation and filtering options for `parse_dns_with_config`.
+#[derive(Debug, Clone, Default)]
+pub struct ParseDnsConfig {
+    pub validation: ValidationProfile,
+    pub reserved_suffix: ReservedSuffixMode,
+    /// Keep queries with a suffix that is not in the public suffix list, using the last two labels as primary
+    /// domain (tagged with `DomainTags::UNKNOWN_SUFFIX`), instead of rejecting them.
+    pub unknown_suffix_fallback: bool,
+    /// Public suffix list used to determine the primary domain.
+    pub suffix_list: SuffixList,
+    /// Tag primary domains with `DomainTags::GENERATED_NAME` if the Shannon entropy (bits per character) of their
+    /// registrable label is at least this threshold. Queries are never dropped for this.
+    pub generated_name_entropy: Option<f32>,
+    /// Dynamic DNS provider domains (lowercase), under which the primary domain is the tenant label with the provider
+    /// domain (e.g. `tenant.duckdns.org`) instead of the registrable domain.
+    pub dyndns_providers: Vec<String>,
+    /// Domains of known services with tunnel-like query patterns (lowercase), queries for these domains or their
+    /// subdomains are tagged with `DomainTags::ALLOWLISTED`.
+    pub allowlist: Vec<String>,
+    /// Leftmost payload labels (lowercase, e.g. `COMMON_PREFIX_LABELS`) to strip from the payload. Queries with only such
+    /// labels are kept with an empty payload, so they count towards their primary domain. If empty, queries with only a
+    /// `www` label are rejected (`ParseDnsError::NoStorageChannel`).
+    pub strip_labels: Vec<String>,
+}
+
+/// Result of parsing a DNS query: primary domain, payload and tags that apply to the primary domain (if any of its
+/// queries has them).
+#[derive(Debug, PartialEq)]
+pub struct ParsedDns {
+    pub primary_domain: String,
+    /// Length of the primary domain in the query, which differs from the length of an escaped `primary_domain`.
+    pub primary_domain_len: u8,
+    pub payload: DnsPayload,
+    pub tags: DomainTags,
+}
+
+const LABEL_SEP: u8 = b'.';
+
+const IPV4_REVERSE_ZONE: &str = "in-addr.arpa";
+const IPV6_REVERSE_ZONE: &str = "ip6.arpa";
+
+const WWW_LABEL: &[u8] = b"www";
+
+/// Common service prefix labels, for `ParseDnsConfig::strip_labels`.
+pub const COMMON_PREFIX_LABELS: [&str; 5] = ["www", "mail", "m", "api", "cdn"];
+const TUNLAN_TLD: &[u8] = b"tun.lan";
+const FILTER_TLD: [&[u8]; 15] = [
+    b"arpa",
+    b"local",
+    b"intranet",
+    b"lan",
+    b"localhost",
+    b"example.com",
+    b"example.net",
+    b"example.org",
+    b"internal",
+    b"private",
+    b"corp",
+    b"home",
+    b"invalid",
+    b"test",
+    b"example",
+];
+
+lazy_static! {
+    static ref VALID_PRIM_RE: Regex = Regex::new(r"^(_?[a-zA-Z0-9]+[a-zA-Z0-9.-]*[a-zA-Z0-9]?)$").unwrap();
+}
+
+/// Parse and validate/filter given byte vector as DNS query.
+pub fn parse_dns(dns_query: &[u8]) -> Result<(String, DnsPayload), ParseDnsError> {
+    parse_dns_with_config(dns_query, &ParseDnsConfig::default())
+        .map(|parsed| (parsed.primary_domain, parsed.payload))
+}
+
+/// Parse and validate/filter given byte vector as DNS query, using the given filter configuration.
+pub fn parse_dns_with_config(dns_query: &[u8], config: &ParseDnsConfig) -> Result<ParsedDns, ParseDnsError> {
+    // TODO: thorough test suite
+    let mut tags = DomainTags::default();
+
+    let q_len = dns_query.len();
+    if q_len < 5 { return Err(ParseDnsError::QueryLength); }
+    if q_len > 255 { return Err(ParseDnsError::InvalidDnsName); }
+
+    // Parse domain name
+    if let Some(domain) = config.suffix_list.domain(dns_query) {
+
+        // Remove optional trailing dot
+        let domain = domain.trim();
+        let is_tunlan_prim = domain.as_bytes().eq(TUNLAN_TLD);
+
+        // FILTER: special use TLD (skip tun.lan domain used for data collection)
+        let is_reserved = !is_tunlan_prim && FILTER_TLD.iter().any(|tld| domain.suffix().as_bytes().ends_with(tld));
+        if is_reserved {
+            match config.reserved_suffix {
+                ReservedSuffixMode::Drop => return Err(ParseDnsError::ReservedSuffix),
+                ReservedSuffixMode::Keep => {}
+                ReservedSuffixMode::KeepAndTag => tags.insert(DomainTags::RESERVED_SUFFIX),
+            }
+        }
+
+        // FILTER: unknown suffix (most reserved suffixes are not in the public suffix list)
+        // For unknown suffixes, the PSL default rule makes the last label the suffix, so the primary domain
+        // consists of the last two labels.
+        if !domain.suffix().is_known() && !is_tunlan_prim && !is_reserved {
+            if !config.unknown_suffix_fallback { return Err(ParseDnsError::UnknownSuffix); }
+            tags.insert(DomainTags::UNKNOWN_SUFFIX);
+        }
+
+        // Group tenants of dynamic DNS providers one label below the provider domain
+        let domain_bytes = match effective_primary(dns_query, domain.as_bytes(), &config.dyndns_providers) {
+            Some(effective) => effective,
+            None => domain.as_bytes(),
+        };
+
+        // Wire length of the primary domain (queries are at most 255 bytes)
+        let primary_domain_len = u8::try_from(domain_bytes.len()).map_err(|_| ParseDnsError::InvalidDnsName)?;
+
+        // Store owned version of primary domain (to return in the end as well)
+        let prim = match config.validation {
+            ValidationProfile::Strict => match std::str::from_utf8(domain_bytes) {
+                Ok(str) => {
+                    // Check if domain follows spec
+                    if !VALID_PRIM_RE.is_match(str) {
+                        return Err(ParseDnsError::InvalidPrim);
+                    }
+                    String::from(str)
+                }
+                Err(_) => return Err(ParseDnsError::InvalidPrim)
+            },
+            ValidationProfile::Lenient => {
+                let mut prim = String::with_capacity(domain_bytes.len());
+                // (writing to a String does not fail)
+                let _ = write_escaped(&mut prim, domain_bytes);
+                prim
+            }
+        };
+
+        // TAG: registrable label that looks generated (e.g. DGA-style tunnel infrastructure)
+        let registrable_label = prim.split(LABEL_SEP as char).next().unwrap_or_default();
+        if config.generated_name_entropy.is_some_and(|threshold| label_entropy(registrable_label.as_bytes()) >= threshold) {
+            tags.insert(DomainTags::GENERATED_NAME);
+        }
+
+        // TAG: reverse lookup that is not a (partial) reversed IP address, e.g. data in PTR queries
+        if is_reverse_lookup(dns_query) && !is_reversed_address(dns_query) {
+            tags.insert(DomainTags::MALFORMED_REVERSE);
+        }
+
+        // TAG: known service (e.g. DNSBL or CDN) that looks like a tunnel
+        let name = dns_query.strip_suffix(&[LABEL_SEP]).unwrap_or(dns_query);
+        if config.allowlist.iter().any(|domain| is_subdomain_of(name, domain)) {
+            tags.insert(DomainTags::ALLOWLISTED);
+        }
+
+        // FILTER no labels (check 1)
+        if q_len - domain_bytes.len() == 0 { return Err(ParseDnsError::NoLabels); }
+
+        // FILTER: no labels (check 2)
+        let labels_concat: &[u8] = dns_query.get(..q_len - domain_bytes.len() - 1).unwrap_or_default();
+        if labels_concat.is_empty() { return Err(ParseDnsError::NoLabels); }
+
+        // FILTER: "www" label (unless prefix labels are stripped instead)
+        if config.strip_labels.is_empty() && labels_concat.len() == 3 && labels_concat.eq(WWW_LABEL) {
+            return Err(ParseDnsError::NoStorageChannel);
+        }
+
+        // Collect labels in vector
+        let mut labels = labels_concat
+            .split(|c| c == &LABEL_SEP)
+            .map(|label| label.to_owned())
+            .collect::<Vec<Vec<u8>>>();
+
+        // FILTER: empty labels
+        let mut payload_len: usize = 0;
+        for label in labels.iter() {
+            if label.is_empty() || label.len() > 63 { return Err(ParseDnsError::InvalidDnsName); }
+            if label.is_empty() || label.len() > 63 { return Err(ParseDnsError::InvalidDnsName); }
+            payload_len += label.len();
+        }
+
+        // STRIP: common prefix labels, a query with only such labels is kept with an empty payload
+        let n_strip = labels.iter()
+            .take_while(|label| config.strip_labels.iter().any(|strip| label.eq_ignore_ascii_case(strip.as_bytes())))
+            .count();
+        payload_len -= labels.drain(..n_strip).map(|label| label.len()).sum::<usize>();
+
+        Ok(ParsedDns {
+            primary_domain: prim,
+            primary_domain_len,
+            payload: DnsPayload {
+                labels,
+                payload_len: payload_len as u8,
+            },
+            tags,
+        })
+    } else {
+// FILTER: invalid DNS name (could not be parsed)
+        Err(ParseDnsError::InvalidDnsName)
+    }
+}
+
+/// Whether the query is a reverse lookup (under `in-addr.arpa` or `ip6.arpa`).
+pub fn is_reverse_lookup(dns_query: &[u8]) -> bool {
+    let name = dns_query.strip_suffix(&[LABEL_SEP]).unwrap_or(dns_query);
+    is_subdomain_of(name, IPV4_REVERSE_ZONE) || is_subdomain_of(name, IPV6_REVERSE_ZONE)
+}
+
+/// Whether a reverse lookup consists of the (partial) reversed octets of an IPv4 address or nibbles of an IPv6
+/// address.
+fn is_reversed_address(dns_query: &[u8]) -> bool {
+    let name = dns_query.strip_suffix(&[LABEL_SEP]).unwrap_or(dns_query);
+    let (zone, max_labels): (&str, usize) = if is_subdomain_of(name, IPV4_REVERSE_ZONE) { (IPV4_REVERSE_ZONE, 4) } else { (IPV6_REVERSE_ZONE, 32) };
+    let labels = match name.len().checked_sub(zone.len() + 1) {
+        Some(len) => &name[..len],
+        None => return false,
+    };
+
+    let n_labels = labels.split(|c| c == &LABEL_SEP).count();
+    n_labels <= max_labels && labels.split(|c| c == &LABEL_SEP).all(|label| match max_labels {
+        4 => std::str::from_utf8(label).ok()
+            .filter(|octet| octet.len() == 1 || !octet.starts_with('0'))
+            .and_then(|octet| octet.parse::<u8>().ok())
+            .is_some(),
+        _ => label.len() == 1 && label[0].is_ascii_hexdigit(),
+    })
+}
+
+/// Primary domain for a query under one of the dynamic DNS providers: the provider domain with the label directly below
+/// it, if that is longer than the registrable domain. Returns None otherwise.
+fn effective_primary<'a>(dns_query: &'a [u8], registrable: &[u8], providers: &[String]) -> Option<&'a [u8]> {
+    let name = dns_query.strip_suffix(&[LABEL_SEP]).unwrap_or(dns_query);
+    let provider = providers.iter().find(|provider| is_subdomain_of(name, provider) && name.len() > provider.len())?;
+
+    let tenants = &name[..name.len() - provider.len() - 1];
+    let tenant_len = tenants.rsplit(|c| c == &LABEL_SEP).next()?.len();
+    let effective = &name[name.len() - provider.len() - tenant_len - 1..];
+
+    if effective.len() > registrable.len() { Some(effective) } else { None }
+}
+
+/// Shannon entropy of the characters of a label, in bits per character (case-insensitive).
+fn label_entropy(label: &[u8]) -> f32 {
+    let mut counts = [0usize; 256];
+    label.iter().for_each(|ch| counts[ch.to_ascii_lowercase() as usize] += 1);
+
+    let n = label.len() as f32;
+    -counts.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| { let p = count as f32 / n; p * p.log2() })
+        .sum::<f32>()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::{DnsPayload, is_reverse_lookup, label_entropy, parse_dns, parse_dns_with_config, ParseDnsConfig, ParseDnsError, ReservedSuffixMode, ValidationProfile};
+    use crate::shared_interface::DomainTags;
+
+    #[test]
+    fn test_valid_domain() {
+        let expected = DnsPayload {
+            labels: vec!["label1".as_bytes().into(), "label2".as_bytes().into()],
+            payload_len: 12,
+        };
+
+        let q = b"label1.label2.example.com";
+        let (_, result) = parse_dns(&q[..]).unwrap();
+
+        assert_eq!(expected, result)
+    }
+
+    #[test]
+    fn filter_empty_query() {
+        let empty = vec![];
+        assert!(parse_dns(&empty).is_err())
+    }
+
+    #[test]
+    fn filter_no_labels() {
+        let no_label = b"example.com".as_ref();
+        assert!(parse_dns(no_label).is_err());
+    }
+
+    #[test]
+    fn filter_empty_label() {
+        let empty_label = b".example.com".as_ref();
+        assert!(parse_dns(empty_label).is_err());
+    }
+
+    #[test]
+    fn filter_invalid_double_sep() {
+        let double_sep_empty = b"..example.com".as_ref();
+        assert!(parse_dns(double_sep_empty).is_err())
+    }
+
+    #[test]
+    fn filter_invalid_double_sep_not_empty() {
+        let double_sep_not_empty = b"test..test.example.com".as_ref();
+        assert!(parse_dns(double_sep_not_empty).is_err())
+    }
+
+    #[test]
+    fn filter_invalid_query_too_long() {
+        let ll = "a".repeat(63);
+        let too_long = format!("{}.{}.{}.{}.example.com", ll, ll, ll, ll);  // max = 253, this is (252 + |.example.com|)
+        let too_long = too_long.as_bytes().to_owned();
+
+        assert!(parse_dns(&too_long).is_err())
+    }
+
+    #[test]
+    fn filter_invalid_label_too_long() {
+        let ll = "a".repeat(70);
+        let one_long_label = format!("{}.example.com", ll);
+        let one_long_label = one_long_label.as_bytes().to_owned();
+
+        assert!(parse_dns(&one_long_label).is_err())
+    }
+
+    #[test]
+    fn filter_root_label() {
+        let root_label = b".".as_ref();
+        assert!(parse_dns(root_label).is_err())
+    }
+
+    #[test]
+    fn filter_short_query_fast_path() {
+        let short_query = b".a.b".as_ref();
+        // Fast path by checking len <= 4 (these cannot have labels)
+        assert!(parse_dns(short_query).is_err())
+    }
+
+    #[test]
+    fn filter_unknown_prim() {
+        let unknown_prim = b"label.domain.com".as_ref();
+        let unknown_tld = b"label.domain.localtld".as_ref();
+
+        // Make sure our query is valid with a known suffix...
+        assert!(parse_dns(unknown_prim).is_ok());
+
+        // .. and rejected with an unknown suffix
+        assert!(parse_dns(unknown_tld).is_err());
+    }
+
+    #[test]
+    fn actual_bytes_in_primary_domain() {
+        let bytes_in_domain = b"null\x00.linefeed\x0A.carriagereturn\x0D.com".as_ref();
+        assert!(parse_dns(bytes_in_domain).is_err());
+    }
+
+    #[test]
+    fn payload_lengths() {
+        let one_label = b"one.domain.com".as_ref();
+        let two_label = b"two.two.domain.com".as_ref();
+        let ten_label = b"a.a.a.a.a.a.a.a.a.a.domain.com".as_ref();
+
+        let (_, pl_one) = parse_dns(one_label).unwrap();
+        let (_, pl_two) = parse_dns(two_label).unwrap();
+        let (_, pl_ten) = parse_dns(ten_label).unwrap();
+
+        assert_eq!(3, pl_one.payload_len);
+        assert_eq!(6, pl_two.payload_len);
+        assert_eq!(10, pl_ten.payload_len);
+    }
+
+    #[test]
+    fn reserved_suffix_modes() {
+        let corp = b"secret.host.corp".as_ref();
+        let arpa = b"4.3.2.1.in-addr.arpa".as_ref();
+
+        let drop = ParseDnsConfig { reserved_suffix: ReservedSuffixMode::Drop, ..ParseDnsConfig::default() };
+        assert!(parse_dns_with_config(corp, &drop).is_err());
+        assert!(parse_dns_with_config(arpa, &drop).is_err());
+
+        let keep = ParseDnsConfig { reserved_suffix: ReservedSuffixMode::Keep, ..ParseDnsConfig::default() };
+        let parsed = parse_dns_with_config(corp, &keep).unwrap();
+        assert_eq!("host.corp", parsed.primary_domain);
+        assert_eq!(vec![b"secret".to_vec()], parsed.payload.labels);
+        assert!(!parsed.tags.contains(DomainTags::RESERVED_SUFFIX));
+
+        let tag = ParseDnsConfig { reserved_suffix: ReservedSuffixMode::KeepAndTag, ..ParseDnsConfig::default() };
+        assert!(parse_dns_with_config(corp, &tag).unwrap().tags.contains(DomainTags::RESERVED_SUFFIX));
+        assert!(parse_dns_with_config(arpa, &tag).unwrap().tags.contains(DomainTags::RESERVED_SUFFIX));
+
+        // Regular domains are not tagged
+        assert!(!parse_dns_with_config(b"a.domain.com", &tag).unwrap().tags.contains(DomainTags::RESERVED_SUFFIX));
+    }
+
+    #[test]
+    fn unknown_suffix_fallback() {
+        let unknown_tld = b"label.domain.newtld".as_ref();
+        let config = ParseDnsConfig { unknown_suffix_fallback: true, ..ParseDnsConfig::default() };
+
+        let parsed = parse_dns_with_config(unknown_tld, &config).unwrap();
+        assert_eq!("domain.newtld", parsed.primary_domain);
+        assert_eq!(vec![b"label".to_vec()], parsed.payload.labels);
+        assert!(parsed.tags.contains(DomainTags::UNKNOWN_SUFFIX));
+
+        // Known suffixes are not affected
+        let parsed = parse_dns_with_config(b"label.domain.co.uk", &config).unwrap();
+        assert_eq!("domain.co.uk", parsed.primary_domain);
+        assert!(!parsed.tags.contains(DomainTags::UNKNOWN_SUFFIX));
+    }
+
+    #[test]
+    fn generated_name_tag() {
+        let config = ParseDnsConfig { generated_name_entropy: Some(3.5), ..ParseDnsConfig::default() };
+
+        // Tagged, not dropped (entropy of the registrable label only, not the payload or suffix)
+        let parsed = parse_dns_with_config(b"data.x7kq2m9vbz4w.co.uk", &config).unwrap();
+        assert_eq!("x7kq2m9vbz4w.co.uk", parsed.primary_domain);
+        assert!(parsed.tags.contains(DomainTags::GENERATED_NAME));
+
+        assert!(!parse_dns_with_config(b"x7kq2m9vbz4w.google.com", &config).unwrap().tags.contains(DomainTags::GENERATED_NAME));
+        assert!(!parse_dns_with_config(b"data.x7kq2m9vbz4w.co.uk", &ParseDnsConfig::default()).unwrap().tags.contains(DomainTags::GENERATED_NAME));
+    }
+
+    #[test]
+    fn test_to_query_string() {
+        let payload = DnsPayload { labels: vec![b"a b".to_vec(), b"\xff\\".to_vec(), b"x".to_vec()], payload_len: 6 };
+        assert_eq!("a b.\\xff\\x5c.x.example.com", payload.to_query_string("example.com"));
+        assert_eq!("a b.\\xff\\x5c.x", payload.to_query_string(""));
+
+        let (prim, payload) = parse_dns(b"data.tunnel.example.com").unwrap();
+        assert_eq!("data.tunnel.example.com", payload.to_query_string(&prim));
+        assert_eq!("example.com", DnsPayload { labels: Vec::new(), payload_len: 0 }.to_query_string("example.com"));
+    }
+
+    #[test]
+    fn reverse_lookup_tag() {
+        let keep = ParseDnsConfig { reserved_suffix: ReservedSuffixMode::Keep, ..ParseDnsConfig::default() };
+        let malformed = |query: &[u8]| parse_dns_with_config(query, &keep).unwrap().tags.contains(DomainTags::MALFORMED_REVERSE);
+
+        assert!(is_reverse_lookup(b"4.3.2.1.in-addr.arpa") && is_reverse_lookup(b"b.a.9.8.ip6.ARPA."));
+        assert!(!is_reverse_lookup(b"in-addr.arpa.example.com"));
+
+        assert!(!malformed(b"4.3.2.1.in-addr.arpa"));
+        assert!(!malformed(b"3.2.1.in-addr.arpa"));
+        assert!(!malformed(b"b.a.9.8.7.6.5.0.4.0.0.0.3.0.0.0.2.0.0.0.1.0.0.0.0.0.0.0.1.2.3.4.ip6.arpa"));
+        assert!(malformed(b"6d7973656372657464617461.1.in-addr.arpa"));
+        assert!(malformed(b"256.3.2.1.in-addr.arpa"));
+        assert!(malformed(b"04.3.2.1.in-addr.arpa"));
+        assert!(malformed(b"ab.9.8.ip6.arpa"));
+        assert!(!malformed(b"a.domain.com"));
+    }
+
+    #[test]
+    fn validation_profiles() {
+        let lenient = ParseDnsConfig { validation: ValidationProfile::Lenient, ..ParseDnsConfig::default() };
+
+        for query in [&b"a_b.ex_ample.com"[..], b"data.-x_.com", b"abc.exa mple.com"].iter() {
+            assert_eq!(Err(ParseDnsError::InvalidPrim), parse_dns(query));
+        }
+
+        assert_eq!("ex_ample.com", parse_dns_with_config(b"a_b.ex_ample.com", &lenient).unwrap().primary_domain);
+        assert_eq!("-x_.com", parse_dns_with_config(b"data.-x_.com", &lenient).unwrap().primary_domain);
+
+        // Binary primary domain is escaped, the payload is not
+        let parsed = parse_dns_with_config(b"\x01ab.exa\xffmple.com", &lenient).unwrap();
+        assert_eq!(("exa\\xffmple.com", 12), (parsed.primary_domain.as_str(), parsed.primary_domain_len));
+        assert_eq!(vec![b"\x01ab".to_vec()], parsed.payload.labels);
+    }
+
+    #[test]
+    fn strip_prefix_labels() {
+        let config = ParseDnsConfig { strip_labels: vec![String::from("www"), String::from("m")], ..ParseDnsConfig::default() };
+
+        assert_eq!(Err(ParseDnsError::NoStorageChannel), parse_dns(b"www.example.com"));
+
+        let parsed = parse_dns_with_config(b"WWW.example.com", &config).unwrap();
+        assert_eq!(("example.com", DnsPayload { labels: vec![], payload_len: 0 }), (parsed.primary_domain.as_str(), parsed.payload));
+
+        // Only leftmost labels are stripped
+        let parsed = parse_dns_with_config(b"m.www.data.m.example.com", &config).unwrap();
+        assert_eq!(DnsPayload { labels: vec![b"data".to_vec(), b"m".to_vec()], payload_len: 5 }, parsed.payload);
+    }
+
+    #[test]
+    fn allowlist_tag() {
+        let config = ParseDnsConfig { allowlist: vec![String::from("zen.spamhaus.org"), String::from("ntp.org")], ..ParseDnsConfig::default() };
+
+        assert!(parse_dns_with_config(b"4.3.2.1.ZEN.spamhaus.org", &config).unwrap().tags.contains(DomainTags::ALLOWLISTED));
+        assert!(parse_dns_with_config(b"0.pool.ntp.org", &config).unwrap().tags.contains(DomainTags::ALLOWLISTED));
+        assert!(!parse_dns_with_config(b"4.3.2.1.xbl.spamhaus.org", &config).unwrap().tags.contains(DomainTags::ALLOWLISTED));
+        assert!(!parse_dns_with_config(b"0.pool.ntp.org", &ParseDnsConfig::default()).unwrap().tags.contains(DomainTags::ALLOWLISTED));
+    }
+
+    #[test]
+    fn dyndns_tenants() {
+        let config = ParseDnsConfig { dyndns_providers: vec![String::from("afraid.org"), String::from("duckdns.org")], ..ParseDnsConfig::default() };
+
+        // Provider that is not in the public suffix list
+        let parsed = parse_dns_with_config(b"data.tenant.afraid.org", &config).unwrap();
+        assert_eq!("tenant.afraid.org", parsed.primary_domain);
+        assert_eq!(vec![b"data".to_vec()], parsed.payload.labels);
+        assert_eq!("afraid.org", parse_dns_with_config(b"data.tenant.afraid.org", &ParseDnsConfig::default()).unwrap().primary_domain);
+
+        // Provider in the public suffix list, same result
+        assert_eq!("tenant.duckdns.org", parse_dns_with_config(b"a.b.tenant.duckdns.org", &config).unwrap().primary_domain);
+
+        // Tenant without payload labels
+        assert!(parse_dns_with_config(b"tenant.afraid.org", &config).is_err());
+    }
+
+    #[test]
+    fn test_label_entropy() {
+        assert_eq!(0., label_entropy(b"aAaa"));
+        assert_eq!(2., label_entropy(b"abcd"));
+    }
+}
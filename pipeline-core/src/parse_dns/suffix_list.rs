@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use psl::{Domain, List, Psl};
+
+/// URL of the latest public suffix list, used by `download_suffix_list`.
+pub const PSL_URL: &str = "https://publicsuffix.org/list/public_suffix_list.dat";
+
+/// Public suffix list used to determine primary domains.
+#[derive(Debug, Clone, Default)]
+pub enum SuffixList {
+    /// List compiled into the `psl` crate.
+    #[default]
+    Builtin,
+    /// List loaded at runtime from a snapshot file, with the version information from its header.
+    Snapshot { list: publicsuffix::List, version: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SuffixListError {
+    #[error("could not read public suffix list")]
+    IO(#[source] std::io::Error),
+    #[error("invalid public suffix list: {0}")]
+    Parse(String),
+    #[error("could not download public suffix list: {0}")]
+    Download(String),
+}
+
+impl SuffixList {
+    /// Load a public suffix list snapshot (in the `public_suffix_list.dat` format) from file.
+    pub fn from_file(path: &Path) -> Result<Self, SuffixListError> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(SuffixListError::IO)?;
+
+        Self::from_snapshot(&contents)
+    }
+
+    /// Parse a public suffix list snapshot (in the `public_suffix_list.dat` format).
+    pub fn from_snapshot(contents: &str) -> Result<Self, SuffixListError> {
+        let list = contents.parse::<publicsuffix::List>()
+            .map_err(|err| SuffixListError::Parse(err.to_string()))?;
+
+        if list.is_empty() {
+            return Err(SuffixListError::Parse(String::from("list contains no rules")));
+        }
+
+        // Version and commit are recorded in the header comments of official snapshots
+        let version = contents.lines()
+            .take_while(|line| line.is_empty() || line.starts_with("//"))
+            .filter_map(|line| line.strip_prefix("// VERSION: ").or_else(|| line.strip_prefix("// COMMIT: ")))
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        let version = if version.is_empty() { String::from("unversioned snapshot") } else { version };
+        Ok(SuffixList::Snapshot { list, version })
+    }
+
+    /// Description of the list in use, to record in run output.
+    pub fn version(&self) -> &str {
+        match self {
+            SuffixList::Builtin => "built-in",
+            SuffixList::Snapshot { version, .. } => version,
+        }
+    }
+
+    pub fn domain<'a>(&self, name: &'a [u8]) -> Option<Domain<'a>> {
+        match self {
+            SuffixList::Builtin => List.domain(name),
+            SuffixList::Snapshot { list, .. } => list.domain(name),
+        }
+    }
+}
+
+/// Download the latest public suffix list to `path` (requires the `psl-download` feature).
+#[cfg(feature = "psl-download")]
+pub fn download_suffix_list(path: &Path) -> Result<SuffixList, SuffixListError> {
+    let contents = ureq::get(PSL_URL).call()
+        .map_err(|err| SuffixListError::Download(err.to_string()))?
+        .into_string()
+        .map_err(SuffixListError::IO)?;
+
+    // Validate before overwriting an existing snapshot
+    let list = SuffixList::from_snapshot(&contents)?;
+    std::fs::write(path, contents).map_err(SuffixListError::IO)?;
+
+    Ok(list)
+}
+
+/// Download the latest public suffix list to `path` (requires the `psl-download` feature).
+#[cfg(not(feature = "psl-download"))]
+pub fn download_suffix_list(_path: &Path) -> Result<SuffixList, SuffixListError> {
+    Err(SuffixListError::Download(String::from("built without the psl-download feature")))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::suffix_list::SuffixList;
+
+    const SNAPSHOT: &str = "// VERSION: 2021-06-01_00-00-00_UTC\n// COMMIT: abc123\n\n// ===BEGIN ICANN DOMAINS===\ncom\nnewtld\n// ===END ICANN DOMAINS===\n";
+
+    #[test]
+    fn test_snapshot_version() {
+        let list = SuffixList::from_snapshot(SNAPSHOT).unwrap();
+        assert_eq!("2021-06-01_00-00-00_UTC, abc123", list.version());
+        assert_eq!("built-in", SuffixList::Builtin.version());
+    }
+
+    #[test]
+    fn test_snapshot_domain() {
+        let list = SuffixList::from_snapshot(SNAPSHOT).unwrap();
+
+        let domain = list.domain(b"label.domain.newtld").unwrap();
+        assert_eq!(b"domain.newtld", domain.as_bytes());
+        assert!(domain.suffix().is_known());
+
+        // Not in snapshot
+        assert!(!list.domain(b"label.domain.co.uk").unwrap().suffix().is_known());
+    }
+
+    #[test]
+    fn test_empty_snapshot() {
+        assert!(SuffixList::from_snapshot("// nothing here\n").is_err());
+    }
+}
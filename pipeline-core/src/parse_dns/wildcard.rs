@@ -0,0 +1,162 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::parse_dns::DnsPayload;
+
+/// Maximum number of distinct payload shapes tracked per primary domain, further shapes are only counted as queries.
+const MAX_SHAPES: usize = 32;
+
+/// Thresholds for wildcard-like primary domains: many unique first labels with a uniform payload structure, such as
+/// DNSBL lookups (`4.3.2.1.zen.example.org`) or per-object names of CDNs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WildcardConfig {
+    /// Minimum number of unique first (leftmost) payload labels.
+    pub min_unique_labels: usize,
+    /// Minimum share of queries with the most common payload shape.
+    pub min_shape_share: f32,
+}
+
+impl Default for WildcardConfig {
+    fn default() -> Self {
+        WildcardConfig { min_unique_labels: 1000, min_shape_share: 0.9 }
+    }
+}
+
+/// Character class of a label, for the payload shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LabelClass {
+    Digits,
+    Hex,
+    Alphanumeric,
+    Other,
+}
+
+/// Number of payload labels and the character classes of the first labels.
+type PayloadShape = (usize, [Option<LabelClass>; 4]);
+
+#[derive(Default)]
+struct WildcardState {
+    /// Hashes of the unique first labels, at most `min_unique_labels` of them.
+    first_labels: HashSet<u64>,
+    shapes: HashMap<PayloadShape, u32>,
+    n_queries: u32,
+    /// Number of queries since the domain was detected as wildcard-like.
+    n_wildcard: Option<u32>,
+}
+
+/// Streaming detection of wildcard-like primary domains. Detection is final, after which the per-domain sets are
+/// released.
+#[derive(Default)]
+pub struct WildcardDetector {
+    config: WildcardConfig,
+    domains: HashMap<u32, WildcardState>,
+}
+
+impl WildcardDetector {
+    pub fn new(config: WildcardConfig) -> Self {
+        WildcardDetector { config, domains: HashMap::new() }
+    }
+
+    /// Observe the payload of a query for a primary domain. Returns the index of the query since the domain was
+    /// detected as wildcard-like (starting at 0), or None if it is not (yet) detected.
+    pub fn observe(&mut self, prim_id: u32, payload: &DnsPayload) -> Option<u32> {
+        let config = &self.config;
+        let state = self.domains.entry(prim_id).or_default();
+
+        if let Some(n) = state.n_wildcard.as_mut() {
+            *n += 1;
+            return Some(*n);
+        }
+
+        state.n_queries += 1;
+        if let Some(label) = payload.labels.first() {
+            if state.first_labels.len() < config.min_unique_labels {
+                let mut hasher = DefaultHasher::new();
+                label.to_ascii_lowercase().hash(&mut hasher);
+                state.first_labels.insert(hasher.finish());
+            }
+        }
+
+        let shape = payload_shape(payload);
+        if let Some(count) = state.shapes.get_mut(&shape) {
+            *count += 1;
+        } else if state.shapes.len() < MAX_SHAPES {
+            state.shapes.insert(shape, 1);
+        }
+
+        if state.first_labels.len() >= config.min_unique_labels {
+            let max_shape_count = state.shapes.values().max().copied().unwrap_or(0);
+            if max_shape_count as f32 >= config.min_shape_share * state.n_queries as f32 {
+                *state = WildcardState { n_wildcard: Some(0), ..WildcardState::default() };
+                return Some(0);
+            }
+        }
+
+        None
+    }
+
+    /// Whether a primary domain was detected as wildcard-like.
+    pub fn is_wildcard(&self, prim_id: u32) -> bool {
+        self.domains.get(&prim_id).is_some_and(|state| state.n_wildcard.is_some())
+    }
+}
+
+fn payload_shape(payload: &DnsPayload) -> PayloadShape {
+    let mut classes = [None; 4];
+    payload.labels.iter().zip(classes.iter_mut()).for_each(|(label, class)| *class = Some(label_class(label)));
+    (payload.labels.len(), classes)
+}
+
+fn label_class(label: &[u8]) -> LabelClass {
+    if label.iter().all(u8::is_ascii_digit) {
+        LabelClass::Digits
+    } else if label.iter().all(u8::is_ascii_hexdigit) {
+        LabelClass::Hex
+    } else if label.iter().all(|c| c.is_ascii_alphanumeric() || c == &b'-' || c == &b'_') {
+        LabelClass::Alphanumeric
+    } else {
+        LabelClass::Other
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::DnsPayload;
+    use crate::parse_dns::wildcard::{WildcardConfig, WildcardDetector};
+
+    fn payload(labels: &[&str]) -> DnsPayload {
+        let labels: Vec<Vec<u8>> = labels.iter().map(|label| label.as_bytes().to_vec()).collect();
+        DnsPayload { payload_len: labels.iter().map(Vec::len).sum::<usize>() as u8, labels }
+    }
+
+    #[test]
+    fn test_dnsbl_detected() {
+        let mut detector = WildcardDetector::new(WildcardConfig { min_unique_labels: 10, min_shape_share: 0.9 });
+
+        // Reversed IPv4 addresses, one query with a different shape
+        assert_eq!(None, detector.observe(0, &payload(&["www"])));
+        for i in 0..8 {
+            assert_eq!(None, detector.observe(0, &payload(&[&i.to_string(), "2", "0", "192"])));
+        }
+        assert_eq!(Some(0), detector.observe(0, &payload(&["8", "2", "0", "192"])));
+        assert_eq!(Some(1), detector.observe(0, &payload(&["www"])));
+        assert!(detector.is_wildcard(0));
+    }
+
+    #[test]
+    fn test_mixed_structure_not_detected() {
+        let mut detector = WildcardDetector::new(WildcardConfig { min_unique_labels: 10, min_shape_share: 0.9 });
+
+        for i in 0..20 {
+            let label = format!("host{}", i);
+            let labels = if i % 2 == 0 { vec![label.as_str()] } else { vec![label.as_str(), "static"] };
+            assert_eq!(None, detector.observe(1, &payload(&labels)));
+        }
+
+        // Few unique labels
+        for _ in 0..20 { assert_eq!(None, detector.observe(2, &payload(&["1", "2", "0", "192"]))); }
+        assert!(!detector.is_wildcard(1) && !detector.is_wildcard(2));
+    }
+}
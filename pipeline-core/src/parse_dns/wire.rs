@@ -0,0 +1,186 @@
+use crate::parse_dns::{LABEL_SEP, parse_dns_with_config, ParseDnsConfig, ParseDnsError, ParsedDns};
+
+/// Size of the fixed DNS message header.
+const HEADER_LEN: usize = 12;
+
+/// Maximum number of compression pointers followed while decoding a name (guards against pointer loops).
+const MAX_POINTERS: usize = 16;
+
+/// Placeholder for separator bytes inside wire-format labels, so the textual filters do not split them.
+const SEP_PLACEHOLDER: u8 = b'_';
+
+/// Question section entry of a DNS message.
+#[derive(Debug, PartialEq)]
+pub struct WireQuestion {
+    pub labels: Vec<Vec<u8>>,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+/// Decode a wire-format name (length-prefixed labels) starting at `offset` in `buf`, following compression
+/// pointers. Returns the labels and the offset directly after the name.
+pub fn decode_wire_name(buf: &[u8], offset: usize) -> Result<(Vec<Vec<u8>>, usize), ParseDnsError> {
+    let mut labels = Vec::new();
+    let mut pos = offset;
+    let mut end = None;
+    let mut n_pointers = 0;
+    let mut name_len = 0;
+
+    loop {
+        let len = *buf.get(pos).ok_or(ParseDnsError::InvalidWireFormat)? as usize;
+
+        match len {
+            // Root label
+            0 => break,
+
+            // Compression pointer (two most significant bits set)
+            0xC0..=0xFF => {
+                let low = *buf.get(pos + 1).ok_or(ParseDnsError::InvalidWireFormat)? as usize;
+                n_pointers += 1;
+                if n_pointers > MAX_POINTERS { return Err(ParseDnsError::InvalidWireFormat); }
+
+                end.get_or_insert(pos + 2);
+                pos = ((len & 0x3F) << 8) | low;
+            }
+
+            // Regular label
+            1..=63 => {
+                let label = buf.get(pos + 1..pos + 1 + len).ok_or(ParseDnsError::InvalidWireFormat)?;
+                name_len += len + 1;
+                if name_len > 255 { return Err(ParseDnsError::InvalidDnsName); }
+
+                labels.push(label.to_vec());
+                pos += len + 1;
+            }
+
+            // Reserved label types
+            _ => return Err(ParseDnsError::InvalidWireFormat)
+        }
+    }
+
+    Ok((labels, end.unwrap_or(pos + 1)))
+}
+
+/// Decode the first question of a full DNS message payload.
+pub fn parse_dns_message(msg: &[u8]) -> Result<WireQuestion, ParseDnsError> {
+    if msg.len() < HEADER_LEN { return Err(ParseDnsError::InvalidWireFormat); }
+
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    if qdcount == 0 { return Err(ParseDnsError::InvalidWireFormat); }
+
+    let (labels, pos) = decode_wire_name(msg, HEADER_LEN)?;
+    let fixed = msg.get(pos..pos + 4).ok_or(ParseDnsError::InvalidWireFormat)?;
+
+    Ok(WireQuestion {
+        labels,
+        qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+        qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+    })
+}
+
+/// Parse and validate/filter a wire-format DNS name. Payload labels are kept as-is, so binary labels (including
+/// labels that contain a dot) are not altered by a text conversion.
+pub fn parse_dns_wire(name: &[u8], config: &ParseDnsConfig) -> Result<ParsedDns, ParseDnsError> {
+    let (labels, end) = decode_wire_name(name, 0)?;
+    if end != name.len() { return Err(ParseDnsError::InvalidWireFormat); }
+
+    parse_wire_labels(labels, config)
+}
+
+/// Parse and validate/filter the first question of a full DNS message payload. Returns the parsed query and QTYPE.
+pub fn parse_dns_wire_message(msg: &[u8], config: &ParseDnsConfig) -> Result<(ParsedDns, u16), ParseDnsError> {
+    let question = parse_dns_message(msg)?;
+    let parsed = parse_wire_labels(question.labels, config)?;
+
+    Ok((parsed, question.qtype))
+}
+
+fn parse_wire_labels(mut labels: Vec<Vec<u8>>, config: &ParseDnsConfig) -> Result<ParsedDns, ParseDnsError> {
+    // Textual name for the suffix list and filters, with separators inside labels replaced
+    let text_name = labels.iter()
+        .map(|label| label.iter().map(|&c| if c == LABEL_SEP { SEP_PLACEHOLDER } else { c }).collect::<Vec<u8>>())
+        .collect::<Vec<Vec<u8>>>()
+        .join(&LABEL_SEP);
+
+    let mut parsed = parse_dns_with_config(&text_name, config)?;
+
+    // Primary domain labels may not contain a separator (it would have been replaced above)
+    let n_prim_labels = parsed.primary_domain.split('.').count();
+    if labels[labels.len() - n_prim_labels..].iter().any(|label| label.contains(&LABEL_SEP)) {
+        return Err(ParseDnsError::InvalidPrim);
+    }
+
+    // Restore original payload labels (same lengths, so payload length does not change), without the leading labels
+    // that were stripped (`ParseDnsConfig::strip_labels`)
+    let n_payload_labels = labels.len() - n_prim_labels;
+    let n_stripped = n_payload_labels - parsed.payload.labels.len();
+    labels.truncate(n_payload_labels);
+    labels.drain(..n_stripped);
+    parsed.payload.labels = labels;
+
+    Ok(parsed)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::{ParseDnsConfig, ParseDnsError};
+    use crate::parse_dns::wire::{decode_wire_name, parse_dns_message, parse_dns_wire, parse_dns_wire_message};
+
+    const NAME: &[u8] = b"\x03a.b\x03cde\x07example\x03com\x00";
+
+    #[test]
+    fn test_decode_wire_name() {
+        let (labels, end) = decode_wire_name(NAME, 0).unwrap();
+        assert_eq!(vec![b"a.b".to_vec(), b"cde".to_vec(), b"example".to_vec(), b"com".to_vec()], labels);
+        assert_eq!(NAME.len(), end);
+
+        // Truncated name
+        assert!(decode_wire_name(&NAME[..6], 0).is_err());
+    }
+
+    #[test]
+    fn test_pointer_loop() {
+        assert_eq!(ParseDnsError::InvalidWireFormat, decode_wire_name(b"\x01a\xC0\x00", 0).unwrap_err());
+    }
+
+    #[test]
+    fn test_parse_dns_wire_keeps_binary_labels() {
+        let parsed = parse_dns_wire(NAME, &ParseDnsConfig::default()).unwrap();
+        assert_eq!("example.com", parsed.primary_domain);
+        assert_eq!(vec![b"a.b".to_vec(), b"cde".to_vec()], parsed.payload.labels);
+        assert_eq!(6, parsed.payload.payload_len);
+    }
+
+    #[test]
+    fn test_parse_dns_wire_strip_labels() {
+        let config = ParseDnsConfig { strip_labels: vec![String::from("www")], ..ParseDnsConfig::default() };
+
+        let parsed = parse_dns_wire(b"\x03WWW\x03a.b\x07example\x03com\x00", &config).unwrap();
+        assert_eq!(vec![b"a.b".to_vec()], parsed.payload.labels);
+        assert_eq!(3, parsed.payload.payload_len);
+
+        // Only leading labels are stripped, a query with only such labels has an empty payload
+        let parsed = parse_dns_wire(b"\x03abc\x03www\x07example\x03com\x00", &config).unwrap();
+        assert_eq!((vec![b"abc".to_vec(), b"www".to_vec()], 6), (parsed.payload.labels, parsed.payload.payload_len));
+        let parsed = parse_dns_wire(b"\x03www\x07example\x03com\x00", &config).unwrap();
+        assert_eq!((0, 0), (parsed.payload.labels.len(), parsed.payload.payload_len));
+    }
+
+    #[test]
+    fn test_parse_dns_message() {
+        let mut msg = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00".to_vec();
+        msg.extend_from_slice(NAME);
+        msg.extend_from_slice(b"\x00\x10\x00\x01");
+
+        let question = parse_dns_message(&msg).unwrap();
+        assert_eq!((16, 1), (question.qtype, question.qclass));
+
+        let (parsed, qtype) = parse_dns_wire_message(&msg, &ParseDnsConfig::default()).unwrap();
+        assert_eq!(16, qtype);
+        assert_eq!(2, parsed.payload.labels.len());
+
+        // Missing QTYPE/QCLASS
+        assert!(parse_dns_message(&msg[..msg.len() - 2]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+/// Detection of large backward jumps in the timestamps of a log, e.g. of merged logs of sensors with skewed clocks,
+/// which distort the time window features. Small jumps (out-of-order logging) below the threshold are ignored.
+#[derive(Debug, Clone)]
+pub struct ClockMonitor {
+    /// Minimum backward jump (seconds) that counts.
+    threshold: f64,
+    last_ts: Option<f64>,
+    n_jumps: usize,
+    largest_jump: f64,
+}
+
+impl ClockMonitor {
+    pub fn new(threshold: f64) -> Self {
+        ClockMonitor { threshold, last_ts: None, n_jumps: 0, largest_jump: 0. }
+    }
+
+    /// Observe the next timestamp, returns the size of the backward jump since the previous timestamp if it is larger
+    /// than the threshold.
+    pub fn observe(&mut self, ts: f64) -> Option<f64> {
+        let jump = self.last_ts.replace(ts).map(|last_ts| last_ts - ts).filter(|&jump| jump > self.threshold)?;
+        self.n_jumps += 1;
+        self.largest_jump = self.largest_jump.max(jump);
+        Some(jump)
+    }
+
+    pub fn n_jumps(&self) -> usize {
+        self.n_jumps
+    }
+
+    /// Largest backward jump in seconds (0 if there were none).
+    pub fn largest_jump(&self) -> f64 {
+        self.largest_jump
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::ClockMonitor;
+
+    #[test]
+    fn test_backward_jumps() {
+        let mut monitor = ClockMonitor::new(60.);
+        let jumps: Vec<Option<f64>> = [1000., 990., 1500., 1100., 1200., 1130.].iter().map(|&ts| monitor.observe(ts)).collect();
+
+        assert_eq!(vec![None, None, None, Some(400.), None, Some(70.)], jumps);
+        assert_eq!((2, 400.), (monitor.n_jumps(), monitor.largest_jump()));
+    }
+}
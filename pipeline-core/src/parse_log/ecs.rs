@@ -0,0 +1,63 @@
+use std::net::IpAddr;
+
+use crate::parse_log::ParseLineError;
+use crate::shared_interface::ClientPrefix;
+
+/// Parse an EDNS client subnet field (e.g. `192.0.2.0/24` or `2001:db8::/56`) into a truncated client prefix.
+/// Returns `Ok(None)` for empty fields (`""` or `"-"`).
+pub fn parse_client_prefix(field: &[u8]) -> Result<Option<ClientPrefix>, ParseLineError> {
+    let field = std::str::from_utf8(field).map_err(|_| ParseLineError::InvalidEcs)?.trim();
+    if field.is_empty() || field == "-" { return Ok(None); }
+
+    let (addr, source_len) = match field.split_once('/') {
+        Some((addr, len)) => (addr, Some(len.parse::<u8>().map_err(|_| ParseLineError::InvalidEcs)?)),
+        None => (field, None),
+    };
+
+    match addr.parse::<IpAddr>().map_err(|_| ParseLineError::InvalidEcs)? {
+        IpAddr::V4(addr) => {
+            let octets = mask(&addr.octets()[..3], source_len.unwrap_or(24));
+            Ok(Some(ClientPrefix::V4([octets[0], octets[1], octets[2]])))
+        }
+        IpAddr::V6(addr) => {
+            let octets = mask(&addr.octets()[..6], source_len.unwrap_or(48));
+            Ok(Some(ClientPrefix::V6([octets[0], octets[1], octets[2], octets[3], octets[4], octets[5]])))
+        }
+    }
+}
+
+/// Zero all bits after the first `prefix_len` bits.
+fn mask(octets: &[u8], prefix_len: u8) -> Vec<u8> {
+    octets.iter().enumerate().map(|(i, &octet)| {
+        let bits = (prefix_len as usize).saturating_sub(i * 8).min(8);
+        if bits == 0 { 0 } else { octet & (0xFF << (8 - bits)) }
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::ecs::parse_client_prefix;
+    use crate::shared_interface::ClientPrefix;
+
+    #[test]
+    fn test_ipv4_prefix() {
+        assert_eq!(Ok(Some(ClientPrefix::V4([192, 0, 2]))), parse_client_prefix(b"192.0.2.77/32"));
+        assert_eq!(Ok(Some(ClientPrefix::V4([192, 0, 2]))), parse_client_prefix(b"192.0.2.1"));
+        assert_eq!(Ok(Some(ClientPrefix::V4([10, 16, 0]))), parse_client_prefix(b"10.20.30.0/12"));
+    }
+
+    #[test]
+    fn test_ipv6_prefix() {
+        assert_eq!(Ok(Some(ClientPrefix::V6([0x20, 0x01, 0x0d, 0xb8, 0x12, 0x34]))), parse_client_prefix(b"2001:db8:1234:5678::/64"));
+        assert_eq!(Ok(Some(ClientPrefix::V6([0x20, 0x01, 0x0d, 0xb8, 0, 0]))), parse_client_prefix(b"2001:db8:1234::/32"));
+    }
+
+    #[test]
+    fn test_empty_and_invalid() {
+        assert_eq!(Ok(None), parse_client_prefix(b""));
+        assert_eq!(Ok(None), parse_client_prefix(b"-"));
+        assert!(parse_client_prefix(b"not-an-ip").is_err());
+        assert!(parse_client_prefix(b"192.0.2.0/x").is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use memchr::{memchr, memchr2};
+
+const HEX_SLASH: u8 = b'\\';
+const HEX_X: u8 = b'x';
+const PERCENT: u8 = b'%';
+
+/// Byte escapes decoded in queries in addition to Zeek-style `\xHH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EscapeSyntax {
+    /// BIND/tcpdump-style decimal escapes (`\DDD`, e.g. `\046`).
+    pub decimal: bool,
+    /// URL percent encoding (`%HH`).
+    pub percent: bool,
+    /// Doubled backslash for a literal backslash (`\\`).
+    pub double_backslash: bool,
+}
+
+impl FromStr for EscapeSyntax {
+    type Err = String;
+
+    /// Parse a comma-separated list of `decimal`, `percent` and `backslash`, or `zeek` for only `\xHH`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut syntax = EscapeSyntax::default();
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "decimal" => syntax.decimal = true,
+                "percent" => syntax.percent = true,
+                "backslash" => syntax.double_backslash = true,
+                "zeek" => {}
+                _ => return Err(format!("unknown escape syntax '{}', expected decimal, percent, backslash or zeek", name)),
+            }
+        }
+        Ok(syntax)
+    }
+}
+
+/// Decode byte escapes with the given syntaxes enabled (Zeek-style `\xHH` is always decoded).
+pub fn decode_escapes(input_slice: &[u8], syntax: EscapeSyntax) -> Option<Vec<u8>> {
+    let mut result: Vec<u8> = Vec::with_capacity(input_slice.len());
+    decode_escapes_into(input_slice, syntax, &mut result);
+    Some(result)
+}
+
+/// Same as `decode_escapes`, appending the decoded bytes to `result` (e.g. a buffer reused across lines).
+pub fn decode_escapes_into(input_slice: &[u8], syntax: EscapeSyntax, result: &mut Vec<u8>) {
+    if syntax == EscapeSyntax::default() { return decode_byte_escapes_into(input_slice, result); }
+
+    let find = |slice: &[u8]| if syntax.percent { memchr2(HEX_SLASH, PERCENT, slice) } else { memchr(HEX_SLASH, slice) };
+    let mut escape = match find(input_slice) {
+        Some(escape) => escape,
+        None => return result.extend_from_slice(input_slice),
+    };
+
+    let mut pos = 0;
+    loop {
+        result.extend_from_slice(&input_slice[pos..escape]);
+
+        let rest = &input_slice[escape + 1..];
+        pos = escape + if input_slice[escape] == PERCENT {
+            match rest {
+                [a, b, ..] => match parse_hex(a, b) {
+                    Some(hex) => { result.push(hex); 3 }
+                    None => { result.push(PERCENT); 1 }
+                },
+                _ => { result.push(PERCENT); 1 }
+            }
+        } else {
+            match rest {
+                [HEX_SLASH, ..] if syntax.double_backslash => { result.push(HEX_SLASH); 2 }
+                [a, b, c, ..] if syntax.decimal => match parse_decimal(a, b, c) {
+                    Some(byte) => { result.push(byte); 4 }
+                    None => decode_backslash(rest, result),
+                },
+                _ => decode_backslash(rest, result),
+            }
+        };
+
+        escape = match find(&input_slice[pos..]) {
+            Some(offset) => pos + offset,
+            None => break,
+        };
+    }
+    result.extend_from_slice(&input_slice[pos..]);
+}
+
+/// Decode Zeek-style `\xHH` byte escapes.
+pub fn decode_byte_escapes(input_slice: &[u8]) -> Option<Vec<u8>> {
+    let mut result: Vec<u8> = Vec::with_capacity(input_slice.len());
+    decode_byte_escapes_into(input_slice, &mut result);
+    Some(result)
+}
+
+/// Same as `decode_byte_escapes`, appending the decoded bytes to `result` (e.g. a buffer reused across lines).
+pub fn decode_byte_escapes_into(input_slice: &[u8], result: &mut Vec<u8>) {
+
+    // Most queries processed have no escaped bytes, copy them right away
+    let mut slash = match memchr(HEX_SLASH, input_slice) {
+        Some(slash) => slash,
+        None => return result.extend_from_slice(input_slice),
+    };
+
+    // Decode hex, copying the spans between backslashes in bulk
+    let mut pos = 0;
+    loop {
+        result.extend_from_slice(&input_slice[pos..slash]);
+
+        pos = slash + decode_backslash(&input_slice[slash + 1..], result);
+
+        slash = match memchr(HEX_SLASH, &input_slice[pos..]) {
+            Some(offset) => pos + offset,
+            None => break,
+        };
+    }
+    result.extend_from_slice(&input_slice[pos..]);
+}
+
+/// Decode a `\xHH` escape, given the input after the backslash. Returns the number of bytes consumed, including the
+/// backslash.
+fn decode_backslash(rest: &[u8], result: &mut Vec<u8>) -> usize {
+    match rest {
+        // Potentially valid byte escape
+        [HEX_X, a, b, ..] => {
+            match parse_hex(a, b) {
+                Some(hex) => result.push(hex),
+                None => result.extend_from_slice(&[HEX_SLASH, HEX_X, *a, *b]),
+            }
+            4
+        }
+        [HEX_X, a] => {
+            result.extend_from_slice(&[HEX_SLASH, HEX_X, *a]);
+            3
+        }
+        [HEX_X] => {
+            result.extend_from_slice(&[HEX_SLASH, HEX_X]);
+            2
+        }
+        // Backslash without x: the next byte is skipped
+        [_, ..] => {
+            result.push(HEX_SLASH);
+            2
+        }
+        [] => {
+            result.push(HEX_SLASH);
+            1
+        }
+    }
+}
+
+/// Escape bytes (e.g. a label from the input log) as text that `decode_byte_escapes` turns back into the same bytes:
+/// valid UTF-8 is kept, except backslashes and control characters other than tab, which are escaped as `\xHH` like
+/// the bytes of invalid UTF-8.
+pub fn escape_bytes(input: &[u8]) -> String {
+    let mut result = String::with_capacity(input.len());
+    escape_bytes_into(input, &mut result);
+    result
+}
+
+/// Same as `escape_bytes`, appending the text to `result`.
+pub fn escape_bytes_into(input: &[u8], result: &mut String) {
+    // (writing to a String does not fail)
+    let push_escape = |result: &mut String, byte: u8| { let _ = write!(result, "\\x{:02x}", byte); };
+    for chunk in input.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '\\' | '\x00'..='\x08' | '\x0a'..='\x1f' | '\x7f' => push_escape(result, ch as u8),
+                _ => result.push(ch),
+            }
+        }
+        chunk.invalid().iter().for_each(|&byte| push_escape(result, byte));
+    }
+}
+
+/// Parse three decimal digits as a byte, None if they are not digits or the value is over 255.
+fn parse_decimal(a: &u8, b: &u8, c: &u8) -> Option<u8> {
+    if !(a.is_ascii_digit() && b.is_ascii_digit() && c.is_ascii_digit()) { return None; }
+    let value = (a - b'0') as u16 * 100 + (b - b'0') as u16 * 10 + (c - b'0') as u16;
+    u8::try_from(value).ok()
+}
+
+fn parse_hex(a: &u8, b: &u8) -> Option<u8> {
+    match (byte_to_hex(a), byte_to_hex(b)) {
+        (Some(first), Some(second)) => Some(16 * first + second),
+        _ => None
+    }
+}
+
+fn byte_to_hex(byte: &u8) -> Option<u8> {
+    match byte {
+        b'a'..=b'f' => Some(10 + (byte - b'a')),
+        b'A'..=b'F' => Some(10 + (byte - b'A')),
+        b'0'..=b'9' => Some(byte - b'0'),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::hex::{byte_to_hex, parse_hex, decode_byte_escapes, decode_escapes, escape_bytes, EscapeSyntax, HEX_SLASH, HEX_X};
+
+    /// Previous byte-by-byte implementation, as reference.
+    fn decode_byte_escapes_bytewise(input_slice: &[u8]) -> Option<Vec<u8>> {
+    
+        // Pre-emptively check if slash in string, if not, just return owned vector
+        // Upside of this approach is that most queries processed have no escaped bytes, so faster than iterative copying
+        // Downside is extra iteration over input
+        if !input_slice.contains(&HEX_SLASH) {
+            return Some(input_slice.to_owned());
+        }
+    
+        // Decode hex
+        let mut result: Vec<u8> = Vec::with_capacity(input_slice.len());
+        let mut it = input_slice.iter();
+    
+        while let Some(ch) = it.next() {
+            if ch != &HEX_SLASH {
+                result.push(*ch);
+                continue;
+            }
+    
+            if it.next() == Some(&HEX_X) {
+                match (it.next(), it.next()) {
+                    (Some(a), Some(b)) => {
+                        // Potentially valid byte escape
+                        match parse_hex(a, b) {
+                            Some(hex) => result.push(hex),
+                            None => {
+                                result.push(HEX_SLASH);
+                                result.push(HEX_X);
+                                result.push(*a);
+                                result.push(*b);
+                            }
+                        }
+                    }
+                    (Some(a), None) => {
+                        result.push(HEX_SLASH);
+                        result.push(HEX_X);
+                        result.push(*a);
+                    }
+                    _ => {
+                        result.push(HEX_SLASH);
+                        result.push(HEX_X);
+                    }
+                }
+            } else {
+                result.push(HEX_SLASH);
+            }
+        }
+    
+        Some(result)
+    }
+
+    #[test]
+    fn test_decode_byte_escapes_matches_bytewise() {
+        // All inputs of up to 6 bytes over an alphabet with (partial) escapes
+        let alphabet = [HEX_SLASH, HEX_X, b'4', b'a', b'g', b'.'];
+        let mut inputs: Vec<Vec<u8>> = vec![Vec::new()];
+        for _ in 0..6 {
+            let longer: Vec<Vec<u8>> = inputs.iter()
+                .flat_map(|input| alphabet.iter().map(move |&c| [input.as_slice(), &[c]].concat()))
+                .collect();
+            for input in &longer {
+                assert_eq!(decode_byte_escapes_bytewise(input), decode_byte_escapes(input), "{:?}", String::from_utf8_lossy(input));
+            }
+            inputs = longer;
+        }
+    }
+
+    #[test]
+    fn test_decode_byte_escapes_valid() {
+        let test_str = br"\x54\x48\x45\x53\x49\x53\x4c\x49\x46\x45".to_vec();
+        let expected = b"THESISLIFE".to_vec();
+
+        assert_eq!(expected, decode_byte_escapes(&test_str).unwrap());
+    }
+
+    #[test]
+    fn test_decode_byte_escapes_invalid() {
+        let test_01 = b"".to_vec();
+        let test_02 = br"\".to_vec();
+        let test_03 = br"\x".to_vec();
+        let test_04 = br"\xA".to_vec();
+        let test_05 = br"\xGG".to_vec();
+        let test_06 = b"noHex".to_vec();
+
+        // Invalid escapes just pass through
+        assert_eq!(test_01, decode_byte_escapes(&test_01).unwrap());
+        assert_eq!(test_02, decode_byte_escapes(&test_02).unwrap());
+        assert_eq!(test_03, decode_byte_escapes(&test_03).unwrap());
+        assert_eq!(test_04, decode_byte_escapes(&test_04).unwrap());
+        assert_eq!(test_05, decode_byte_escapes(&test_05).unwrap());
+        assert_eq!(test_06, decode_byte_escapes(&test_06).unwrap());
+    }
+
+    #[test]
+    fn test_escape_bytes() {
+        assert_eq!("benign", escape_bytes(b"benign"));
+        assert_eq!("b\u{e9}nin\tC2", escape_bytes("bénin\tC2".as_bytes()));
+        assert_eq!(r"a\x5cb\x0a\xff\xc3", escape_bytes(b"a\\b\n\xff\xc3"));
+
+        // Lossless: decodes to the same bytes
+        for input in [&b"\xff\xfe"[..], b"\\x41", "ä\\".as_bytes(), b"\x00\x7f\xc3\xa4\xa4"] {
+            assert_eq!(input, &decode_byte_escapes(escape_bytes(input).as_bytes()).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn test_decode_alternative_escapes() {
+        let all: EscapeSyntax = "decimal,percent,backslash".parse().unwrap();
+        assert_eq!(b"a.b\\c%zz\xff%".to_vec(), decode_escapes(br"a\046b\\c%zz%ff%", all).unwrap());
+        assert_eq!(b"T\xff9".to_vec(), decode_escapes(br"\x54\2559", all).unwrap());
+
+        // Out of range or too short decimal escapes are not decoded
+        let decimal = EscapeSyntax { decimal: true, ..EscapeSyntax::default() };
+        assert_eq!(br"\56".to_vec(), decode_escapes(br"\256", decimal).unwrap());
+        assert_eq!(br"\4".to_vec(), decode_escapes(br"\04", decimal).unwrap());
+
+        // Only enabled syntaxes
+        let percent = EscapeSyntax { percent: true, ..EscapeSyntax::default() };
+        assert_eq!(b"a b\\46".to_vec(), decode_escapes(br"a%20b\046", percent).unwrap());
+        assert_eq!(br"%20".to_vec(), decode_escapes(br"%20", EscapeSyntax::default()).unwrap());
+
+        assert!("percent,url".parse::<EscapeSyntax>().is_err());
+        assert_eq!(Ok(EscapeSyntax::default()), "zeek".parse());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(0, parse_hex(&b'0', &b'0').unwrap());
+
+        assert_eq!(26, parse_hex(&b'1', &b'a').unwrap());
+        assert_eq!(26, parse_hex(&b'1', &b'A').unwrap());
+
+        assert_eq!(161, parse_hex(&b'a', &b'1').unwrap());
+        assert_eq!(161, parse_hex(&b'A', &b'1').unwrap());
+
+        assert_eq!(255, parse_hex(&b'F', &b'F').unwrap());
+
+        assert!(parse_hex(&b'G', &b'G').is_none());
+    }
+
+    #[test]
+    fn test_byte_to_hex_valid() {
+        let result_lower: Vec<u8> = b"abcdef".iter().map(|b| byte_to_hex(b).unwrap()).collect();
+        let result_upper: Vec<u8> = b"ABCDEF".iter().map(|b| byte_to_hex(b).unwrap()).collect();
+        let result_digits: Vec<u8> = b"0123456789".iter().map(|b| byte_to_hex(b).unwrap()).collect();
+
+        assert_eq!(vec![10, 11, 12, 13, 14, 15], result_lower);
+        assert_eq!(vec![10, 11, 12, 13, 14, 15], result_upper);
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9], result_digits);
+    }
+
+    #[test]
+    fn test_byte_to_hex_invalid() {
+        let invalids: Vec<Option<u8>> = b"GZ\\\xEB\xE1_\x00".iter().map(byte_to_hex).collect();
+
+        for invalid in invalids {
+            assert!(invalid.is_none());
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,78 @@
+use std::io::{Read, Result};
+
+/// Reader that turns all line endings of a log into `\n`: `\r\n` (Windows) as well as a lone `\r` (e.g. exports of some
+/// Windows and classic Mac OS tools), so the input can be split into lines on `\n` alone.
+pub struct NormalizeLineEndings<R> {
+    inner: R,
+    /// Whether the last byte read was a `\r`, whose `\n` (if any) is dropped.
+    after_cr: bool,
+}
+
+impl<R: Read> NormalizeLineEndings<R> {
+    pub fn new(inner: R) -> Self {
+        NormalizeLineEndings { inner, after_cr: false }
+    }
+}
+
+impl<R: Read> Read for NormalizeLineEndings<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 { return Ok(0); }
+
+            // Rewrite in place, \r becomes \n and the \n of \r\n is dropped
+            let mut len = 0;
+            for i in 0..n {
+                let byte = buf[i];
+                if byte == b'\n' && self.after_cr {
+                    self.after_cr = false;
+                    continue;
+                }
+                self.after_cr = byte == b'\r';
+                buf[len] = if self.after_cr { b'\n' } else { byte };
+                len += 1;
+            }
+
+            // (nothing left if the read was only the \n of a \r\n split across reads, which is not the end)
+            if len > 0 { return Ok(len); }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::parse_log::NormalizeLineEndings;
+
+    /// Reader that returns one byte per read, to split line endings across reads.
+    struct Bytewise<'a>(&'a [u8]);
+
+    impl Read for Bytewise<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let input = b"0\ta\r\n1\tb\r2\tc\n\r\n3\td\r";
+        let expected = b"0\ta\n1\tb\n2\tc\n\n3\td\n".to_vec();
+
+        let mut out = Vec::new();
+        NormalizeLineEndings::new(&input[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(expected, out);
+
+        let mut out = Vec::new();
+        NormalizeLineEndings::new(Bytewise(input)).read_to_end(&mut out).unwrap();
+        assert_eq!(expected, out);
+    }
+}
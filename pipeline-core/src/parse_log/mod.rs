@@ -0,0 +1,614 @@
+use std::borrow::Cow;
+
+use chrono_tz::Tz;
+
+use crate::shared_interface::ClientPrefix;
+
+pub use self::clock::ClockMonitor;
+pub use self::hex::{decode_byte_escapes, decode_byte_escapes_into, decode_escapes, decode_escapes_into, escape_bytes, escape_bytes_into, EscapeSyntax};
+pub use self::line_endings::NormalizeLineEndings;
+pub use self::separator::{detect_separator, SEPARATOR_CANDIDATES};
+pub use self::sequence::SequenceGaps;
+
+mod clock;
+mod ecs;
+mod hex;
+mod line_endings;
+mod separator;
+mod sequence;
+mod timestamp;
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ParseLineError {
+    #[error("field separator not found")]
+    SepNotFound,
+    #[error("invalid timestamp")]
+    InvalidTimestamp,
+    #[error("invalid query field")]
+    InvalidQuery,
+    #[error("invalid client field")]
+    InvalidClient,
+    #[error("invalid resolver field")]
+    InvalidResolver,
+    #[error("invalid EDNS client subnet")]
+    InvalidEcs,
+    #[error("invalid label field")]
+    InvalidLabel,
+    #[error("invalid TTL")]
+    InvalidTtl,
+    #[error("invalid CNAME depth")]
+    InvalidCnameDepth,
+    #[error("invalid sequence number")]
+    InvalidSequence,
+}
+
+/// Fields of a parsed log line.
+#[derive(Debug, PartialEq)]
+pub struct ParsedLine {
+    pub ts: f64,
+    /// Client identifier, if the schema has a client field.
+    pub client: Option<Vec<u8>>,
+    /// Resolver identifier (e.g. server address), if the schema has a resolver field.
+    pub resolver: Option<Vec<u8>>,
+    /// Truncated EDNS client subnet, if the schema has an ECS field and the field is not empty.
+    pub client_prefix: Option<ClientPrefix>,
+    /// Label or verdict of the query (e.g. from a blocklist match), if the schema has a label field and it is not empty.
+    /// Bytes that are not text are escaped (see `escape_bytes`).
+    pub label: Option<String>,
+    /// Response TTL in seconds, if the schema has a TTL field and the field is not empty or "-".
+    pub ttl: Option<u32>,
+    /// Length of the CNAME chain of the response, if the schema has a CNAME field and the field is not empty or "-".
+    pub cname_depth: Option<u8>,
+    /// DNS transaction ID or log sequence number, if the schema has a sequence field and the field is not empty or "-".
+    pub seq: Option<u64>,
+    pub query: Vec<u8>,
+}
+
+const R_BYTE: u8 = b'\r';
+const N_BYTE: u8 = b'\n';
+
+/// Layout of a line in the textual input log: {TS}{SEP}{QUERY}{NEWLINE}, with optional client, resolver, EDNS client
+/// subnet, label, TTL, CNAME depth and sequence number fields before the query:
+/// {TS}{SEP}{CLIENT}{SEP}{RESOLVER}{SEP}{ECS}{SEP}{LABEL}{SEP}{TTL}{SEP}{CNAME}{SEP}{SEQ}{SEP}{QUERY}{NEWLINE}.
+#[derive(Debug, Clone)]
+pub struct LogSchema {
+    /// Field separator, may consist of multiple bytes.
+    pub separator: Vec<u8>,
+    /// Optional quote character for CSV-style quoted fields (a doubled quote inside a quoted field is a literal quote).
+    pub quote: Option<u8>,
+    /// Time zone for textual timestamps without offset (epoch timestamps are always UTC).
+    pub assume_tz: Tz,
+    /// Whether a client identifier (e.g. source address) field precedes the query.
+    pub client_field: bool,
+    /// Whether a resolver identifier (e.g. server address) field precedes the query (after the client field).
+    pub resolver_field: bool,
+    /// Whether an EDNS client subnet field (e.g. `192.0.2.0/24`) precedes the query (after the resolver field).
+    pub ecs_field: bool,
+    /// Whether a label field (e.g. a verdict from a prior blocklist match) precedes the query (after the ECS field).
+    pub label_field: bool,
+    /// Whether a response TTL field (e.g. Zeek `TTLs`, of which the first is used) precedes the query (after the label
+    /// field).
+    pub ttl_field: bool,
+    /// Whether a CNAME chain length field precedes the query (after the TTL field).
+    pub cname_field: bool,
+    /// Whether a DNS transaction ID or log sequence number field precedes the query (after the CNAME field).
+    pub seq_field: bool,
+    /// Byte escapes in the query decoded in addition to `\xHH`.
+    pub escapes: EscapeSyntax,
+    /// Number of decimals retained in timestamps (e.g. 3 for milliseconds), truncating the rest, or all if None.
+    pub ts_decimals: Option<u8>,
+}
+
+impl Default for LogSchema {
+    fn default() -> Self {
+        LogSchema { separator: vec![b'\t'], quote: None, assume_tz: Tz::UTC, client_field: false, resolver_field: false, ecs_field: false, label_field: false, ttl_field: false, cname_field: false, seq_field: false, escapes: EscapeSyntax::default(), ts_decimals: None }
+    }
+}
+
+/// Parse a line of bytes and return the timestamp as f64 and query as &str.
+/// Expects the input in the form {TS}{TAB}{QUERY}{NEWLINE}, will NOT check for validity.
+/// TODO: maybe check first line for validation
+pub fn parse_log_line(line: &[u8], sep: u8) -> Result<(f64, Vec<u8>), ParseLineError> {
+    parse_log_line_with_schema(line, &LogSchema { separator: vec![sep], ..LogSchema::default() })
+}
+
+/// Parse a line of bytes according to the given schema and return the timestamp as f64 and query as bytes.
+pub fn parse_log_line_with_schema(line: &[u8], schema: &LogSchema) -> Result<(f64, Vec<u8>), ParseLineError> {
+    parse_log_line_with_client(line, schema).map(|parsed| (parsed.ts, parsed.query))
+}
+
+/// Parse a line of bytes according to the given schema and return the timestamp, client and resolver information and
+/// label (if the schema has client, resolver, ECS or label fields) and query.
+pub fn parse_log_line_with_client(line: &[u8], schema: &LogSchema) -> Result<ParsedLine, ParseLineError> {
+    let fields = split_line(line, schema)?;
+    let query = hex::decode_escapes(&fields.query, schema.escapes).ok_or(ParseLineError::InvalidQuery)?;
+
+    Ok(ParsedLine {
+        ts: fields.ts,
+        client: fields.client.map(Cow::into_owned),
+        resolver: fields.resolver.map(Cow::into_owned),
+        client_prefix: fields.client_prefix,
+        label: fields.label.map(|label| hex::escape_bytes(&label)),
+        ttl: fields.ttl,
+        cname_depth: fields.cname_depth,
+        seq: fields.seq,
+        query,
+    })
+}
+
+/// Fields of a parsed log line, borrowed from the buffers of a `LineParser`.
+#[derive(Debug, PartialEq)]
+pub struct ParsedLineRef<'a> {
+    pub ts: f64,
+    pub client: Option<&'a [u8]>,
+    pub resolver: Option<&'a [u8]>,
+    pub client_prefix: Option<ClientPrefix>,
+    pub label: Option<&'a str>,
+    pub ttl: Option<u32>,
+    pub cname_depth: Option<u8>,
+    pub seq: Option<u64>,
+    pub query: &'a [u8],
+}
+
+/// Log line parser with the same output as `parse_log_line_with_client`, which decodes into buffers that are reused
+/// for every line instead of allocating the query, client, resolver and label of each line.
+#[derive(Debug)]
+pub struct LineParser {
+    schema: LogSchema,
+    query: Vec<u8>,
+    client: Vec<u8>,
+    resolver: Vec<u8>,
+    label: String,
+}
+
+impl LineParser {
+    pub fn new(schema: LogSchema) -> Self {
+        LineParser { schema, query: Vec::new(), client: Vec::new(), resolver: Vec::new(), label: String::new() }
+    }
+
+    pub fn schema(&self) -> &LogSchema {
+        &self.schema
+    }
+
+    /// Parse a line, the fields are valid until the next line is parsed.
+    pub fn parse(&mut self, line: &[u8]) -> Result<ParsedLineRef<'_>, ParseLineError> {
+        let LineParser { schema, query, client, resolver, label } = self;
+        let fields = split_line(line, schema)?;
+
+        query.clear();
+        hex::decode_escapes_into(&fields.query, schema.escapes, query);
+        let client = fields.client.map(move |field| reuse(client, &field));
+        let resolver = fields.resolver.map(move |field| reuse(resolver, &field));
+        let label = fields.label.map(move |field| {
+            label.clear();
+            hex::escape_bytes_into(&field, label);
+            label.as_str()
+        });
+
+        Ok(ParsedLineRef { ts: fields.ts, client, resolver, client_prefix: fields.client_prefix, label, ttl: fields.ttl, cname_depth: fields.cname_depth, seq: fields.seq, query })
+    }
+}
+
+/// Copy `field` into `buffer` (keeping its allocation).
+fn reuse<'a>(buffer: &'a mut Vec<u8>, field: &[u8]) -> &'a [u8] {
+    buffer.clear();
+    buffer.extend_from_slice(field);
+    buffer
+}
+
+/// Fields of a line with quotes removed (borrowed from the line, unless quotes were unescaped) and the query not
+/// decoded yet.
+struct LineFields<'a> {
+    ts: f64,
+    client: Option<Cow<'a, [u8]>>,
+    resolver: Option<Cow<'a, [u8]>>,
+    client_prefix: Option<ClientPrefix>,
+    /// Not empty
+    label: Option<Cow<'a, [u8]>>,
+    ttl: Option<u32>,
+    cname_depth: Option<u8>,
+    seq: Option<u64>,
+    query: Cow<'a, [u8]>,
+}
+
+/// Split a line into its fields according to the schema and parse all but the query.
+fn split_line<'a>(line: &'a [u8], schema: &LogSchema) -> Result<LineFields<'a>, ParseLineError> {
+
+    // Trim \n, \r\n or a lone \r
+    let mut line = match line.last() {
+        Some(byte) if byte == &N_BYTE || byte == &R_BYTE => &line[..line.len() - 1],
+        _ => return Err(ParseLineError::InvalidQuery), // Query nor newline
+    };
+    if line.last() == Some(&R_BYTE) { line = &line[..line.len() - 1] };
+
+    // Split timestamp and query fields (a quoted timestamp may not contain the separator)
+    let (ts_field, q_field) = split_fields(line, schema)?;
+    let ts_slice = unquote(ts_field, schema.quote).ok_or(ParseLineError::InvalidTimestamp)?;
+
+    // Split client and query fields
+    let (client, q_field) = if schema.client_field {
+        let (client_field, q_field) = split_fields(q_field, schema)?;
+        let client = unquote(client_field, schema.quote).ok_or(ParseLineError::InvalidClient)?;
+        (Some(client), q_field)
+    } else { (None, q_field) };
+
+    // Split resolver and query fields
+    let (resolver, q_field) = if schema.resolver_field {
+        let (resolver_field, q_field) = split_fields(q_field, schema)?;
+        let resolver = unquote(resolver_field, schema.quote).ok_or(ParseLineError::InvalidResolver)?;
+        (Some(resolver), q_field)
+    } else { (None, q_field) };
+
+    // Split EDNS client subnet and query fields
+    let (client_prefix, q_field) = if schema.ecs_field {
+        let (ecs_field, q_field) = split_fields(q_field, schema)?;
+        let ecs = unquote(ecs_field, schema.quote).ok_or(ParseLineError::InvalidEcs)?;
+        (ecs::parse_client_prefix(&ecs)?, q_field)
+    } else { (None, q_field) };
+
+    // Split label and query fields, the label is passed through untouched
+    let (label, q_field) = if schema.label_field {
+        let (label_field, q_field) = split_fields(q_field, schema)?;
+        let label = unquote(label_field, schema.quote).ok_or(ParseLineError::InvalidLabel)?;
+        (Some(label).filter(|label| !label.is_empty()), q_field)
+    } else { (None, q_field) };
+
+    // Split TTL and query fields
+    let (ttl, q_field) = if schema.ttl_field {
+        let (ttl_field, q_field) = split_fields(q_field, schema)?;
+        let ttl = unquote(ttl_field, schema.quote).ok_or(ParseLineError::InvalidTtl)?;
+        (parse_ttl(&ttl)?, q_field)
+    } else { (None, q_field) };
+
+    // Split CNAME depth and query fields
+    let (cname_depth, q_field) = if schema.cname_field {
+        let (cname_field, q_field) = split_fields(q_field, schema)?;
+        let cname = unquote(cname_field, schema.quote).ok_or(ParseLineError::InvalidCnameDepth)?;
+        (parse_cname_depth(&cname)?, q_field)
+    } else { (None, q_field) };
+
+    // Split sequence number and query fields
+    let (seq, q_field) = if schema.seq_field {
+        let (seq_field, q_field) = split_fields(q_field, schema)?;
+        let seq = unquote(seq_field, schema.quote).ok_or(ParseLineError::InvalidSequence)?;
+        (parse_seq(&seq)?, q_field)
+    } else { (None, q_field) };
+
+    let query = unquote(q_field, schema.quote).ok_or(ParseLineError::InvalidQuery)?;
+
+    // Parse timestamp as (finite) f64, falling back to textual timestamps. Decimals of numeric timestamps are truncated
+    // in the text, which is exact (unlike truncating the parsed value)
+    let numeric = schema.ts_decimals.map_or(&ts_slice[..], |decimals| timestamp::truncate_decimal_digits(&ts_slice, decimals));
+    let ts = fast_float::parse::<f64, _>(numeric).ok()
+        .or_else(|| {
+            timestamp::parse_textual_timestamp(&ts_slice, &schema.assume_tz)
+                .map(|ts| schema.ts_decimals.map_or(ts, |decimals| timestamp::truncate_timestamp(ts, decimals)))
+        });
+
+    match ts {
+        Some(ts) if ts.is_finite() => Ok(LineFields { ts, client, resolver, client_prefix, label, ttl, cname_depth, seq, query }),
+        _ => Err(ParseLineError::InvalidTimestamp)
+    }
+}
+
+/// Parse the first TTL of a (comma-separated) TTL field, fractional seconds are truncated. Empty or "-" means no TTL.
+fn parse_ttl(field: &[u8]) -> Result<Option<u32>, ParseLineError> {
+    let first = field.split(|ch| *ch == b',').next().unwrap_or_default();
+    if first.is_empty() || first == b"-" { return Ok(None); }
+
+    match fast_float::parse::<f64, _>(first) {
+        Ok(ttl) if ttl >= 0. && ttl <= u32::MAX as f64 => Ok(Some(ttl as u32)),
+        _ => Err(ParseLineError::InvalidTtl)
+    }
+}
+
+/// Parse a CNAME chain length (saturating at 255). Empty or "-" means unknown.
+fn parse_cname_depth(field: &[u8]) -> Result<Option<u8>, ParseLineError> {
+    if field.is_empty() || field == b"-" { return Ok(None); }
+
+    match std::str::from_utf8(field).ok().and_then(|field| field.parse::<u32>().ok()) {
+        Some(depth) => Ok(Some(depth.min(u8::MAX as u32) as u8)),
+        None => Err(ParseLineError::InvalidCnameDepth)
+    }
+}
+
+/// Parse a (decimal) transaction ID or sequence number. Empty or "-" means unknown.
+fn parse_seq(field: &[u8]) -> Result<Option<u64>, ParseLineError> {
+    if field.is_empty() || field == b"-" { return Ok(None); }
+
+    match std::str::from_utf8(field).ok().and_then(|field| field.parse::<u64>().ok()) {
+        Some(seq) => Ok(Some(seq)),
+        None => Err(ParseLineError::InvalidSequence)
+    }
+}
+
+/// Split line into the part before and after the first separator.
+fn split_fields<'a>(line: &'a [u8], schema: &LogSchema) -> Result<(&'a [u8], &'a [u8]), ParseLineError> {
+    let sep = &schema.separator[..];
+
+    let sep_index = match sep {
+        [] => None,
+        [byte] => line.iter().position(|c| c == byte),
+        _ => line.windows(sep.len()).position(|window| window == sep),
+    };
+
+    match sep_index {
+        Some(idx) => Ok((&line[..idx], &line[(idx + sep.len())..])),
+        None => Err(ParseLineError::SepNotFound)
+    }
+}
+
+/// Remove surrounding quotes from a field and unescape doubled quotes. Returns None for unbalanced quotes.
+fn unquote(field: &[u8], quote: Option<u8>) -> Option<Cow<'_, [u8]>> {
+    let quote = match quote {
+        Some(quote) if field.first() == Some(&quote) => quote,
+        _ => return Some(Cow::Borrowed(field)),
+    };
+
+    if field.len() < 2 || field.last() != Some(&quote) { return None; }
+    let inner = &field[1..field.len() - 1];
+
+    // Fast path: no quotes inside
+    if !inner.contains(&quote) { return Some(Cow::Borrowed(inner)); }
+
+    let mut result = Vec::with_capacity(inner.len());
+    let mut it = inner.iter();
+    while let Some(ch) = it.next() {
+        result.push(*ch);
+        if *ch == quote && it.next() != Some(&quote) { return None; }
+    }
+    Some(Cow::Owned(result))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::{LineParser, LogSchema, parse_log_line, parse_log_line_with_client, parse_log_line_with_schema, ParseLineError};
+    use crate::shared_interface::ClientPrefix;
+
+    #[test]
+    fn test_parse_log_line() {
+        let (ts, q) = parse_log_line(b"0	a\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a']);
+    }
+
+    #[test]
+    fn test_parse_log_line_no_linefeed() {
+        assert!(parse_log_line(b"0	a", b'\t').is_err());
+    }
+
+    #[test]
+    fn test_parse_log_line_rn() {
+        let (ts, q) = parse_log_line(b"0	a\r\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a']);
+    }
+
+    #[test]
+    fn test_parse_log_line_r() {
+        let (ts, q) = parse_log_line(b"0	a\r", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a']);
+    }
+
+    #[test]
+    fn test_parse_log_line_byte_encoded_r() {
+        let (ts, q) = parse_log_line(b"0	a\\x0d\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a', b'\r']);
+    }
+
+    #[test]
+    fn test_parse_log_line_byte_encoded_n() {
+        let (ts, q) = parse_log_line(b"0	a\\x0a\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a', b'\n']);
+    }
+
+    #[test]
+    fn test_parse_log_line_hex() {
+        let (ts, q) = parse_log_line(b"0	a\\xff\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'a', 255]);
+    }
+
+    #[test]
+    fn test_parse_log_line_hex_accent() {
+        let (ts, q) = parse_log_line(b"0	ex\xc3\xa4mple.com\n", b'\t').unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, vec![b'e', b'x', b'\xc3', b'\xa4', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm']);
+    }
+
+    #[test]
+    fn test_parse_log_line_multi_byte_separator() {
+        let schema = LogSchema { separator: b"||".to_vec(), quote: None, ..LogSchema::default() };
+        let (ts, q) = parse_log_line_with_schema(b"1.5||a|b\n", &schema).unwrap();
+        assert_eq!(ts, 1.5);
+        assert_eq!(q, b"a|b".to_vec());
+
+        assert!(parse_log_line_with_schema(b"1.5|a\n", &schema).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_line_quoted() {
+        let schema = LogSchema { separator: vec![b','], quote: Some(b'"'), ..LogSchema::default() };
+
+        let (ts, q) = parse_log_line_with_schema(b"\"0\",\"data.example.com\"\r\n", &schema).unwrap();
+        assert_eq!(ts, 0.);
+        assert_eq!(q, b"data.example.com".to_vec());
+
+        // Separator and escaped quotes inside quoted query
+        let (_, q) = parse_log_line_with_schema(b"0,\"a,\"\"b\"\"\"\n", &schema).unwrap();
+        assert_eq!(q, b"a,\"b\"".to_vec());
+
+        // Unquoted fields still work
+        let (_, q) = parse_log_line_with_schema(b"0,a.example.com\n", &schema).unwrap();
+        assert_eq!(q, b"a.example.com".to_vec());
+    }
+
+    #[test]
+    fn test_parse_log_line_unbalanced_quotes() {
+        let schema = LogSchema { separator: vec![b','], quote: Some(b'"'), ..LogSchema::default() };
+        assert!(parse_log_line_with_schema(b"0,\"a.example.com\n", &schema).is_err());
+        assert!(parse_log_line_with_schema(b"0,\"a\"b\"\n", &schema).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_line_textual_timestamp() {
+        let (ts, q) = parse_log_line(b"2021-03-04T12:00:00Z\ta\n", b'\t').unwrap();
+        assert_eq!(ts, 1614859200.);
+        assert_eq!(q, vec![b'a']);
+
+        let schema = LogSchema { assume_tz: chrono_tz::Tz::Europe__Amsterdam, ..LogSchema::default() };
+        let (ts, _) = parse_log_line_with_schema(b"2021-03-04 13:00:00\ta\n", &schema).unwrap();
+        assert_eq!(ts, 1614859200.);
+    }
+
+    #[test]
+    fn test_parse_log_line_ts_decimals() {
+        let schema = LogSchema { ts_decimals: Some(3), ..LogSchema::default() };
+        let (ts, _) = parse_log_line_with_schema(b"1614859200.123987\ta\n", &schema).unwrap();
+        assert_eq!(ts, 1614859200.123);
+
+        let schema = LogSchema { ts_decimals: Some(0), ..LogSchema::default() };
+        let (ts, _) = parse_log_line_with_schema(b"2021-03-04T12:00:00.75Z\ta\n", &schema).unwrap();
+        assert_eq!(ts, 1614859200.);
+
+        // Exact for every number of decimals, e.g. .9994 is not pulled down to .9993 by floating-point error
+        let schema = LogSchema { ts_decimals: Some(4), ..LogSchema::default() };
+        for i in 0..10_000 {
+            let expected = format!("1614859200.{:04}", i);
+            let (ts, _) = parse_log_line_with_schema(format!("{}7\ta\n", expected).as_bytes(), &schema).unwrap();
+            assert_eq!(expected.parse::<f64>().unwrap(), ts, "{}", expected);
+        }
+        let (ts, _) = parse_log_line_with_schema(b"1614859200\ta\n", &schema).unwrap();
+        assert_eq!(ts, 1614859200.);
+    }
+
+    #[test]
+    fn test_parse_log_line_with_client() {
+        let schema = LogSchema { client_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\tab.example.com\n", &schema).unwrap();
+        assert_eq!(1.5, parsed.ts);
+        assert_eq!(Some(b"10.0.0.1".to_vec()), parsed.client);
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        // Without client field, the client is part of the query
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\tab.example.com\n", &LogSchema::default()).unwrap();
+        assert_eq!(None, parsed.client);
+        assert_eq!(b"10.0.0.1\tab.example.com".to_vec(), parsed.query);
+
+        // Missing query field
+        assert!(parse_log_line_with_client(b"1.5\t10.0.0.1\n", &schema).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_line_with_ecs() {
+        let schema = LogSchema { client_field: true, ecs_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\t192.0.2.0/24\tab.example.com\n", &schema).unwrap();
+        assert_eq!(Some(ClientPrefix::V4([192, 0, 2])), parsed.client_prefix);
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\t-\tab.example.com\n", &schema).unwrap();
+        assert_eq!(None, parsed.client_prefix);
+
+        assert_eq!(Err(ParseLineError::InvalidEcs), parse_log_line_with_client(b"1.5\t10.0.0.1\tbogus\tab.example.com\n", &schema));
+    }
+
+    #[test]
+    fn test_parse_log_line_with_resolver() {
+        let schema = LogSchema { client_field: true, resolver_field: true, ecs_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\t192.0.2.53\t-\tab.example.com\n", &schema).unwrap();
+        assert_eq!(Some(b"10.0.0.1".to_vec()), parsed.client);
+        assert_eq!(Some(b"192.0.2.53".to_vec()), parsed.resolver);
+        assert_eq!(None, parsed.client_prefix);
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\tab.example.com\n", &LogSchema { client_field: true, ..LogSchema::default() }).unwrap();
+        assert_eq!(None, parsed.resolver);
+    }
+
+    #[test]
+    fn test_parse_log_line_with_ttl() {
+        let schema = LogSchema { ttl_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t60.000000,300.000000\tab.example.com\n", &schema).unwrap();
+        assert_eq!(Some(60), parsed.ttl);
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        assert_eq!(None, parse_log_line_with_client(b"1.5\t-\tab.example.com\n", &schema).unwrap().ttl);
+        assert_eq!(Some(0), parse_log_line_with_client(b"1.5\t0\tab.example.com\n", &schema).unwrap().ttl);
+        assert_eq!(Err(ParseLineError::InvalidTtl), parse_log_line_with_client(b"1.5\t-1\tab.example.com\n", &schema));
+    }
+
+    #[test]
+    fn test_parse_log_line_with_cname_depth() {
+        let schema = LogSchema { ttl_field: true, cname_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t60\t2\tab.example.com\n", &schema).unwrap();
+        assert_eq!((Some(60), Some(2)), (parsed.ttl, parsed.cname_depth));
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        assert_eq!(None, parse_log_line_with_client(b"1.5\t60\t-\tab.example.com\n", &schema).unwrap().cname_depth);
+        assert_eq!(Some(255), parse_log_line_with_client(b"1.5\t60\t1000\tab.example.com\n", &schema).unwrap().cname_depth);
+        assert_eq!(Err(ParseLineError::InvalidCnameDepth), parse_log_line_with_client(b"1.5\t60\tx\tab.example.com\n", &schema));
+    }
+
+    #[test]
+    fn test_parse_log_line_with_seq() {
+        let schema = LogSchema { cname_field: true, seq_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t1\t65535\tab.example.com\n", &schema).unwrap();
+        assert_eq!((Some(1), Some(65535)), (parsed.cname_depth, parsed.seq));
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        assert_eq!(None, parse_log_line_with_client(b"1.5\t1\t-\tab.example.com\n", &schema).unwrap().seq);
+        assert_eq!(Err(ParseLineError::InvalidSequence), parse_log_line_with_client(b"1.5\t1\t0x1f\tab.example.com\n", &schema));
+        assert_eq!(Err(ParseLineError::InvalidSequence), parse_log_line_with_client(b"1.5\t1\t-3\tab.example.com\n", &schema));
+    }
+
+    #[test]
+    fn test_parse_log_line_with_label() {
+        let schema = LogSchema { client_field: true, label_field: true, ..LogSchema::default() };
+
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\tmalware c2\tab.example.com\n", &schema).unwrap();
+        assert_eq!(Some(String::from("malware c2")), parsed.label);
+        assert_eq!(b"ab.example.com".to_vec(), parsed.query);
+
+        // Empty label
+        let parsed = parse_log_line_with_client(b"1.5\t10.0.0.1\t\tab.example.com\n", &schema).unwrap();
+        assert_eq!(None, parsed.label);
+
+        let schema = LogSchema { label_field: true, quote: Some(b'"'), ..LogSchema::default() };
+        assert_eq!(Err(ParseLineError::InvalidLabel), parse_log_line_with_client(b"1.5\t\"benign\tab.example.com\n", &schema));
+    }
+
+    #[test]
+    fn test_line_parser() {
+        let schema = LogSchema { client_field: true, label_field: true, quote: Some(b'"'), ..LogSchema::default() };
+        let lines: [&[u8]; 5] = [
+            b"1.5\t10.0.0.1\tmalware c2\t\\x41b.example.com\n",
+            b"2\t\"10.0.0.2\"\t\tshort.example.com\n",
+            b"2.5\t10.0.0.1\t\"say \"\"hi\"\"\"\ta.example.com\r\n",
+            b"x\t10.0.0.1\t\ta.example.com\n",
+            b"3\t10.0.0.1\ta.example.com\n",
+        ];
+
+        // Same fields as without reused buffers, also after a longer line
+        let mut parser = LineParser::new(schema.clone());
+        for line in lines.iter() {
+            let expected = parse_log_line_with_client(line, &schema);
+            let parsed = parser.parse(line);
+            assert_eq!(expected.as_ref().err(), parsed.as_ref().err());
+
+            if let (Ok(expected), Ok(parsed)) = (expected, parsed) {
+                assert_eq!((expected.ts, expected.client.as_deref(), expected.label.as_deref(), expected.query.as_slice()),
+                           (parsed.ts, parsed.client, parsed.label, parsed.query));
+            }
+        }
+    }
+}
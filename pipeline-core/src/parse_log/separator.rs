@@ -0,0 +1,52 @@
+use crate::parse_log::{LogSchema, parse_log_line_with_client};
+
+/// Field separators tried by `detect_separator`, in order of preference: tab, comma, pipe and space.
+pub const SEPARATOR_CANDIDATES: [&[u8]; 4] = [b"\t", b",", b"|", b" "];
+
+/// Detect the field separator of a log from a sample of its lines (e.g. the first hundred, with their line endings):
+/// the candidate with which most lines parse according to the rest of the schema (fields, quotes, timestamps). Ties go
+/// to the earlier candidate in `SEPARATOR_CANDIDATES`. Returns None if no line parses with any candidate.
+pub fn detect_separator<'a, I: IntoIterator<Item=&'a [u8]>>(lines: I, schema: &LogSchema) -> Option<Vec<u8>> {
+    let lines: Vec<&[u8]> = lines.into_iter().filter(|line| !line.trim_ascii().is_empty()).collect();
+
+    let mut best: Option<(usize, &[u8])> = None;
+    for &separator in SEPARATOR_CANDIDATES.iter() {
+        let schema = LogSchema { separator: separator.to_vec(), ..schema.clone() };
+        let n_parsed = lines.iter().filter(|line| parse_log_line_with_client(line, &schema).is_ok()).count();
+        if n_parsed > 0 && best.is_none_or(|(n_best, _)| n_parsed > n_best) {
+            best = Some((n_parsed, separator));
+        }
+    }
+    best.map(|(_, separator)| separator.to_vec())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::{detect_separator, LogSchema};
+
+    fn detect(log: &[u8], schema: &LogSchema) -> Option<Vec<u8>> {
+        detect_separator(log.split_inclusive(|&ch| ch == b'\n'), schema)
+    }
+
+    #[test]
+    fn test_detect_separator() {
+        let schema = LogSchema::default();
+        assert_eq!(Some(b"\t".to_vec()), detect(b"1614859200.5\twww.example.com\n1614859201\tmail.example.com\n", &schema));
+        assert_eq!(Some(b",".to_vec()), detect(b"1614859200.5,www.example.com\n\n1614859201,mail.example.com\n", &schema));
+        assert_eq!(Some(b"|".to_vec()), detect(b"1614859200.5|www.example.com\n", &schema));
+        assert_eq!(Some(b" ".to_vec()), detect(b"1614859200.5 www.example.com\n", &schema));
+
+        // Spaces in textual timestamps, a header and a malformed line do not outvote the separator
+        let log = b"ts,query\n2021-03-04 12:00:00,www.example.com\n2021-03-04 12:00:01,a.example.com\n2021-03-04 12:00:02 b.example.com\n";
+        assert_eq!(Some(b",".to_vec()), detect(log, &schema));
+
+        // Number of fields of the schema, and quoted fields
+        let schema = LogSchema { client_field: true, quote: Some(b'"'), ..LogSchema::default() };
+        assert_eq!(Some(b",".to_vec()), detect(b"1614859200,\"192.0.2.1\",\"a,b.example.com\"\n", &schema));
+        assert_eq!(Some(b"|".to_vec()), detect(b"1614859200|192.0.2.1|www.example.com\n", &schema));
+
+        assert_eq!(None, detect(b"", &LogSchema::default()));
+        assert_eq!(None, detect(b"www.example.com\nnot a log line\n", &LogSchema::default()));
+    }
+}
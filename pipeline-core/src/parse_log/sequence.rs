@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+/// Detection of gaps in the sequence numbers of a log (e.g. a log sequence number or incrementing DNS transaction ID),
+/// which estimate the number of records dropped by the measurement. Sequence numbers are tracked per source (e.g. per
+/// resolver), and wrap around at the modulus if given (65536 for 16-bit transaction IDs). Steps backwards (reordering or
+/// repeats) count as no gap and keep the highest sequence number, so later records are not counted as a gap again; with
+/// a modulus, that is any step of more than half the modulus (as in RFC 1982).
+#[derive(Debug, Clone)]
+pub struct SequenceGaps {
+    modulus: Option<u64>,
+    last_seq: HashMap<Vec<u8>, u64>,
+    n_observed: u64,
+    n_missing: u64,
+}
+
+impl SequenceGaps {
+    pub fn new(modulus: Option<u64>) -> Self {
+        SequenceGaps { modulus, last_seq: HashMap::new(), n_observed: 0, n_missing: 0 }
+    }
+
+    /// Observe the next sequence number of a source, returns the number of sequence numbers missing since its highest
+    /// one (None for the first of the source).
+    pub fn observe(&mut self, source: &[u8], seq: u64) -> Option<u32> {
+        self.n_observed += 1;
+        let last_seq = match self.last_seq.get_mut(source) {
+            Some(last_seq) => last_seq,
+            None => {
+                self.last_seq.insert(source.to_vec(), seq);
+                return None;
+            }
+        };
+
+        let step = match self.modulus {
+            Some(modulus) => Some((seq % modulus + modulus - *last_seq % modulus) % modulus).filter(|&step| step <= modulus / 2).unwrap_or(0),
+            None => seq.saturating_sub(*last_seq),
+        };
+        if step > 0 { *last_seq = seq; }
+        let gap = step.saturating_sub(1);
+        self.n_missing += gap;
+        Some(gap.min(u32::MAX as u64) as u32)
+    }
+
+    pub fn n_observed(&self) -> u64 {
+        self.n_observed
+    }
+
+    pub fn n_missing(&self) -> u64 {
+        self.n_missing
+    }
+
+    /// Estimated fraction of records dropped: missing / (missing + observed), NaN if nothing was observed.
+    pub fn drop_rate(&self) -> f64 {
+        self.n_missing as f64 / (self.n_missing + self.n_observed) as f64
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log::SequenceGaps;
+
+    #[test]
+    fn test_sequence_gaps() {
+        let mut gaps = SequenceGaps::new(None);
+        let observed: Vec<Option<u32>> = [(b"a", 10), (b"a", 11), (b"b", 3), (b"a", 14), (b"b", 4), (b"a", 12), (b"a", 12)]
+            .iter().map(|(source, seq)| gaps.observe(*source, *seq)).collect();
+
+        assert_eq!(vec![None, Some(0), None, Some(2), Some(0), Some(0), Some(0)], observed);
+        assert_eq!((7, 2), (gaps.n_observed(), gaps.n_missing()));
+        assert!((gaps.drop_rate() - 2. / 9.).abs() < 1e-12);
+
+        // Wrap around
+        let mut gaps = SequenceGaps::new(Some(65536));
+        let observed: Vec<Option<u32>> = [65534, 0, 3, 1, 5].iter().map(|&seq| gaps.observe(b"", seq)).collect();
+        assert_eq!(vec![None, Some(1), Some(2), Some(0), Some(1)], observed);
+
+        // Reordered records are not counted as a gap again after the step backwards
+        let mut gaps = SequenceGaps::new(None);
+        let observed: Vec<Option<u32>> = [10, 12, 11, 13].iter().map(|&seq| gaps.observe(b"", seq)).collect();
+        assert_eq!(vec![None, Some(1), Some(0), Some(0)], observed);
+        assert_eq!(1, gaps.n_missing());
+
+        assert!(SequenceGaps::new(None).drop_rate().is_nan());
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// Parse a textual timestamp and normalize it to a UTC epoch timestamp (in seconds).
+///
+/// Accepts RFC 3339 timestamps with an offset (e.g. `2021-03-04T12:00:00+01:00` or `...Z`), and naive timestamps
+/// (`2021-03-04 12:00:00.123`) which are interpreted in the `assume_tz` time zone. Naive timestamps that are
+/// ambiguous due to a DST transition resolve to the earliest instant, non-existent local times are rejected.
+pub fn parse_textual_timestamp(input: &[u8], assume_tz: &Tz) -> Option<f64> {
+    let input = std::str::from_utf8(input).ok()?.trim();
+
+    // Timestamp with explicit offset
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Some(to_epoch(dt.timestamp(), dt.timestamp_subsec_nanos()));
+    }
+
+    // Naive timestamp in assumed time zone
+    let naive = NAIVE_FORMATS.iter().find_map(|fmt| NaiveDateTime::parse_from_str(input, fmt).ok())?;
+    let dt = match assume_tz.from_local_datetime(&naive) {
+        LocalResult::Single(dt) => dt,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => return None,
+    };
+
+    Some(to_epoch(dt.timestamp(), dt.timestamp_subsec_nanos()))
+}
+
+fn to_epoch(secs: i64, nanos: u32) -> f64 {
+    secs as f64 + nanos as f64 / 1e9
+}
+
+/// Truncate a timestamp to the given number of decimals (e.g. 3 for milliseconds). Values that are a whole number of
+/// ticks up to floating-point error (e.g. `1614859200.9994 * 1e4`) are not pulled down by a tick.
+pub fn truncate_timestamp(ts: f64, decimals: u8) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    let scaled = ts * scale;
+    (scaled + scaled.abs() * 2. * f64::EPSILON).floor() / scale
+}
+
+/// Truncate a numeric timestamp (e.g. `1614859200.123987`) to the given number of decimals before it is parsed, which
+/// is exact. Timestamps in exponent notation are returned as-is (and truncated after parsing).
+pub fn truncate_decimal_digits(ts: &[u8], decimals: u8) -> &[u8] {
+    if ts.iter().any(|c| matches!(c, b'e' | b'E')) { return ts; }
+    match ts.iter().position(|&c| c == b'.') {
+        Some(dot) if decimals == 0 => &ts[..dot],
+        Some(dot) => &ts[..ts.len().min(dot + 1 + decimals as usize)],
+        None => ts,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use crate::parse_log::timestamp::{parse_textual_timestamp, truncate_decimal_digits, truncate_timestamp};
+
+    #[test]
+    fn test_offset_timestamps() {
+        assert_eq!(Some(1614859200.), parse_textual_timestamp(b"2021-03-04T12:00:00Z", &Tz::UTC));
+        assert_eq!(Some(1614859200.), parse_textual_timestamp(b"2021-03-04T13:00:00+01:00", &Tz::UTC));
+        assert_eq!(Some(1614859200.5), parse_textual_timestamp(b"2021-03-04 12:00:00.5Z", &Tz::UTC));
+
+        // Explicit offset takes precedence over assumed time zone
+        assert_eq!(Some(1614859200.), parse_textual_timestamp(b"2021-03-04T12:00:00Z", &Tz::Europe__Amsterdam));
+    }
+
+    #[test]
+    fn test_naive_timestamps() {
+        assert_eq!(Some(1614859200.), parse_textual_timestamp(b"2021-03-04 12:00:00", &Tz::UTC));
+        assert_eq!(Some(1614859200.), parse_textual_timestamp(b"2021-03-04T13:00:00", &Tz::Europe__Amsterdam));
+
+        // Summer time (UTC+2)
+        assert_eq!(Some(1625392800.), parse_textual_timestamp(b"2021-07-04 12:00:00", &Tz::Europe__Amsterdam));
+    }
+
+    #[test]
+    fn test_dst_transitions() {
+        // 02:30 does not exist on 2021-03-28 in Amsterdam
+        assert_eq!(None, parse_textual_timestamp(b"2021-03-28 02:30:00", &Tz::Europe__Amsterdam));
+
+        // 02:30 happens twice on 2021-10-31 in Amsterdam, earliest is still UTC+2
+        assert_eq!(Some(1635640200.), parse_textual_timestamp(b"2021-10-31 02:30:00", &Tz::Europe__Amsterdam));
+    }
+
+    #[test]
+    fn test_truncate_timestamp() {
+        assert_eq!(1614859200.123, truncate_timestamp(1614859200.123987, 3));
+        assert_eq!(1614859200., truncate_timestamp(1614859200.999, 0));
+
+        // Whole ticks are kept (flooring `ts * 1e4` gives .9993 for 896 of these)
+        for i in 0..10_000 {
+            let ts: f64 = format!("1614859200.{:04}", i).parse().unwrap();
+            assert_eq!(ts, truncate_timestamp(ts, 4), "{}", ts);
+        }
+    }
+
+    #[test]
+    fn test_truncate_decimal_digits() {
+        assert_eq!(b"1614859200.9994", truncate_decimal_digits(b"1614859200.99949", 4));
+        assert_eq!(b"1614859200.5", truncate_decimal_digits(b"1614859200.5", 4));
+        assert_eq!(b"1614859200", truncate_decimal_digits(b"1614859200.5", 0));
+        assert_eq!(b"1614859200", truncate_decimal_digits(b"1614859200", 3));
+        assert_eq!(b"1.6148592e9", truncate_decimal_digits(b"1.6148592e9", 3));
+    }
+
+    #[test]
+    fn test_invalid_timestamps() {
+        assert_eq!(None, parse_textual_timestamp(b"yesterday", &Tz::UTC));
+        assert_eq!(None, parse_textual_timestamp(b"2021-13-04 12:00:00", &Tz::UTC));
+        assert_eq!(None, parse_textual_timestamp(b"\xff", &Tz::UTC));
+    }
+}
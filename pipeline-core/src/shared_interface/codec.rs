@@ -0,0 +1,180 @@
+use std::io::{BufRead, Read, Write};
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Serialization format of the intermediate files (log records and primary domain statistics).
+///
+/// Files start with a header (`HEADER_MAGIC` and a byte identifying the codec, see `write_header`), followed by a plain
+/// sequence of serialized values. MessagePack and CBOR encode structs as maps with field names and labels as byte
+/// strings, so they can be read and written by other tools (e.g. Python's `msgpack` or `cbor2`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Codec {
+    #[default]
+    Bincode,
+    MessagePack,
+    Cbor,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    #[error("bincode")]
+    Bincode(#[source] bincode::Error),
+    #[error("MessagePack")]
+    MessagePackEncode(#[source] rmp_serde::encode::Error),
+    #[error("MessagePack")]
+    MessagePackDecode(#[source] rmp_serde::decode::Error),
+    #[error("CBOR: {0}")]
+    Cbor(String),
+    #[error("unknown codec {0:#04x} in the file header")]
+    UnknownCodec(u8),
+    #[error(transparent)]
+    IO(std::io::Error),
+}
+
+/// Magic bytes at the start of an intermediate file, followed by the codec byte (`b`, `m` or `c`).
+pub const HEADER_MAGIC: &[u8; 4] = b"TDPi";
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bincode" => Ok(Codec::Bincode),
+            "msgpack" | "messagepack" => Ok(Codec::MessagePack),
+            "cbor" => Ok(Codec::Cbor),
+            _ => Err(String::from("expected bincode, msgpack or cbor")),
+        }
+    }
+}
+
+impl Codec {
+    fn header_byte(&self) -> u8 {
+        match self {
+            Codec::Bincode => b'b',
+            Codec::MessagePack => b'm',
+            Codec::Cbor => b'c',
+        }
+    }
+
+    /// Write the file header, so readers detect the codec (see `detect`).
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> Result<(), CodecError> {
+        writer.write_all(HEADER_MAGIC).and_then(|_| writer.write_all(&[self.header_byte()])).map_err(CodecError::IO)
+    }
+
+    /// Codec of a file from its header, which is consumed. Files without header (written before headers were added)
+    /// are read with `default`.
+    pub fn detect<R: BufRead>(reader: &mut R, default: Codec) -> Result<Codec, CodecError> {
+        let buf = reader.fill_buf().map_err(CodecError::IO)?;
+        if buf.len() <= HEADER_MAGIC.len() || !buf.starts_with(HEADER_MAGIC) { return Ok(default); }
+
+        let codec = match buf[HEADER_MAGIC.len()] {
+            b'b' => Codec::Bincode,
+            b'm' => Codec::MessagePack,
+            b'c' => Codec::Cbor,
+            byte => return Err(CodecError::UnknownCodec(byte)),
+        };
+        reader.consume(HEADER_MAGIC.len() + 1);
+        Ok(codec)
+    }
+
+    pub fn serialize_into<W: Write, T: Serialize>(&self, writer: &mut W, value: &T) -> Result<(), CodecError> {
+        match self {
+            Codec::Bincode => bincode::serialize_into(writer, value).map_err(CodecError::Bincode),
+            Codec::MessagePack => rmp_serde::encode::write_named(writer, value).map_err(CodecError::MessagePackEncode),
+            Codec::Cbor => ciborium::ser::into_writer(value, writer).map_err(|err| CodecError::Cbor(err.to_string())),
+        }
+    }
+
+    /// Read the next value, returns an error at the end of the input.
+    pub fn deserialize_from<R: Read, T: DeserializeOwned>(&self, reader: &mut R) -> Result<T, CodecError> {
+        match self {
+            Codec::Bincode => bincode::deserialize_from(reader).map_err(CodecError::Bincode),
+            Codec::MessagePack => rmp_serde::decode::from_read(reader).map_err(CodecError::MessagePackDecode),
+            Codec::Cbor => ciborium::de::from_reader(reader).map_err(|err| CodecError::Cbor(err.to_string())),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{ClientPrefix, Codec, CodecError, DomainTags, GeoInfo, LogRecord, PrimaryDomainStats, SerializedLogEntry};
+
+    #[test]
+    fn test_round_trip() {
+        for codec in [Codec::Bincode, Codec::MessagePack, Codec::Cbor].iter() {
+            let record: SerializedLogEntry = (3, LogRecord {
+                id: 7,
+                ts: 1.5,
+                payload: DnsPayload { labels: vec![b"ab".to_vec(), vec![0xFF]], payload_len: 3 },
+                client_prefix: Some(ClientPrefix::V4([10, 0, 0])),
+                label: Some(String::from("benign")),
+                client_geo: Some(GeoInfo { country: Some(String::from("NL")), asn: Some(1103) }),
+                resolver_geo: None,
+                ttl: Some(0),
+                cname_depth: Some(2),
+                seq_gap: Some(3),
+            });
+            let stats = PrimaryDomainStats { id: 3, name: String::from("example.com"), length: 11, count: 1, tags: DomainTags(2), first_seen: 0., last_seen: 0., n_unique: 1 };
+
+            let mut buf = Vec::new();
+            codec.serialize_into(&mut buf, &record).unwrap();
+            codec.serialize_into(&mut buf, &stats).unwrap();
+
+            let mut reader = &buf[..];
+            let (prim_id, decoded) = codec.deserialize_from::<_, SerializedLogEntry>(&mut reader).unwrap();
+            assert_eq!((3, 7, 1.5), (prim_id, decoded.id, decoded.ts));
+            assert_eq!(record.1.payload, decoded.payload);
+            assert_eq!(record.1.client_prefix, decoded.client_prefix);
+            assert_eq!(record.1.client_geo, decoded.client_geo);
+            assert_eq!(record.1.seq_gap, decoded.seq_gap);
+
+            let decoded = codec.deserialize_from::<_, PrimaryDomainStats>(&mut reader).unwrap();
+            assert_eq!((3, 11, 1, DomainTags(2)), (decoded.id, decoded.length, decoded.count, decoded.tags));
+
+            // End of input
+            assert!(codec.deserialize_from::<_, PrimaryDomainStats>(&mut reader).is_err());
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        for codec in [Codec::Bincode, Codec::MessagePack, Codec::Cbor].iter() {
+            let mut buf = Vec::new();
+            codec.write_header(&mut buf).unwrap();
+            codec.serialize_into(&mut buf, &7u32).unwrap();
+
+            let mut reader = &buf[..];
+            assert_eq!(*codec, Codec::detect(&mut reader, Codec::Bincode).unwrap());
+            assert_eq!(7u32, codec.deserialize_from(&mut reader).unwrap());
+        }
+
+        // Files without header are read with the default codec, an unknown codec byte is an error
+        let mut buf = Vec::new();
+        Codec::Cbor.serialize_into(&mut buf, &7u32).unwrap();
+        assert_eq!(Codec::Cbor, Codec::detect(&mut &buf[..], Codec::Cbor).unwrap());
+        assert!(matches!(Codec::detect(&mut &b"TDPix"[..], Codec::Bincode), Err(CodecError::UnknownCodec(b'x'))));
+    }
+
+    #[test]
+    fn test_labels_as_bytes() {
+        let payload = DnsPayload { labels: vec![b"ab".to_vec()], payload_len: 2 };
+
+        // A MessagePack bin 8 value instead of an array of integers
+        let mut buf = Vec::new();
+        Codec::MessagePack.serialize_into(&mut buf, &payload).unwrap();
+        assert!(buf.windows(4).any(|window| window == [0xc4, 2, b'a', b'b']));
+
+        // Bincode layout is the same as for a sequence of integers
+        assert_eq!(bincode::serialize(&(vec![b"ab".to_vec()], 2u8)).unwrap(), bincode::serialize(&payload).unwrap());
+    }
+
+    #[test]
+    fn test_parse_codec() {
+        assert_eq!(Ok(Codec::MessagePack), "msgpack".parse());
+        assert!("json".parse::<Codec>().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+/// Primary domain IDs of one chunk of the input, assigned in order of first occurrence within the chunk. Every thread
+/// of a parallel preprocessing keeps its own map, so no shared map (or lock) is needed while parsing; records carry
+/// these local IDs until the chunks are merged with `DomainIdMerger`.
+#[derive(Debug, Default, Clone)]
+pub struct LocalDomainIds {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl LocalDomainIds {
+    /// Local ID of a primary domain, a new one if it did not occur in this chunk before.
+    pub fn get_or_insert(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) { return id; }
+
+        let id = self.names.len() as u32;
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        id
+    }
+
+    /// Primary domains of this chunk, indexed by local ID.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Assigns global primary domain IDs to the local IDs of chunks. Chunks must be merged in input order (e.g. after
+/// collecting the results of all threads); the IDs are then assigned in order of first occurrence in the whole input,
+/// the same as a sequential pass, regardless of which thread parsed which chunk or when it finished.
+#[derive(Debug, Default, Clone)]
+pub struct DomainIdMerger {
+    ids: HashMap<String, u32>,
+}
+
+impl DomainIdMerger {
+    /// Merge the next chunk, returns the global ID of every local ID of the chunk.
+    pub fn merge(&mut self, chunk: &LocalDomainIds) -> Vec<u32> {
+        chunk.names().iter()
+            .map(|name| {
+                let next_id = self.ids.len() as u32;
+                *self.ids.entry(name.clone()).or_insert(next_id)
+            })
+            .collect()
+    }
+
+    /// Global ID of a primary domain, if it occurred in a merged chunk.
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::shared_interface::{DomainIdMerger, LocalDomainIds};
+
+    #[test]
+    fn test_domain_ids() {
+        let input = ["a.com", "b.com", "a.com", "c.org", "b.com", "d.net", "a.com", "e.nl", "c.org", "f.io"];
+
+        // Sequential assignment in order of first occurrence
+        let mut sequential = LocalDomainIds::default();
+        let expected: Vec<u32> = input.iter().map(|name| sequential.get_or_insert(name)).collect();
+        assert_eq!(vec![0, 1, 0, 2, 1, 3, 0, 4, 2, 5], expected);
+
+        // Chunks parsed on their own threads, finishing in any order, give the same IDs once merged in input order
+        for chunk_len in 1..=input.len() {
+            let handles: Vec<_> = input.chunks(chunk_len).rev()
+                .map(|chunk| {
+                    let chunk: Vec<String> = chunk.iter().map(|name| name.to_string()).collect();
+                    thread::spawn(move || {
+                        let mut ids = LocalDomainIds::default();
+                        let local: Vec<u32> = chunk.iter().map(|name| ids.get_or_insert(name)).collect();
+                        (ids, local)
+                    })
+                })
+                .collect();
+            let mut chunks: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+            chunks.reverse();
+
+            let mut merger = DomainIdMerger::default();
+            let global: Vec<u32> = chunks.iter()
+                .flat_map(|(ids, local)| {
+                    let to_global = merger.merge(ids);
+                    local.iter().map(move |&id| to_global[id as usize]).collect::<Vec<_>>()
+                })
+                .collect();
+            assert_eq!(expected, global, "chunks of {}", chunk_len);
+            assert_eq!(6, merger.len());
+            assert_eq!(Some(4), merger.get("e.nl"));
+        }
+    }
+}
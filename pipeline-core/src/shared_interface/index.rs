@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use serde::{Deserialize, Serialize};
+
+use crate::shared_interface::{Codec, CodecError, LogRecord, RecordReader};
+
+/// Byte ranges of the records of every primary domain in a record file (`--out-index` of the preprocessing), to read
+/// the records of a single domain without scanning the file. Consecutive records of a domain form one range, so a
+/// sorted record file (`--sort-records`) has a single range per domain.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct RecordIndex {
+    /// Primary domain ID <--> `(start, end)` byte offsets, in file order.
+    domains: BTreeMap<u32, Vec<(u64, u64)>>,
+    /// Length of the indexed record file, to detect an index of another file.
+    len: u64,
+}
+
+impl RecordIndex {
+    /// Add a record of `prim_id` at bytes `start..end`, after all records added before.
+    pub fn add(&mut self, prim_id: u32, start: u64, end: u64) {
+        let ranges = self.domains.entry(prim_id).or_default();
+        match ranges.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+        self.len = self.len.max(end);
+    }
+
+    /// Byte ranges of the records of a primary domain (empty if it has none).
+    pub fn ranges(&self, prim_id: u32) -> &[(u64, u64)] {
+        self.domains.get(&prim_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// IDs of the indexed primary domains.
+    pub fn prim_ids(&self) -> impl Iterator<Item=u32> + '_ {
+        self.domains.keys().copied()
+    }
+
+    /// Length of the indexed record file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Read the records of a primary domain (in file order) from the indexed record file.
+    pub fn read_domain<R: Read + Seek>(&self, records: &mut R, codec: Codec, prim_id: u32) -> Result<Vec<LogRecord>, CodecError> {
+        let mut domain_records = Vec::new();
+        for &(start, end) in self.ranges(prim_id) {
+            records.seek(SeekFrom::Start(start)).map_err(CodecError::IO)?;
+            let range = BufReader::new(records.by_ref().take(end - start));
+            for entry in RecordReader::values(range, codec) {
+                domain_records.push(entry?.1);
+            }
+        }
+        Ok(domain_records)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{Codec, LogRecord, RecordIndex};
+
+    #[test]
+    fn test_record_index() {
+        let mut buf = Vec::new();
+        let mut index = RecordIndex::default();
+        for (id, prim_id) in [3, 1, 1, 3, 3, 2].iter().enumerate() {
+            let payload = DnsPayload { labels: vec![b"ab".to_vec()], payload_len: 2 };
+            let record = LogRecord { id, ts: id as f64, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None };
+            let start = buf.len() as u64;
+            Codec::MessagePack.serialize_into(&mut buf, &(*prim_id, record)).unwrap();
+            index.add(*prim_id, start, buf.len() as u64);
+        }
+
+        // Consecutive records are merged into one range
+        assert_eq!(buf.len() as u64, index.len());
+        assert_eq!(vec![1, 2, 3], index.prim_ids().collect::<Vec<_>>());
+        assert_eq!(1, index.ranges(1).len());
+        assert_eq!(2, index.ranges(3).len());
+        assert!(index.ranges(4).is_empty());
+
+        let mut reader = Cursor::new(&buf);
+        let ids = |records: Vec<LogRecord>| records.iter().map(|record| record.id).collect::<Vec<_>>();
+        assert_eq!(vec![0, 3, 4], ids(index.read_domain(&mut reader, Codec::MessagePack, 3).unwrap()));
+        assert_eq!(vec![1, 2], ids(index.read_domain(&mut reader, Codec::MessagePack, 1).unwrap()));
+        assert!(index.read_domain(&mut reader, Codec::MessagePack, 4).unwrap().is_empty());
+
+        // The index itself is written with a codec as well
+        let mut index_buf = Vec::new();
+        Codec::Bincode.serialize_into(&mut index_buf, &index).unwrap();
+        assert_eq!(index, Codec::Bincode.deserialize_from(&mut &index_buf[..]).unwrap());
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parse_dns::DnsPayload;
+
+pub use self::codec::{Codec, CodecError, HEADER_MAGIC};
+pub use self::domain_ids::{DomainIdMerger, LocalDomainIds};
+pub use self::index::RecordIndex;
+pub use self::reader::{CodecReader, PrimStatsReader, RecordReader};
+
+mod codec;
+mod domain_ids;
+mod index;
+mod reader;
+
+pub type SerializedLogEntry = (u32, LogRecord);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogRecord {
+    pub id: usize,
+    pub ts: f64,
+    pub payload: DnsPayload,
+    pub client_prefix: Option<ClientPrefix>,
+    /// Label of the query from the input log, passed through to the output rows (empty for unlabeled queries if the log
+    /// has a label field).
+    pub label: Option<String>,
+    /// GeoIP information of the client and resolver addresses, if looked up during preprocessing.
+    pub client_geo: Option<GeoInfo>,
+    pub resolver_geo: Option<GeoInfo>,
+    /// TTL (in seconds) of the response, if the input log has a TTL field.
+    pub ttl: Option<u32>,
+    /// Length of the CNAME chain of the response, if the input log has a CNAME field.
+    pub cname_depth: Option<u8>,
+    /// Number of sequence numbers missing before the record, if the input log has a sequence field.
+    pub seq_gap: Option<u32>,
+}
+
+/// Country and autonomous system of an IP address.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct GeoInfo {
+    /// ISO 3166-1 country code.
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+}
+
+/// Truncated client subnet (e.g. from EDNS client subnet), IPv4 to /24 and IPv6 to /48.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientPrefix {
+    V4([u8; 3]),
+    V6([u8; 6]),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PrimaryDomainStats {
+    pub id: u32,
+    pub name: String,
+    pub length: u8,
+    pub count: u32,
+    pub tags: DomainTags,
+    /// Timestamp of the first and last record.
+    pub first_seen: f64,
+    pub last_seen: f64,
+    /// Number of unique (case-insensitive) query payloads, estimated for domains with many (see `HyperLogLog`).
+    pub n_unique: u32,
+}
+
+/// Set of flags attached to a primary domain during preprocessing, written as a bitmask in the output.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct DomainTags(pub u32);
+
+impl DomainTags {
+    /// Primary domain has a special use (reserved) suffix.
+    pub const RESERVED_SUFFIX: u32 = 1;
+    /// Primary domain has a suffix that is not in the public suffix list (heuristic primary domain).
+    pub const UNKNOWN_SUFFIX: u32 = 1 << 1;
+    /// Registrable label of the primary domain has a high character entropy, like DGA-generated domains.
+    pub const GENERATED_NAME: u32 = 1 << 2;
+    /// Primary domain has many unique first labels with a uniform structure, like DNSBL services (wildcard-like).
+    pub const WILDCARD: u32 = 1 << 3;
+    /// Primary domain has queries for a known service with tunnel-like queries (DNSBL, NTP pool, anti-virus, CDN).
+    pub const ALLOWLISTED: u32 = 1 << 4;
+    /// Primary domain has reverse lookups that are not reversed IP addresses (possible data in PTR queries).
+    pub const MALFORMED_REVERSE: u32 = 1 << 5;
+
+    pub fn insert(&mut self, flag: u32) {
+        self.0 |= flag;
+    }
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+}
+
+impl fmt::Display for LogRecord {
+    // This trait requires `fmt` with this exact signature.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LogRecord<id={}, ts={}, payload=", self.id, self.ts)?;
+        self.payload.write_query(f, "")?;
+        f.write_str(">")
+    }
+}
+
+impl LogRecord {
+    /// Compact single-line format for sampling records in logs: ID, timestamp and payload, followed by the fields
+    /// that are present, e.g. `7 1.5 ab.\xff client=10.0.0.0/24 ttl=60 label=benign`.
+    pub fn compact(&self) -> CompactLogRecord<'_> {
+        CompactLogRecord(self)
+    }
+}
+
+/// Compact Display of a `LogRecord`, see `LogRecord::compact`.
+pub struct CompactLogRecord<'a>(&'a LogRecord);
+
+impl fmt::Display for CompactLogRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let record = self.0;
+        write!(f, "{} {} ", record.id, record.ts)?;
+        record.payload.write_query(f, "")?;
+
+        if let Some(prefix) = &record.client_prefix { write!(f, " client={}", prefix)?; }
+        if let Some(ttl) = record.ttl { write!(f, " ttl={}", ttl)?; }
+        if let Some(depth) = record.cname_depth { write!(f, " cname={}", depth)?; }
+        if let Some(gap) = record.seq_gap.filter(|&gap| gap > 0) { write!(f, " gap={}", gap)?; }
+        if let Some(label) = &record.label { write!(f, " label={}", label)?; }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ClientPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientPrefix::V4([a, b, c]) => write!(f, "{}.{}.{}.0/24", a, b, c),
+            ClientPrefix::V6(bytes) => {
+                for pair in bytes.chunks(2) { write!(f, "{:x}:", u16::from_be_bytes([pair[0], pair[1]]))?; }
+                f.write_str(":/48")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{ClientPrefix, LogRecord};
+
+    #[test]
+    fn test_display() {
+        let payload = DnsPayload { labels: vec![b"ab".to_vec(), vec![0xFF]], payload_len: 3 };
+        let mut record = LogRecord { id: 7, ts: 1.5, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None };
+        assert_eq!("LogRecord<id=7, ts=1.5, payload=ab.\\xff>", record.to_string());
+        assert_eq!("7 1.5 ab.\\xff", record.compact().to_string());
+
+        record.client_prefix = Some(ClientPrefix::V4([10, 0, 1]));
+        record.ttl = Some(60);
+        record.seq_gap = Some(0);
+        record.label = Some(String::from("benign"));
+        assert_eq!("7 1.5 ab.\\xff client=10.0.1.0/24 ttl=60 label=benign", record.compact().to_string());
+
+        record.seq_gap = Some(4);
+        assert_eq!("7 1.5 ab.\\xff client=10.0.1.0/24 ttl=60 gap=4 label=benign", record.compact().to_string());
+    }
+
+    #[test]
+    fn test_client_prefix_display() {
+        assert_eq!("2001:db8:1::/48", ClientPrefix::V6([0x20, 0x01, 0x0d, 0xb8, 0, 1]).to_string());
+    }
+}
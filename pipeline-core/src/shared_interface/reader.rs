@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::shared_interface::{Codec, CodecError, PrimaryDomainStats, SerializedLogEntry};
+
+/// Iterator over the values of an intermediate file. Iteration ends at the end of the input; a value that cannot be
+/// read (e.g. a truncated file or another codec) is returned as error, after which iteration ends as well.
+pub struct CodecReader<R, T> {
+    reader: R,
+    codec: Codec,
+    /// Whether the file header is still to be read.
+    header: bool,
+    done: bool,
+    value: PhantomData<T>,
+}
+
+/// Reader for record files (`--out-records` of the preprocessing).
+pub type RecordReader<R> = CodecReader<R, SerializedLogEntry>;
+
+/// Reader for primary domain statistics files (`--out-prim` of the preprocessing).
+pub type PrimStatsReader<R> = CodecReader<R, PrimaryDomainStats>;
+
+impl<R: BufRead, T: DeserializeOwned> CodecReader<R, T> {
+    /// Reader for a complete intermediate file, with the codec of its header (`codec` for files without header).
+    pub fn new(reader: R, codec: Codec) -> Self {
+        CodecReader { reader, codec, header: true, done: false, value: PhantomData }
+    }
+
+    /// Reader for values without file header, e.g. a byte range of a record file (see `RecordIndex`).
+    pub fn values(reader: R, codec: Codec) -> Self {
+        CodecReader { reader, codec, header: false, done: false, value: PhantomData }
+    }
+}
+
+impl<T: DeserializeOwned> CodecReader<BufReader<File>, T> {
+    pub fn open<P: AsRef<Path>>(path: P, codec: Codec) -> std::io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?), codec))
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for CodecReader<R, T> {
+    type Item = Result<T, CodecError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None; }
+
+        if self.header {
+            self.header = false;
+            match Codec::detect(&mut self.reader, self.codec) {
+                Ok(codec) => self.codec = codec,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        // End of input only between values, anything else is an error
+        let value = match self.reader.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(self.codec.deserialize_from(&mut self.reader)),
+            Err(err) => Some(Err(CodecError::IO(err))),
+        };
+
+        self.done = !matches!(value, Some(Ok(_)));
+        value
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_dns::DnsPayload;
+    use crate::shared_interface::{Codec, DomainTags, LogRecord, PrimaryDomainStats, PrimStatsReader, RecordReader};
+
+    fn record(id: usize) -> (u32, LogRecord) {
+        let payload = DnsPayload { labels: vec![b"ab".to_vec()], payload_len: 2 };
+        (1, LogRecord { id, ts: id as f64, payload, client_prefix: None, label: None, client_geo: None, resolver_geo: None, ttl: None, cname_depth: None, seq_gap: None })
+    }
+
+    #[test]
+    fn test_read_records() {
+        for codec in [Codec::Bincode, Codec::MessagePack, Codec::Cbor].iter() {
+            let mut buf = Vec::new();
+            (0..3).for_each(|id| codec.serialize_into(&mut buf, &record(id)).unwrap());
+
+            let ids: Vec<usize> = RecordReader::new(&buf[..], *codec).map(|entry| entry.unwrap().1.id).collect();
+            assert_eq!(vec![0, 1, 2], ids);
+
+            // Truncated input: error after the complete values, then the end
+            let mut reader = RecordReader::new(&buf[..buf.len() - 1], *codec);
+            assert_eq!(2, reader.by_ref().take_while(Result::is_ok).count());
+            assert!(reader.next().is_none());
+
+            assert!(RecordReader::new(&[][..], *codec).next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_codec_from_header() {
+        let mut buf = Vec::new();
+        Codec::Cbor.write_header(&mut buf).unwrap();
+        (0..2).for_each(|id| Codec::Cbor.serialize_into(&mut buf, &record(id)).unwrap());
+
+        // The header overrides the given codec
+        let ids: Vec<usize> = RecordReader::new(&buf[..], Codec::Bincode).map(|entry| entry.unwrap().1.id).collect();
+        assert_eq!(vec![0, 1], ids);
+
+        // A header only, and values without header
+        assert!(RecordReader::new(&buf[..5], Codec::Bincode).next().is_none());
+        assert!(RecordReader::values(&buf[..], Codec::Cbor).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_read_prim_stats() {
+        let stats = PrimaryDomainStats { id: 2, name: String::from("example.com"), length: 11, count: 4, tags: DomainTags::default(), first_seen: 1., last_seen: 9., n_unique: 3 };
+        let mut buf = Vec::new();
+        Codec::Bincode.serialize_into(&mut buf, &stats).unwrap();
+
+        let read: Vec<PrimaryDomainStats> = PrimStatsReader::new(&buf[..], Codec::Bincode).collect::<Result<_, _>>().unwrap();
+        assert_eq!(vec![(2, 4)], read.iter().map(|stats| (stats.id, stats.count)).collect::<Vec<_>>());
+
+        // Records are not primary domain statistics
+        let mut buf = Vec::new();
+        Codec::MessagePack.serialize_into(&mut buf, &record(0)).unwrap();
+        assert!(PrimStatsReader::new(&buf[..], Codec::MessagePack).next().unwrap().is_err());
+    }
+}
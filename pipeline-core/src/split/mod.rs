@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Dataset partition a primary domain is assigned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Split {
+    Train,
+    Validation,
+    Test,
+}
+
+impl Split {
+    pub const ALL: [Split; 3] = [Split::Train, Split::Validation, Split::Test];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Split::Train => "train",
+            Split::Validation => "validation",
+            Split::Test => "test",
+        }
+    }
+}
+
+/// Fractions of primary domains assigned to the train, validation and test sets (summing to one).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitRatios {
+    pub train: f64,
+    pub validation: f64,
+    pub test: f64,
+}
+
+impl Default for SplitRatios {
+    fn default() -> Self {
+        SplitRatios { train: 0.7, validation: 0.15, test: 0.15 }
+    }
+}
+
+impl FromStr for SplitRatios {
+    type Err = String;
+
+    /// Parse `train,validation,test` weights, e.g. `0.8,0.1,0.1` or `8,1,1` (normalized to sum to one).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights = s.split(',')
+            .map(|w| w.trim().parse::<f64>().map_err(|_| format!("invalid ratio \"{}\"", w.trim())))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        if weights.len() != 3 { return Err(String::from("expected three ratios (train,validation,test)")); }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.) { return Err(String::from("ratios must be non-negative")); }
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0. { return Err(String::from("ratios must not all be zero")); }
+
+        Ok(SplitRatios { train: weights[0] / total, validation: weights[1] / total, test: weights[2] / total })
+    }
+}
+
+impl fmt::Display for SplitRatios {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}/{:.3}/{:.3}", self.train, self.validation, self.test)
+    }
+}
+
+/// Assign a primary domain to a split. Deterministic for a given seed, independent of processing order and of
+/// the other domains, so all feature vectors of a domain end up in the same split.
+pub fn assign_split(prim_id: u32, seed: u64, ratios: &SplitRatios) -> Split {
+    // Uniform value in [0, 1) from the 53 most significant bits
+    let u = (splitmix64(seed ^ splitmix64(prim_id as u64)) >> 11) as f64 / (1u64 << 53) as f64;
+
+    if u < ratios.train {
+        Split::Train
+    } else if u < ratios.train + ratios.validation {
+        Split::Validation
+    } else {
+        Split::Test
+    }
+}
+
+/// SplitMix64 finalizer, a fixed hash function (unlike `DefaultHasher`, stable across Rust versions).
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::split::{assign_split, Split, SplitRatios};
+
+    #[test]
+    fn test_parse_ratios() {
+        let ratios: SplitRatios = "8,1,1".parse().unwrap();
+        assert_eq!(SplitRatios { train: 0.8, validation: 0.1, test: 0.1 }, ratios);
+
+        assert!("0.5,0.5".parse::<SplitRatios>().is_err());
+        assert!("1,-1,1".parse::<SplitRatios>().is_err());
+        assert!("0,0,0".parse::<SplitRatios>().is_err());
+    }
+
+    #[test]
+    fn test_assignment_is_deterministic() {
+        let ratios = SplitRatios::default();
+        let first: Vec<Split> = (0..100).map(|id| assign_split(id, 42, &ratios)).collect();
+        let second: Vec<Split> = (0..100).map(|id| assign_split(id, 42, &ratios)).collect();
+        assert_eq!(first, second);
+
+        let other_seed: Vec<Split> = (0..100).map(|id| assign_split(id, 43, &ratios)).collect();
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn test_assignment_follows_ratios() {
+        let ratios = SplitRatios::default();
+        let n_train = (0..10_000).filter(|id| assign_split(*id, 1, &ratios) == Split::Train).count();
+        assert!((6_700..7_300).contains(&n_train));
+
+        let all_test = SplitRatios { train: 0., validation: 0., test: 1. };
+        assert!((0..100).all(|id| assign_split(id, 1, &all_test) == Split::Test));
+    }
+}